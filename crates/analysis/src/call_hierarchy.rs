@@ -0,0 +1,259 @@
+//! Finding the calls to and from functions.
+
+use crate::{rename, selection_range, source_files};
+use fast_hash::FxHashMap;
+use paths::{PathId, PathMap, WithPath};
+use sml_statics_types::def;
+use sml_syntax::ast::{self, AstNode as _, SyntaxNodePtr};
+use sml_syntax::kind::{SyntaxKind as SK, SyntaxNode};
+use text_pos::{PositionUtf16, RangeUtf16};
+use text_size_util::TextRange;
+
+/// A function in a call hierarchy.
+#[derive(Debug)]
+pub struct CallHierarchyItem {
+  /// The name of the function.
+  pub name: String,
+  /// The type of the function.
+  pub detail: Option<String>,
+  /// The range of the whole declaration of the function.
+  pub range: WithPath<RangeUtf16>,
+  /// The range of the name of the function. Contained in `range`.
+  pub selection_range: RangeUtf16,
+}
+
+/// A function, and the ranges of the calls to or from it.
+#[derive(Debug)]
+pub struct CallHierarchyCall {
+  /// The function.
+  pub item: CallHierarchyItem,
+  /// The ranges of the calls.
+  ///
+  /// For incoming calls, these are in the file of the calling `item`. For outgoing calls, these are
+  /// in the file of the function the calls are from.
+  pub from_ranges: Vec<RangeUtf16>,
+}
+
+/// A function binding: a `fun`, or a `val` whose expression is a `fn`.
+#[derive(Debug, Clone, Copy)]
+struct Fun {
+  path: PathId,
+  pat: sml_hir::la_arena::Idx<sml_hir::Pat>,
+  dec: sml_hir::DecIdx,
+  exp: sml_hir::ExpIdx,
+}
+
+impl Fun {
+  fn key(self) -> (PathId, sml_hir::Idx) {
+    (self.path, self.pat.into())
+  }
+}
+
+pub(crate) fn prepare(
+  st: &sml_statics_types::St,
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  pos: WithPath<PositionUtf16>,
+) -> Option<CallHierarchyItem> {
+  let fun = at_pos(source_files, pos)?;
+  item(st, source_files, fun)
+}
+
+pub(crate) fn incoming(
+  st: &sml_statics_types::St,
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  pos: WithPath<PositionUtf16>,
+) -> Option<Vec<CallHierarchyCall>> {
+  let fun = at_pos(source_files, pos)?;
+  let def = def::Def::Path(def::Path::Regular(fun.path), fun.pat.into());
+  let mut calls = Calls::default();
+  for (&path, sf) in source_files {
+    let root = sf.syntax.parse.root();
+    let root = root.syntax();
+    for idx in sf.info.get_with_def(def) {
+      let Some(ptr) = sf.syntax.lower.ptrs.hir_to_ast(idx) else { continue };
+      let node = ptr.to_node(root);
+      let Some(caller) = enclosing_fun(source_files, path, &node) else { continue };
+      calls.add(caller, path.wrap(call_range(&node)));
+    }
+  }
+  Some(calls.finish(st, source_files))
+}
+
+pub(crate) fn outgoing(
+  st: &sml_statics_types::St,
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  pos: WithPath<PositionUtf16>,
+) -> Option<Vec<CallHierarchyCall>> {
+  let fun = at_pos(source_files, pos)?;
+  let sf = source_files.get(&fun.path)?;
+  let body = sf.syntax.lower.ptrs.hir_to_ast(fun.exp?.into())?;
+  let body = body.to_node(sf.syntax.parse.root().syntax());
+  let mut calls = Calls::default();
+  for node in body.descendants() {
+    if !matches!(node.kind(), SK::PathExp | SK::InfixExp) {
+      continue;
+    }
+    let Some(indices) = sf.syntax.lower.ptrs.ast_to_hir_all(&SyntaxNodePtr::new(&node)) else {
+      continue;
+    };
+    let callee = indices
+      .iter()
+      .filter(|&&idx| is_path_exp(sf, idx))
+      .filter_map(|&idx| sf.info.get_defs(idx))
+      .flatten()
+      .filter_map(|def| def.to_regular_idx())
+      .find_map(|idx| fun_of(source_files, idx.path, idx.val));
+    if let Some(callee) = callee {
+      calls.add(callee, fun.path.wrap(call_range(&node)));
+    }
+  }
+  Some(calls.finish(st, source_files))
+}
+
+/// The calls to or from functions, grouped by function, in the order first seen.
+#[derive(Default)]
+struct Calls {
+  idx: FxHashMap<(PathId, sml_hir::Idx), usize>,
+  calls: Vec<(Fun, Vec<WithPath<TextRange>>)>,
+}
+
+impl Calls {
+  fn add(&mut self, fun: Fun, range: WithPath<TextRange>) {
+    let idx = *self.idx.entry(fun.key()).or_insert_with(|| {
+      self.calls.push((fun, Vec::new()));
+      self.calls.len() - 1
+    });
+    self.calls[idx].1.push(range);
+  }
+
+  fn finish(
+    self,
+    st: &sml_statics_types::St,
+    source_files: &PathMap<mlb_statics::SourceFile>,
+  ) -> Vec<CallHierarchyCall> {
+    self
+      .calls
+      .into_iter()
+      .filter_map(|(fun, ranges)| {
+        let item = item(st, source_files, fun)?;
+        let from_ranges = ranges
+          .into_iter()
+          .filter_map(|range| {
+            let sf = source_files.get(&range.path)?;
+            sf.syntax.pos_db.range_utf16(range.val)
+          })
+          .collect();
+        Some(CallHierarchyCall { item, from_ranges })
+      })
+      .collect()
+  }
+}
+
+/// Returns the function at the position, either because the position is on the function's name
+/// where it is bound, or on a reference to the function.
+fn at_pos(
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  pos: WithPath<PositionUtf16>,
+) -> Option<Fun> {
+  let ft = source_files::file_and_token(source_files, pos)?;
+  let (_, indices) = ft.get_ptr_and_indices()?;
+  let this = indices.iter().map(|&idx| pos.path.wrap(idx));
+  let defs = indices
+    .iter()
+    .filter_map(|&idx| ft.file.info.get_defs(idx))
+    .flatten()
+    .filter_map(|def| def.to_regular_idx());
+  this.chain(defs).find_map(|idx| fun_of(source_files, idx.path, idx.val))
+}
+
+/// Returns the function whose name is bound by the pattern at this index, if any.
+fn fun_of(
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  path: PathId,
+  idx: sml_hir::Idx,
+) -> Option<Fun> {
+  let sml_hir::Idx::Pat(pat) = idx else { return None };
+  let arenas = &source_files.get(&path)?.syntax.lower.arenas;
+  arenas.dec.iter().find_map(|(dec, d)| {
+    let sml_hir::Dec::Val(_, val_binds, flavor) = d else { return None };
+    val_binds.iter().find_map(|val_bind| {
+      if val_bind.pat != Some(pat) {
+        return None;
+      }
+      let is_fun = matches!(flavor, sml_hir::ValFlavor::Fun)
+        || matches!(val_bind.exp.map(|exp| &arenas.exp[exp]), Some(sml_hir::Exp::Fn(..)));
+      is_fun.then_some(Fun { path, pat, dec, exp: val_bind.exp })
+    })
+  })
+}
+
+/// Returns the nearest function binding containing the node.
+fn enclosing_fun(
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  path: PathId,
+  node: &SyntaxNode,
+) -> Option<Fun> {
+  let sf = source_files.get(&path)?;
+  node.ancestors().find_map(|node| {
+    let pat_node = match node.kind() {
+      SK::FunBind => node,
+      SK::ValBind => ast::ValBind::cast(node)?.pat()?.syntax().clone(),
+      _ => return None,
+    };
+    let indices = sf.syntax.lower.ptrs.ast_to_hir_all(&SyntaxNodePtr::new(&pat_node))?;
+    indices.iter().find_map(|&idx| fun_of(source_files, path, idx))
+  })
+}
+
+fn is_path_exp(sf: &mlb_statics::SourceFile, idx: sml_hir::Idx) -> bool {
+  let sml_hir::Idx::Exp(exp) = idx else { return false };
+  matches!(sf.syntax.lower.arenas.exp[exp], sml_hir::Exp::Path(_))
+}
+
+/// Returns the range of a call. For infix calls, this is just the operator.
+fn call_range(node: &SyntaxNode) -> TextRange {
+  if let Some(op) = ast::InfixExp::cast(node.clone()).and_then(|x| x.name_star_eq()) {
+    return op.token.text_range();
+  }
+  selection_range::trimmed_range(node).unwrap_or_else(|| node.text_range())
+}
+
+fn item(
+  st: &sml_statics_types::St,
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  fun: Fun,
+) -> Option<CallHierarchyItem> {
+  let sf = source_files.get(&fun.path)?;
+  let name = pat_name(&sf.syntax.lower.arenas, fun.pat)?;
+  let range = sf.syntax.lower.ptrs.hir_to_ast(fun.dec.into())?.text_range();
+  let range = sf.syntax.pos_db.range_utf16(range)?;
+  let name_tok = rename::name_tokens(sf, fun.pat.into(), name.as_str(), rename::Namespace::Value)
+    .next()
+    .map(|tok| tok.text_range());
+  let selection_range = match name_tok {
+    Some(tr) => tr,
+    None => sf.syntax.lower.ptrs.hir_to_ast(fun.pat.into())?.text_range(),
+  };
+  let selection_range = sf.syntax.pos_db.range_utf16(selection_range)?;
+  let detail = sf
+    .info
+    .get_ty_scheme(fun.pat.into())
+    .map(|ty_scheme| ty_scheme.display(st, config::DiagnosticLines::One).to_string());
+  Some(CallHierarchyItem {
+    name: name.as_str().to_owned(),
+    detail,
+    range: fun.path.wrap(range),
+    selection_range,
+  })
+}
+
+fn pat_name(
+  arenas: &sml_hir::Arenas,
+  pat: sml_hir::la_arena::Idx<sml_hir::Pat>,
+) -> Option<&str_util::Name> {
+  match &arenas.pat[pat] {
+    sml_hir::Pat::Con(path, None) => Some(path.last()),
+    sml_hir::Pat::Typed(pat, _) => pat_name(arenas, (*pat)?),
+    _ => None,
+  }
+}
@@ -1,5 +1,6 @@
 //! Helpers for working with diagnostics.
 
+use crate::fix;
 use sml_syntax::ast::AstNode as _;
 
 /// A diagnostic.
@@ -13,6 +14,28 @@ pub struct Diagnostic<R> {
   pub code: diagnostic::Code,
   /// The severity.
   pub severity: diagnostic::Severity,
+  /// The fixes that can be automatically applied. Usually empty.
+  pub fixes: Vec<Fix<R>>,
+}
+
+/// A fix for a diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix<R> {
+  /// A short description of the fix.
+  pub title: String,
+  /// The edits to the file with the diagnostic, as ranges to replace and the text to replace them
+  /// with. The ranges do not overlap.
+  pub edits: Vec<(R, String)>,
+}
+
+impl<R> Fix<R> {
+  fn try_map<F, R2>(self, mut f: F) -> Option<Fix<R2>>
+  where
+    F: FnMut(R) -> Option<R2>,
+  {
+    let edits = self.edits.into_iter().map(|(range, text)| Some((f(range)?, text)));
+    Some(Fix { title: self.title, edits: edits.collect::<Option<_>>()? })
+  }
 }
 
 impl<R> Diagnostic<R> {
@@ -24,6 +47,7 @@ impl<R> Diagnostic<R> {
       message: "comment prevents formatting".to_owned(),
       code: diagnostic::Code::n(6001),
       severity: diagnostic::Severity::Warning,
+      fixes: Vec::new(),
     }
   }
 }
@@ -57,21 +81,35 @@ where
     config::init::DiagnosticsIgnore::AfterSyntax => true,
     config::init::DiagnosticsIgnore::All => return Vec::new(),
   };
+  let root = file.syntax.parse.root();
+  let root = root.syntax();
+  let pos_db = &file.syntax.pos_db;
+  let map_fixes = |fixes: Vec<Fix<text_size_util::TextRange>>| -> Vec<Fix<R>> {
+    fixes.into_iter().filter_map(|fix| fix.try_map(|range| f(pos_db, range))).collect()
+  };
   let mut ret: Vec<_> = std::iter::empty()
     .chain(file.syntax.lex_errors.iter().filter_map(|err| {
-      let range = f(&file.syntax.pos_db, err.range())?;
+      let range = f(pos_db, err.range())?;
       let message = err.to_string();
-      Some(Diagnostic { range, message, code: err.code(), severity: err.severity() })
+      Some(Diagnostic {
+        range,
+        message,
+        code: err.code(),
+        severity: err.severity(),
+        fixes: Vec::new(),
+      })
     }))
     .chain(file.syntax.parse.errors.iter().filter_map(|err| {
-      let range = f(&file.syntax.pos_db, err.range())?;
+      let range = f(pos_db, err.range())?;
       let message = err.to_string();
-      Some(Diagnostic { range, message, code: err.code(), severity: err.severity() })
+      let fixes = map_fixes(fix::parse(root, err));
+      Some(Diagnostic { range, message, code: err.code(), severity: err.severity(), fixes })
     }))
     .chain(file.syntax.lower.errors.iter().filter_map(|err| {
-      let range = f(&file.syntax.pos_db, err.range())?;
+      let range = f(pos_db, err.range())?;
       let message = err.to_string();
-      Some(Diagnostic { range, message, code: err.code(), severity: err.severity() })
+      let fixes = map_fixes(fix::lower(root, err));
+      Some(Diagnostic { range, message, code: err.code(), severity: err.severity(), fixes })
     }))
     .collect();
   let has_any_error = ret.iter().any(|x| matches!(x.severity, diagnostic::Severity::Error));
@@ -79,17 +117,18 @@ where
     ret.extend(file.statics_errors.iter().filter_map(|err| {
       let idx = err.idx();
       let syntax = file.syntax.lower.ptrs.hir_to_ast(idx).expect("no pointer for idx");
-      let node = syntax.to_node(file.syntax.parse.root.syntax());
-      let range = f(&file.syntax.pos_db, sml_syntax::node_range(&node))?;
+      let node = syntax.to_node(root);
+      let range = f(pos_db, sml_syntax::node_range(&node))?;
       let message = err.display(syms_tys, options.lines).to_string();
-      Some(Diagnostic { range, message, code: err.code(), severity: err.severity() })
+      let fixes = map_fixes(fix::statics(&node, err, &file.statics_errors));
+      Some(Diagnostic { range, message, code: err.code(), severity: err.severity(), fixes })
     }));
     if matches!(options.format, config::init::FormatEngine::Naive) {
       if let Err(sml_naive_fmt::Error::Comments(ranges)) =
-        sml_naive_fmt::check(&file.syntax.parse.root)
+        sml_naive_fmt::check(&file.syntax.parse.root())
       {
         ret.extend(ranges.into_iter().filter_map(|range| {
-          let range = f(&file.syntax.pos_db, range)?;
+          let range = f(pos_db, range)?;
           Some(Diagnostic::naive_fmt_comment(range))
         }));
       }
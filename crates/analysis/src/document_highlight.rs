@@ -0,0 +1,70 @@
+//! Highlighting the occurrences in one file of the item at a position.
+
+use crate::{rename, source_files};
+use fast_hash::FxHashSet;
+use paths::{PathMap, WithPath};
+use sml_statics_types::def;
+use sml_syntax::kind::SyntaxKind as SK;
+use text_pos::{PositionUtf16, RangeUtf16};
+
+/// The kind of a document highlight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentHighlightKind {
+  /// A use of the item.
+  Read,
+  /// A binding of the item, like in a pattern or the name of a `datatype` constructor.
+  Write,
+}
+
+/// An occurrence of an item in a file.
+#[derive(Debug)]
+pub struct DocumentHighlight {
+  /// The range.
+  pub range: RangeUtf16,
+  /// The kind.
+  pub kind: DocumentHighlightKind,
+}
+
+pub(crate) fn get(
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  pos: WithPath<PositionUtf16>,
+) -> Option<Vec<DocumentHighlight>> {
+  let ft = source_files::file_and_token(source_files, pos)?;
+  if ft.token.kind() != SK::Name {
+    return None;
+  }
+  let (ns, binding) = rename::namespace(&ft.token)?;
+  let name = ft.token.text();
+  let (_, indices) = ft.get_ptr_and_indices()?;
+  let mut defs = def::Set::new();
+  if !binding {
+    defs.extend(indices.iter().filter_map(|&idx| ft.file.info.get_defs(idx)).flatten().copied());
+  }
+  if defs.is_empty() {
+    let path = def::Path::Regular(pos.path);
+    defs.extend(indices.iter().map(|&idx| def::Def::Path(path, idx)));
+  }
+  let mut ret = Vec::<DocumentHighlight>::new();
+  let mut seen = FxHashSet::default();
+  // do the bindings first, so that a binding is not also reported as a use.
+  let bindings = defs.iter().filter_map(|&def| match def {
+    def::Def::Path(def::Path::Regular(path), idx) => {
+      (path == pos.path).then_some((idx, DocumentHighlightKind::Write))
+    }
+    def::Def::Path(def::Path::BuiltinLib(_), _) | def::Def::Primitive(_) => None,
+  });
+  let uses = defs
+    .iter()
+    .flat_map(|&def| ft.file.info.get_with_def(def).map(|idx| (idx, DocumentHighlightKind::Read)));
+  for (idx, kind) in bindings.chain(uses) {
+    for tok in rename::name_tokens(ft.file, idx, name, ns) {
+      let tr = tok.text_range();
+      if !seen.insert(tr) {
+        continue;
+      }
+      let Some(range) = ft.file.syntax.pos_db.range_utf16(tr) else { continue };
+      ret.push(DocumentHighlight { range, kind });
+    }
+  }
+  Some(ret)
+}
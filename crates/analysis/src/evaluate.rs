@@ -0,0 +1,293 @@
+//! Evaluating top-level `val` declarations and expressions on their own with the dynamics, to show
+//! their values in the editor.
+
+use fast_hash::FxHashSet;
+use sml_statics_types::{def, env::Env, info::IdStatus};
+use std::fmt;
+
+/// The most steps to take when evaluating, so that e.g. an infinite loop doesn't hang the server.
+pub(crate) const MAX_STEPS: usize = 10_000;
+
+/// The result of evaluating something with the dynamics.
+#[derive(Debug)]
+pub enum Evaluation {
+  /// It finished normally, with this value, or these bindings for a declaration.
+  Value(String),
+  /// It raised this unhandled exception.
+  Raise(String),
+  /// It did not finish after this many steps.
+  Stopped(usize),
+}
+
+impl fmt::Display for Evaluation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Evaluation::Value(s) => f.write_str(s),
+      Evaluation::Raise(s) => write!(f, "uncaught exception {s}"),
+      Evaluation::Stopped(steps) => write!(f, "stopped after {steps} steps"),
+    }
+  }
+}
+
+impl Evaluation {
+  /// Returns a Markdown string for this, e.g. for a hover.
+  pub(crate) fn md(&self) -> String {
+    match self {
+      Evaluation::Value(s) => format!("evaluates to:\n\n```sml\n{s}\n```"),
+      Evaluation::Raise(s) => format!("raises `{s}`"),
+      Evaluation::Stopped(steps) => format!("did not finish evaluating after {steps} steps"),
+    }
+  }
+}
+
+/// Returns the top-level `val` declarations in the file that can be evaluated on their own.
+pub(crate) fn vals(
+  std_basis: &Env,
+  path: paths::PathId,
+  file: &mlb_statics::SourceFile,
+) -> Vec<sml_hir::StrDecIdx> {
+  if has_errors(file) {
+    return Vec::new();
+  }
+  let ars = &file.syntax.lower.arenas;
+  let iter = file.syntax.lower.root.iter().filter(|&&str_dec| {
+    let sml_hir::StrDec::Dec(decs) = &ars.str_dec[str_dec] else { return false };
+    let [dec] = decs.as_slice() else { return false };
+    matches!(ars.dec[*dec], sml_hir::Dec::Val(_, _, sml_hir::ValFlavor::Val))
+      && is_closed(std_basis, path, file, sml_hir::Idx::Dec(*dec))
+  });
+  iter.copied().collect()
+}
+
+/// Returns whether the exp can be evaluated on its own.
+pub(crate) fn is_exp_evaluable(
+  std_basis: &Env,
+  path: paths::PathId,
+  file: &mlb_statics::SourceFile,
+  exp: sml_hir::la_arena::Idx<sml_hir::Exp>,
+) -> bool {
+  // the values of these are already obvious, or not interesting to show.
+  let interesting = !matches!(
+    file.syntax.lower.arenas.exp[exp],
+    sml_hir::Exp::Hole | sml_hir::Exp::SCon(_) | sml_hir::Exp::Path(_) | sml_hir::Exp::Fn(_, _)
+  );
+  interesting && !has_errors(file) && is_closed(std_basis, path, file, sml_hir::Idx::Exp(exp))
+}
+
+/// Returns the syntax in the file that uses parts of the std basis the dynamics does not implement,
+/// with a description of what it uses. The dynamics can't evaluate a file with any of these.
+///
+/// Only the implemented values are available, so this includes any structure, signature, or functor
+/// from the std basis.
+pub(crate) fn unsupported(
+  std_basis: &Env,
+  file: &mlb_statics::SourceFile,
+) -> Vec<(sml_hir::Idx, String)> {
+  let ars = &file.syntax.lower.arenas;
+  let mut strs = FxHashSet::<&str_util::Name>::default();
+  let mut sigs = FxHashSet::<&str_util::Name>::default();
+  let mut funs = FxHashSet::<&str_util::Name>::default();
+  for (_, str_dec) in ars.str_dec.iter() {
+    match str_dec {
+      sml_hir::StrDec::Structure(str_binds) => strs.extend(str_binds.iter().map(|x| &x.name)),
+      sml_hir::StrDec::Signature(sig_binds) => sigs.extend(sig_binds.iter().map(|x| &x.name)),
+      sml_hir::StrDec::Functor(fun_binds) => {
+        for fun_bind in fun_binds {
+          funs.insert(&fun_bind.functor_name);
+          strs.insert(&fun_bind.param_name);
+        }
+      }
+      sml_hir::StrDec::Dec(_) | sml_hir::StrDec::Local(_, _) => {}
+    }
+  }
+  let is_local_str =
+    |path: &sml_path::Path| path.all_names().next().is_some_and(|x| strs.contains(x));
+  let mut ret = Vec::<(sml_hir::Idx, String)>::new();
+  for (exp, e) in ars.exp.iter() {
+    let sml_hir::Exp::Path(p) = e else { continue };
+    if !matches!(file.exp_id_statuses.get(exp), Some(IdStatus::Val)) {
+      continue;
+    }
+    let idx = sml_hir::Idx::Exp(exp);
+    let Some(defs) = file.info.get_defs(idx) else { continue };
+    let ok = defs.iter().all(|&def| match def {
+      def::Def::Path(def::Path::Regular(_), _) => true,
+      def::Def::Path(def::Path::BuiltinLib(_), _) | def::Def::Primitive(_) => {
+        is_builtin(std_basis, p, def)
+      }
+    });
+    if !ok {
+      ret.push((idx, format!("value `{p}`")));
+    }
+  }
+  for (str_exp, e) in ars.str_exp.iter() {
+    match e {
+      sml_hir::StrExp::Path(p) if !is_local_str(p) => {
+        ret.push((sml_hir::Idx::StrExp(str_exp), format!("structure `{p}`")));
+      }
+      sml_hir::StrExp::App(name, _, _) if !funs.contains(name) => {
+        ret.push((sml_hir::Idx::StrExp(str_exp), format!("functor `{name}`")));
+      }
+      _ => {}
+    }
+  }
+  for (sig_exp, e) in ars.sig_exp.iter() {
+    if let sml_hir::SigExp::Name(name) = e {
+      if !sigs.contains(name) {
+        ret.push((sml_hir::Idx::SigExp(sig_exp), format!("signature `{name}`")));
+      }
+    }
+  }
+  for (dec, d) in ars.dec.iter() {
+    let sml_hir::Dec::Open(paths) = d else { continue };
+    for p in paths.iter().filter(|p| !is_local_str(p)) {
+      ret.push((sml_hir::Idx::Dec(dec), format!("structure `{p}`")));
+    }
+  }
+  ret
+}
+
+/// Runs the dynamics for at most [`MAX_STEPS`] steps. Any output is discarded.
+pub(crate) fn get(mut dynamics: sml_dynamics::Dynamics<'_>) -> Evaluation {
+  for _ in 0..MAX_STEPS {
+    dynamics = match dynamics.step() {
+      sml_dynamics::Progress::Still(d) => d,
+      sml_dynamics::Progress::Done(finished) => {
+        let s = finished.to_string();
+        let lines: Vec<_> = s.lines().collect();
+        return Evaluation::Value(lines.join(", "));
+      }
+      sml_dynamics::Progress::Raise(finished) => {
+        let s = finished.to_string();
+        let s = s.trim_end();
+        return Evaluation::Raise(s.strip_prefix("uncaught exception ").unwrap_or(s).to_owned());
+      }
+    };
+  }
+  Evaluation::Stopped(MAX_STEPS)
+}
+
+/// Returns whether the file has any errors. We don't evaluate those files, since e.g. they might not
+/// typecheck.
+fn has_errors(file: &mlb_statics::SourceFile) -> bool {
+  let is_error = |severity| matches!(severity, diagnostic::Severity::Error);
+  file.syntax.lex_errors.iter().any(|e| is_error(e.severity()))
+    || file.syntax.parse.errors.iter().any(|e| is_error(e.severity()))
+    || file.syntax.lower.errors.iter().any(|e| is_error(e.severity()))
+    || file.statics_errors.iter().any(|e| is_error(e.severity()))
+}
+
+/// Returns whether the syntax at the idx uses only the parts of the std basis the dynamics
+/// implements and the values it binds itself, and has no syntax the dynamics can't evaluate.
+fn is_closed(
+  std_basis: &Env,
+  path: paths::PathId,
+  file: &mlb_statics::SourceFile,
+  idx: sml_hir::Idx,
+) -> bool {
+  let mut walk =
+    Walk { ars: &file.syntax.lower.arenas, pats: FxHashSet::default(), paths: Vec::new() };
+  let ok = match idx {
+    sml_hir::Idx::Exp(exp) => walk.exp(Some(exp)),
+    sml_hir::Idx::Dec(dec) => walk.dec(dec),
+    _ => false,
+  };
+  ok && walk.paths.iter().all(|&exp| {
+    match file.exp_id_statuses.get(exp) {
+      None => return false,
+      Some(IdStatus::Con | IdStatus::Exn(_)) => return true,
+      Some(IdStatus::Val) => {}
+    }
+    let sml_hir::Exp::Path(p) = &walk.ars.exp[exp] else { return false };
+    let Some(defs) = file.info.get_defs(sml_hir::Idx::Exp(exp)) else { return false };
+    !defs.is_empty()
+      && defs.iter().all(|&def| match def {
+        def::Def::Path(def::Path::Regular(def_path), sml_hir::Idx::Pat(pat)) => {
+          def_path == path && walk.pats.contains(&pat)
+        }
+        def::Def::Path(def::Path::Regular(_), _) => false,
+        def::Def::Path(def::Path::BuiltinLib(_), _) | def::Def::Primitive(_) => {
+          is_builtin(std_basis, p, def)
+        }
+      })
+  })
+}
+
+/// Returns whether the path is to a value from the std basis with this def that the dynamics
+/// implements.
+fn is_builtin(std_basis: &Env, path: &sml_path::Path, def: def::Def) -> bool {
+  if !sml_dynamics::std_basis_paths().any(|p| p == *path) {
+    return false;
+  }
+  let mut env = std_basis;
+  for name in path.prefix() {
+    match env.str_env.get(name) {
+      Some(x) => env = x,
+      None => return false,
+    }
+  }
+  env.val_env.get(path.last()).is_some_and(|val_info| val_info.defs.contains(&def))
+}
+
+/// Collects the pats and path exps in some syntax.
+struct Walk<'a> {
+  ars: &'a sml_hir::Arenas,
+  pats: FxHashSet<sml_hir::la_arena::Idx<sml_hir::Pat>>,
+  paths: Vec<sml_hir::la_arena::Idx<sml_hir::Exp>>,
+}
+
+impl Walk<'_> {
+  fn exp(&mut self, exp: sml_hir::ExpIdx) -> bool {
+    let Some(exp) = exp else { return false };
+    match &self.ars.exp[exp] {
+      sml_hir::Exp::Hole => false,
+      sml_hir::Exp::SCon(_) => true,
+      sml_hir::Exp::Path(_) => {
+        self.paths.push(exp);
+        true
+      }
+      sml_hir::Exp::Record(rows) => rows.iter().all(|&(_, exp)| self.exp(exp)),
+      sml_hir::Exp::Let(decs, exp) => decs.iter().all(|&dec| self.dec(dec)) && self.exp(*exp),
+      sml_hir::Exp::App(func, arg) => self.exp(*func) && self.exp(*arg),
+      sml_hir::Exp::Handle(exp, arms) => self.exp(*exp) && self.arms(arms),
+      sml_hir::Exp::Raise(exp) | sml_hir::Exp::Typed(exp, _, _) => self.exp(*exp),
+      sml_hir::Exp::Fn(arms, _) => self.arms(arms),
+      sml_hir::Exp::Vector(exps) => exps.iter().all(|&exp| self.exp(exp)),
+    }
+  }
+
+  fn arms(&mut self, arms: &[sml_hir::Arm]) -> bool {
+    arms.iter().all(|arm| self.pat(arm.pat) && self.exp(arm.exp))
+  }
+
+  fn dec(&mut self, dec: sml_hir::DecIdx) -> bool {
+    match &self.ars.dec[dec] {
+      sml_hir::Dec::Val(_, val_binds, _) => {
+        val_binds.iter().all(|val_bind| self.pat(val_bind.pat) && self.exp(val_bind.exp))
+      }
+      sml_hir::Dec::Ty(_)
+      | sml_hir::Dec::Datatype(_, _)
+      | sml_hir::Dec::DatatypeCopy(_, _)
+      | sml_hir::Dec::Exception(_) => true,
+      // the dynamics does not evaluate the decs in an `abstype`, and `open` needs structures.
+      sml_hir::Dec::Abstype(_, _, _) | sml_hir::Dec::Open(_) => false,
+      sml_hir::Dec::Local(local_decs, in_decs) => {
+        local_decs.iter().chain(in_decs).all(|&dec| self.dec(dec))
+      }
+    }
+  }
+
+  fn pat(&mut self, pat: sml_hir::PatIdx) -> bool {
+    let Some(pat) = pat else { return false };
+    self.pats.insert(pat);
+    match &self.ars.pat[pat] {
+      sml_hir::Pat::Wild | sml_hir::Pat::SCon(_) | sml_hir::Pat::Con(_, None) => true,
+      sml_hir::Pat::Con(_, Some(pat)) | sml_hir::Pat::Typed(pat, _) | sml_hir::Pat::As(_, pat) => {
+        self.pat(*pat)
+      }
+      sml_hir::Pat::Record { rows, .. } => rows.iter().all(|&(_, pat)| self.pat(pat)),
+      sml_hir::Pat::Or(or_pat) => or_pat.all_pats().all(|pat| self.pat(pat)),
+      sml_hir::Pat::Vector(pats) => pats.iter().all(|&pat| self.pat(pat)),
+    }
+  }
+}
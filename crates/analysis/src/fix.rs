@@ -0,0 +1,161 @@
+//! Getting automatic fixes for diagnostics.
+
+use crate::diagnostic::Fix;
+use crate::selection_range;
+use sml_syntax::ast::{self, AstNode as _};
+use sml_syntax::kind::{SyntaxKind as SK, SyntaxNode};
+use text_size_util::{TextRange, TextSize};
+
+/// Returns the fixes for a parse error.
+pub(crate) fn parse(root: &SyntaxNode, err: &sml_parse::parser::Error) -> Vec<Fix<TextRange>> {
+  let fix = if err.is_unnecessary_op() { unnecessary_op(root, err.range()) } else { None };
+  fix.into_iter().collect()
+}
+
+/// Returns the fixes for a lowering error.
+pub(crate) fn lower(root: &SyntaxNode, err: &sml_hir_lower::Error) -> Vec<Fix<TextRange>> {
+  let fix = if err.is_unnecessary_parens() {
+    unnecessary_parens(root, err.range())
+  } else if err.is_trailing() {
+    trailing(root, err.range())
+  } else {
+    None
+  };
+  fix.into_iter().collect()
+}
+
+/// Returns the fixes for a statics error on the node. The errors are all the statics errors for the
+/// file, including this one.
+pub(crate) fn statics(
+  node: &SyntaxNode,
+  err: &sml_statics::Error,
+  errors: &[sml_statics::Error],
+) -> Vec<Fix<TextRange>> {
+  let fix = if let Some((name, sug)) = err.suggestion() {
+    suggestion(node, name, sug)
+  } else if err.stub().is_some() {
+    missing(node, err, errors)
+  } else if err.is_unused() {
+    unused(node)
+  } else if err.can_eta_reduce() {
+    eta_reduce(node)
+  } else {
+    None
+  };
+  fix.into_iter().collect()
+}
+
+fn unnecessary_op(root: &SyntaxNode, range: TextRange) -> Option<Fix<TextRange>> {
+  let op = root.token_at_offset(range.start()).find(|tok| tok.kind() == SK::OpKw)?;
+  // also delete the whitespace after the `op`, so as to not leave behind extra whitespace.
+  let end = match op.next_token() {
+    Some(tok) if tok.kind() == SK::Whitespace => tok.text_range().end(),
+    _ => op.text_range().end(),
+  };
+  let range = TextRange::new(op.text_range().start(), end);
+  Some(Fix { title: "Remove unnecessary `op`".to_owned(), edits: vec![(range, String::new())] })
+}
+
+fn unnecessary_parens(root: &SyntaxNode, range: TextRange) -> Option<Fix<TextRange>> {
+  let node = root.covering_element(range).into_node().or_else(|| {
+    let tok = root.token_at_offset(range.start()).right_biased()?;
+    tok.parent()
+  })?;
+  let node = node.ancestors().find(|node| {
+    matches!(node.kind(), SK::ParenExp | SK::ParenPat | SK::ParenTy)
+      && sml_syntax::node_range(node) == range
+  })?;
+  let toks: Vec<_> = node.children_with_tokens().filter_map(|x| x.into_token()).collect();
+  let l_round = toks.iter().find(|tok| tok.kind() == SK::LRound)?;
+  let r_round = toks.iter().rev().find(|tok| tok.kind() == SK::RRound)?;
+  Some(Fix {
+    title: "Remove unnecessary parentheses".to_owned(),
+    edits: vec![(l_round.text_range(), String::new()), (r_round.text_range(), String::new())],
+  })
+}
+
+fn trailing(root: &SyntaxNode, range: TextRange) -> Option<Fix<TextRange>> {
+  let tok = root.token_at_offset(range.start()).find(|tok| tok.text_range() == range)?;
+  Some(Fix {
+    title: format!("Remove trailing `{}`", tok.text()),
+    edits: vec![(tok.text_range(), String::new())],
+  })
+}
+
+fn suggestion(node: &SyntaxNode, name: &str, sug: &str) -> Option<Fix<TextRange>> {
+  let tok = node
+    .descendants_with_tokens()
+    .filter_map(|x| x.into_token())
+    .find(|tok| matches!(tok.kind(), SK::Name | SK::Star | SK::Eq) && tok.text() == name)?;
+  Some(Fix {
+    title: format!("Replace with `{sug}`"),
+    edits: vec![(tok.text_range(), sug.to_owned())],
+  })
+}
+
+fn missing(
+  node: &SyntaxNode,
+  err: &sml_statics::Error,
+  errors: &[sml_statics::Error],
+) -> Option<Fix<TextRange>> {
+  err.stub()?;
+  let str_exp = match ast::StrExp::cast(node.clone())? {
+    ast::StrExp::StructStrExp(str_exp) => str_exp,
+    ast::StrExp::AscriptionStrExp(str_exp) => match str_exp.str_exp()? {
+      ast::StrExp::StructStrExp(str_exp) => str_exp,
+      _ => return None,
+    },
+    _ => return None,
+  };
+  let end_kw = str_exp.end_kw()?;
+  // add all the missing items for this structure at once, not just the one for this error.
+  let stubs: Vec<_> =
+    errors.iter().filter(|e| e.idx() == err.idx()).filter_map(sml_statics::Error::stub).collect();
+  let text = node.ancestors().last()?.to_string();
+  let end_start = usize::from(end_kw.text_range().start());
+  let line_start = text[..end_start].rfind('\n').map_or(0, |idx| idx + 1);
+  let before_end = &text[line_start..end_start];
+  let indent: String = before_end.chars().take_while(|c| c.is_whitespace()).collect();
+  let lines = stubs.iter().flat_map(|stub| stub.lines());
+  let (start, end, new_text) = if before_end.trim().is_empty() {
+    // the `end` starts its line, so add the stubs on new lines before it.
+    let new_text: String = lines.map(|line| format!("{indent}  {line}\n")).collect();
+    (line_start, line_start, new_text)
+  } else {
+    // put the `end` on its own line after the stubs, replacing the whitespace before it.
+    let mut new_text: String = lines.map(|line| format!("\n{indent}  {line}")).collect();
+    new_text.push('\n');
+    new_text.push_str(&indent);
+    (line_start + before_end.trim_end().len(), end_start, new_text)
+  };
+  let range = TextRange::new(TextSize::try_from(start).ok()?, TextSize::try_from(end).ok()?);
+  Some(Fix { title: "Add stubs for missing items".to_owned(), edits: vec![(range, new_text)] })
+}
+
+fn unused(node: &SyntaxNode) -> Option<Fix<TextRange>> {
+  if let Some(as_pat) = ast::AsPat::cast(node.clone()) {
+    // delete the name and the `as`, keeping the pattern after the `as`.
+    let name = as_pat.pat()?;
+    let rhs = as_pat.as_pat_tail()?.pat()?;
+    let start = selection_range::trimmed_range(name.syntax())?.start();
+    let end = selection_range::trimmed_range(rhs.syntax())?.start();
+    let title = format!("Remove unused `{}`", name.syntax().text().to_string().trim());
+    return Some(Fix { title, edits: vec![(TextRange::new(start, end), String::new())] });
+  }
+  let con_pat = ast::ConPat::cast(node.clone())?;
+  if con_pat.pat().is_some() {
+    return None;
+  }
+  let range = selection_range::trimmed_range(con_pat.syntax())?;
+  let title = format!("Replace unused `{}` with `_`", con_pat.syntax().text().to_string().trim());
+  Some(Fix { title, edits: vec![(range, "_".to_owned())] })
+}
+
+fn eta_reduce(node: &SyntaxNode) -> Option<Fix<TextRange>> {
+  let fn_exp = ast::FnExp::cast(node.clone())?;
+  let arm = fn_exp.matcher()?.arms().next()?;
+  let ast::Exp::AppExp(app) = arm.exp()? else { return None };
+  let func_text = app.func()?.syntax().text().to_string().trim().to_owned();
+  let range = selection_range::trimmed_range(node)?;
+  Some(Fix { title: format!("Simplify to `{func_text}`"), edits: vec![(range, func_text)] })
+}
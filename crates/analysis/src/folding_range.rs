@@ -0,0 +1,67 @@
+//! Getting the ranges of a file that can be folded.
+
+use crate::selection_range;
+use sml_syntax::ast::{self, AstNode as _};
+use sml_syntax::kind::{SyntaxKind as SK, SyntaxNode};
+use text_size_util::TextRange;
+
+/// A kind of folding range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoldingRangeKind {
+  /// A region of code, like a `struct ... end`.
+  Region,
+  /// A comment.
+  Comment,
+}
+
+/// A range of a file that can be folded.
+#[derive(Debug)]
+pub struct FoldingRange {
+  /// The range. Always spans many lines.
+  pub range: text_pos::RangeUtf16,
+  /// The kind.
+  pub kind: FoldingRangeKind,
+}
+
+pub(crate) fn get(file: &sml_file_syntax::SourceFileSyntax) -> Vec<FoldingRange> {
+  let root = file.parse.root();
+  let root = root.syntax();
+  let mut ret = Vec::<FoldingRange>::new();
+  for node in root.descendants().filter(is_foldable) {
+    let Some(tr) = selection_range::trimmed_range(&node) else { continue };
+    push(&mut ret, file, tr, FoldingRangeKind::Region);
+  }
+  let comments = root.descendants_with_tokens().filter_map(|x| {
+    let tok = x.into_token()?;
+    (tok.kind() == SK::BlockComment).then_some(tok)
+  });
+  for tok in comments {
+    push(&mut ret, file, tok.text_range(), FoldingRangeKind::Comment);
+  }
+  ret
+}
+
+fn push(
+  ac: &mut Vec<FoldingRange>,
+  file: &sml_file_syntax::SourceFileSyntax,
+  tr: TextRange,
+  kind: FoldingRangeKind,
+) {
+  let Some(range) = file.pos_db.range_utf16(tr) else { return };
+  if range.start.line < range.end.line {
+    ac.push(FoldingRange { range, kind });
+  }
+}
+
+fn is_foldable(node: &SyntaxNode) -> bool {
+  match node.kind() {
+    SK::StructStrExp | SK::SigSigExp | SK::LetExp | SK::LetStrExp | SK::LocalDec => true,
+    SK::CaseExp | SK::FnExp | SK::HandleExp => {
+      node.children().find_map(ast::Matcher::cast).map_or(false, |m| m.arms().count() > 1)
+    }
+    SK::FunBind => {
+      ast::FunBind::cast(node.clone()).map_or(false, |fun| fun.fun_bind_cases().count() > 1)
+    }
+    _ => false,
+  }
+}
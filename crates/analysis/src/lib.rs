@@ -1,21 +1,42 @@
 //! The unification of all the passes into a single high-level API.
 
+mod call_hierarchy;
 mod diagnostic;
+mod document_highlight;
+mod evaluate;
+mod fix;
+mod folding_range;
 mod matcher;
+mod refactor;
+mod rename;
+mod selection_range;
+mod semantic_tokens;
+mod signature_help;
 mod source_files;
+mod workspace_symbols;
 
 use fast_hash::FxHashSet;
 use paths::{PathId, PathMap, WithPath};
 use sml_statics_types::{def, env::Env};
 use sml_syntax::ast::{self, AstNode as _, SyntaxNodePtr};
 use sml_syntax::kind::SyntaxKind;
+use std::ops::ControlFlow;
 use std::process::{Command, Stdio};
 use std::{error::Error, fmt, io::Write as _};
 use text_pos::{PositionDb, PositionUtf16, RangeUtf16};
 use text_size_util::TextRange;
 
-pub use crate::diagnostic::{Diagnostic, Options};
-pub use mlb_statics::StdBasis;
+pub use crate::call_hierarchy::{CallHierarchyCall, CallHierarchyItem};
+pub use crate::diagnostic::{Diagnostic, Fix, Options};
+pub use crate::document_highlight::{DocumentHighlight, DocumentHighlightKind};
+pub use crate::evaluate::Evaluation;
+pub use crate::folding_range::{FoldingRange, FoldingRangeKind};
+pub use crate::refactor::{Refactor, RefactorKind};
+pub use crate::rename::RenameError;
+pub use crate::semantic_tokens::{SemanticToken, SemanticTokenKind, SemanticTokenModifiers};
+pub use crate::signature_help::SignatureHelp;
+pub use crate::workspace_symbols::{SymbolLocation, WorkspaceSymbol};
+pub use mlb_statics::{Progress, StdBasis};
 
 /// The url to go to for information about diagnostics.
 pub const URL: &str = "https://github.com/azdavis/millet/blob/main/docs/diagnostics";
@@ -43,13 +64,38 @@ impl Analysis {
     }
   }
 
+  /// Returns a new `Analysis` with the same std basis and options as this, but with no source
+  /// files analyzed.
+  ///
+  /// This lets many files be analyzed on another thread while this keeps answering queries about
+  /// the files it already analyzed.
+  #[must_use]
+  pub fn fresh(&self) -> Self {
+    Self::new(self.std_basis.clone(), self.diagnostics_options)
+  }
+
+  /// Sets the options. They take effect for the next analysis.
+  pub fn set_options(&mut self, diagnostics_options: diagnostic::Options) {
+    self.diagnostics_options = diagnostics_options;
+  }
+
   /// Given information about many interdependent source files and their groupings, returns a
   /// mapping from source paths to diagnostics.
   pub fn get_many(
     &mut self,
     input: &input::Input,
   ) -> PathMap<Vec<Diagnostic<text_pos::RangeUtf16>>> {
-    self.get_many_impl(input, text_pos::PositionDb::range_utf16)
+    self.get_many_impl(input, text_pos::PositionDb::range_utf16, &mut |_| ControlFlow::Continue(()))
+  }
+
+  /// Like [`Self::get_many`], but calls `on_progress` after analyzing each source file. If it
+  /// breaks, stops early, with only some of the source files analyzed.
+  pub fn get_many_with_progress(
+    &mut self,
+    input: &input::Input,
+    on_progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+  ) -> PathMap<Vec<Diagnostic<text_pos::RangeUtf16>>> {
+    self.get_many_impl(input, text_pos::PositionDb::range_utf16, on_progress)
   }
 
   /// Given information about many interdependent source files and their groupings, returns a
@@ -58,34 +104,21 @@ impl Analysis {
     &mut self,
     input: &input::Input,
   ) -> PathMap<Vec<Diagnostic<TextRange>>> {
-    self.get_many_impl(input, |_, b| Some(b))
+    self.get_many_impl(input, |_, b| Some(b), &mut |_| ControlFlow::Continue(()))
   }
 
-  fn get_many_impl<F, R>(&mut self, input: &input::Input, f: F) -> PathMap<Vec<Diagnostic<R>>>
+  fn get_many_impl<F, R>(
+    &mut self,
+    input: &input::Input,
+    f: F,
+    on_progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+  ) -> PathMap<Vec<Diagnostic<R>>>
   where
     F: Fn(&text_pos::PositionDb, text_size_util::TextRange) -> Option<R>,
   {
-    let mut basis = self.std_basis.basis().clone();
-    for path in &input.lang.val {
-      // TODO do not ignore failed disallow
-      _ = basis.disallow_val(path);
-    }
-    for path in &input.lang.structure {
-      // TODO do not ignore failed disallow
-      _ = basis.disallow_str(path);
-    }
     let groups: paths::PathMap<_> =
       input.groups.iter().map(|(&path, group)| (path, &group.bas_dec)).collect();
-    let res = elapsed::log("mlb_statics::get", || {
-      mlb_statics::get(
-        &mut self.syms_tys,
-        &input.lang,
-        &basis,
-        &input.sources,
-        &groups,
-        &input.root_group_paths,
-      )
-    });
+    let res = self.statics(input, &input.sources, &groups, on_progress);
     self.source_files = res.source_files;
     std::iter::empty()
       .chain(res.mlb_errors.into_iter().filter_map(|err| {
@@ -96,6 +129,7 @@ impl Analysis {
           message: err.to_string(),
           code: err.code(),
           severity: err.severity(),
+          fixes: Vec::new(),
         };
         Some((path, vec![err]))
       }))
@@ -103,29 +137,163 @@ impl Analysis {
         let ds = diagnostic::source_file(file, &self.syms_tys, self.diagnostics_options, &f);
         (path, ds)
       }))
-      .map(|(p, ds)| {
-        let iter = ds.into_iter().filter_map(|mut d| {
-          match input.severities.get(&d.code) {
-            Some(&Some(sev)) => d.severity = sev,
-            Some(None) => return None,
-            None => {}
-          }
-          Some(d)
-        });
-        (p, iter.collect())
-      })
+      .map(|(p, ds)| (p, with_severities(input, ds)))
       .collect()
   }
 
+  /// Runs the statics on the input, but with these sources and groups instead of the input's.
+  fn statics(
+    &mut self,
+    input: &input::Input,
+    sources: &PathMap<String>,
+    groups: &PathMap<&mlb_hir::BasDec>,
+    on_progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+  ) -> mlb_statics::MlbStatics {
+    let mut basis = self.std_basis.basis().clone();
+    for path in &input.lang.val {
+      // TODO do not ignore failed disallow
+      _ = basis.disallow_val(path);
+    }
+    for path in &input.lang.structure {
+      // TODO do not ignore failed disallow
+      _ = basis.disallow_str(path);
+    }
+    elapsed::log("mlb_statics::get", || {
+      mlb_statics::get(
+        &mut self.syms_tys,
+        &input.lang,
+        &basis,
+        sources,
+        groups,
+        &input.root_group_paths,
+        on_progress,
+      )
+    })
+  }
+
+  /// Analyzes only the source file at the path in the input on its own, with only the std basis in
+  /// scope, and returns its diagnostics.
+  ///
+  /// Returns `None` if the input has no source file at the path.
+  pub fn get_one_text_range(
+    &mut self,
+    input: &input::Input,
+    path: PathId,
+  ) -> Option<Vec<Diagnostic<TextRange>>> {
+    let contents = input.sources.get(&path)?;
+    let bs = self.std_basis.basis();
+    let file = mlb_statics::get_one(&mut self.syms_tys, &input.lang, bs, path, contents);
+    let ds =
+      diagnostic::source_file(&file, &self.syms_tys, self.diagnostics_options, |_, b| Some(b));
+    self.source_files.insert(path, file);
+    Some(with_severities(input, ds))
+  }
+
+  /// Returns the dynamics for evaluating the source file at the path on its own.
+  ///
+  /// Only the std basis is available to the evaluation, so the file should not depend on other
+  /// source files. It should also have no errors. Returns `None` if the file uses parts of the std
+  /// basis the dynamics does not implement, as reported by [`Self::unsupported`].
+  #[must_use]
+  pub fn dynamics(&self, path: PathId) -> Option<sml_dynamics::Dynamics<'_>> {
+    let file = self.source_files.get(&path)?;
+    if !evaluate::unsupported(&self.std_basis.basis().env, file).is_empty() {
+      return None;
+    }
+    sml_dynamics::Dynamics::new(self.dynamics_cx(file)?, file.syntax.lower.root.clone())
+  }
+
+  /// Returns the ranges in the source file at the path that use parts of the std basis the dynamics
+  /// does not implement, with a description of what they use.
+  ///
+  /// [`Self::dynamics`] can't evaluate the file if there are any.
+  #[must_use]
+  pub fn unsupported(&self, path: PathId) -> Option<Vec<(RangeUtf16, String)>> {
+    let file = self.source_files.get(&path)?;
+    let iter = evaluate::unsupported(&self.std_basis.basis().env, file).into_iter();
+    Some(iter.filter_map(|(idx, s)| Some((file_hir_range(file, idx)?, s))).collect())
+  }
+
+  fn dynamics_cx<'a>(&'a self, file: &'a mlb_statics::SourceFile) -> Option<sml_dynamics::Cx<'a>> {
+    Some(sml_dynamics::Cx {
+      ars: &file.syntax.lower.arenas,
+      exp: &file.exp_id_statuses,
+      pat: &file.pat_id_statuses,
+      exns: sml_dynamics::Exns::new(&self.std_basis.basis().env)?,
+    })
+  }
+
+  /// Returns the ranges of the top-level `val` declarations in the source file at the path that can
+  /// be evaluated on their own with [`Self::evaluate_val`].
+  #[must_use]
+  pub fn evaluable_vals(&self, path: PathId) -> Option<Vec<RangeUtf16>> {
+    let file = self.source_files.get(&path)?;
+    let str_decs = evaluate::vals(&self.std_basis.basis().env, path, file);
+    let iter = str_decs.into_iter().filter_map(|x| file_hir_range(file, sml_hir::Idx::StrDec(x)));
+    Some(iter.collect())
+  }
+
+  /// Evaluates the top-level `val` declaration starting at the position on its own, with only the
+  /// std basis, for a limited number of steps.
+  #[must_use]
+  pub fn evaluate_val(&self, pos: WithPath<PositionUtf16>) -> Option<Evaluation> {
+    let file = self.source_files.get(&pos.path)?;
+    let str_decs = evaluate::vals(&self.std_basis.basis().env, pos.path, file);
+    let str_dec = str_decs.into_iter().find(|&x| {
+      file_hir_range(file, sml_hir::Idx::StrDec(x)).is_some_and(|range| range.start == pos.val)
+    })?;
+    let dynamics = sml_dynamics::Dynamics::new(self.dynamics_cx(file)?, vec![str_dec])?;
+    Some(evaluate::get(dynamics))
+  }
+
+  /// Returns the range of the syntax for the HIR index in the source file at the path, e.g. the
+  /// syntax the dynamics is about to evaluate.
+  #[must_use]
+  pub fn hir_range(&self, path: PathId, idx: sml_hir::Idx) -> Option<RangeUtf16> {
+    file_hir_range(self.source_files.get(&path)?, idx)
+  }
+
+  /// Returns the lines in the source file at the path with syntax that the dynamics may be about to
+  /// evaluate, e.g. to check where breakpoints may be set.
+  #[must_use]
+  pub fn dynamics_lines(&self, path: PathId) -> Option<FxHashSet<u32>> {
+    let file = self.source_files.get(&path)?;
+    let ars = &file.syntax.lower.arenas;
+    let indices = std::iter::empty()
+      .chain(ars.exp.iter().map(|(x, _)| sml_hir::Idx::Exp(x)))
+      .chain(ars.dec.iter().map(|(x, _)| sml_hir::Idx::Dec(x)))
+      .chain(ars.str_dec.iter().map(|(x, _)| sml_hir::Idx::StrDec(x)))
+      .chain(ars.str_exp.iter().map(|(x, _)| sml_hir::Idx::StrExp(x)));
+    Some(indices.filter_map(|idx| Some(file_hir_range(file, idx)?.start.line)).collect())
+  }
+
+  /// Returns the diagnostics for only the source file at the path, as of when it was last analyzed
+  /// or updated with [`Self::update_one`].
+  ///
+  /// Unlike [`Self::get_many`], this does not analyze any files, so it is cheap.
+  #[must_use]
+  pub fn diagnostics(
+    &self,
+    input: &input::Input,
+    path: PathId,
+  ) -> Option<Vec<Diagnostic<text_pos::RangeUtf16>>> {
+    let file = self.source_files.get(&path)?;
+    let ds = diagnostic::source_file(
+      file,
+      &self.syms_tys,
+      self.diagnostics_options,
+      text_pos::PositionDb::range_utf16,
+    );
+    Some(with_severities(input, ds))
+  }
+
   /// Update only the give path to have the new text, not recalculating diagnostics or anything in
   /// any other paths.
   ///
-  /// # Panics
-  ///
-  /// If we couldn't find the source file for this path.
+  /// Does nothing if this has no source file for the path, e.g. because it was not analyzed yet.
   pub fn update_one(&mut self, input: &input::Input, path: paths::PathId) {
-    let source_file = self.source_files.get_mut(&path).expect("no source file");
-    let contents = input.sources.get(&path).expect("no contents");
+    let Some(source_file) = self.source_files.get_mut(&path) else { return };
+    let Some(contents) = input.sources.get(&path) else { return };
     mlb_statics::update_one(&mut self.syms_tys, &input.lang, source_file, path, contents);
   }
 
@@ -135,10 +303,21 @@ impl Analysis {
     let ft = source_files::file_and_token(&self.source_files, pos)?;
     let mut parts = Vec::<&str>::new();
     let ty_md: Option<String>;
+    let evaluation_md: Option<String>;
     let range = match ft.get_ptr_and_idx() {
       Some((ptr, idx)) => {
         ty_md = ft.file.info.get_ty_md(&self.syms_tys, idx, self.diagnostics_options.lines);
         parts.extend(ty_md.as_deref());
+        evaluation_md = match idx {
+          sml_hir::Idx::Exp(exp)
+            if evaluate::is_exp_evaluable(&self.std_basis.basis().env, pos.path, ft.file, exp) =>
+          {
+            let cx = self.dynamics_cx(ft.file);
+            cx.map(|cx| evaluate::get(sml_dynamics::Dynamics::new_exp(cx, Some(exp))).md())
+          }
+          _ => None,
+        };
+        parts.extend(evaluation_md.as_deref());
         let this = def::Def::Path(def::Path::Regular(pos.path), idx);
         parts.extend(self.get_doc(this));
         let defs = ft.file.info.get_defs(idx);
@@ -200,6 +379,26 @@ impl Analysis {
     Some(iter.collect())
   }
 
+  /// Returns the ranges of the structure items that implement the signature item at this position,
+  /// from every structure that ascribes to the signature.
+  #[must_use]
+  pub fn get_impls(&self, pos: WithPath<PositionUtf16>) -> Option<Vec<WithPath<RangeUtf16>>> {
+    let ft = source_files::file_and_token(&self.source_files, pos)?;
+    let (_, idx) = ft.get_ptr_and_idx()?;
+    let this = def::Def::Path(def::Path::Regular(pos.path), idx);
+    let mut specs = def::Set::from([this]);
+    specs.extend(ft.file.info.get_defs(idx).into_iter().flatten().copied());
+    let impls: def::Set = self
+      .source_files
+      .values()
+      .flat_map(|sf| specs.iter().flat_map(|&spec| sf.info.get_impls(spec)))
+      .collect();
+    let iter = impls
+      .into_iter()
+      .filter_map(|def| source_files::path_and_range(&self.source_files, def.to_regular_idx()?));
+    Some(iter.collect())
+  }
+
   /// Given a position on a `case` expression, return the code and its range to fill the case with
   /// all of the variants of the head's type.
   #[must_use]
@@ -207,7 +406,7 @@ impl Analysis {
     let ft = source_files::file_and_token(&self.source_files, pos)?;
     let (ptr, _) = ft.get_ptr_and_idx()?;
     let ptr = ptr.cast::<ast::CaseExp>()?;
-    let case = ptr.to_node(ft.file.syntax.parse.root.syntax());
+    let case = ptr.to_node(ft.file.syntax.parse.root().syntax());
     let range = TextRange::empty(case.syntax().text_range().end());
     let range = ft.file.syntax.pos_db.range_utf16(range)?;
     let head_ast = case.exp()?;
@@ -235,6 +434,12 @@ impl Analysis {
     Some((range, case.to_string()))
   }
 
+  /// Returns the refactorings available for the range.
+  #[must_use]
+  pub fn refactors(&self, range: WithPath<RangeUtf16>) -> Vec<Refactor> {
+    refactor::get(&self.syms_tys, &self.source_files, range)
+  }
+
   /// Format the given file, and return the end position of the file.
   ///
   /// # Errors
@@ -253,10 +458,10 @@ impl Analysis {
     let buf = match self.diagnostics_options.format {
       config::init::FormatEngine::None => return Err(FormatError::Disabled),
       config::init::FormatEngine::Naive => {
-        sml_naive_fmt::get(&file.syntax.parse.root, tab_size).map_err(FormatError::NaiveFmt)?
+        sml_naive_fmt::get(&file.syntax.parse.root(), tab_size).map_err(FormatError::NaiveFmt)?
       }
       config::init::FormatEngine::Smlfmt => {
-        let contents = file.syntax.parse.root.syntax().to_string();
+        let contents = file.syntax.parse.root().syntax().to_string();
         let mut prog = Command::new("smlfmt")
           .stdin(Stdio::piped())
           .stdout(Stdio::piped())
@@ -288,13 +493,37 @@ impl Analysis {
     let file = self.source_files.get(&path)?;
     let ret: Vec<_> = file
       .info
-      .document_symbols(&self.syms_tys, path)
+      .document_symbols(&self.syms_tys, def::Path::Regular(path))
       .into_iter()
       .filter_map(|s| symbol(&file.syntax, s))
       .collect();
     Some(ret)
   }
 
+  /// Returns the symbols in all source files that fuzzy-match the query, best matches first. If
+  /// `std_basis`, also includes symbols from the std basis.
+  #[must_use]
+  pub fn workspace_symbols(&self, query: &str, std_basis: bool) -> Vec<WorkspaceSymbol> {
+    let std_basis = std_basis.then_some(&self.std_basis);
+    workspace_symbols::get(&self.syms_tys, &self.source_files, std_basis, query)
+  }
+
+  /// Returns the ranges of the file that can be folded.
+  #[must_use]
+  pub fn folding_ranges(&self, path: PathId) -> Option<Vec<FoldingRange>> {
+    let file = self.source_files.get(&path)?;
+    Some(folding_range::get(&file.syntax))
+  }
+
+  /// Returns the ranges to expand a selection through from the position, smallest first.
+  #[must_use]
+  pub fn selection_ranges(&self, pos: WithPath<PositionUtf16>) -> Option<Vec<RangeUtf16>> {
+    let file = self.source_files.get(&pos.path)?;
+    let offset = file.syntax.pos_db.text_size_utf16(pos.val)?;
+    let iter = selection_range::get(&file.syntax, offset).into_iter();
+    Some(iter.filter_map(|tr| file.syntax.pos_db.range_utf16(tr)).collect())
+  }
+
   /// Returns all references to the position.
   #[must_use]
   pub fn find_all_references(
@@ -315,6 +544,138 @@ impl Analysis {
     Some(ret.collect())
   }
 
+  /// Returns the occurrences of the item at the position in the same file as the position.
+  #[must_use]
+  pub fn document_highlights(
+    &self,
+    pos: WithPath<PositionUtf16>,
+  ) -> Option<Vec<DocumentHighlight>> {
+    document_highlight::get(&self.source_files, pos)
+  }
+
+  /// Returns the function at the position, for a call hierarchy.
+  #[must_use]
+  pub fn prepare_call_hierarchy(&self, pos: WithPath<PositionUtf16>) -> Option<CallHierarchyItem> {
+    call_hierarchy::prepare(&self.syms_tys, &self.source_files, pos)
+  }
+
+  /// Returns the functions that call the function at the position, and where they call it.
+  #[must_use]
+  pub fn incoming_calls(&self, pos: WithPath<PositionUtf16>) -> Option<Vec<CallHierarchyCall>> {
+    call_hierarchy::incoming(&self.syms_tys, &self.source_files, pos)
+  }
+
+  /// Returns the functions that the function at the position calls, and where it calls them.
+  #[must_use]
+  pub fn outgoing_calls(&self, pos: WithPath<PositionUtf16>) -> Option<Vec<CallHierarchyCall>> {
+    call_hierarchy::outgoing(&self.syms_tys, &self.source_files, pos)
+  }
+
+  /// Returns the range and text of the name to rename at the position.
+  ///
+  /// # Errors
+  ///
+  /// If there is no name at the position that can be renamed.
+  pub fn prepare_rename(
+    &self,
+    pos: WithPath<PositionUtf16>,
+  ) -> Result<(RangeUtf16, String), RenameError> {
+    let target = rename::target(&self.source_files, pos)?;
+    Ok((target.range, target.name.as_str().to_owned()))
+  }
+
+  /// Returns the edits, grouped by path, to rename the item at the position to the new name.
+  ///
+  /// This analyzes all the files again with the rename applied, which may be slow. To do that
+  /// elsewhere, e.g. on another thread, use [`Self::start_rename`] and [`Rename::check`] instead.
+  ///
+  /// # Errors
+  ///
+  /// If the item can't be renamed, or the new name is invalid or would conflict with another name.
+  pub fn rename(
+    &self,
+    input: &input::Input,
+    pos: WithPath<PositionUtf16>,
+    new_name: &str,
+  ) -> Result<PathMap<Vec<TextEdit>>, RenameError> {
+    let rename = self.start_rename(input, pos, new_name)?;
+    let res = self
+      .fresh()
+      .statics(input, &rename.sources, &rename.groups(), &mut |_| ControlFlow::Continue(()));
+    if rename::changes_meaning(&self.source_files, &res.source_files) {
+      return Err(RenameError::Conflict(rename.new_name));
+    }
+    Ok(rename.edits)
+  }
+
+  /// Returns the rename of the item at the position to the new name, which has yet to be checked
+  /// for conflicts with [`Rename::check`].
+  ///
+  /// # Errors
+  ///
+  /// If the item can't be renamed, or the new name is invalid.
+  pub fn start_rename(
+    &self,
+    input: &input::Input,
+    pos: WithPath<PositionUtf16>,
+    new_name: &str,
+  ) -> Result<Rename, RenameError> {
+    let target = rename::target(&self.source_files, pos)?;
+    let edits = rename::get(&self.source_files, input, &target, new_name)?;
+    let groups = rename::renamed_groups(input, &target, new_name);
+    let sources = rename::renamed_sources(&self.source_files, input, &edits, new_name);
+    Ok(Rename { edits, new_name: new_name.to_owned(), sources, groups })
+  }
+
+  /// Returns the semantic tokens for the file, or only those in the range if one is given.
+  #[must_use]
+  pub fn semantic_tokens(
+    &self,
+    path: PathId,
+    range: Option<RangeUtf16>,
+  ) -> Option<Vec<SemanticToken>> {
+    let file = self.source_files.get(&path)?;
+    let range = match range {
+      None => None,
+      Some(range) => Some(file.syntax.pos_db.text_range_utf16(range)?),
+    };
+    Some(semantic_tokens::get(&self.syms_tys, file, range))
+  }
+
+  /// Returns signature help for the function application around the position.
+  #[must_use]
+  pub fn signature_help(&self, pos: WithPath<PositionUtf16>) -> Option<SignatureHelp> {
+    let ft = source_files::file_and_token(&self.source_files, pos)?;
+    let offset = ft.file.syntax.pos_db.text_size_utf16(pos.val)?;
+    let call = signature_help::call(&ft.token, offset)?;
+    let ast::Exp::PathExp(head) = call.head else { return None };
+    let path = head.path()?;
+    let name = path.name_star_eq_dots().last()?.name_star_eq()?.token.text().to_owned();
+    let ptr = SyntaxNodePtr::new(head.syntax());
+    let idx = ft.file.syntax.lower.ptrs.ast_to_hir(&ptr)?;
+    let ty_scheme = ft.file.info.get_ty_scheme(idx)?;
+    let defs = ft.file.info.get_defs(idx).into_iter().flatten();
+    let fun_case_tuple = defs.clone().find_map(|&def| match def {
+      def::Def::Path(def::Path::Regular(path), sml_hir::Idx::Pat(pat)) => {
+        let arenas = &self.source_files.get(&path)?.syntax.lower.arenas;
+        signature_help::fun_case_tuple(arenas, pat)
+      }
+      _ => None,
+    });
+    let tupled = fun_case_tuple.unwrap_or(call.active_comp.is_some() && call.active_arg == 0);
+    let (label, args) = signature_help::label(&self.syms_tys, &name, &ty_scheme, tupled);
+    let comp = if tupled && call.active_arg == 0 { call.active_comp.unwrap_or(0) } else { 0 };
+    let active_param = args
+      .get(call.active_arg)
+      .filter(|params| comp < params.len())
+      .map(|_| args[..call.active_arg].iter().map(Vec::len).sum::<usize>() + comp)
+      .and_then(|x| u32::try_from(x).ok());
+    let params = args.into_iter().flatten().collect();
+    let docs: Vec<_> = defs.filter_map(|&def| self.get_doc(def)).collect();
+    let documentation = (!docs.is_empty()).then(|| docs.join(DOC_SEPARATOR));
+    Some(SignatureHelp { label, params, active_param, documentation })
+  }
+
   /// Returns all completions for the position.
   #[must_use]
   pub fn completions(&self, pos: WithPath<PositionUtf16>) -> Option<Vec<CompletionItem>> {
@@ -422,41 +783,54 @@ impl Analysis {
         _ => None,
       })
       .flat_map(|xs| xs.iter().filter_map(|x| x.exp))
-      .filter_map(|mut exp| {
-        let func = loop {
-          match &arenas.exp[exp] {
-            sml_hir::Exp::Fn(arms, sml_hir::FnFlavor::FunArg) => exp = arms.first()?.exp?,
-            sml_hir::Exp::App(func, _) => break (*func)?,
-            _ => unreachable!("non-(FunArg Fn) or App exp for Fun Val"),
-          }
-        };
-        let fst_arm_body = match &arenas.exp[func] {
-          sml_hir::Exp::Fn(arms, sml_hir::FnFlavor::FunCase { .. }) => arms.first()?.exp?,
-          _ => unreachable!("non-(FunCase Fn) for Fun Val App func"),
-        };
-        match &arenas.exp[fst_arm_body] {
-          sml_hir::Exp::Typed(_, _, sml_hir::TypedFlavor::Fun) => None,
-          _ => Some((exp, fst_arm_body)),
-        }
-      })
-      .filter_map(|(ptr_exp, exp)| {
-        let ptr = file.syntax.lower.ptrs.hir_to_ast(ptr_exp.into())?;
-        let fun_bind_ptr = ptr.cast::<sml_syntax::ast::FunBind>()?;
-        let fun_bind = fun_bind_ptr.to_node(file.syntax.parse.root.syntax());
-        let case = fun_bind.fun_bind_cases().next()?;
-        if case.ty_annotation().is_some() {
-          return None;
-        }
-        let end = case.pats().last()?.syntax().text_range().end();
-        let position = file.syntax.pos_db.position_utf16(end)?;
-        let label = file.info.show_ty_annot(&self.syms_tys, exp)?;
-        Some(InlayHint { position, label })
-      });
+      .filter_map(|exp| fun_return_ty_hint(&self.syms_tys, file, exp));
     hints.extend(fun_return_ty_hints);
     Some(hints)
   }
 }
 
+/// Returns the `FunCase` `fn` for the expression of a `fun` binding.
+fn fun_case_fn(
+  arenas: &sml_hir::Arenas,
+  mut exp: sml_hir::la_arena::Idx<sml_hir::Exp>,
+) -> sml_hir::ExpIdx {
+  loop {
+    match &arenas.exp[exp] {
+      sml_hir::Exp::Fn(arms, sml_hir::FnFlavor::FunArg) => exp = arms.first()?.exp?,
+      sml_hir::Exp::App(func, _) => break *func,
+      _ => unreachable!("non-(FunArg Fn) or App exp for Fun Val"),
+    }
+  }
+}
+
+/// Returns the inlay hint for the return type of the `fun` binding with this expression, unless it
+/// already has a return type annotation.
+fn fun_return_ty_hint(
+  st: &sml_statics_types::St,
+  file: &mlb_statics::SourceFile,
+  exp: sml_hir::la_arena::Idx<sml_hir::Exp>,
+) -> Option<InlayHint> {
+  let arenas = &file.syntax.lower.arenas;
+  let fst_arm_body = match &arenas.exp[fun_case_fn(arenas, exp)?] {
+    sml_hir::Exp::Fn(arms, sml_hir::FnFlavor::FunCase { .. }) => arms.first()?.exp?,
+    _ => unreachable!("non-(FunCase Fn) for Fun Val App func"),
+  };
+  if matches!(&arenas.exp[fst_arm_body], sml_hir::Exp::Typed(_, _, sml_hir::TypedFlavor::Fun)) {
+    return None;
+  }
+  let ptr = file.syntax.lower.ptrs.hir_to_ast(exp.into())?;
+  let fun_bind_ptr = ptr.cast::<sml_syntax::ast::FunBind>()?;
+  let fun_bind = fun_bind_ptr.to_node(file.syntax.parse.root().syntax());
+  let case = fun_bind.fun_bind_cases().next()?;
+  if case.ty_annotation().is_some() {
+    return None;
+  }
+  let end = case.pats().last()?.syntax().text_range().end();
+  let position = file.syntax.pos_db.position_utf16(end)?;
+  let label = file.info.show_ty_annot(st, fst_arm_body)?;
+  Some(InlayHint { position, label })
+}
+
 fn inlay_hint_pat(
   ac: &mut Vec<InlayHint>,
   st: &sml_statics_types::St,
@@ -603,6 +977,15 @@ pub struct InlayHint {
   pub label: String,
 }
 
+/// A text edit.
+#[derive(Debug, Clone)]
+pub struct TextEdit {
+  /// The range to replace.
+  pub range: text_pos::RangeUtf16,
+  /// The text to replace it with.
+  pub new_text: String,
+}
+
 /// A completion item.
 #[derive(Debug)]
 pub struct CompletionItem {
@@ -616,6 +999,51 @@ pub struct CompletionItem {
   pub documentation: Option<String>,
 }
 
+/// A rename of an item, with the edits to make, that has yet to be checked for conflicts.
+#[derive(Debug)]
+pub struct Rename {
+  edits: PathMap<Vec<TextEdit>>,
+  new_name: String,
+  /// The contents of the source files after the rename.
+  sources: PathMap<String>,
+  /// The group files after the rename.
+  groups: PathMap<mlb_hir::BasDec>,
+}
+
+impl Rename {
+  /// Checks that the rename would not change what any name refers to, by analyzing all the files
+  /// with only the std basis of the analysis, both before and after the rename. Returns the edits,
+  /// grouped by path.
+  ///
+  /// This is slow for big inputs, but needs no analyzed files, so it can happen on another thread
+  /// than the one that started the rename.
+  ///
+  /// # Errors
+  ///
+  /// If the new name would conflict with another name.
+  pub fn check(
+    self,
+    analysis: &Analysis,
+    input: &input::Input,
+  ) -> Result<PathMap<Vec<TextEdit>>, RenameError> {
+    let groups: PathMap<_> =
+      input.groups.iter().map(|(&path, group)| (path, &group.bas_dec)).collect();
+    let old =
+      analysis.fresh().statics(input, &input.sources, &groups, &mut |_| ControlFlow::Continue(()));
+    let new = analysis
+      .fresh()
+      .statics(input, &self.sources, &self.groups(), &mut |_| ControlFlow::Continue(()));
+    if rename::changes_meaning(&old.source_files, &new.source_files) {
+      return Err(RenameError::Conflict(self.new_name));
+    }
+    Ok(self.edits)
+  }
+
+  fn groups(&self) -> PathMap<&mlb_hir::BasDec> {
+    self.groups.iter().map(|(&path, bas_dec)| (path, bas_dec)).collect()
+  }
+}
+
 fn get_env<'e>(mut env: &'e Env, path: &sml_syntax::ast::Path) -> Option<&'e Env> {
   for part in path.name_star_eq_dots() {
     if part.dot().is_none() {
@@ -627,3 +1055,23 @@ fn get_env<'e>(mut env: &'e Env, path: &sml_syntax::ast::Path) -> Option<&'e Env
   }
   Some(env)
 }
+
+/// Applies the severities from the input config to the diagnostics, removing the ignored ones.
+fn with_severities<R>(input: &input::Input, ds: Vec<Diagnostic<R>>) -> Vec<Diagnostic<R>> {
+  let iter = ds.into_iter().filter_map(|mut d| {
+    match input.severities.get(&d.code) {
+      Some(&Some(sev)) => d.severity = sev,
+      Some(None) => return None,
+      None => {}
+    }
+    Some(d)
+  });
+  iter.collect()
+}
+
+/// Returns the range in the file of the HIR index, without surrounding trivia.
+fn file_hir_range(file: &mlb_statics::SourceFile, idx: sml_hir::Idx) -> Option<RangeUtf16> {
+  let ptr = file.syntax.lower.ptrs.hir_to_ast(idx)?;
+  let node = ptr.to_node(file.syntax.parse.root().syntax());
+  file.syntax.pos_db.range_utf16(selection_range::trimmed_range(&node)?)
+}
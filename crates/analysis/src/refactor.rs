@@ -0,0 +1,514 @@
+//! Refactorings, which are code actions not tied to any diagnostic.
+
+mod curry;
+mod extract;
+mod inline;
+
+use crate::{selection_range, source_files, InlayHint, TextEdit};
+use fast_hash::FxHashSet;
+use paths::{PathId, PathMap, WithPath};
+use sml_statics_types::ty::TyData;
+use sml_syntax::ast::{self, AstNode as _, SyntaxNodePtr};
+use sml_syntax::kind::{SyntaxKind as SK, SyntaxNode};
+use text_pos::RangeUtf16;
+use text_size_util::{TextRange, TextSize};
+
+/// A refactoring.
+#[derive(Debug)]
+pub struct Refactor {
+  /// A short description of it.
+  pub title: String,
+  /// The kind of refactoring.
+  pub kind: RefactorKind,
+  /// The edits to each file. The edits to a file do not overlap.
+  pub edits: PathMap<Vec<TextEdit>>,
+  /// Whether some changes it needs could not be made by the edits, and so must be made by hand.
+  pub incomplete: bool,
+}
+
+/// A kind of refactoring.
+#[derive(Debug, Clone, Copy)]
+pub enum RefactorKind {
+  /// Makes something new out of existing code.
+  Extract,
+  /// Replaces a name with its definition.
+  Inline,
+  /// Changes existing code.
+  Rewrite,
+}
+
+pub(crate) fn get(
+  st: &sml_statics_types::St,
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  range: WithPath<RangeUtf16>,
+) -> Vec<Refactor> {
+  let mut ret = Vec::<Refactor>::new();
+  let Some(ft) = source_files::file_and_token(source_files, range.path.wrap(range.val.start))
+  else {
+    return ret;
+  };
+  generate_sig(&mut ret, st, range.path, &ft);
+  annotate_pat(&mut ret, st, range.path, &ft);
+  annotate_fun(&mut ret, st, range.path, &ft);
+  expand_record_pat(&mut ret, st, range.path, &ft);
+  inline::get(&mut ret, source_files, range.path, &ft);
+  curry::get(&mut ret, source_files, range.path, &ft);
+  let pos_db = &ft.file.syntax.pos_db;
+  if let (Some(start), Some(end)) =
+    (pos_db.text_size_utf16(range.val.start), pos_db.text_size_utf16(range.val.end))
+  {
+    extract::get(&mut ret, range.path, ft.file, TextRange::new(start, end));
+  }
+  ret
+}
+
+/// Generates a signature from a structure that has no ascription, optionally ascribing the
+/// structure to it.
+fn generate_sig(
+  ac: &mut Vec<Refactor>,
+  st: &sml_statics_types::St,
+  path: PathId,
+  ft: &source_files::FileAndToken<'_>,
+) -> Option<()> {
+  let str_bind = ft.token.parent_ancestors().find_map(ast::StrBind::cast)?;
+  let str_dec = str_bind.syntax().parent()?;
+  // only top-level structures are in the basis for the file.
+  let nested = str_dec
+    .ancestors()
+    .any(|node| matches!(node.kind(), SK::StructStrExp | SK::LetStrExp | SK::FunctorBind));
+  if nested || str_bind.ascription_tail().is_some() {
+    return None;
+  }
+  let name = str_bind.name()?;
+  let sig = ft.file.info.show_sig(st, &str_util::Name::new(name.text()))?;
+  let sig_name = sig_name(name.text());
+  let sig_edit = insert_before(ft.file, &str_dec, &format!("signature {sig_name} = {sig}"))?;
+  let pos_db = &ft.file.syntax.pos_db;
+  let asc_range = pos_db.range_utf16(TextRange::empty(name.text_range().end()))?;
+  let asc_edit = TextEdit { range: asc_range, new_text: format!(" : {sig_name}") };
+  ac.push(Refactor {
+    title: format!("Generate signature `{sig_name}`"),
+    kind: RefactorKind::Extract,
+    edits: one_file(path, vec![sig_edit.clone()]),
+    incomplete: false,
+  });
+  ac.push(Refactor {
+    title: format!("Generate signature `{sig_name}` and ascribe to it"),
+    kind: RefactorKind::Extract,
+    edits: one_file(path, vec![sig_edit, asc_edit]),
+    incomplete: false,
+  });
+  Some(())
+}
+
+/// Returns the edits for a refactoring that only changes one file.
+fn one_file(path: PathId, edits: Vec<TextEdit>) -> PathMap<Vec<TextEdit>> {
+  let mut ret = PathMap::<Vec<TextEdit>>::default();
+  ret.insert(path, edits);
+  ret
+}
+
+/// Returns an edit that inserts the text on its own lines before the node, at the same indentation
+/// as the node, with a blank line between them.
+fn insert_before(
+  file: &mlb_statics::SourceFile,
+  node: &SyntaxNode,
+  text: &str,
+) -> Option<TextEdit> {
+  let root_text = file.syntax.parse.root().syntax().to_string();
+  let node_start = selection_range::trimmed_range(node)?.start();
+  let line_start = root_text[..usize::from(node_start)].rfind('\n').map_or(0, |idx| idx + 1);
+  let before_node = &root_text[line_start..usize::from(node_start)];
+  let indent: String = before_node.chars().take_while(|c| c.is_whitespace()).collect();
+  let mut new_text = String::new();
+  for line in text.lines() {
+    new_text.push_str(&indent);
+    new_text.push_str(line);
+    new_text.push('\n');
+  }
+  new_text.push('\n');
+  let start = if before_node.trim().is_empty() {
+    TextSize::try_from(line_start).ok()?
+  } else {
+    new_text.insert(0, '\n');
+    new_text.push_str(&indent);
+    node_start
+  };
+  Some(TextEdit { range: file.syntax.pos_db.range_utf16(TextRange::empty(start))?, new_text })
+}
+
+/// Returns the conventional signature name for the structure name, like `LIST_PAIR` for
+/// `ListPair`.
+fn sig_name(str_name: &str) -> String {
+  let mut ret = String::with_capacity(str_name.len() + 1);
+  let mut prev_lower = false;
+  for c in str_name.chars() {
+    if prev_lower && c.is_ascii_uppercase() {
+      ret.push('_');
+    }
+    prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+    ret.push(c.to_ascii_uppercase());
+  }
+  ret
+}
+
+/// Adds a type annotation to the name pattern at the cursor, like the inlay hint for it.
+fn annotate_pat(
+  ac: &mut Vec<Refactor>,
+  st: &sml_statics_types::St,
+  path: PathId,
+  ft: &source_files::FileAndToken<'_>,
+) -> Option<()> {
+  let (ptr, idx) = ft.get_ptr_and_idx()?;
+  let sml_hir::Idx::Pat(pat) = idx else { return None };
+  if !matches!(
+    ft.file.syntax.lower.arenas.pat[pat],
+    sml_hir::Pat::Wild | sml_hir::Pat::Con(_, None)
+  ) {
+    return None;
+  }
+  let node = ptr.to_node(ft.file.syntax.parse.root().syntax());
+  if node.ancestors().any(|node| node.kind() == SK::TypedPat) {
+    return None;
+  }
+  // parentheses are needed unless the pattern is already delimited, like by commas in a tuple.
+  let parens =
+    !matches!(node.parent()?.kind(), SK::ValBind | SK::PatArg | SK::ParenPat | SK::LabAndPatPatRow);
+  let mut hints = Vec::<InlayHint>::new();
+  crate::inlay_hint_pat(&mut hints, st, ft.file, Some(pat), parens);
+  let edits = hint_edits(&node, hints)?;
+  ac.push(Refactor {
+    title: "Add type annotation".to_owned(),
+    kind: RefactorKind::Rewrite,
+    edits: one_file(path, edits),
+    incomplete: false,
+  });
+  Some(())
+}
+
+/// Adds type annotations to all the parameters and the return type of the `fun` whose name is at
+/// the cursor, like the inlay hints for them.
+fn annotate_fun(
+  ac: &mut Vec<Refactor>,
+  st: &sml_statics_types::St,
+  path: PathId,
+  ft: &source_files::FileAndToken<'_>,
+) -> Option<()> {
+  let head = ft.token.parent_ancestors().find_map(ast::FunBindCaseHead::cast)?;
+  let fun_bind = head.syntax().ancestors().find_map(ast::FunBind::cast)?;
+  let arenas = &ft.file.syntax.lower.arenas;
+  let exp = fun_val_bind(ft.file, &fun_bind)?.exp?;
+  let sml_hir::Exp::Fn(arms, sml_hir::FnFlavor::FunCase { tuple }) =
+    &arenas.exp[crate::fun_case_fn(arenas, exp)?]
+  else {
+    return None;
+  };
+  let pat = arms.first()?.pat?;
+  let pats: Vec<sml_hir::PatIdx> = if *tuple {
+    match &arenas.pat[pat] {
+      sml_hir::Pat::Record { rows, .. } => rows.iter().map(|&(_, pat)| pat).collect(),
+      _ => return None,
+    }
+  } else {
+    vec![Some(pat)]
+  };
+  let mut hints = Vec::<InlayHint>::new();
+  for pat in pats {
+    crate::inlay_hint_pat(&mut hints, st, ft.file, pat, true);
+  }
+  hints.extend(crate::fun_return_ty_hint(st, ft.file, exp));
+  let edits = hint_edits(fun_bind.syntax(), hints)?;
+  let name = head
+    .syntax()
+    .descendants_with_tokens()
+    .filter_map(|x| x.into_token())
+    .find(|tok| matches!(tok.kind(), SK::Name | SK::Star | SK::Eq))?;
+  ac.push(Refactor {
+    title: format!("Add type annotations to `{}`", name.text()),
+    kind: RefactorKind::Rewrite,
+    edits: one_file(path, edits),
+    incomplete: false,
+  });
+  Some(())
+}
+
+/// Expands the record pattern at the cursor to have a row for every field of its type, instead of
+/// `...` or missing some fields.
+fn expand_record_pat(
+  ac: &mut Vec<Refactor>,
+  st: &sml_statics_types::St,
+  path: PathId,
+  ft: &source_files::FileAndToken<'_>,
+) -> Option<()> {
+  let pat = ft.token.parent_ancestors().find_map(ast::RecordPat::cast)?;
+  let idx = ft.file.syntax.lower.ptrs.ast_to_hir(&SyntaxNodePtr::new(pat.syntax()))?;
+  let sml_hir::Idx::Pat(pat_idx) = idx else { return None };
+  let sml_hir::Pat::Record { rows, .. } = &ft.file.syntax.lower.arenas.pat[pat_idx] else {
+    return None;
+  };
+  // only a resolved record type has all the fields.
+  let TyData::Record(fields) = st.tys.data(ft.file.info.get_ty(idx)?) else { return None };
+  let missing: Vec<_> = fields
+    .keys()
+    .filter(|&lab| rows.iter().all(|(row_lab, _)| row_lab != lab))
+    .map(|lab| format!("{lab} = _"))
+    .collect();
+  let mut inners: Vec<_> = pat.pat_rows().filter_map(|row| row.pat_row_inner()).collect();
+  let rest = match inners.last() {
+    Some(ast::PatRowInner::RestPatRow(_)) => inners.pop(),
+    _ => None,
+  };
+  if missing.is_empty() && rest.is_none() {
+    return None;
+  }
+  let prev_end = match inners.last() {
+    Some(inner) => selection_range::trimmed_range(inner.syntax())?.end(),
+    None => pat.l_curly()?.text_range().end(),
+  };
+  let (range, new_text) = match (rest, missing.is_empty()) {
+    (Some(rest), false) => (selection_range::trimmed_range(rest.syntax())?, missing.join(", ")),
+    (Some(rest), true) => {
+      let rest = selection_range::trimmed_range(rest.syntax())?;
+      (TextRange::new(prev_end, rest.end()), String::new())
+    }
+    (None, _) => {
+      let sep = if inners.is_empty() { "" } else { ", " };
+      (TextRange::empty(prev_end), format!("{sep}{}", missing.join(", ")))
+    }
+  };
+  let edit = TextEdit { range: ft.file.syntax.pos_db.range_utf16(range)?, new_text };
+  ac.push(Refactor {
+    title: "Expand to all fields".to_owned(),
+    kind: RefactorKind::Rewrite,
+    edits: one_file(path, vec![edit]),
+    incomplete: false,
+  });
+  Some(())
+}
+
+/// Returns the binding in the HIR for the `fun` binding.
+fn fun_val_bind<'a>(
+  file: &'a mlb_statics::SourceFile,
+  fun_bind: &ast::FunBind,
+) -> Option<&'a sml_hir::ValBind> {
+  let ptr = SyntaxNodePtr::new(fun_bind.syntax());
+  file
+    .syntax
+    .lower
+    .arenas
+    .dec
+    .iter()
+    .filter_map(|(_, dec)| match dec {
+      sml_hir::Dec::Val(_, val_binds, sml_hir::ValFlavor::Fun) => Some(val_binds),
+      _ => None,
+    })
+    .flatten()
+    .find(|val_bind| {
+      val_bind
+        .exp
+        .is_some_and(|exp| file.syntax.lower.ptrs.hir_to_ast(exp.into()) == Some(ptr.clone()))
+    })
+}
+
+/// Returns edits that insert the inlay hints into the source, or `None` if there are no hints, or
+/// if some hint cannot be written in source.
+///
+/// The hints are for somewhere in the node. The types in them are qualified as little as possible
+/// for the structures containing the node.
+fn hint_edits(node: &SyntaxNode, hints: Vec<InlayHint>) -> Option<Vec<TextEdit>> {
+  if hints.is_empty() {
+    return None;
+  }
+  // the structures containing the node, outermost first.
+  let mut strs: Vec<_> = node.ancestors().filter_map(ast::StrBind::cast).collect();
+  strs.reverse();
+  hints
+    .into_iter()
+    .map(|hint| {
+      // types with unsolved type variables are shown with names that are not valid in source.
+      if hint.label.contains('?') {
+        return None;
+      }
+      let range = RangeUtf16 { start: hint.position, end: hint.position };
+      Some(TextEdit { range, new_text: unqualify(node, &hint.label, &strs)? })
+    })
+    .collect()
+}
+
+/// Removes the qualification on the paths in the type, to the extent they are qualified by the
+/// structures, which contain the node, before which the type will be written.
+///
+/// A structure's name is not in scope inside the structure, so that qualification must be removed.
+/// Returns `None` if the unqualified path might then refer to something else.
+fn unqualify(node: &SyntaxNode, ty: &str, strs: &[ast::StrBind]) -> Option<String> {
+  let mut ret = String::with_capacity(ty.len());
+  let mut rest = ty;
+  while let Some(c) = rest.chars().next() {
+    let word_len = rest.find(|c: char| !is_path_char(c)).unwrap_or(rest.len());
+    if word_len == 0 {
+      ret.push(c);
+      rest = &rest[c.len_utf8()..];
+      continue;
+    }
+    let (word, after) = rest.split_at(word_len);
+    let parts: Vec<_> = word.split('.').collect();
+    let n = parts
+      .iter()
+      .zip(strs)
+      .take(parts.len() - 1)
+      .take_while(|&(a, b)| b.name().is_some_and(|name| name.text() == *a))
+      .count();
+    if n != 0 && !refers_to_item_in(node, &parts[n..], &strs[n - 1]) {
+      return None;
+    }
+    ret.push_str(&parts[n..].join("."));
+    rest = after;
+  }
+  Some(ret)
+}
+
+/// Returns whether the path, written before the node, refers to the item with that name declared
+/// directly in the structure, and no other item with that name is declared there.
+///
+/// The item a qualified path like `S.t` refers to is the last `t` declared in `S`, but the types we
+/// unqualify are shown qualified like that even if they were shadowed, so we require there be only
+/// one.
+fn refers_to_item_in(node: &SyntaxNode, path: &[&str], str_bind: &ast::StrBind) -> bool {
+  let Some(ast::StrExp::StructStrExp(body)) = str_bind.eq_str_exp().and_then(|x| x.str_exp())
+  else {
+    return false;
+  };
+  let Some((&name, rest)) = path.split_first() else { return false };
+  let kinds: &[SK] =
+    if rest.is_empty() { &[SK::TyBind, SK::DatBind, SK::DatCopyDec] } else { &[SK::StrBind] };
+  let point = node.text_range().start();
+  let Some(root) = node.ancestors().last() else { return false };
+  let mut last: Option<SyntaxNode> = None;
+  let mut in_body = 0usize;
+  let mut last_open: Option<TextSize> = None;
+  for x in root.descendants() {
+    if x.text_range().end() > point || !in_scope(&x, point) {
+      continue;
+    }
+    if x.kind() == SK::OpenDec {
+      last_open = Some(x.text_range().start());
+    }
+    if !kinds.contains(&x.kind()) || bound_name(&x).as_deref() != Some(name) {
+      continue;
+    }
+    if scope(&x).as_ref() == Some(body.syntax()) {
+      in_body += 1;
+    }
+    last = Some(x);
+  }
+  // an `open` after the item might have shadowed it, and we can't easily tell what it opened.
+  last.is_some_and(|last| {
+    in_body == 1
+      && scope(&last).as_ref() == Some(body.syntax())
+      && !last_open.is_some_and(|open| open > last.text_range().start())
+  })
+}
+
+/// Returns whether the things declared by the node are in scope at the point.
+fn in_scope(node: &SyntaxNode, point: TextSize) -> bool {
+  node.ancestors().skip(1).all(|node| match node.kind() {
+    SK::StructStrExp | SK::SigSigExp | SK::LetExp | SK::LetStrExp => {
+      node.text_range().contains(point)
+    }
+    SK::LocalDecHd => node.parent().is_some_and(|local| local.text_range().contains(point)),
+    _ => true,
+  })
+}
+
+/// Returns the innermost syntax the node is declared in, whose declarations are not in scope
+/// outside it.
+fn scope(node: &SyntaxNode) -> Option<SyntaxNode> {
+  node.ancestors().skip(1).find(|node| {
+    matches!(
+      node.kind(),
+      SK::StructStrExp | SK::SigSigExp | SK::LetExp | SK::LetStrExp | SK::LocalDecHd
+    )
+  })
+}
+
+/// Returns the name bound by the node, like a `TyBind` or `StrBind`.
+fn bound_name(node: &SyntaxNode) -> Option<String> {
+  let tok = node
+    .children_with_tokens()
+    .filter_map(|x| x.into_token())
+    .find(|tok| tok.kind() == SK::Name)?;
+  Some(tok.text().to_owned())
+}
+
+fn is_path_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || matches!(c, '_' | '\'' | '.')
+}
+
+/// Returns a name based on the base name that is not used anywhere in the file.
+fn fresh_name(file: &mlb_statics::SourceFile, base: &str) -> String {
+  let names = used_names(file);
+  let mut ret = base.to_owned();
+  let mut n = 1u32;
+  while names.contains(&ret) {
+    ret = format!("{base}{n}");
+    n += 1;
+  }
+  ret
+}
+
+/// Returns all the names used in the file.
+fn used_names(file: &mlb_statics::SourceFile) -> FxHashSet<String> {
+  file
+    .syntax
+    .parse
+    .root()
+    .syntax()
+    .descendants_with_tokens()
+    .filter_map(|x| x.into_token())
+    .filter(|tok| tok.kind() == SK::Name)
+    .map(|tok| tok.text().to_owned())
+    .collect()
+}
+
+/// How tightly an expression holds together, for deciding whether it needs parentheses.
+enum Prec {
+  Atomic,
+  App,
+  Infix(String),
+  Low,
+}
+
+impl Prec {
+  fn get(exp: &ast::Exp) -> Self {
+    match exp {
+      ast::Exp::HoleExp(_)
+      | ast::Exp::WildcardExp(_)
+      | ast::Exp::OpAndalsoExp(_)
+      | ast::Exp::OpOrelseExp(_)
+      | ast::Exp::SConExp(_)
+      | ast::Exp::PathExp(_)
+      | ast::Exp::RecordExp(_)
+      | ast::Exp::SelectorExp(_)
+      | ast::Exp::ParenExp(_)
+      | ast::Exp::TupleExp(_)
+      | ast::Exp::ListExp(_)
+      | ast::Exp::VectorExp(_)
+      | ast::Exp::SeqExp(_)
+      | ast::Exp::LetExp(_) => Prec::Atomic,
+      ast::Exp::AppExp(_) => Prec::App,
+      ast::Exp::InfixExp(exp) => match exp.name_star_eq() {
+        Some(op) => Prec::Infix(op.token.text().to_owned()),
+        None => Prec::Low,
+      },
+      ast::Exp::TypedExp(_)
+      | ast::Exp::AndalsoExp(_)
+      | ast::Exp::OrelseExp(_)
+      | ast::Exp::HandleExp(_)
+      | ast::Exp::RaiseExp(_)
+      | ast::Exp::IfExp(_)
+      | ast::Exp::WhileExp(_)
+      | ast::Exp::CaseExp(_)
+      | ast::Exp::FnExp(_) => Prec::Low,
+    }
+  }
+}
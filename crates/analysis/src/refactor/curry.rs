@@ -0,0 +1,355 @@
+//! Converting a `fun` between curried and tupled forms.
+
+use super::{fun_val_bind, used_names, Prec, Refactor, RefactorKind};
+use crate::{selection_range, source_files, TextEdit};
+use paths::{PathId, PathMap};
+use sml_statics_types::def;
+use sml_syntax::ast::{self, AstNode as _};
+use sml_syntax::kind::{SyntaxKind as SK, SyntaxNode};
+use text_size_util::TextRange;
+
+/// The form a `fun` takes its arguments in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Form {
+  /// Like `fun f a b = ...`.
+  Curried,
+  /// Like `fun f (a, b) = ...`.
+  Tupled,
+}
+
+/// A replacement of the text in a range.
+type Edit = (TextRange, String);
+
+/// The edits for the definition or for one use of the `fun`.
+struct Group {
+  path: PathId,
+  /// The range the edits replace entirely, if they replace something entirely.
+  whole: Option<TextRange>,
+  edits: Vec<Edit>,
+}
+
+/// Converts the `fun` whose name is at the cursor from curried form to tupled form or vice versa,
+/// rewriting the uses of it in every file.
+///
+/// Uses that cannot be rewritten are left as they are, and counted in the title.
+pub(super) fn get(
+  ac: &mut Vec<Refactor>,
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  path: PathId,
+  ft: &source_files::FileAndToken<'_>,
+) -> Option<()> {
+  let head = ft.token.parent_ancestors().find_map(ast::PrefixFunBindCaseHead::cast)?;
+  let fun_bind = head.syntax().ancestors().find_map(ast::FunBind::cast)?;
+  let name = head.name_star_eq()?.token.text().to_owned();
+  let (form, n) = form(&fun_bind)?;
+  let val_bind = fun_val_bind(ft.file, &fun_bind)?;
+  let def = def::Def::Path(def::Path::Regular(path), val_bind.pat?.into());
+  let mut groups = vec![Group { path, whole: None, edits: def_edits(ft.file, &fun_bind, form)? }];
+  let mut flagged = 0usize;
+  for (&p, sf) in source_files {
+    let mut uses = Vec::<SyntaxNode>::new();
+    let root = sf.syntax.parse.root();
+    for idx in sf.info.get_with_def(def) {
+      if !matches!(idx, sml_hir::Idx::Exp(_)) {
+        continue;
+      }
+      let Some(ptr) = sf.syntax.lower.ptrs.hir_to_ast(idx) else { continue };
+      let node = ptr.to_node(root.syntax());
+      if !uses.contains(&node) {
+        uses.push(node);
+      }
+    }
+    if uses.is_empty() {
+      continue;
+    }
+    let cx = FileCx { root_text: root.syntax().to_string(), names: fresh_names(sf, n) };
+    for use_node in uses {
+      match use_edits(&cx, form, n, &use_node) {
+        Some((whole, edits)) => groups.push(Group { path: p, whole, edits }),
+        None => flagged += 1,
+      }
+    }
+  }
+  // a use in the arguments of a partial application that gets replaced entirely cannot also be
+  // rewritten where it is.
+  let keep: Vec<bool> = groups
+    .iter()
+    .enumerate()
+    .map(|(idx, group)| {
+      !groups.iter().enumerate().any(|(other_idx, other)| {
+        other_idx != idx
+          && other.path == group.path
+          && other
+            .whole
+            .is_some_and(|whole| group.edits.iter().any(|&(range, _)| whole.contains_range(range)))
+      })
+    })
+    .collect();
+  let mut edits = PathMap::<Vec<TextEdit>>::default();
+  for (group, keep) in groups.into_iter().zip(keep) {
+    if !keep {
+      flagged += 1;
+      continue;
+    }
+    let pos_db = &source_files.get(&group.path)?.syntax.pos_db;
+    let file_edits = edits.entry(group.path).or_default();
+    for (range, new_text) in group.edits {
+      if range.is_empty() && new_text.is_empty() {
+        continue;
+      }
+      file_edits.push(TextEdit { range: pos_db.range_utf16(range)?, new_text });
+    }
+  }
+  let to = match form {
+    Form::Curried => "tupled",
+    Form::Tupled => "curried",
+  };
+  let mut title = format!("Convert `{name}` to {to} form");
+  match flagged {
+    0 => {}
+    1 => title.push_str(" (1 use needs manual changes)"),
+    _ => title.push_str(&format!(" ({flagged} uses need manual changes)")),
+  }
+  ac.push(Refactor { title, kind: RefactorKind::Rewrite, edits, incomplete: flagged != 0 });
+  Some(())
+}
+
+/// Returns the form of the `fun` and how many arguments it takes in that form, if it takes at least
+/// two arguments in the same form in every case.
+fn form(fun_bind: &ast::FunBind) -> Option<(Form, usize)> {
+  let mut ret = None::<(Form, usize)>;
+  for case in fun_bind.fun_bind_cases() {
+    if !matches!(case.fun_bind_case_head()?, ast::FunBindCaseHead::PrefixFunBindCaseHead(_)) {
+      return None;
+    }
+    let pats: Vec<_> = case.pats().collect();
+    let this = match pats.as_slice() {
+      [ast::Pat::TuplePat(pat)] => (Form::Tupled, pat.pat_args().count()),
+      [_] => return None,
+      _ => (Form::Curried, pats.len()),
+    };
+    match ret {
+      None => ret = Some(this),
+      Some(ret) => {
+        if ret != this {
+          return None;
+        }
+      }
+    }
+  }
+  ret.filter(|&(_, n)| n >= 2)
+}
+
+/// Returns the edits to the patterns in each case of the `fun`.
+fn def_edits(
+  file: &mlb_statics::SourceFile,
+  fun_bind: &ast::FunBind,
+  form: Form,
+) -> Option<Vec<Edit>> {
+  let root_text = file.syntax.parse.root().syntax().to_string();
+  let mut ret = Vec::<Edit>::new();
+  for case in fun_bind.fun_bind_cases() {
+    match form {
+      Form::Curried => {
+        let pats: Vec<_> = case.pats().collect();
+        let first = selection_range::trimmed_range(pats.first()?.syntax())?;
+        let last = selection_range::trimmed_range(pats.last()?.syntax())?;
+        let items = pats
+          .iter()
+          .map(|pat| Some((selection_range::trimmed_range(&strip_paren_pat(pat)?)?, false)))
+          .collect::<Option<Vec<_>>>()?;
+        ret.extend(join(first.cover(last), &items, "(", ", ", ")"));
+      }
+      Form::Tupled => {
+        let Some(ast::Pat::TuplePat(tuple)) = case.pats().next() else { return None };
+        let items = tuple
+          .pat_args()
+          .map(|arg| {
+            let pat = arg.pat()?;
+            Some((selection_range::trimmed_range(pat.syntax())?, !atomic_pat(&pat)))
+          })
+          .collect::<Option<Vec<_>>>()?;
+        let whole = selection_range::trimmed_range(tuple.syntax())?;
+        ret.extend(join(whole, &items, space_before(&root_text, whole), " ", ""));
+      }
+    }
+  }
+  Some(ret)
+}
+
+struct FileCx {
+  root_text: String,
+  /// Names for the parameters of new `fn`s, not used anywhere in the file.
+  names: Vec<String>,
+}
+
+/// Returns the edits for the use of the `fun`, and the range they replace entirely, if any. Returns
+/// `None` if the use cannot be rewritten.
+fn use_edits(
+  cx: &FileCx,
+  form: Form,
+  n: usize,
+  use_node: &SyntaxNode,
+) -> Option<(Option<TextRange>, Vec<Edit>)> {
+  if use_node.kind() != SK::PathExp {
+    return None;
+  }
+  let use_range = selection_range::trimmed_range(use_node)?;
+  let func = &cx.root_text[use_range];
+  match form {
+    Form::Tupled => {
+      if let Some(ast::Exp::TupleExp(tuple)) = app_args(use_node, 1).0.pop() {
+        let exps = tuple.exp_args().map(|arg| arg.exp()).collect::<Option<Vec<_>>>()?;
+        if exps.len() == n {
+          let items = exps
+            .iter()
+            .map(|exp| {
+              let parens = !matches!(Prec::get(exp), Prec::Atomic);
+              Some((selection_range::trimmed_range(exp.syntax())?, parens))
+            })
+            .collect::<Option<Vec<_>>>()?;
+          let whole = selection_range::trimmed_range(tuple.syntax())?;
+          return Some((None, join(whole, &items, space_before(&cx.root_text, whole), " ", "")));
+        }
+      }
+      // not applied to a tuple, so keep taking a tuple.
+      let names = &cx.names[..n];
+      let fun = format!("fn ({}) => {func} {}", names.join(", "), names.join(" "));
+      Some((Some(use_range), vec![(use_range, wrap(use_node, fun))]))
+    }
+    Form::Curried => {
+      let (args, app) = app_args(use_node, n);
+      if args.len() == n {
+        let first = selection_range::trimmed_range(args.first()?.syntax())?;
+        let last = selection_range::trimmed_range(args.last()?.syntax())?;
+        let items = args
+          .iter()
+          .map(|arg| Some((selection_range::trimmed_range(strip_paren_exp(arg)?.syntax())?, false)))
+          .collect::<Option<Vec<_>>>()?;
+        return Some((None, join(first.cover(last), &items, "(", ", ", ")")));
+      }
+      // a partial application. the arguments will be evaluated only once all the rest are given,
+      // which is only the same if evaluating them does nothing.
+      if !args.iter().all(|arg| matches!(arg, ast::Exp::PathExp(_) | ast::Exp::SConExp(_))) {
+        return None;
+      }
+      let whole = selection_range::trimmed_range(&app)?;
+      let mut tuple = Vec::<&str>::with_capacity(n);
+      for arg in &args {
+        tuple.push(&cx.root_text[selection_range::trimmed_range(arg.syntax())?]);
+      }
+      let names = &cx.names[args.len()..n];
+      tuple.extend(names.iter().map(String::as_str));
+      let params: Vec<_> = names.iter().map(|name| format!("fn {name} =>")).collect();
+      let fun = format!("{} {func} ({})", params.join(" "), tuple.join(", "));
+      Some((Some(whole), vec![(whole, wrap(&app, fun))]))
+    }
+  }
+}
+
+/// Returns up to `n` arguments the expression is applied to, and the outermost application of it to
+/// them, which is the expression itself if there are none.
+fn app_args(exp: &SyntaxNode, n: usize) -> (Vec<ast::Exp>, SyntaxNode) {
+  let mut cur = exp.clone();
+  let mut args = Vec::<ast::Exp>::new();
+  while args.len() < n {
+    let Some(app) = cur.parent().and_then(ast::AppExp::cast) else { break };
+    if !app.func().is_some_and(|func| func.syntax() == &cur) {
+      break;
+    }
+    let Some(arg) = app.arg() else { break };
+    args.push(arg);
+    cur = app.syntax().clone();
+  }
+  (args, cur)
+}
+
+/// Returns edits that replace the delimiters of the items in the whole range.
+///
+/// Each item is a range, and whether it needs parentheses. The text before the first item becomes
+/// `open`, the text between items becomes `sep`, and the text after the last item becomes `close`.
+fn join(
+  whole: TextRange,
+  items: &[(TextRange, bool)],
+  open: &str,
+  sep: &str,
+  close: &str,
+) -> Vec<Edit> {
+  let mut ret = Vec::<Edit>::with_capacity(items.len() + 1);
+  let mut start = whole.start();
+  let mut text = open.to_owned();
+  for &(range, parens) in items {
+    if parens {
+      text.push('(');
+    }
+    ret.push((TextRange::new(start, range.start()), std::mem::take(&mut text)));
+    if parens {
+      text.push(')');
+    }
+    text.push_str(sep);
+    start = range.end();
+  }
+  text.truncate(text.len() - sep.len());
+  text.push_str(close);
+  ret.push((TextRange::new(start, whole.end()), text));
+  ret
+}
+
+/// Returns a space if there is no whitespace before the range, so that removing the delimiters at
+/// the start of the range does not join what comes before it to what is in it.
+fn space_before(root_text: &str, range: TextRange) -> &'static str {
+  let before = root_text[..usize::from(range.start())].chars().next_back();
+  if before.map_or(true, char::is_whitespace) {
+    ""
+  } else {
+    " "
+  }
+}
+
+/// Returns the text, with parentheses unless the node is already in parentheses.
+fn wrap(node: &SyntaxNode, text: String) -> String {
+  if node.parent().is_some_and(|parent| parent.kind() == SK::ParenExp) {
+    text
+  } else {
+    format!("({text})")
+  }
+}
+
+fn strip_paren_exp(exp: &ast::Exp) -> Option<ast::Exp> {
+  let mut ret = exp.clone();
+  while let ast::Exp::ParenExp(paren) = ret {
+    ret = paren.exp()?;
+  }
+  Some(ret)
+}
+
+fn strip_paren_pat(pat: &ast::Pat) -> Option<SyntaxNode> {
+  let mut ret = pat.clone();
+  while let ast::Pat::ParenPat(paren) = ret {
+    ret = paren.pat()?;
+  }
+  Some(ret.syntax().clone())
+}
+
+/// Returns whether the pattern can be an argument in a `fun` case without parentheses.
+fn atomic_pat(pat: &ast::Pat) -> bool {
+  match pat {
+    ast::Pat::WildcardPat(_)
+    | ast::Pat::SConPat(_)
+    | ast::Pat::RecordPat(_)
+    | ast::Pat::ParenPat(_)
+    | ast::Pat::TuplePat(_)
+    | ast::Pat::ListPat(_)
+    | ast::Pat::VectorPat(_) => true,
+    ast::Pat::ConPat(pat) => pat.pat().is_none(),
+    ast::Pat::InfixPat(_) | ast::Pat::TypedPat(_) | ast::Pat::AsPat(_) | ast::Pat::OrPat(_) => {
+      false
+    }
+  }
+}
+
+/// Returns names not used anywhere in the file, enough for a `fn` for every argument.
+fn fresh_names(file: &mlb_statics::SourceFile, n: usize) -> Vec<String> {
+  let used = used_names(file);
+  (1..).map(|idx| format!("x{idx}")).filter(|name| !used.contains(name)).take(n).collect()
+}
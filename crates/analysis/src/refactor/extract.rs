@@ -0,0 +1,290 @@
+//! Extracting an expression into a `val` or a new `fun`.
+
+use super::{fresh_name, insert_before, one_file, Refactor, RefactorKind};
+use crate::{selection_range, TextEdit};
+use sml_statics_types::def;
+use sml_syntax::ast::{self, AstNode as _, SyntaxNodePtr};
+use sml_syntax::kind::{SyntaxKind as SK, SyntaxNode};
+use text_size_util::TextRange;
+
+/// A reference from the expression to something defined outside of it in this file.
+struct Ref {
+  /// The range of the definition.
+  def: TextRange,
+  /// The name, if this is a reference to a value by an unqualified name.
+  name: Option<str_util::Name>,
+  /// Whether the type scheme of the definition binds type variables, so it may be used at many
+  /// types.
+  poly: bool,
+}
+
+/// An explicit type variable in the expression bound outside of it.
+struct TyVar {
+  name: String,
+  /// The range of the `val` or `fun` that binds it.
+  dec: TextRange,
+}
+
+pub(super) fn get(
+  ac: &mut Vec<Refactor>,
+  path: paths::PathId,
+  file: &mlb_statics::SourceFile,
+  range: TextRange,
+) -> Option<()> {
+  if range.is_empty() {
+    return None;
+  }
+  let root = file.syntax.parse.root();
+  let elem = root.syntax().covering_element(range);
+  let node = match elem.as_node() {
+    Some(node) => node.clone(),
+    None => elem.as_token()?.parent()?,
+  };
+  let exp = node
+    .ancestors()
+    .filter_map(ast::Exp::cast)
+    .find(|exp| selection_range::trimmed_range(exp.syntax()) == Some(range))?;
+  let cx = Cx {
+    path,
+    file,
+    refs: refs(path, file, exp.syntax()),
+    ty_vars: ty_vars(file, exp.syntax())?,
+    name: fresh_name(file, "extracted"),
+    root_text: root.syntax().to_string(),
+    range,
+    exp,
+  };
+  extract_val(ac, &cx);
+  extract_fun(ac, &cx);
+  Some(())
+}
+
+struct Cx<'a> {
+  path: paths::PathId,
+  file: &'a mlb_statics::SourceFile,
+  /// The expression to extract.
+  exp: ast::Exp,
+  /// The trimmed range of the expression.
+  range: TextRange,
+  root_text: String,
+  refs: Vec<Ref>,
+  ty_vars: Vec<TyVar>,
+  /// The name of the new `val` or `fun`.
+  name: String,
+}
+
+impl Cx<'_> {
+  fn text(&self) -> &str {
+    &self.root_text[self.range]
+  }
+}
+
+/// Extracts the expression into a `val` at the end of the declarations of the nearest `let` with
+/// the expression in its body.
+fn extract_val(ac: &mut Vec<Refactor>, cx: &Cx<'_>) -> Option<()> {
+  let let_exp =
+    cx.exp.syntax().ancestors().skip(1).filter_map(ast::LetExp::cast).find(|let_exp| {
+      let_exp.in_kw().is_some_and(|in_kw| in_kw.text_range().end() <= cx.range.start())
+    })?;
+  // the `val` is evaluated once, before the body, so the expression must be too.
+  if !always_evaluated(cx.exp.syntax(), let_exp.syntax()) {
+    return None;
+  }
+  // the `val` will not be able to see anything bound in the body.
+  let body =
+    TextRange::new(let_exp.in_kw()?.text_range().start(), let_exp.syntax().text_range().end());
+  if cx.refs.iter().any(|r| body.contains_range(r.def))
+    || cx.ty_vars.iter().any(|ty_var| body.contains_range(ty_var.dec))
+  {
+    return None;
+  }
+  let val = format!("val {} = {}", cx.name, cx.text());
+  let (at, new_text) = match let_exp.decs().last() {
+    Some(dec) => {
+      let dec_range = selection_range::trimmed_range(dec.syntax())?;
+      let line_start =
+        cx.root_text[..usize::from(dec_range.start())].rfind('\n').map_or(0, |x| x + 1);
+      let before_dec = &cx.root_text[line_start..usize::from(dec_range.start())];
+      if before_dec.trim().is_empty() {
+        (dec_range.end(), format!("\n{before_dec}{val}"))
+      } else {
+        (dec_range.end(), format!(" {val}"))
+      }
+    }
+    None => (let_exp.let_kw()?.text_range().end(), format!(" {val}")),
+  };
+  let pos_db = &cx.file.syntax.pos_db;
+  let val_edit = TextEdit { range: pos_db.range_utf16(TextRange::empty(at))?, new_text };
+  let use_edit = TextEdit { range: pos_db.range_utf16(cx.range)?, new_text: cx.name.clone() };
+  ac.push(Refactor {
+    title: "Extract into `val`".to_owned(),
+    kind: RefactorKind::Extract,
+    edits: one_file(cx.path, vec![val_edit, use_edit]),
+    incomplete: false,
+  });
+  Some(())
+}
+
+/// Returns whether the node is evaluated exactly once each time its ancestor is, with the same
+/// handlers for exceptions. For instance, it is not if it is in a `fn` or in a branch of an `if` in
+/// the ancestor.
+fn always_evaluated(node: &SyntaxNode, ancestor: &SyntaxNode) -> bool {
+  let mut node = node.clone();
+  while let Some(parent) = node.parent() {
+    if parent == *ancestor {
+      return true;
+    }
+    let delayed = match parent.kind() {
+      SK::FnExp | SK::Matcher | SK::HandleExp | SK::WhileExp | SK::FunBind => true,
+      SK::IfExp => !ast::IfExp::cast(parent.clone())
+        .and_then(|x| x.cond())
+        .is_some_and(|x| *x.syntax() == node),
+      SK::AndalsoExp => ast::AndalsoExp::cast(parent.clone())
+        .and_then(|x| x.rhs())
+        .is_some_and(|x| *x.syntax() == node),
+      SK::OrelseExp => ast::OrelseExp::cast(parent.clone())
+        .and_then(|x| x.rhs())
+        .is_some_and(|x| *x.syntax() == node),
+      _ => false,
+    };
+    if delayed {
+      return false;
+    }
+    node = parent;
+  }
+  false
+}
+
+/// Extracts the expression into a new `fun` before the top-level declaration containing it. The
+/// values the expression refers to that are bound in that declaration become parameters.
+fn extract_fun(ac: &mut Vec<Refactor>, cx: &Cx<'_>) -> Option<()> {
+  // the nearest declaration not in any expression.
+  let top_dec = cx.exp.syntax().ancestors().find(|node| {
+    node.parent().is_some_and(|parent| parent.kind() == SK::DecInSeq)
+      && !node.ancestors().skip(1).any(|node| ast::Exp::can_cast(node.kind()))
+  })?;
+  let top_range = top_dec.text_range();
+  let mut params = Vec::<&str_util::Name>::new();
+  for r in &cx.refs {
+    if !top_range.contains_range(r.def) {
+      continue;
+    }
+    // only values referred to by an unqualified name can be passed as parameters. and parameters
+    // are not polymorphic, so a polymorphic value may no longer be usable at all its types.
+    let name = r.name.as_ref()?;
+    if r.poly {
+      return None;
+    }
+    if !params.contains(&name) {
+      params.push(name);
+    }
+  }
+  // bind the type variables explicitly at the new `fun`, since they are no longer bound where they
+  // were.
+  let ty_var_seq = match cx.ty_vars.as_slice() {
+    [] => String::new(),
+    [ty_var] => format!("{} ", ty_var.name),
+    _ => {
+      let names: Vec<_> = cx.ty_vars.iter().map(|ty_var| ty_var.name.as_str()).collect();
+      format!("({}) ", names.join(", "))
+    }
+  };
+  let args = if params.is_empty() {
+    "()".to_owned()
+  } else {
+    params.iter().map(|name| name.as_str()).collect::<Vec<_>>().join(" ")
+  };
+  let fun = format!("fun {ty_var_seq}{} {args} = {}", cx.name, cx.text());
+  let fun_edit = insert_before(cx.file, &top_dec, &fun)?;
+  let mut call = format!("{} {args}", cx.name);
+  let is_arg = cx
+    .exp
+    .syntax()
+    .parent()
+    .and_then(ast::AppExp::cast)
+    .and_then(|app| app.arg())
+    .is_some_and(|arg| arg.syntax() == cx.exp.syntax());
+  if is_arg {
+    call = format!("({call})");
+  }
+  let use_edit = TextEdit { range: cx.file.syntax.pos_db.range_utf16(cx.range)?, new_text: call };
+  ac.push(Refactor {
+    title: "Extract into `fun`".to_owned(),
+    kind: RefactorKind::Extract,
+    edits: one_file(cx.path, vec![fun_edit, use_edit]),
+    incomplete: false,
+  });
+  Some(())
+}
+
+/// Returns the references in the node to things defined outside of it in this file, in the order
+/// they appear.
+fn refs(path: paths::PathId, file: &mlb_statics::SourceFile, node: &SyntaxNode) -> Vec<Ref> {
+  let range = node.text_range();
+  let ptrs = &file.syntax.lower.ptrs;
+  let mut ret = Vec::<Ref>::new();
+  for node in node.descendants() {
+    let Some(indices) = ptrs.ast_to_hir_all(&SyntaxNodePtr::new(&node)) else { continue };
+    for &idx in indices {
+      let Some(defs) = file.info.get_defs(idx) else { continue };
+      for &d in defs {
+        let def::Def::Path(def::Path::Regular(def_path), def_idx) = d else { continue };
+        if def_path != path {
+          continue;
+        }
+        let Some(def) = ptrs.hir_to_ast(def_idx).map(|ptr| ptr.text_range()) else { continue };
+        if range.contains_range(def) {
+          continue;
+        }
+        let name = match (idx, def_idx) {
+          (sml_hir::Idx::Exp(exp), sml_hir::Idx::Pat(_)) => {
+            match &file.syntax.lower.arenas.exp[exp] {
+              sml_hir::Exp::Path(p) if p.prefix().is_empty() => Some(p.last().clone()),
+              _ => None,
+            }
+          }
+          _ => None,
+        };
+        let poly = file.info.get_ty_scheme(def_idx).is_some_and(|ts| !ts.bound_vars.is_empty());
+        ret.push(Ref { def, name, poly });
+      }
+    }
+  }
+  ret
+}
+
+/// Returns the explicit type variables in the node bound outside of it, or `None` if we could not
+/// tell where some type variable is bound.
+///
+/// This relies on the implicitly scoped type variables having been explicitly bound to their `val`
+/// or `fun` when lowering.
+fn ty_vars(file: &mlb_statics::SourceFile, node: &SyntaxNode) -> Option<Vec<TyVar>> {
+  let range = node.text_range();
+  let mut ret = Vec::<TyVar>::new();
+  for ty in node.descendants().filter_map(ast::TyVarTy::cast) {
+    let name = ty.ty_var()?.text().to_owned();
+    let ty_var = sml_hir::TyVar::name(str_util::Name::new(name.as_str()));
+    let dec = ty
+      .syntax()
+      .ancestors()
+      .filter(|node| matches!(node.kind(), SK::ValDec | SK::FunDec))
+      .find(|node| binds(file, node, &ty_var))?;
+    let dec = dec.text_range();
+    if range.contains_range(dec) || ret.iter().any(|x| x.name == name) {
+      continue;
+    }
+    ret.push(TyVar { name, dec });
+  }
+  Some(ret)
+}
+
+fn binds(file: &mlb_statics::SourceFile, dec: &SyntaxNode, ty_var: &sml_hir::TyVar) -> bool {
+  let indices = file.syntax.lower.ptrs.ast_to_hir_all(&SyntaxNodePtr::new(dec));
+  indices.into_iter().flatten().any(|&idx| match idx {
+    sml_hir::Idx::Dec(dec) => match &file.syntax.lower.arenas.dec[dec] {
+      sml_hir::Dec::Val(ty_vars, _, _) => ty_vars.contains(ty_var),
+      _ => false,
+    },
+    _ => false,
+  })
+}
@@ -0,0 +1,346 @@
+//! Inlining a `val`.
+
+use super::{one_file, Prec, Refactor, RefactorKind};
+use crate::{selection_range, source_files, TextEdit};
+use paths::PathMap;
+use sml_statics_types::{def, info::IdStatus};
+use sml_syntax::ast::{self, AstNode as _, SyntaxNodePtr};
+use sml_syntax::kind::{SyntaxKind as SK, SyntaxNode};
+use text_size_util::{TextRange, TextSize};
+
+/// Inlines the `val` whose name is at the cursor, replacing every use of the name with the
+/// expression and removing the `val`.
+pub(super) fn get(
+  ac: &mut Vec<Refactor>,
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  path: paths::PathId,
+  ft: &source_files::FileAndToken<'_>,
+) -> Option<()> {
+  let file = ft.file;
+  let (ptr, idx) = ft.get_ptr_and_idx()?;
+  let sml_hir::Idx::Pat(pat) = idx else { return None };
+  let sml_hir::Pat::Con(name, None) = &file.syntax.lower.arenas.pat[pat] else { return None };
+  if !name.prefix().is_empty() || !matches!(file.pat_id_statuses.get(pat), Some(IdStatus::Val)) {
+    return None;
+  }
+  let name = name.last();
+  let root = file.syntax.parse.root();
+  let val_bind = ast::ValBind::cast(ptr.to_node(root.syntax()).parent()?)?;
+  let val_dec = ast::ValDec::cast(val_bind.syntax().parent()?)?;
+  if val_bind.rec_kw().is_some()
+    || val_dec.ty_var_seq().is_some()
+    || val_dec.val_binds().count() != 1
+  {
+    return None;
+  }
+  let exp = val_bind.eq_exp()?.exp()?;
+  // explicit type variables may be bound differently at the uses.
+  if exp.syntax().descendants().any(|node| node.kind() == SK::TyVarTy) {
+    return None;
+  }
+  let exp_idx = file.syntax.lower.ptrs.ast_to_hir(&SyntaxNodePtr::new(exp.syntax()))?;
+  let sml_hir::Idx::Exp(exp_idx) = exp_idx else { return None };
+  // inlining an expansive expression could both change when its effects happen and make it no
+  // longer polymorphic.
+  if expansive(file, Some(exp_idx)) {
+    return None;
+  }
+  let def = def::Def::Path(def::Path::Regular(path), pat.into());
+  let used_elsewhere =
+    source_files.iter().any(|(&p, sf)| p != path && sf.info.get_with_def(def).next().is_some());
+  if used_elsewhere {
+    return None;
+  }
+  let dec_in_seq = val_dec.syntax().parent()?;
+  let dec_range = selection_range::trimmed_range(&dec_in_seq)?;
+  let scope = dec_scope(&dec_in_seq)?;
+  // removing the only declaration in a `let` would leave it empty.
+  if let Some(let_exp) = ast::LetExp::cast(scope.clone()) {
+    let mut decs = let_exp.decs().filter_map(|dec| selection_range::trimmed_range(dec.syntax()));
+    let first = decs.next()?;
+    let decs_range = decs.last().map_or(first, |last| first.cover(last));
+    if decs_range == dec_range {
+      return None;
+    }
+  }
+  let exp_range = selection_range::trimmed_range(exp.syntax())?;
+  let root_text = root.syntax().to_string();
+  let exp_text = &root_text[exp_range];
+  let free = free_names(path, file, exp.syntax());
+  let mut uses = Vec::<SyntaxNode>::new();
+  for idx in file.info.get_with_def(def) {
+    let sml_hir::Idx::Exp(use_idx) = idx else { continue };
+    match &file.syntax.lower.arenas.exp[use_idx] {
+      // a qualified use is outside the structure, where the names in the expression may not be.
+      sml_hir::Exp::Path(p) if p.prefix().is_empty() => {}
+      _ => return None,
+    }
+    let use_node = file.syntax.lower.ptrs.hir_to_ast(idx)?.to_node(root.syntax());
+    let use_range = use_node.text_range();
+    if !scope.text_range().contains_range(use_range)
+      || captures(file, &free, dec_range.end(), &use_node)
+    {
+      return None;
+    }
+    uses.push(use_node);
+  }
+  let exp_prec = Prec::get(&exp);
+  let pos_db = &file.syntax.pos_db;
+  let mut edits = Vec::<TextEdit>::with_capacity(uses.len() + 1);
+  for use_node in uses {
+    let new_text = if needs_parens(&exp_prec, &use_node) {
+      format!("({exp_text})")
+    } else {
+      exp_text.to_owned()
+    };
+    let range = pos_db.range_utf16(selection_range::trimmed_range(&use_node)?)?;
+    edits.push(TextEdit { range, new_text });
+  }
+  edits.push(TextEdit {
+    range: pos_db.range_utf16(removal_range(&root_text, dec_range))?,
+    new_text: String::new(),
+  });
+  ac.push(Refactor {
+    title: format!("Inline `{name}`"),
+    kind: RefactorKind::Inline,
+    edits: one_file(path, edits),
+    incomplete: false,
+  });
+  Some(())
+}
+
+/// Returns the node in which the declaration's bindings are in scope.
+fn dec_scope(dec: &SyntaxNode) -> Option<SyntaxNode> {
+  dec.ancestors().find(|node| {
+    matches!(
+      node.kind(),
+      SK::LetExp
+        | SK::LocalDec
+        | SK::StructStrExp
+        | SK::LetStrExp
+        | SK::FunctorBind
+        | SK::AbstypeDec
+        | SK::Root
+    )
+  })
+}
+
+/// Returns the range to delete to remove the declaration with this range. If the declaration is on
+/// a line by itself, this is the whole line.
+fn removal_range(root_text: &str, dec_range: TextRange) -> TextRange {
+  let start = usize::from(dec_range.start());
+  let end = usize::from(dec_range.end());
+  let line_start = root_text[..start].rfind('\n').map_or(0, |idx| idx + 1);
+  let after = &root_text[end..];
+  let ws_after = after.len() - after.trim_start_matches([' ', '\t']).len();
+  let (start, end) =
+    if root_text[line_start..start].trim().is_empty() && after[ws_after..].starts_with('\n') {
+      (line_start, end + ws_after + 1)
+    } else {
+      (start, end + ws_after)
+    };
+  let to_size = |x: usize| TextSize::try_from(x).expect("text size should fit");
+  TextRange::new(to_size(start), to_size(end))
+}
+
+/// Returns the unqualified names of values the expression refers to that are defined outside of it,
+/// and the first names of the qualified ones.
+fn free_names(
+  path: paths::PathId,
+  file: &mlb_statics::SourceFile,
+  exp: &SyntaxNode,
+) -> Vec<str_util::Name> {
+  let ptrs = &file.syntax.lower.ptrs;
+  let mut ret = Vec::<str_util::Name>::new();
+  for node in exp.descendants() {
+    let Some(sml_hir::Idx::Exp(idx)) = ptrs.ast_to_hir(&SyntaxNodePtr::new(&node)) else {
+      continue;
+    };
+    let sml_hir::Exp::Path(p) = &file.syntax.lower.arenas.exp[idx] else { continue };
+    let defined_inside = file.info.get_defs(idx.into()).into_iter().flatten().any(|&d| match d {
+      def::Def::Path(def::Path::Regular(def_path), def_idx) if def_path == path => ptrs
+        .hir_to_ast(def_idx)
+        .is_some_and(|ptr| exp.text_range().contains_range(ptr.text_range())),
+      _ => false,
+    });
+    let name = p.all_names().next().expect("paths are not empty");
+    if !defined_inside && !ret.contains(name) {
+      ret.push(name.clone());
+    }
+  }
+  ret
+}
+
+/// Returns whether any of the names would refer to something different at the use than at the
+/// declaration ending at `dec_end`, because of a binding between them.
+///
+/// This is conservative, since it assumes every binding with one of the names is of something
+/// different.
+fn captures(
+  file: &mlb_statics::SourceFile,
+  names: &[str_util::Name],
+  dec_end: TextSize,
+  use_node: &SyntaxNode,
+) -> bool {
+  if names.is_empty() {
+    return false;
+  }
+  let use_range = use_node.text_range();
+  if use_range.start() < dec_end {
+    return true;
+  }
+  let between = TextRange::new(dec_end, use_range.start());
+  let root = file.syntax.parse.root();
+  for node in root.syntax().descendants() {
+    let range = node.text_range();
+    if range.end() <= between.start() || range.start() >= between.end() {
+      continue;
+    }
+    if node.kind() == SK::OpenDec && between.contains_range(range) {
+      return true;
+    }
+    if let Some(str_bind) = ast::StrBind::cast(node.clone()) {
+      let binds = str_bind.name().is_some_and(|n| names.iter().any(|x| x.as_str() == n.text()));
+      if binds && between.contains(range.start()) {
+        return true;
+      }
+    }
+  }
+  file.syntax.lower.arenas.pat.iter().any(|(pat, data)| {
+    let bound = match data {
+      sml_hir::Pat::Con(p, None) => {
+        p.prefix().is_empty()
+          && matches!(file.pat_id_statuses.get(pat), Some(IdStatus::Val))
+          && names.contains(p.last())
+      }
+      sml_hir::Pat::As(name, _) => names.contains(name),
+      _ => false,
+    };
+    if !bound {
+      return false;
+    }
+    let Some(ptr) = file.syntax.lower.ptrs.hir_to_ast(pat.into()) else { return true };
+    let node = ptr.to_node(root.syntax());
+    between.contains(node.text_range().start())
+      && bind_scope(&node).map_or(true, |scope| scope.text_range().contains_range(use_range))
+  })
+}
+
+/// Returns the node in which the name bound by the pattern is in scope.
+fn bind_scope(pat: &SyntaxNode) -> Option<SyntaxNode> {
+  // the pattern for the name of a `fun` is the whole `fun` binding.
+  if pat.kind() == SK::FunBind {
+    return dec_scope(pat);
+  }
+  pat.ancestors().find_map(|node| match node.kind() {
+    SK::Arm | SK::FunBindCase => Some(node),
+    SK::ValBind => dec_scope(&node),
+    _ => None,
+  })
+}
+
+/// Returns whether an expression with the precedence needs parentheses to replace the use.
+fn needs_parens(prec: &Prec, use_node: &SyntaxNode) -> bool {
+  let Some(parent) = use_node.parent() else { return true };
+  match parent.kind() {
+    SK::ParenExp | SK::ExpArg | SK::ExpRow | SK::ExpInSeq => false,
+    // a `case` at the end of a `fun` case would take the following `fun` cases as its own.
+    SK::EqExp => {
+      matches!(prec, Prec::Low) && parent.parent().map_or(true, |x| x.kind() != SK::ValBind)
+    }
+    SK::AppExp => {
+      let is_func = ast::AppExp::cast(parent)
+        .and_then(|app| app.func())
+        .is_some_and(|func| func.syntax() == use_node);
+      match prec {
+        Prec::Atomic => false,
+        Prec::App => !is_func,
+        Prec::Infix(_) | Prec::Low => true,
+      }
+    }
+    SK::InfixExp => match prec {
+      Prec::Atomic | Prec::App => false,
+      Prec::Low => true,
+      Prec::Infix(op) => {
+        let Some(infix) = ast::InfixExp::cast(parent) else { return true };
+        let Some(parent_op) = infix.name_star_eq() else { return true };
+        let is_lhs = infix.lhs().is_some_and(|lhs| lhs.syntax() == use_node);
+        let fix_env = fix_env(use_node);
+        let (Some(inner), Some(outer)) =
+          (fix_env.get(op.as_str()), fix_env.get(parent_op.token.text()))
+        else {
+          return true;
+        };
+        if inner.prec != outer.prec {
+          return inner.prec < outer.prec;
+        }
+        let assoc = if is_lhs { sml_fixity::Assoc::Left } else { sml_fixity::Assoc::Right };
+        inner.assoc != outer.assoc || inner.assoc != assoc
+      }
+    },
+    _ => matches!(prec, Prec::Low),
+  }
+}
+
+/// Returns the infix operators in effect at the node, assuming the std basis ones and the ones
+/// declared before the node in this file.
+///
+/// This ignores the scoping of fixity declarations, and operators declared in other files.
+/// Operators not found need parentheses, so this is conservative.
+fn fix_env(node: &SyntaxNode) -> sml_fixity::Env {
+  let mut ret = sml_fixity::STD_BASIS.clone();
+  let Some(root) = node.ancestors().last() else { return ret };
+  let end = node.text_range().start();
+  for dec in root.descendants() {
+    if dec.text_range().start() >= end {
+      break;
+    }
+    let (mk_infix, prec, names): (fn(u16) -> sml_fixity::Infix, _, _) =
+      if let Some(dec) = ast::InfixDec::cast(dec.clone()) {
+        (sml_fixity::Infix::left, dec.int_lit(), dec.name_star_eqs())
+      } else if let Some(dec) = ast::InfixrDec::cast(dec.clone()) {
+        (sml_fixity::Infix::right, dec.int_lit(), dec.name_star_eqs())
+      } else {
+        if let Some(dec) = ast::NonfixDec::cast(dec) {
+          for name in dec.name_star_eqs() {
+            ret.remove(name.token.text());
+          }
+        }
+        continue;
+      };
+    let prec = prec.and_then(|x| x.text().parse().ok()).unwrap_or(0);
+    for name in names {
+      ret.insert(str_util::Name::new(name.token.text()), mk_infix(prec));
+    }
+  }
+  ret
+}
+
+/// Returns whether the expression is expansive, in the sense of the value restriction. Compare
+/// with the one in the statics.
+fn expansive(file: &mlb_statics::SourceFile, exp: sml_hir::ExpIdx) -> bool {
+  let Some(exp) = exp else { return true };
+  match &file.syntax.lower.arenas.exp[exp] {
+    sml_hir::Exp::SCon(_) | sml_hir::Exp::Path(_) | sml_hir::Exp::Fn(_, _) => false,
+    sml_hir::Exp::Hole
+    | sml_hir::Exp::Let(_, _)
+    | sml_hir::Exp::Raise(_)
+    | sml_hir::Exp::Handle(_, _) => true,
+    sml_hir::Exp::Record(rows) => rows.iter().any(|&(_, exp)| expansive(file, exp)),
+    sml_hir::Exp::App(func, argument) => !constructor(file, *func) || expansive(file, *argument),
+    sml_hir::Exp::Typed(exp, _, _) => expansive(file, *exp),
+    sml_hir::Exp::Vector(exps) => exps.iter().any(|&exp| expansive(file, exp)),
+  }
+}
+
+fn constructor(file: &mlb_statics::SourceFile, exp: sml_hir::ExpIdx) -> bool {
+  let Some(exp) = exp else { return false };
+  match &file.syntax.lower.arenas.exp[exp] {
+    sml_hir::Exp::Typed(exp, _, _) => constructor(file, *exp),
+    sml_hir::Exp::Path(path) => {
+      let is_ref = path.prefix().is_empty() && path.last().as_str() == "ref";
+      !is_ref && matches!(file.exp_id_statuses.get(exp), Some(IdStatus::Con | IdStatus::Exn(_)))
+    }
+    _ => false,
+  }
+}
@@ -0,0 +1,429 @@
+//! Renaming an item across all the source and group files.
+
+use crate::{source_files, TextEdit};
+use fast_hash::FxHashSet;
+use paths::{PathMap, WithPath};
+use sml_statics_types::def;
+use sml_syntax::ast::{self, AstNode as _, SyntaxNodePtr};
+use sml_syntax::kind::{SyntaxKind as SK, SyntaxToken};
+use std::fmt;
+use text_pos::{PositionUtf16, RangeUtf16};
+use text_size_util::TextRange;
+
+/// An error when renaming.
+#[derive(Debug)]
+pub enum RenameError {
+  /// There was no renameable name at the position.
+  NotName,
+  /// The item is defined in a built-in library or is a primitive.
+  Builtin(str_util::Name),
+  /// The new name is not a valid name for the item.
+  InvalidName(String),
+  /// The new name would capture or be captured by another binding, so some name would refer to
+  /// something else after the rename.
+  Conflict(String),
+}
+
+impl fmt::Display for RenameError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RenameError::NotName => f.write_str("no renameable name at this position"),
+      RenameError::Builtin(name) => write!(f, "cannot rename built-in item: `{name}`"),
+      RenameError::InvalidName(name) => write!(f, "not a valid name: `{name}`"),
+      RenameError::Conflict(name) => {
+        write!(f, "renaming to `{name}` would change what some names refer to")
+      }
+    }
+  }
+}
+
+impl std::error::Error for RenameError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Namespace {
+  Value,
+  Ty,
+  Module(sml_namespace::Module),
+}
+
+/// The thing to be renamed.
+pub(crate) struct Target {
+  pub(crate) range: RangeUtf16,
+  pub(crate) name: str_util::Name,
+  ns: Namespace,
+  defs: Vec<WithPath<sml_hir::Idx>>,
+}
+
+pub(crate) fn target(
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  pos: WithPath<PositionUtf16>,
+) -> Result<Target, RenameError> {
+  let ft = source_files::file_and_token(source_files, pos).ok_or(RenameError::NotName)?;
+  if ft.token.kind() != SK::Name {
+    return Err(RenameError::NotName);
+  }
+  let (ns, binding) = namespace(&ft.token).ok_or(RenameError::NotName)?;
+  let name = str_util::Name::new(ft.token.text());
+  let range =
+    ft.file.syntax.pos_db.range_utf16(ft.token.text_range()).ok_or(RenameError::NotName)?;
+  let (_, indices) = ft.get_ptr_and_indices().ok_or(RenameError::NotName)?;
+  let mut defs = def::Set::new();
+  if !binding {
+    defs.extend(indices.iter().filter_map(|&idx| ft.file.info.get_defs(idx)).flatten().copied());
+  }
+  if defs.is_empty() {
+    let path = def::Path::Regular(pos.path);
+    defs.extend(indices.iter().map(|&idx| def::Def::Path(path, idx)));
+  }
+  // a reference may also refer to other defs, like when a structure ascribes to a signature. we want
+  // to rename all of those too, so that they stay consistent.
+  let mut work: Vec<_> = defs.iter().copied().collect();
+  while let Some(def) = work.pop() {
+    for sf in source_files.values() {
+      for idx in sf.info.get_with_def(def) {
+        for &other in sf.info.get_defs(idx).into_iter().flatten() {
+          if defs.insert(other) {
+            work.push(other);
+          }
+        }
+      }
+    }
+  }
+  let defs = defs
+    .into_iter()
+    .map(|def| def.to_regular_idx().ok_or_else(|| RenameError::Builtin(name.clone())))
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(Target { range, name, ns, defs })
+}
+
+pub(crate) fn get(
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  input: &input::Input,
+  target: &Target,
+  new_name: &str,
+) -> Result<PathMap<Vec<TextEdit>>, RenameError> {
+  if !is_valid_name(target.ns, new_name) {
+    return Err(RenameError::InvalidName(new_name.to_owned()));
+  }
+  let mut ret = PathMap::<Vec<TextEdit>>::default();
+  if target.name.as_str() == new_name {
+    return Ok(ret);
+  }
+  let mut seen = FxHashSet::<(paths::PathId, sml_hir::Idx)>::default();
+  let mut ranges = FxHashSet::<(paths::PathId, TextRange)>::default();
+  for &def_idx in &target.defs {
+    let def = def::Def::Path(def::Path::Regular(def_idx.path), def_idx.val);
+    let refs = source_files
+      .iter()
+      .flat_map(|(&path, sf)| sf.info.get_with_def(def).map(move |idx| path.wrap(idx)));
+    for idx in std::iter::once(def_idx).chain(refs) {
+      if !seen.insert((idx.path, idx.val)) {
+        continue;
+      }
+      let Some(sf) = source_files.get(&idx.path) else { continue };
+      for tok in name_tokens(sf, idx.val, target.name.as_str(), target.ns) {
+        let tr = tok.text_range();
+        if !ranges.insert((idx.path, tr)) {
+          continue;
+        }
+        let Some(range) = sf.syntax.pos_db.range_utf16(tr) else { continue };
+        ret.entry(idx.path).or_default().push(TextEdit { range, new_text: new_name.to_owned() });
+      }
+    }
+  }
+  if let Namespace::Module(ns) = target.ns {
+    for (&path, group) in &*input.groups {
+      let mut edits = Vec::<TextEdit>::new();
+      group_exports(&mut edits, &group.pos_db, &group.bas_dec, ns, &target.name, new_name);
+      if !edits.is_empty() {
+        ret.entry(path).or_default().extend(edits);
+      }
+    }
+  }
+  Ok(ret)
+}
+
+/// Returns the namespace of the name token, and whether the token is at the binding site of the
+/// name.
+pub(crate) fn namespace(tok: &SyntaxToken) -> Option<(Namespace, bool)> {
+  let parent = tok.parent()?;
+  let ret = match parent.kind() {
+    SK::NameStarEq => {
+      let gp = parent.parent()?;
+      match gp.kind() {
+        SK::NameStarEqDot => {
+          // only the last name in a path. the structure qualifiers before it are not recorded as
+          // references to the structures.
+          if ast::NameStarEqDot::cast(gp.clone())?.dot().is_some() {
+            return None;
+          }
+          let ns = match gp.parent()?.parent()?.kind() {
+            SK::PathExp | SK::ConPat => Namespace::Value,
+            SK::ConTy | SK::OneArgConTy | SK::WhereTypeSigExp | SK::DatCopyDec => Namespace::Ty,
+            SK::PathStrExp | SK::OpenDec | SK::WhereSigExp => {
+              Namespace::Module(sml_namespace::Module::Structure)
+            }
+            _ => return None,
+          };
+          (ns, false)
+        }
+        SK::ConBind | SK::ExBind | SK::PrefixFunBindCaseHead | SK::InfixFunBindCaseHead => {
+          (Namespace::Value, true)
+        }
+        SK::InfixExp | SK::InfixPat => (Namespace::Value, false),
+        _ => return None,
+      }
+    }
+    SK::TyBind | SK::DatBind | SK::DatCopyDec => (Namespace::Ty, true),
+    SK::StrBind | SK::FunctorArgNameSigExp => {
+      (Namespace::Module(sml_namespace::Module::Structure), true)
+    }
+    SK::SigBind => (Namespace::Module(sml_namespace::Module::Signature), true),
+    SK::NameSigExp => (Namespace::Module(sml_namespace::Module::Signature), false),
+    SK::FunctorBind => (Namespace::Module(sml_namespace::Module::Functor), true),
+    SK::AppStrExp => (Namespace::Module(sml_namespace::Module::Functor), false),
+    _ => return None,
+  };
+  Some(ret)
+}
+
+/// Returns the name tokens in the syntax for the index that have this name and namespace, and
+/// whose nearest ancestor with a HIR index is that syntax.
+pub(crate) fn name_tokens<'a>(
+  sf: &'a mlb_statics::SourceFile,
+  idx: sml_hir::Idx,
+  name: &'a str,
+  ns: Namespace,
+) -> impl Iterator<Item = SyntaxToken> + 'a {
+  let ptr = sf.syntax.lower.ptrs.hir_to_ast(idx);
+  let node = ptr.map(|ptr| ptr.to_node(sf.syntax.parse.root().syntax()));
+  node.into_iter().flat_map(|node| node.descendants_with_tokens()).filter_map(move |x| {
+    let tok = x.into_token()?;
+    let is_ref = tok.kind() == SK::Name
+      && tok.text() == name
+      && namespace(&tok).map(|(ns, _)| ns) == Some(ns)
+      && nearest_mapped(sf, &tok) == ptr;
+    is_ref.then_some(tok)
+  })
+}
+
+/// Returns the pointer of the nearest ancestor of the token that has a HIR index.
+fn nearest_mapped(sf: &mlb_statics::SourceFile, tok: &SyntaxToken) -> Option<SyntaxNodePtr> {
+  let mut node = tok.parent()?;
+  loop {
+    let ptr = SyntaxNodePtr::new(&node);
+    if sf.syntax.lower.ptrs.ast_to_hir(&ptr).is_some() {
+      return Some(ptr);
+    }
+    node = node.parent()?;
+  }
+}
+
+fn is_valid_name(ns: Namespace, name: &str) -> bool {
+  let lexed = sml_lex::get(name);
+  let [tok] = lexed.tokens.as_slice() else { return false };
+  if !lexed.errors.is_empty() || tok.kind != SK::Name {
+    return false;
+  }
+  match ns {
+    Namespace::Value => true,
+    Namespace::Ty | Namespace::Module(_) => {
+      name.chars().next().map_or(false, |c| c.is_ascii_alphabetic())
+    }
+  }
+}
+
+/// Returns the sources with the edits from [`get`] made.
+pub(crate) fn renamed_sources(
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  input: &input::Input,
+  edits: &PathMap<Vec<TextEdit>>,
+  new_name: &str,
+) -> PathMap<String> {
+  let mut ret = input.sources.clone();
+  for (path, edits) in edits {
+    let (Some(contents), Some(sf)) = (ret.get_mut(path), source_files.get(path)) else { continue };
+    let mut ranges: Vec<_> =
+      edits.iter().filter_map(|edit| sf.syntax.pos_db.text_range_utf16(edit.range)).collect();
+    // later edits first, so the earlier ranges stay valid.
+    ranges.sort_unstable_by_key(|range| std::cmp::Reverse(range.start()));
+    for range in ranges {
+      contents.replace_range(std::ops::Range::<usize>::from(range), new_name);
+    }
+  }
+  ret
+}
+
+/// Returns the group decs with the exports renamed like the edits from [`get`].
+pub(crate) fn renamed_groups(
+  input: &input::Input,
+  target: &Target,
+  new_name: &str,
+) -> PathMap<mlb_hir::BasDec> {
+  let new_name = str_util::Name::new(new_name);
+  input
+    .groups
+    .iter()
+    .map(|(&path, group)| {
+      let mut bas_dec = group.bas_dec.clone();
+      if let Namespace::Module(ns) = target.ns {
+        rename_exports(&mut bas_dec, ns, &target.name, &new_name);
+      }
+      (path, bas_dec)
+    })
+    .collect()
+}
+
+/// Returns whether any name in the renamed files refers to something different than it did before,
+/// or the renamed files have more errors than before.
+///
+/// This is how we check the new name does not conflict with other names: checking only the
+/// bindings in scope at each reference misses e.g. capture by local bindings between the reference
+/// and the definition, and rejects e.g. a local that could shadow a top-level binding.
+pub(crate) fn changes_meaning(
+  old: &PathMap<mlb_statics::SourceFile>,
+  new: &PathMap<mlb_statics::SourceFile>,
+) -> bool {
+  old.len() != new.len()
+    || old.iter().any(|(path, old)| {
+      let Some(new) = new.get(path) else { return true };
+      let (old_ars, new_ars) = (&old.syntax.lower.arenas, &new.syntax.lower.arenas);
+      // the rename only changes names, so the HIR should be the same shape, unless e.g. the new
+      // name is infix and so parses differently.
+      let same_shape = old_ars.str_dec.len() == new_ars.str_dec.len()
+        && old_ars.str_exp.len() == new_ars.str_exp.len()
+        && old_ars.sig_exp.len() == new_ars.sig_exp.len()
+        && old_ars.spec.len() == new_ars.spec.len()
+        && old_ars.dec.len() == new_ars.dec.len()
+        && old_ars.exp.len() == new_ars.exp.len()
+        && old_ars.pat.len() == new_ars.pat.len()
+        && old_ars.ty.len() == new_ars.ty.len();
+      !same_shape
+        || error_count(new) > error_count(old)
+        || hir_indices(old_ars).any(|idx| old.info.get_defs(idx) != new.info.get_defs(idx))
+    })
+}
+
+fn error_count(sf: &mlb_statics::SourceFile) -> usize {
+  let is_error = |severity| matches!(severity, diagnostic::Severity::Error);
+  sf.syntax.lex_errors.iter().filter(|e| is_error(e.severity())).count()
+    + sf.syntax.parse.errors.iter().filter(|e| is_error(e.severity())).count()
+    + sf.syntax.lower.errors.iter().filter(|e| is_error(e.severity())).count()
+    + sf.statics_errors.iter().filter(|e| is_error(e.severity())).count()
+}
+
+fn hir_indices(ars: &sml_hir::Arenas) -> impl Iterator<Item = sml_hir::Idx> + '_ {
+  std::iter::empty()
+    .chain(ars.str_dec.iter().map(|(idx, _)| sml_hir::Idx::from(idx)))
+    .chain(ars.str_exp.iter().map(|(idx, _)| sml_hir::Idx::from(idx)))
+    .chain(ars.sig_exp.iter().map(|(idx, _)| sml_hir::Idx::from(idx)))
+    .chain(ars.spec.iter().map(|(idx, _)| sml_hir::Idx::from(idx)))
+    .chain(ars.dec.iter().map(|(idx, _)| sml_hir::Idx::from(idx)))
+    .chain(ars.exp.iter().map(|(idx, _)| sml_hir::Idx::from(idx)))
+    .chain(ars.pat.iter().map(|(idx, _)| sml_hir::Idx::from(idx)))
+    .chain(ars.ty.iter().map(|(idx, _)| sml_hir::Idx::from(idx)))
+}
+
+/// Renames the right-hand sides of exports in group files. For an export like `structure A` (where
+/// the left- and right-hand sides are the same name) this also renames the exported name.
+///
+/// Group files do not record defs, so this renames every export with the old name in the
+/// namespace.
+fn group_exports(
+  ac: &mut Vec<TextEdit>,
+  pos_db: &text_pos::PositionDb,
+  dec: &mlb_hir::BasDec,
+  ns: sml_namespace::Module,
+  old_name: &str_util::Name,
+  new_name: &str,
+) {
+  match dec {
+    mlb_hir::BasDec::Export(dec_ns, _, rhs) => {
+      // CM exports implied by e.g. `source(-)` have a range that is not a name.
+      let is_name = usize::from(rhs.range.len()) == old_name.as_str().len();
+      if *dec_ns == ns && rhs.val == *old_name && is_name {
+        if let Some(range) = pos_db.range_utf16(rhs.range) {
+          ac.push(TextEdit { range, new_text: new_name.to_owned() });
+        }
+      }
+    }
+    mlb_hir::BasDec::Basis(_, exp) => group_exports_exp(ac, pos_db, exp, ns, old_name, new_name),
+    mlb_hir::BasDec::Local(fst, snd) => {
+      group_exports(ac, pos_db, fst, ns, old_name, new_name);
+      group_exports(ac, pos_db, snd, ns, old_name, new_name);
+    }
+    mlb_hir::BasDec::Ann(_, dec) => group_exports(ac, pos_db, dec, ns, old_name, new_name),
+    mlb_hir::BasDec::Seq(decs) => {
+      for dec in decs {
+        group_exports(ac, pos_db, dec, ns, old_name, new_name);
+      }
+    }
+    mlb_hir::BasDec::Open(_) | mlb_hir::BasDec::Path(_, _) | mlb_hir::BasDec::SourcePathSet(_) => {}
+  }
+}
+
+fn group_exports_exp(
+  ac: &mut Vec<TextEdit>,
+  pos_db: &text_pos::PositionDb,
+  exp: &mlb_hir::BasExp,
+  ns: sml_namespace::Module,
+  old_name: &str_util::Name,
+  new_name: &str,
+) {
+  match exp {
+    mlb_hir::BasExp::Bas(dec) => group_exports(ac, pos_db, dec, ns, old_name, new_name),
+    mlb_hir::BasExp::Name(_) => {}
+    mlb_hir::BasExp::Let(dec, exp) => {
+      group_exports(ac, pos_db, dec, ns, old_name, new_name);
+      group_exports_exp(ac, pos_db, exp, ns, old_name, new_name);
+    }
+  }
+}
+
+/// Like [`group_exports`], but renames the names in the dec instead of returning edits.
+fn rename_exports(
+  dec: &mut mlb_hir::BasDec,
+  ns: sml_namespace::Module,
+  old_name: &str_util::Name,
+  new_name: &str_util::Name,
+) {
+  match dec {
+    mlb_hir::BasDec::Export(dec_ns, lhs, rhs) => {
+      let is_name = usize::from(rhs.range.len()) == old_name.as_str().len();
+      if *dec_ns == ns && rhs.val == *old_name && is_name {
+        if lhs.range == rhs.range {
+          lhs.val = new_name.clone();
+        }
+        rhs.val = new_name.clone();
+      }
+    }
+    mlb_hir::BasDec::Basis(_, exp) => rename_exports_exp(exp, ns, old_name, new_name),
+    mlb_hir::BasDec::Local(fst, snd) => {
+      rename_exports(fst, ns, old_name, new_name);
+      rename_exports(snd, ns, old_name, new_name);
+    }
+    mlb_hir::BasDec::Ann(_, dec) => rename_exports(dec, ns, old_name, new_name),
+    mlb_hir::BasDec::Seq(decs) => {
+      for dec in decs {
+        rename_exports(dec, ns, old_name, new_name);
+      }
+    }
+    mlb_hir::BasDec::Open(_) | mlb_hir::BasDec::Path(_, _) | mlb_hir::BasDec::SourcePathSet(_) => {}
+  }
+}
+
+fn rename_exports_exp(
+  exp: &mut mlb_hir::BasExp,
+  ns: sml_namespace::Module,
+  old_name: &str_util::Name,
+  new_name: &str_util::Name,
+) {
+  match exp {
+    mlb_hir::BasExp::Bas(dec) => rename_exports(dec, ns, old_name, new_name),
+    mlb_hir::BasExp::Name(_) => {}
+    mlb_hir::BasExp::Let(dec, exp) => {
+      rename_exports(dec, ns, old_name, new_name);
+      rename_exports_exp(exp, ns, old_name, new_name);
+    }
+  }
+}
@@ -0,0 +1,40 @@
+//! Getting the ranges to expand a selection through.
+
+use sml_syntax::kind::{SyntaxKind as SK, SyntaxNode};
+use text_size_util::{TextRange, TextSize};
+
+/// Returns the ranges of the token at the offset and the nodes containing it, smallest first.
+pub(crate) fn get(file: &sml_file_syntax::SourceFileSyntax, offset: TextSize) -> Vec<TextRange> {
+  let mut ret = Vec::<TextRange>::new();
+  let root = file.parse.root();
+  let Some(tok) = sml_syntax::node_token(root.syntax(), offset) else { return ret };
+  if !is_trivia(tok.kind()) {
+    ret.push(tok.text_range());
+  }
+  for node in tok.parent_ancestors() {
+    let Some(tr) = trimmed_range(&node) else { continue };
+    // the ranges must strictly grow, and contain the offset.
+    if tr.contains_inclusive(offset) && ret.last().map_or(true, |&last| tr != last) {
+      ret.push(tr);
+    }
+  }
+  ret
+}
+
+/// Returns the range of the node, without the trivia at its start and end.
+pub(crate) fn trimmed_range(node: &SyntaxNode) -> Option<TextRange> {
+  let mut toks = node.descendants_with_tokens().filter_map(|x| {
+    let tok = x.into_token()?;
+    (!is_trivia(tok.kind())).then_some(tok)
+  });
+  let start = toks.next()?.text_range();
+  let ret = match toks.last() {
+    Some(end) => start.cover(end.text_range()),
+    None => start,
+  };
+  Some(ret)
+}
+
+fn is_trivia(kind: SK) -> bool {
+  matches!(kind, SK::Whitespace | SK::BlockComment | SK::Invalid)
+}
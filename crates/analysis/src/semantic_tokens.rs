@@ -0,0 +1,219 @@
+//! Classifying the tokens in a file for semantic highlighting.
+
+use sml_statics_types::{def, sym::Sym, ty::TyData};
+use sml_syntax::ast::{self, AstNode as _, SyntaxNodePtr};
+use sml_syntax::kind::{SyntaxKind as SK, SyntaxNode, SyntaxToken};
+use text_size_util::TextRange;
+
+/// A kind of semantic token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemanticTokenKind {
+  /// A `datatype` constructor.
+  Constructor,
+  /// An `exception` constructor.
+  Exception,
+  /// A regular value.
+  Value,
+  /// A value with function type.
+  Function,
+  /// A type constructor.
+  Type,
+  /// A type variable.
+  TyVar,
+  /// A `structure`.
+  Structure,
+  /// A `signature`.
+  Signature,
+  /// A `functor`.
+  Functor,
+  /// A record label.
+  Label,
+  /// An infix operator.
+  Operator,
+}
+
+/// Modifiers for a semantic token.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SemanticTokenModifiers {
+  /// Whether this is the declaration of the item, not a reference to it.
+  pub declaration: bool,
+  /// Whether the item is defined in a built-in library, like the std basis.
+  pub default_library: bool,
+  /// Whether the item has a `ref` type.
+  pub mutable: bool,
+}
+
+/// A semantic token.
+#[derive(Debug)]
+pub struct SemanticToken {
+  /// The range. Always on a single line.
+  pub range: text_pos::RangeUtf16,
+  /// The kind.
+  pub kind: SemanticTokenKind,
+  /// The modifiers.
+  pub modifiers: SemanticTokenModifiers,
+}
+
+pub(crate) fn get(
+  st: &sml_statics_types::St,
+  file: &mlb_statics::SourceFile,
+  range: Option<TextRange>,
+) -> Vec<SemanticToken> {
+  file
+    .syntax
+    .parse
+    .root()
+    .syntax()
+    .descendants_with_tokens()
+    .filter_map(|x| {
+      let tok = x.into_token()?;
+      let tr = tok.text_range();
+      if range.map_or(false, |range| range.intersect(tr).is_none()) {
+        return None;
+      }
+      let (kind, modifiers) = classify(st, file, &tok)?;
+      let range = file.syntax.pos_db.range_utf16(tr)?;
+      Some(SemanticToken { range, kind, modifiers })
+    })
+    .collect()
+}
+
+fn classify(
+  st: &sml_statics_types::St,
+  file: &mlb_statics::SourceFile,
+  tok: &SyntaxToken,
+) -> Option<(SemanticTokenKind, SemanticTokenModifiers)> {
+  let mut mods = SemanticTokenModifiers::default();
+  if tok.kind() == SK::TyVar {
+    return Some((SemanticTokenKind::TyVar, mods));
+  }
+  if !matches!(tok.kind(), SK::Name | SK::Star | SK::IntLit) {
+    return None;
+  }
+  let parent = tok.parent()?;
+  let kind = match parent.kind() {
+    SK::Lab => SemanticTokenKind::Label,
+    SK::NameStarEq => {
+      let gp = parent.parent()?;
+      match gp.kind() {
+        SK::NameStarEqDot => {
+          if ast::NameStarEqDot::cast(gp.clone())?.dot().is_some() {
+            return Some((SemanticTokenKind::Structure, mods));
+          }
+          let path = gp.parent()?;
+          let path_parent = path.parent()?;
+          match path_parent.kind() {
+            SK::PathExp | SK::ConPat => val_kind(st, file, &path_parent, &mut mods)?,
+            SK::ConTy | SK::OneArgConTy => {
+              mods.default_library = is_default_library(file, &path_parent);
+              SemanticTokenKind::Type
+            }
+            SK::WhereTypeSigExp | SK::DatCopyDec => SemanticTokenKind::Type,
+            SK::PathStrExp | SK::OpenDec | SK::WhereSigExp => SemanticTokenKind::Structure,
+            SK::PathEq => {
+              let sharing = ast::SharingTail::cast(path_parent.parent()?)?;
+              if sharing.type_kw().is_some() {
+                SemanticTokenKind::Type
+              } else {
+                SemanticTokenKind::Structure
+              }
+            }
+            _ => return None,
+          }
+        }
+        SK::ConBind => {
+          mods.declaration = true;
+          SemanticTokenKind::Constructor
+        }
+        SK::ExBind => {
+          mods.declaration = true;
+          SemanticTokenKind::Exception
+        }
+        SK::PrefixFunBindCaseHead | SK::InfixFunBindCaseHead => {
+          mods.declaration = true;
+          SemanticTokenKind::Function
+        }
+        SK::LabPatRow => {
+          mods.declaration = true;
+          SemanticTokenKind::Value
+        }
+        SK::InfixExp | SK::InfixPat => {
+          mods.default_library = is_default_library(file, &gp);
+          SemanticTokenKind::Operator
+        }
+        SK::InfixDec | SK::InfixrDec | SK::NonfixDec => SemanticTokenKind::Operator,
+        _ => return None,
+      }
+    }
+    SK::TyBind | SK::DatBind | SK::DatCopyDec => {
+      mods.declaration = true;
+      SemanticTokenKind::Type
+    }
+    SK::StrBind | SK::FunctorArgNameSigExp => {
+      mods.declaration = true;
+      SemanticTokenKind::Structure
+    }
+    SK::SigBind => {
+      mods.declaration = true;
+      SemanticTokenKind::Signature
+    }
+    SK::NameSigExp => SemanticTokenKind::Signature,
+    SK::FunctorBind => {
+      mods.declaration = true;
+      SemanticTokenKind::Functor
+    }
+    SK::AppStrExp => SemanticTokenKind::Functor,
+    _ => return None,
+  };
+  Some((kind, mods))
+}
+
+/// Gets the kind of a value path expression or pattern, using the id status from statics.
+fn val_kind(
+  st: &sml_statics_types::St,
+  file: &mlb_statics::SourceFile,
+  node: &SyntaxNode,
+  mods: &mut SemanticTokenModifiers,
+) -> Option<SemanticTokenKind> {
+  let ptr = SyntaxNodePtr::new(node);
+  let idx = file.syntax.lower.ptrs.ast_to_hir(&ptr)?;
+  let id_status = match idx {
+    sml_hir::Idx::Exp(exp) => *file.exp_id_statuses.get(exp)?,
+    sml_hir::Idx::Pat(pat) => {
+      let id_status = *file.pat_id_statuses.get(pat)?;
+      // path patterns with val id status are variables being bound.
+      mods.declaration =
+        matches!(id_status, sml_statics_types::info::IdStatus::Val) && !has_defs(file, idx);
+      id_status
+    }
+    _ => return None,
+  };
+  let ty = file.info.get_ty(idx)?;
+  mods.default_library = is_default_library(file, node);
+  mods.mutable = matches!(st.tys.data(ty), TyData::Con(data) if data.sym == Sym::REF);
+  let ret = match sml_symbol_kind::get_with_id_status(&st.tys, id_status, ty) {
+    sml_namespace::SymbolKind::Constructor => SemanticTokenKind::Constructor,
+    sml_namespace::SymbolKind::Exception => SemanticTokenKind::Exception,
+    sml_namespace::SymbolKind::Function => SemanticTokenKind::Function,
+    sml_namespace::SymbolKind::Value => SemanticTokenKind::Value,
+    sml_namespace::SymbolKind::Structure
+    | sml_namespace::SymbolKind::Signature
+    | sml_namespace::SymbolKind::Functor
+    | sml_namespace::SymbolKind::Type => return None,
+  };
+  Some(ret)
+}
+
+fn has_defs(file: &mlb_statics::SourceFile, idx: sml_hir::Idx) -> bool {
+  file.info.get_defs(idx).map_or(false, |defs| !defs.is_empty())
+}
+
+/// Returns whether any of the HIR indices for the node are defined in a built-in library.
+fn is_default_library(file: &mlb_statics::SourceFile, node: &SyntaxNode) -> bool {
+  let ptr = SyntaxNodePtr::new(node);
+  let Some(indices) = file.syntax.lower.ptrs.ast_to_hir_all(&ptr) else { return false };
+  indices.iter().filter_map(|&idx| file.info.get_defs(idx)).flatten().any(|def| match def {
+    def::Def::Path(def::Path::Regular(_), _) => false,
+    def::Def::Path(def::Path::BuiltinLib(_), _) | def::Def::Primitive(_) => true,
+  })
+}
@@ -0,0 +1,170 @@
+//! Finding the function application around a position, and showing the function's type.
+
+use sml_statics_types::ty::{Ty, TyData, TyScheme};
+use sml_syntax::ast::{self, AstNode as _};
+use sml_syntax::kind::SyntaxToken;
+use text_size_util::TextSize;
+
+/// Signature help for a function application.
+#[derive(Debug)]
+pub struct SignatureHelp {
+  /// The label, containing the name and type of the function.
+  pub label: String,
+  /// The start and end offsets of each parameter in the label, in UTF-16 code units.
+  pub params: Vec<(u32, u32)>,
+  /// The index of the active parameter.
+  pub active_param: Option<u32>,
+  /// Markdown documentation for the function.
+  pub documentation: Option<String>,
+}
+
+/// A function application around a position.
+pub(crate) struct Call {
+  pub(crate) head: ast::Exp,
+  /// The index of the curried argument the position is in.
+  pub(crate) active_arg: usize,
+  /// If the position is inside a tuple (or parenthesized) argument, the index of the tuple
+  /// component the position is in.
+  pub(crate) active_comp: Option<usize>,
+}
+
+pub(crate) fn call(tok: &SyntaxToken, offset: TextSize) -> Option<Call> {
+  let app = tok.parent_ancestors().find_map(|node| {
+    let app = ast::AppExp::cast(node)?;
+    (app.func()?.syntax().text_range().end() < offset).then_some(app)
+  })?;
+  let mut args: Vec<_> = app.arg().into_iter().collect();
+  let mut head = app.func()?;
+  while let ast::Exp::AppExp(inner) = head {
+    args.extend(inner.arg());
+    head = inner.func()?;
+  }
+  args.reverse();
+  let active_arg = args
+    .iter()
+    .position(|arg| arg.syntax().text_range().contains_inclusive(offset))
+    .unwrap_or(args.len());
+  let active_comp = args.get(active_arg).and_then(|arg| {
+    let range = arg.syntax().text_range();
+    if offset <= range.start() {
+      return None;
+    }
+    match arg {
+      ast::Exp::TupleExp(tuple) => {
+        let comp = tuple
+          .exp_args()
+          .filter(|arg| arg.comma().map_or(false, |c| c.text_range().end() <= offset))
+          .count();
+        Some(comp)
+      }
+      ast::Exp::ParenExp(_) => Some(0),
+      _ => None,
+    }
+  });
+  Some(Call { head, active_arg, active_comp })
+}
+
+/// Returns whether the `fun` that defines the pattern takes a tuple of arguments, if the pattern is
+/// the name of a `fun`.
+pub(crate) fn fun_case_tuple(
+  arenas: &sml_hir::Arenas,
+  pat: sml_hir::la_arena::Idx<sml_hir::Pat>,
+) -> Option<bool> {
+  let mut exp = arenas.dec.iter().find_map(|(_, dec)| match dec {
+    sml_hir::Dec::Val(_, val_binds, sml_hir::ValFlavor::Fun) => {
+      val_binds.iter().find_map(|val_bind| (val_bind.pat == Some(pat)).then_some(val_bind.exp))
+    }
+    _ => None,
+  })??;
+  let func = loop {
+    match &arenas.exp[exp] {
+      sml_hir::Exp::Fn(arms, sml_hir::FnFlavor::FunArg) => exp = arms.first()?.exp?,
+      sml_hir::Exp::App(func, _) => break (*func)?,
+      _ => return None,
+    }
+  };
+  match &arenas.exp[func] {
+    sml_hir::Exp::Fn(_, sml_hir::FnFlavor::FunCase { tuple }) => Some(*tuple),
+    _ => None,
+  }
+}
+
+/// Returns the label for a function with this name and type, and the offsets in it of the
+/// parameters for each curried argument.
+///
+/// If `tupled`, the parameters for the first argument are the components of its tuple type.
+/// Otherwise each argument has one parameter.
+pub(crate) fn label(
+  st: &sml_statics_types::St,
+  name: &str,
+  ty_scheme: &TyScheme,
+  tupled: bool,
+) -> (String, Vec<Vec<(u32, u32)>>) {
+  let mut label = format!("{name} : ");
+  let mut args = Vec::<Vec<(u32, u32)>>::new();
+  let mut ty = ty_scheme.ty;
+  while let TyData::Fn(data) = st.tys.data(ty) {
+    let mut params = Vec::<(u32, u32)>::new();
+    match tuple_components(st, data.param).filter(|_| args.is_empty() && tupled) {
+      Some(comps) => {
+        for (idx, comp) in comps.into_iter().enumerate() {
+          if idx != 0 {
+            label.push_str(" * ");
+          }
+          let start = utf16_len(&label);
+          push_ty(&mut label, st, ty_scheme, comp, needs_parens(st, comp, true));
+          params.push((start, utf16_len(&label)));
+        }
+      }
+      None => {
+        let start = utf16_len(&label);
+        push_ty(&mut label, st, ty_scheme, data.param, needs_parens(st, data.param, false));
+        params.push((start, utf16_len(&label)));
+      }
+    }
+    args.push(params);
+    label.push_str(" -> ");
+    ty = data.res;
+  }
+  push_ty(&mut label, st, ty_scheme, ty, false);
+  (label, args)
+}
+
+fn tuple_components(st: &sml_statics_types::St, ty: Ty) -> Option<Vec<Ty>> {
+  match st.tys.data(ty) {
+    TyData::Record(rows) => {
+      let is_tuple = rows.len() > 1
+        && rows.keys().enumerate().all(|(idx, lab)| sml_hir::Lab::tuple(idx) == *lab);
+      is_tuple.then(|| rows.into_values().collect())
+    }
+    _ => None,
+  }
+}
+
+fn needs_parens(st: &sml_statics_types::St, ty: Ty, in_tuple: bool) -> bool {
+  match st.tys.data(ty) {
+    TyData::Fn(_) => true,
+    TyData::Record(_) => in_tuple && tuple_components(st, ty).is_some(),
+    _ => false,
+  }
+}
+
+fn push_ty(
+  buf: &mut String,
+  st: &sml_statics_types::St,
+  ty_scheme: &TyScheme,
+  ty: Ty,
+  parens: bool,
+) {
+  let ty_scheme = TyScheme { bound_vars: ty_scheme.bound_vars.clone(), ty };
+  let ty = ty_scheme.display(st, config::DiagnosticLines::One);
+  if parens {
+    buf.push_str(&format!("({ty})"));
+  } else {
+    buf.push_str(&ty.to_string());
+  }
+}
+
+fn utf16_len(s: &str) -> u32 {
+  u32::try_from(s.encode_utf16().count()).unwrap_or(u32::MAX)
+}
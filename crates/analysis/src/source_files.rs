@@ -20,7 +20,8 @@ pub(crate) fn file_and_token(
 ) -> Option<FileAndToken<'_>> {
   let file = source_files.get(&pos.path)?;
   let offset = file.syntax.pos_db.text_size_utf16(pos.val)?;
-  let syntax = file.syntax.parse.root.syntax();
+  let root = file.syntax.parse.root();
+  let syntax = root.syntax();
   let tr = syntax.text_range();
   if !tr.contains_inclusive(offset) {
     return None;
@@ -0,0 +1,139 @@
+//! Searching for symbols across all the source files, and optionally the std basis.
+
+use paths::{PathMap, WithPath};
+use sml_statics::info::DocumentSymbol;
+use sml_statics_types::def;
+use text_pos::RangeUtf16;
+
+/// The most symbols to return for one query.
+const LIMIT: usize = 128;
+
+/// The location of a workspace symbol.
+#[derive(Debug)]
+pub enum SymbolLocation {
+  /// A range in a source file.
+  Source(WithPath<RangeUtf16>),
+  /// A built-in library file with this name, like the std basis.
+  BuiltinLib(&'static str),
+}
+
+/// A symbol found by searching the workspace.
+#[derive(Debug)]
+pub struct WorkspaceSymbol {
+  /// The name of the symbol.
+  pub name: String,
+  /// What kind of symbol this is.
+  pub kind: sml_namespace::SymbolKind,
+  /// The qualified name of the structure, signature, or functor containing the symbol, if any.
+  pub container_name: Option<String>,
+  /// Where the symbol is.
+  pub location: SymbolLocation,
+  /// How well the symbol matched the query. Higher is better.
+  score: u32,
+}
+
+impl WorkspaceSymbol {
+  /// Merges the lists of symbols, e.g. from different analyses, into one list of the best matches,
+  /// best first.
+  #[must_use]
+  pub fn merge<I>(lists: I) -> Vec<Self>
+  where
+    I: IntoIterator<Item = Vec<Self>>,
+  {
+    rank(lists.into_iter().flatten().collect())
+  }
+}
+
+pub(crate) fn get(
+  st: &sml_statics_types::St,
+  source_files: &PathMap<mlb_statics::SourceFile>,
+  std_basis: Option<&mlb_statics::StdBasis>,
+  query: &str,
+) -> Vec<WorkspaceSymbol> {
+  let query: Vec<_> =
+    query.chars().filter(|c| !c.is_whitespace()).map(|c| c.to_ascii_lowercase()).collect();
+  let mut found = Vec::<WorkspaceSymbol>::new();
+  for (&path, file) in source_files {
+    let location = |idx| {
+      let ptr = file.syntax.lower.ptrs.hir_to_ast(idx)?;
+      let range = file.syntax.pos_db.range_utf16(ptr.text_range())?;
+      Some(SymbolLocation::Source(path.wrap(range)))
+    };
+    for sym in file.info.document_symbols(st, def::Path::Regular(path)) {
+      add(&mut found, &query, None, sym, &location);
+    }
+  }
+  for (name, info) in std_basis.into_iter().flat_map(mlb_statics::StdBasis::infos) {
+    let location = |_| Some(SymbolLocation::BuiltinLib(name));
+    for sym in info.document_symbols(st, def::Path::BuiltinLib(name)) {
+      add(&mut found, &query, None, sym, &location);
+    }
+  }
+  rank(found)
+}
+
+fn rank(mut syms: Vec<WorkspaceSymbol>) -> Vec<WorkspaceSymbol> {
+  syms.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.len().cmp(&b.name.len())));
+  syms.truncate(LIMIT);
+  syms
+}
+
+fn add<F>(
+  ac: &mut Vec<WorkspaceSymbol>,
+  query: &[char],
+  container: Option<&str>,
+  sym: DocumentSymbol,
+  location: &F,
+) where
+  F: Fn(sml_hir::Idx) -> Option<SymbolLocation>,
+{
+  let qualified = match container {
+    Some(container) => format!("{container}.{}", sym.name),
+    None => sym.name.clone(),
+  };
+  for child in sym.children {
+    add(ac, query, Some(&qualified), child, location);
+  }
+  let Some(score) = fuzzy_score(query, &qualified) else { return };
+  let Some(location) = location(sym.idx) else { return };
+  let sym = WorkspaceSymbol {
+    name: sym.name,
+    kind: sym.kind,
+    container_name: container.map(ToOwned::to_owned),
+    location,
+    score,
+  };
+  ac.push(sym);
+}
+
+/// Returns how well the lowercase query matches the candidate, or `None` if it does not match at
+/// all. Higher is better.
+///
+/// The query matches if its chars appear in order in the candidate, ignoring case. Consecutive
+/// matches, and matches at the start of a word, are better.
+fn fuzzy_score(query: &[char], candidate: &str) -> Option<u32> {
+  let mut query = query.iter().peekable();
+  let mut score = 0u32;
+  let mut prev = None::<char>;
+  let mut prev_matched = false;
+  for c in candidate.chars() {
+    let Some(&&q) = query.peek() else { break };
+    let matched = c.to_ascii_lowercase() == q;
+    if matched {
+      query.next();
+      score += 1;
+      if prev_matched {
+        score += 2;
+      }
+      let word_start = prev.map_or(true, |p| {
+        matches!(p, '.' | '_' | '\'') || (p.is_ascii_lowercase() && c.is_ascii_uppercase())
+      });
+      if word_start {
+        score += 3;
+      }
+    }
+    prev_matched = matched;
+    prev = Some(c);
+  }
+  query.peek().is_none().then_some(score)
+}
@@ -21,6 +21,8 @@ pub struct Options {
   pub format: FormatEngine,
   #[serde(default)]
   pub diagnostics: DiagnosticsOptions,
+  #[serde(default)]
+  pub workspace_symbols: WorkspaceSymbolsOptions,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -33,6 +35,12 @@ pub struct DiagnosticsOptions {
   pub ignore: DiagnosticsIgnore,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct WorkspaceSymbolsOptions {
+  #[serde(default)]
+  pub std_basis: bool,
+}
+
 #[derive(Debug, Default, Clone, Copy, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum DiagnosticsIgnore {
@@ -8,6 +8,7 @@ mod types;
 mod util;
 
 use paths::{PathId, PathMap};
+use std::sync::Arc;
 use util::{ErrorKind, ErrorSource, GroupPathKind};
 
 pub use types::{Group, Severities};
@@ -19,7 +20,9 @@ pub struct Input {
   /// A map from source paths to their contents.
   pub sources: PathMap<String>,
   /// A map from group paths to their (parsed) contents.
-  pub groups: PathMap<types::Group>,
+  ///
+  /// Shared, since this does not change after getting the input, and copying it is not cheap.
+  pub groups: Arc<PathMap<types::Group>>,
   /// The root group ids.
   pub root_group_paths: Vec<PathId>,
   /// Severities to override.
@@ -45,6 +48,7 @@ impl Input {
     F: paths_glob::FileSystem,
   {
     let mut ret = Input::default();
+    let mut groups = PathMap::<types::Group>::default();
     let root = root::Root::new(fs, paths, root, &mut ret.errors);
     ret.severities = root.config.severities;
     ret.lang = root.config.lang;
@@ -63,10 +67,10 @@ impl Input {
         GroupPathKind::Cm => lower_cm::get,
         GroupPathKind::Mlb => lower_mlb::get,
       };
-      f(fs, &mut ret.sources, &mut ret.groups, paths, &path_var_env, group.path, &mut ret.errors);
+      f(fs, &mut ret.sources, &mut groups, paths, &path_var_env, group.path, &mut ret.errors);
       ret.root_group_paths.push(group.path);
     }
-    let bas_decs = ret.groups.iter().map(|(&a, b)| (a, &b.bas_dec));
+    let bas_decs = groups.iter().map(|(&a, b)| (a, &b.bas_dec));
     if let Err(err) = topo::check(bas_decs) {
       ret.errors.push(Error::new(
         ErrorSource::default(),
@@ -75,9 +79,23 @@ impl Input {
       ));
       // TODO only clear out the problematic files
       ret.sources.clear();
-      ret.groups.clear();
+      groups.clear();
       ret.root_group_paths.clear();
     }
+    ret.groups = Arc::new(groups);
     ret
   }
+
+  /// Returns a copy of this without the errors, e.g. to analyze on another thread.
+  #[must_use]
+  pub fn snapshot(&self) -> Input {
+    Input {
+      sources: self.sources.clone(),
+      groups: Arc::clone(&self.groups),
+      root_group_paths: self.root_group_paths.clone(),
+      severities: self.severities.clone(),
+      lang: self.lang.clone(),
+      errors: Vec::new(),
+    }
+  }
 }
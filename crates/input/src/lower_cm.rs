@@ -360,7 +360,7 @@ where
 fn get_top_defs(contents: &str, ac: &mut NameExports, range: TextRange) {
   let mut fix_env = sml_fixity::STD_BASIS.clone();
   let (_, parse) = sml_file_syntax::SourceFileSyntax::lex_and_parse(&mut fix_env, contents);
-  get_top_defs_dec(ac, parse.root.decs(), range);
+  get_top_defs_dec(ac, parse.root().decs(), range);
 }
 
 fn get_top_defs_dec<I>(ac: &mut NameExports, iter: I, range: TextRange)
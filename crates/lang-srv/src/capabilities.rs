@@ -17,15 +17,59 @@ pub(crate) fn get() -> lsp_types::ServerCapabilities {
     hover_provider: Some(lsp_types::HoverProviderCapability::Simple(true)),
     definition_provider: Some(lsp_types::OneOf::Left(true)),
     type_definition_provider: Some(lsp_types::TypeDefinitionProviderCapability::Simple(true)),
+    implementation_provider: Some(lsp_types::ImplementationProviderCapability::Simple(true)),
     code_action_provider: Some(lsp_types::CodeActionProviderCapability::Simple(true)),
+    code_lens_provider: Some(lsp_types::CodeLensOptions { resolve_provider: Some(false) }),
+    execute_command_provider: Some(lsp_types::ExecuteCommandOptions {
+      commands: vec![crate::cx::EVALUATE.to_owned()],
+      work_done_progress_options: lsp_types::WorkDoneProgressOptions::default(),
+    }),
     document_formatting_provider: Some(lsp_types::OneOf::Left(true)),
     document_symbol_provider: Some(lsp_types::OneOf::Left(true)),
+    workspace_symbol_provider: Some(lsp_types::OneOf::Left(true)),
+    folding_range_provider: Some(lsp_types::FoldingRangeProviderCapability::Simple(true)),
+    selection_range_provider: Some(lsp_types::SelectionRangeProviderCapability::Simple(true)),
     references_provider: Some(lsp_types::OneOf::Left(true)),
+    document_highlight_provider: Some(lsp_types::OneOf::Left(true)),
+    call_hierarchy_provider: Some(lsp_types::CallHierarchyServerCapability::Simple(true)),
     completion_provider: Some(lsp_types::CompletionOptions {
       trigger_characters: Some(vec![".".to_owned()]),
       ..lsp_types::CompletionOptions::default()
     }),
     inlay_hint_provider: Some(lsp_types::OneOf::Left(true)),
+    rename_provider: Some(lsp_types::OneOf::Right(lsp_types::RenameOptions {
+      prepare_provider: Some(true),
+      work_done_progress_options: lsp_types::WorkDoneProgressOptions::default(),
+    })),
+    semantic_tokens_provider: Some(
+      lsp_types::SemanticTokensOptions {
+        work_done_progress_options: lsp_types::WorkDoneProgressOptions::default(),
+        legend: crate::convert::semantic_tokens_legend(),
+        range: Some(true),
+        full: Some(lsp_types::SemanticTokensFullOptions::Bool(true)),
+      }
+      .into(),
+    ),
+    signature_help_provider: Some(lsp_types::SignatureHelpOptions {
+      trigger_characters: Some(vec!["(".to_owned(), ",".to_owned(), " ".to_owned()]),
+      retrigger_characters: None,
+      work_done_progress_options: lsp_types::WorkDoneProgressOptions::default(),
+    }),
+    diagnostic_provider: Some(lsp_types::DiagnosticServerCapabilities::Options(
+      lsp_types::DiagnosticOptions {
+        identifier: Some("millet".to_owned()),
+        inter_file_dependencies: true,
+        workspace_diagnostics: true,
+        work_done_progress_options: lsp_types::WorkDoneProgressOptions::default(),
+      },
+    )),
+    workspace: Some(lsp_types::WorkspaceServerCapabilities {
+      workspace_folders: Some(lsp_types::WorkspaceFoldersServerCapabilities {
+        supported: Some(true),
+        change_notifications: Some(lsp_types::OneOf::Left(true)),
+      }),
+      file_operations: None,
+    }),
     ..Default::default()
   }
 }
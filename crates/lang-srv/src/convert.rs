@@ -102,7 +102,7 @@ pub(crate) fn lsp_location(
   Some(lsp_types::Location { uri, range: lsp_range(range.val) })
 }
 
-fn analysis_position(pos: lsp_types::Position) -> text_pos::PositionUtf16 {
+pub(crate) fn analysis_position(pos: lsp_types::Position) -> text_pos::PositionUtf16 {
   text_pos::PositionUtf16 { line: pos.line, col: pos.character }
 }
 
@@ -123,6 +123,14 @@ pub(crate) fn text_doc_pos_params(
   Ok(path.wrap(pos))
 }
 
+pub(crate) fn call_hierarchy_item_pos(
+  paths: &mut paths::Store,
+  item: &lsp_types::CallHierarchyItem,
+) -> Result<paths::WithPath<text_pos::PositionUtf16>> {
+  let path = url_to_path_id(paths, &item.uri)?;
+  Ok(path.wrap(analysis_position(item.selection_range.start)))
+}
+
 pub(crate) fn registration<N, T>(options: T) -> lsp_types::Registration
 where
   N: lsp_types::notification::Notification,
@@ -163,22 +171,92 @@ pub(crate) fn quick_fix(
   })
 }
 
+/// Returns the quick fixes for the diagnostic, tied to it.
+pub(crate) fn diagnostic_fixes(
+  url: &Url,
+  d: analysis::Diagnostic<text_pos::RangeUtf16>,
+  more_info_hint: bool,
+) -> impl Iterator<Item = lsp_types::CodeActionOrCommand> + '_ {
+  let is_preferred = d.fixes.len() == 1;
+  let lsp_diagnostic = diagnostic(d.message, Some(d.range), d.code, d.severity, more_info_hint);
+  d.fixes.into_iter().map(move |fix| {
+    let edits = fix
+      .edits
+      .into_iter()
+      .map(|(range, new_text)| {
+        lsp_types::OneOf::Left(lsp_types::TextEdit { range: lsp_range(range), new_text })
+      })
+      .collect();
+    lsp_types::CodeActionOrCommand::CodeAction(lsp_types::CodeAction {
+      title: fix.title,
+      kind: Some(lsp_types::CodeActionKind::QUICKFIX),
+      diagnostics: Some(vec![lsp_diagnostic.clone()]),
+      edit: Some(lsp_types::WorkspaceEdit {
+        document_changes: Some(lsp_types::DocumentChanges::Edits(vec![
+          lsp_types::TextDocumentEdit {
+            text_document: lsp_types::OptionalVersionedTextDocumentIdentifier {
+              uri: url.clone(),
+              version: None,
+            },
+            edits,
+          },
+        ])),
+        ..Default::default()
+      }),
+      is_preferred: Some(is_preferred),
+      ..Default::default()
+    })
+  })
+}
+
+/// Returns the code action for the refactoring.
+pub(crate) fn refactor(
+  paths: &paths::Store,
+  refactor: analysis::Refactor,
+) -> Result<lsp_types::CodeActionOrCommand> {
+  let kind = match refactor.kind {
+    analysis::RefactorKind::Extract => lsp_types::CodeActionKind::REFACTOR_EXTRACT,
+    analysis::RefactorKind::Inline => lsp_types::CodeActionKind::REFACTOR_INLINE,
+    analysis::RefactorKind::Rewrite => lsp_types::CodeActionKind::REFACTOR_REWRITE,
+  };
+  Ok(lsp_types::CodeActionOrCommand::CodeAction(lsp_types::CodeAction {
+    title: refactor.title,
+    kind: Some(kind),
+    edit: Some(workspace_edit(paths, refactor.edits)?),
+    // applying it leaves some things to fix by hand, so it should not be chosen automatically.
+    is_preferred: refactor.incomplete.then_some(false),
+    ..Default::default()
+  }))
+}
+
+pub(crate) fn workspace_edit(
+  paths: &paths::Store,
+  edits: paths::PathMap<Vec<analysis::TextEdit>>,
+) -> Result<lsp_types::WorkspaceEdit> {
+  let mut document_edits = Vec::<lsp_types::TextDocumentEdit>::new();
+  for (path, edits) in edits {
+    let uri = file_url(paths.get_path(path).as_path())?;
+    document_edits.push(lsp_types::TextDocumentEdit {
+      text_document: lsp_types::OptionalVersionedTextDocumentIdentifier { uri, version: None },
+      edits: edits.into_iter().map(|edit| lsp_types::OneOf::Left(text_edit(edit))).collect(),
+    });
+  }
+  Ok(lsp_types::WorkspaceEdit {
+    document_changes: Some(lsp_types::DocumentChanges::Edits(document_edits)),
+    ..Default::default()
+  })
+}
+
+fn text_edit(edit: analysis::TextEdit) -> lsp_types::TextEdit {
+  lsp_types::TextEdit { range: lsp_range(edit.range), new_text: edit.new_text }
+}
+
 pub(crate) fn document_symbol(sym: analysis::DocumentSymbol) -> lsp_types::DocumentSymbol {
   #[allow(deprecated)]
   lsp_types::DocumentSymbol {
     name: sym.name,
     detail: sym.detail,
-    kind: match sym.kind {
-      sml_namespace::SymbolKind::Signature => lsp_types::SymbolKind::INTERFACE,
-      sml_namespace::SymbolKind::Structure => lsp_types::SymbolKind::MODULE,
-      sml_namespace::SymbolKind::Functor | sml_namespace::SymbolKind::Function => {
-        lsp_types::SymbolKind::FUNCTION
-      }
-      sml_namespace::SymbolKind::Value => lsp_types::SymbolKind::VARIABLE,
-      sml_namespace::SymbolKind::Type => lsp_types::SymbolKind::CLASS,
-      sml_namespace::SymbolKind::Constructor => lsp_types::SymbolKind::CONSTRUCTOR,
-      sml_namespace::SymbolKind::Exception => lsp_types::SymbolKind::EVENT,
-    },
+    kind: symbol_kind(sym.kind),
     tags: None,
     deprecated: None,
     range: lsp_range(sym.range),
@@ -187,6 +265,123 @@ pub(crate) fn document_symbol(sym: analysis::DocumentSymbol) -> lsp_types::Docum
   }
 }
 
+pub(crate) fn workspace_symbol(
+  paths: &paths::Store,
+  sym: analysis::WorkspaceSymbol,
+) -> Option<lsp_types::WorkspaceSymbol> {
+  let location = match sym.location {
+    analysis::SymbolLocation::Source(range) => lsp_types::OneOf::Left(lsp_location(paths, range)?),
+    analysis::SymbolLocation::BuiltinLib(name) => {
+      let uri = match Url::parse(&format!("{BUILTIN_LIB_SCHEME}:{name}")) {
+        Ok(x) => x,
+        Err(e) => {
+          log::error!("couldn't get built-in lib name as a url: {e:#}");
+          return None;
+        }
+      };
+      lsp_types::OneOf::Right(lsp_types::WorkspaceLocation { uri })
+    }
+  };
+  Some(lsp_types::WorkspaceSymbol {
+    name: sym.name,
+    kind: symbol_kind(sym.kind),
+    tags: None,
+    container_name: sym.container_name,
+    location,
+    data: None,
+  })
+}
+
+/// Built-in libraries are not files on disk, so they get their own URL scheme.
+const BUILTIN_LIB_SCHEME: &str = "millet-builtin";
+
+fn symbol_kind(kind: sml_namespace::SymbolKind) -> lsp_types::SymbolKind {
+  match kind {
+    sml_namespace::SymbolKind::Signature => lsp_types::SymbolKind::INTERFACE,
+    sml_namespace::SymbolKind::Structure => lsp_types::SymbolKind::MODULE,
+    sml_namespace::SymbolKind::Functor | sml_namespace::SymbolKind::Function => {
+      lsp_types::SymbolKind::FUNCTION
+    }
+    sml_namespace::SymbolKind::Value => lsp_types::SymbolKind::VARIABLE,
+    sml_namespace::SymbolKind::Type => lsp_types::SymbolKind::CLASS,
+    sml_namespace::SymbolKind::Constructor => lsp_types::SymbolKind::CONSTRUCTOR,
+    sml_namespace::SymbolKind::Exception => lsp_types::SymbolKind::EVENT,
+  }
+}
+
+pub(crate) fn document_highlight(
+  highlight: analysis::DocumentHighlight,
+) -> lsp_types::DocumentHighlight {
+  let kind = match highlight.kind {
+    analysis::DocumentHighlightKind::Read => lsp_types::DocumentHighlightKind::READ,
+    analysis::DocumentHighlightKind::Write => lsp_types::DocumentHighlightKind::WRITE,
+  };
+  lsp_types::DocumentHighlight { range: lsp_range(highlight.range), kind: Some(kind) }
+}
+
+pub(crate) fn folding_range(range: analysis::FoldingRange) -> lsp_types::FoldingRange {
+  let kind = match range.kind {
+    analysis::FoldingRangeKind::Region => lsp_types::FoldingRangeKind::Region,
+    analysis::FoldingRangeKind::Comment => lsp_types::FoldingRangeKind::Comment,
+  };
+  lsp_types::FoldingRange {
+    start_line: range.range.start.line,
+    start_character: Some(range.range.start.col),
+    end_line: range.range.end.line,
+    end_character: Some(range.range.end.col),
+    kind: Some(kind),
+    collapsed_text: None,
+  }
+}
+
+/// The ranges must be smallest first. If there are none, returns an empty range at the position.
+pub(crate) fn selection_range(
+  pos: lsp_types::Position,
+  ranges: Vec<text_pos::RangeUtf16>,
+) -> lsp_types::SelectionRange {
+  let ret = ranges.into_iter().rev().fold(None::<lsp_types::SelectionRange>, |parent, range| {
+    Some(lsp_types::SelectionRange { range: lsp_range(range), parent: parent.map(Box::new) })
+  });
+  ret.unwrap_or(lsp_types::SelectionRange { range: lsp_types::Range::new(pos, pos), parent: None })
+}
+
+pub(crate) fn call_hierarchy_item(
+  paths: &paths::Store,
+  item: analysis::CallHierarchyItem,
+) -> Option<lsp_types::CallHierarchyItem> {
+  let loc = lsp_location(paths, item.range)?;
+  Some(lsp_types::CallHierarchyItem {
+    name: item.name,
+    kind: lsp_types::SymbolKind::FUNCTION,
+    tags: None,
+    detail: item.detail,
+    uri: loc.uri,
+    range: loc.range,
+    selection_range: lsp_range(item.selection_range),
+    data: None,
+  })
+}
+
+pub(crate) fn incoming_call(
+  paths: &paths::Store,
+  call: analysis::CallHierarchyCall,
+) -> Option<lsp_types::CallHierarchyIncomingCall> {
+  Some(lsp_types::CallHierarchyIncomingCall {
+    from: call_hierarchy_item(paths, call.item)?,
+    from_ranges: call.from_ranges.into_iter().map(lsp_range).collect(),
+  })
+}
+
+pub(crate) fn outgoing_call(
+  paths: &paths::Store,
+  call: analysis::CallHierarchyCall,
+) -> Option<lsp_types::CallHierarchyOutgoingCall> {
+  Some(lsp_types::CallHierarchyOutgoingCall {
+    to: call_hierarchy_item(paths, call.item)?,
+    from_ranges: call.from_ranges.into_iter().map(lsp_range).collect(),
+  })
+}
+
 pub(crate) fn completion_item(item: analysis::CompletionItem) -> lsp_types::CompletionItem {
   lsp_types::CompletionItem {
     label: item.label,
@@ -225,6 +420,27 @@ pub(crate) fn completion_item(item: analysis::CompletionItem) -> lsp_types::Comp
   }
 }
 
+pub(crate) fn evaluate_code_lens(
+  url: &Url,
+  range: text_pos::RangeUtf16,
+  evaluation: Option<&analysis::Evaluation>,
+) -> lsp_types::CodeLens {
+  let params = lsp_types::TextDocumentPositionParams {
+    text_document: lsp_types::TextDocumentIdentifier { uri: url.clone() },
+    position: lsp_position(range.start),
+  };
+  let title = evaluation.map_or_else(|| "Evaluate".to_owned(), ToString::to_string);
+  lsp_types::CodeLens {
+    range: lsp_range(range),
+    command: Some(lsp_types::Command {
+      title,
+      command: crate::cx::EVALUATE.to_owned(),
+      arguments: Some(vec![serde_json::json!(params)]),
+    }),
+    data: None,
+  }
+}
+
 pub(crate) fn inlay_hint(hint: analysis::InlayHint) -> lsp_types::InlayHint {
   lsp_types::InlayHint {
     position: lsp_position(hint.position),
@@ -237,3 +453,105 @@ pub(crate) fn inlay_hint(hint: analysis::InlayHint) -> lsp_types::InlayHint {
     data: None,
   }
 }
+
+const SEMANTIC_TOKEN_KINDS: [analysis::SemanticTokenKind; 11] = [
+  analysis::SemanticTokenKind::Constructor,
+  analysis::SemanticTokenKind::Exception,
+  analysis::SemanticTokenKind::Value,
+  analysis::SemanticTokenKind::Function,
+  analysis::SemanticTokenKind::Type,
+  analysis::SemanticTokenKind::TyVar,
+  analysis::SemanticTokenKind::Structure,
+  analysis::SemanticTokenKind::Signature,
+  analysis::SemanticTokenKind::Functor,
+  analysis::SemanticTokenKind::Label,
+  analysis::SemanticTokenKind::Operator,
+];
+
+const SEMANTIC_TOKEN_MODIFIERS: [lsp_types::SemanticTokenModifier; 3] = [
+  lsp_types::SemanticTokenModifier::DECLARATION,
+  lsp_types::SemanticTokenModifier::DEFAULT_LIBRARY,
+  lsp_types::SemanticTokenModifier::new("mutable"),
+];
+
+fn semantic_token_type(kind: analysis::SemanticTokenKind) -> lsp_types::SemanticTokenType {
+  match kind {
+    analysis::SemanticTokenKind::Constructor => lsp_types::SemanticTokenType::ENUM_MEMBER,
+    analysis::SemanticTokenKind::Exception => lsp_types::SemanticTokenType::EVENT,
+    analysis::SemanticTokenKind::Value => lsp_types::SemanticTokenType::VARIABLE,
+    analysis::SemanticTokenKind::Function => lsp_types::SemanticTokenType::FUNCTION,
+    analysis::SemanticTokenKind::Type => lsp_types::SemanticTokenType::TYPE,
+    analysis::SemanticTokenKind::TyVar => lsp_types::SemanticTokenType::TYPE_PARAMETER,
+    analysis::SemanticTokenKind::Structure => lsp_types::SemanticTokenType::NAMESPACE,
+    analysis::SemanticTokenKind::Signature => lsp_types::SemanticTokenType::INTERFACE,
+    analysis::SemanticTokenKind::Functor => lsp_types::SemanticTokenType::CLASS,
+    analysis::SemanticTokenKind::Label => lsp_types::SemanticTokenType::PROPERTY,
+    analysis::SemanticTokenKind::Operator => lsp_types::SemanticTokenType::OPERATOR,
+  }
+}
+
+pub(crate) fn semantic_tokens_legend() -> lsp_types::SemanticTokensLegend {
+  lsp_types::SemanticTokensLegend {
+    token_types: SEMANTIC_TOKEN_KINDS.into_iter().map(semantic_token_type).collect(),
+    token_modifiers: SEMANTIC_TOKEN_MODIFIERS.to_vec(),
+  }
+}
+
+/// The tokens must be sorted by position.
+pub(crate) fn semantic_tokens(tokens: Vec<analysis::SemanticToken>) -> lsp_types::SemanticTokens {
+  let mut prev = lsp_types::Position::default();
+  let data = tokens
+    .into_iter()
+    .map(|tok| {
+      let start = lsp_position(tok.range.start);
+      let delta_line = start.line - prev.line;
+      let delta_start =
+        if delta_line == 0 { start.character - prev.character } else { start.character };
+      prev = start;
+      let token_type = SEMANTIC_TOKEN_KINDS
+        .iter()
+        .position(|&k| k == tok.kind)
+        .and_then(|x| u32::try_from(x).ok())
+        .expect("no semantic token kind in legend");
+      let mods = [tok.modifiers.declaration, tok.modifiers.default_library, tok.modifiers.mutable];
+      let token_modifiers_bitset =
+        mods.into_iter().enumerate().fold(0u32, |ac, (i, x)| if x { ac | (1 << i) } else { ac });
+      lsp_types::SemanticToken {
+        delta_line,
+        delta_start,
+        length: tok.range.end.col.saturating_sub(tok.range.start.col),
+        token_type,
+        token_modifiers_bitset,
+      }
+    })
+    .collect();
+  lsp_types::SemanticTokens { result_id: None, data }
+}
+
+pub(crate) fn signature_help(help: analysis::SignatureHelp) -> lsp_types::SignatureHelp {
+  let parameters = help
+    .params
+    .into_iter()
+    .map(|offsets| lsp_types::ParameterInformation {
+      label: lsp_types::ParameterLabel::LabelOffsets([offsets.0, offsets.1]),
+      documentation: None,
+    })
+    .collect();
+  let documentation = help.documentation.map(|value| {
+    lsp_types::Documentation::MarkupContent(lsp_types::MarkupContent {
+      kind: lsp_types::MarkupKind::Markdown,
+      value,
+    })
+  });
+  let signature = lsp_types::SignatureInformation {
+    label: help.label,
+    documentation,
+    parameters: Some(parameters),
+    active_parameter: help.active_param,
+  };
+  lsp_types::SignatureHelp {
+    signatures: vec![signature],
+    active_signature: Some(0),
+    active_parameter: help.active_param,
+  }
+}
@@ -7,6 +7,9 @@ use lsp_types::Url;
 
 pub(crate) const HELP_FIX: &str = "Learn how to fix";
 
+/// The command to evaluate a top-level `val` declaration, from a code lens.
+pub(crate) const EVALUATE: &str = "millet.evaluate";
+
 /// The context, kind of like "semi-permanent" state.
 ///
 /// Some things on this are totally immutable after initialization. Other things are mutable, but
@@ -16,10 +19,20 @@ pub(crate) struct Cx {
   pub(crate) options: config::init::Options,
   pub(crate) registered_for_watched_files: bool,
   pub(crate) paths: paths::Store,
-  pub(crate) open_paths: paths::PathSet,
+  /// The open paths, and the versions of their contents.
+  pub(crate) open_paths: paths::PathMap<i32>,
   pub(crate) fs: paths::RealFileSystem,
   pub(crate) sender: Sender<Message>,
   pub(crate) req_queue: ReqQueue<(), Option<Code>>,
+  /// Whether the client supports progress reporting initiated by the server.
+  pub(crate) work_done_progress: bool,
+  pub(crate) next_progress_id: u32,
+  /// Whether the client pulls diagnostics, instead of us pushing them.
+  pub(crate) pull_diagnostics: bool,
+  /// Whether the client supports being asked to pull diagnostics again.
+  pub(crate) diagnostic_refresh: bool,
+  /// Whether the client supports being asked to get code lenses again.
+  pub(crate) code_lens_refresh: bool,
 }
 
 impl Cx {
@@ -51,10 +64,50 @@ impl Cx {
     self.send(notif.into());
   }
 
-  pub(crate) fn send_diagnostics(&mut self, url: Url, diagnostics: Vec<lsp_types::Diagnostic>) {
+  pub(crate) fn send_diagnostics(
+    &mut self,
+    url: Url,
+    version: Option<i32>,
+    diagnostics: Vec<lsp_types::Diagnostic>,
+  ) {
     self.send_notification::<lsp_types::notification::PublishDiagnostics>(
-      lsp_types::PublishDiagnosticsParams { uri: url, diagnostics, version: None },
+      lsp_types::PublishDiagnosticsParams { uri: url, diagnostics, version },
+    );
+  }
+
+  /// Asks the client to show progress for a new task with the title, returning the token to report
+  /// progress with, if the client supports it.
+  pub(crate) fn begin_progress(&mut self, title: &str) -> Option<lsp_types::ProgressToken> {
+    if !self.work_done_progress {
+      return None;
+    }
+    let token = lsp_types::ProgressToken::String(format!("millet/{}", self.next_progress_id));
+    self.next_progress_id += 1;
+    self.send_request::<lsp_types::request::WorkDoneProgressCreate>(
+      lsp_types::WorkDoneProgressCreateParams { token: token.clone() },
+      None,
+    );
+    self.send_progress(
+      token.clone(),
+      lsp_types::WorkDoneProgress::Begin(lsp_types::WorkDoneProgressBegin {
+        title: title.to_owned(),
+        cancellable: Some(false),
+        message: None,
+        percentage: Some(0),
+      }),
     );
+    Some(token)
+  }
+
+  pub(crate) fn send_progress(
+    &self,
+    token: lsp_types::ProgressToken,
+    progress: lsp_types::WorkDoneProgress,
+  ) {
+    self.send_notification::<lsp_types::notification::Progress>(lsp_types::ProgressParams {
+      token,
+      value: lsp_types::ProgressParamsValue::WorkDone(progress),
+    });
   }
 
   pub(crate) fn show_error(&mut self, message: String, code: Code) {
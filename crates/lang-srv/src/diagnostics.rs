@@ -1,17 +1,85 @@
 //! Publish diagnostics.
 
-use crate::convert;
-use crate::state::{Mode, St};
-use fast_hash::{FxHashMap, FxHashSet};
+use crate::state::{Analyzing, FileDiagnostics, Mode, Root, St};
+use crate::{convert, cx::Cx, worker};
+use fast_hash::FxHashMap;
 use lsp_types::Url;
 use paths::FileSystem as _;
 
-pub(crate) fn try_publish(st: &mut St) -> bool {
-  let root = match &mut st.mode {
-    Mode::Root(x) => x,
-    Mode::NoRoot => return false,
+/// Starts analyzing all the files of the root on the worker, to publish diagnostics when done. If
+/// an analysis of the root is already running, cancels it, since its result would be out of date.
+///
+/// Returns whether the root will be analyzed.
+pub(crate) fn try_publish(st: &mut St, root_id: paths::PathId) -> bool {
+  let Mode::Root(roots) = &mut st.mode else { return false };
+  let Some(root) = roots.get_mut(&root_id) else { return false };
+  let old = root.analyzing.take();
+  if let Some(old) = &old {
+    old.cancel.cancel();
+  }
+  // keep reporting progress to the client with the canceled analysis's token, if any.
+  let old_token = old.and_then(|x| x.progress_token);
+  let analysis = st.empty_analysis.fresh();
+  let Some((job, cancel)) = st.worker.send(root_id, analysis, root.input.snapshot()) else {
+    if let Some(token) = old_token {
+      st.cx.send_progress(
+        token,
+        lsp_types::WorkDoneProgress::End(lsp_types::WorkDoneProgressEnd { message: None }),
+      );
+    }
+    return false;
   };
-  let mut input_diagnostics = FxHashMap::<Url, Vec<lsp_types::Diagnostic>>::default();
+  let progress_token = old_token.or_else(|| st.cx.begin_progress("Analyzing"));
+  root.analyzing = Some(Analyzing {
+    job,
+    cancel,
+    progress_token,
+    edited: paths::PathSet::default(),
+    versions: st.cx.open_paths.clone(),
+  });
+  true
+}
+
+/// Reports the progress of the running analysis for the job to the client.
+pub(crate) fn progress(st: &St, job: worker::JobId, progress: analysis::Progress) {
+  let Mode::Root(roots) = &st.mode else { return };
+  // the root may have been removed, or removed and added again, while it was being analyzed.
+  let analyzing = roots.values().find_map(|root| root.analyzing.as_ref().filter(|x| x.job == job));
+  let Some(token) = analyzing.and_then(|x| x.progress_token.clone()) else { return };
+  let percentage = (progress.done * 100) / progress.total.max(1);
+  st.cx.send_progress(
+    token,
+    lsp_types::WorkDoneProgress::Report(lsp_types::WorkDoneProgressReport {
+      cancellable: Some(false),
+      message: Some(format!("{}/{} files", progress.done, progress.total)),
+      percentage: Some(u32::try_from(percentage).unwrap_or(100)),
+    }),
+  );
+}
+
+/// Replaces the analysis of the root with the newly finished one, and publishes its diagnostics.
+pub(crate) fn finish(st: &mut St, done: worker::Done) {
+  let Mode::Root(roots) = &mut st.mode else { return };
+  // the root may have been removed, or removed and added again, while it was being analyzed. in
+  // either case, the result is for a job that is no longer running.
+  let Some(root) = roots.get_mut(&done.root) else { return };
+  if !root.analyzing.as_ref().is_some_and(|x| x.job == done.id) {
+    log::info!("dropping the result of an analysis that is no longer running");
+    return;
+  }
+  let Some(analyzing) = root.analyzing.take() else { return };
+  if let Some(token) = analyzing.progress_token {
+    st.cx.send_progress(
+      token,
+      lsp_types::WorkDoneProgress::End(lsp_types::WorkDoneProgressEnd { message: None }),
+    );
+  }
+  root.analysis = done.analysis;
+  for &path in &analyzing.edited {
+    root.analysis.update_one(&root.input, path);
+  }
+  let more_info_hint = st.cx.options.diagnostics.more_info_hint.0;
+  let mut diagnostics = FxHashMap::<Url, FileDiagnostics>::default();
   for err in &root.input.errors {
     let did_send_as_diagnostic = if st.cx.fs.is_file(err.abs_path()) {
       match convert::file_url(err.abs_path()) {
@@ -21,9 +89,9 @@ pub(crate) fn try_publish(st: &mut St) -> bool {
             err.range(),
             err.code(),
             err.severity(),
-            st.cx.options.diagnostics.more_info_hint.0,
+            more_info_hint,
           );
-          input_diagnostics.entry(url).or_default().push(d);
+          diagnostics.entry(url).or_default().items.push(d);
           true
         }
         Err(e) => {
@@ -45,13 +113,7 @@ pub(crate) fn try_publish(st: &mut St) -> bool {
       );
     }
   }
-  let mut has_diagnostics = FxHashSet::<Url>::default();
-  for (url, ds) in input_diagnostics {
-    has_diagnostics.insert(url.clone());
-    st.cx.send_diagnostics(url, ds);
-  }
-  let got_many = st.analysis.get_many(&root.input);
-  for (path_id, errors) in got_many {
+  for (path_id, errors) in done.diagnostics {
     let path = st.cx.paths.get_path(path_id);
     let url = match convert::file_url(path.as_path()) {
       Ok(x) => x,
@@ -60,22 +122,101 @@ pub(crate) fn try_publish(st: &mut St) -> bool {
         continue;
       }
     };
-    let ds = convert::diagnostics(errors, st.cx.options.diagnostics.more_info_hint.0);
-    if ds.is_empty() {
-      continue;
+    let file = diagnostics.entry(url).or_default();
+    // the diagnostics are for the contents of the file when the analysis started.
+    file.version = analyzing.versions.get(&path_id).copied();
+    file.items.extend(convert::diagnostics(errors, more_info_hint));
+  }
+  let old = std::mem::replace(&mut root.diagnostics, diagnostics);
+  if st.cx.pull_diagnostics {
+    refresh(&mut st.cx);
+  } else {
+    for (url, file) in &root.diagnostics {
+      if !file.items.is_empty() {
+        st.cx.send_diagnostics(url.clone(), file.version, file.items.clone());
+      }
     }
-    has_diagnostics.insert(url.clone());
-    st.cx.send_diagnostics(url, ds);
-  }
-  // iter over the old list of urls with diagnostics.
-  for url in std::mem::take(&mut st.has_diagnostics) {
-    if has_diagnostics.contains(&url) {
-      // had old and new diagnostics. just sent the new ones.
-      continue;
+    // iter over the old diagnostics.
+    for (url, file) in old {
+      if file.items.is_empty() || root.diagnostics.get(&url).is_some_and(|x| !x.items.is_empty()) {
+        // had no old diagnostics, or had new diagnostics and just sent them.
+        continue;
+      }
+      // had old diagnostics, but no new diagnostics. clear the old diagnostics.
+      st.cx.send_diagnostics(url, None, Vec::new());
     }
-    // had old diagnostics, but no new diagnostics. clear the old diagnostics.
-    st.cx.send_diagnostics(url, Vec::new());
   }
-  st.has_diagnostics = has_diagnostics;
-  true
+}
+
+/// Clears all the diagnostics of the root, and cancels its running analysis, if any.
+/// Used when the root is removed.
+pub(crate) fn clear(cx: &mut Cx, root: Root) {
+  if let Some(analyzing) = &root.analyzing {
+    analyzing.cancel.cancel();
+  }
+  if let Some(token) = root.analyzing.and_then(|x| x.progress_token) {
+    cx.send_progress(
+      token,
+      lsp_types::WorkDoneProgress::End(lsp_types::WorkDoneProgressEnd { message: None }),
+    );
+  }
+  if cx.pull_diagnostics {
+    refresh(cx);
+    return;
+  }
+  for (url, file) in root.diagnostics {
+    if !file.items.is_empty() {
+      cx.send_diagnostics(url, None, Vec::new());
+    }
+  }
+}
+
+/// Asks the client to pull diagnostics again, if it supports it.
+fn refresh(cx: &mut Cx) {
+  if cx.diagnostic_refresh {
+    cx.send_request::<lsp_types::request::WorkspaceDiagnosticRefresh>((), None);
+  }
+}
+
+/// Returns the diagnostics for the file, for a client that pulls diagnostics.
+///
+/// For an open file, if diagnostics are wanted on change, these are from the current contents of
+/// the file. Otherwise, they are from the most recent finished analysis of its root.
+pub(crate) fn pull(st: &St, url: &Url, path: paths::PathId) -> FileDiagnostics {
+  let Some(root) = st.root(path) else { return FileDiagnostics::default() };
+  if let Some(file) = current(st, root, path) {
+    return file;
+  }
+  root.diagnostics.get(url).cloned().unwrap_or_default()
+}
+
+/// Returns the diagnostics for all the files in all the roots, for a client that pulls diagnostics.
+pub(crate) fn pull_all(st: &St) -> Vec<(Url, FileDiagnostics)> {
+  let Mode::Root(roots) = &st.mode else { return Vec::new() };
+  let mut ret = FxHashMap::<Url, FileDiagnostics>::default();
+  for root in roots.values() {
+    ret.extend(root.diagnostics.iter().map(|(url, file)| (url.clone(), file.clone())));
+  }
+  for &path in st.cx.open_paths.keys() {
+    let Some(file) = st.root(path).and_then(|root| current(st, root, path)) else { continue };
+    match convert::file_url(st.cx.paths.get_path(path).as_path()) {
+      Ok(url) => {
+        ret.insert(url, file);
+      }
+      Err(e) => log::error!("couldn't get path as a file url: {e:#}"),
+    }
+  }
+  ret.into_iter().collect()
+}
+
+/// Returns the diagnostics for the current contents of the file, if it is open and diagnostics are
+/// wanted on change.
+fn current(st: &St, root: &Root, path: paths::PathId) -> Option<FileDiagnostics> {
+  if !st.cx.options.diagnostics.on_change {
+    return None;
+  }
+  let &version = st.cx.open_paths.get(&path)?;
+  let ds = root.analysis.diagnostics(&root.input, path)?;
+  let items = convert::diagnostics(ds, st.cx.options.diagnostics.more_info_hint.0);
+  Some(FileDiagnostics { version: Some(version), items })
 }
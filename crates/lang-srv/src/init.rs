@@ -1,10 +1,9 @@
 //! Initialize a new server.
 
 use crate::state::{Mode, Root, St};
-use crate::{convert, cx::Cx, diagnostics};
+use crate::{convert, cx::Cx, diagnostics, worker::Worker};
 use crossbeam_channel::Sender;
 use diagnostic::Code;
-use fast_hash::FxHashSet;
 use lsp_server::{Message, ReqQueue};
 
 pub(crate) fn init(init: lsp_types::InitializeParams, sender: Sender<Message>) -> St {
@@ -18,51 +17,49 @@ pub(crate) fn init(init: lsp_types::InitializeParams, sender: Sender<Message>) -
       }
     })
     .unwrap_or_default();
-  let analysis_options = analysis::Options {
-    lines: config::DiagnosticLines::Many,
-    ignore: options.diagnostics.ignore,
-    format: options.format,
-  };
-  let analysis = analysis::Analysis::new(analysis::StdBasis::full(), analysis_options);
+  let analysis_options = analysis_options(&options);
+  let work_done_progress =
+    init.capabilities.window.as_ref().and_then(|x| x.work_done_progress).unwrap_or_default();
+  let pull_diagnostics =
+    init.capabilities.text_document.as_ref().map_or(false, |x| x.diagnostic.is_some());
+  let diagnostic_refresh = init
+    .capabilities
+    .workspace
+    .as_ref()
+    .and_then(|x| x.diagnostic.as_ref()?.refresh_support)
+    .unwrap_or_default();
+  let code_lens_refresh = init
+    .capabilities
+    .workspace
+    .as_ref()
+    .and_then(|x| x.code_lens.as_ref()?.refresh_support)
+    .unwrap_or_default();
   let mut cx = Cx {
     options,
     registered_for_watched_files: false,
     paths: paths::Store::new(),
-    open_paths: paths::PathSet::default(),
+    open_paths: paths::PathMap::default(),
     fs: paths::RealFileSystem::default(),
     sender,
     req_queue: ReqQueue::default(),
+    work_done_progress,
+    next_progress_id: 0,
+    pull_diagnostics,
+    diagnostic_refresh,
+    code_lens_refresh,
   };
-  let last_workspace_folder = init
-    .workspace_folders
-    .and_then(|mut xs| {
-      let ret = xs.pop();
-      if !xs.is_empty() {
-        log::warn!("we only support the last workspace folder");
-      }
-      ret
-    })
-    .map(|x| x.uri.clone());
   #[allow(deprecated)]
-  let root_uri = last_workspace_folder.or(init.root_uri);
-  let mut root =
-    root_uri.map(|url| convert::clean_path_buf(&url).map_err(|e| (e, url))).transpose();
+  let root_urls = match init.workspace_folders {
+    Some(xs) if !xs.is_empty() => xs.into_iter().map(|x| x.uri).collect(),
+    _ => init.root_uri.into_iter().collect::<Vec<_>>(),
+  };
   let mut ret = St {
-    // do this convoluted incantation because we need `ret` to show the error in the `Err` case.
-    mode: match root.as_mut().ok().and_then(Option::take) {
-      Some(path) => {
-        let input = cx.get_input(path.as_clean_path());
-        Mode::Root(Box::new(Root { path, input }))
-      }
-      None => Mode::NoRoot,
-    },
+    mode: Mode::NoRoot,
     cx,
-    analysis,
-    has_diagnostics: FxHashSet::default(),
+    empty_analysis: analysis::Analysis::new(analysis::StdBasis::full(), analysis_options),
+    worker: Worker::new(),
   };
-  if let Err((e, url)) = root {
-    ret.cx.show_error(format!("cannot initialize workspace root {url}: {e:#}"), Code::n(1018));
-  }
+  let root_ids = add_roots(&mut ret, root_urls);
   let want_file_ops = ret.cx.options.fs_watcher.0
     && init
       .capabilities
@@ -70,28 +67,11 @@ pub(crate) fn init(init: lsp_types::InitializeParams, sender: Sender<Message>) -
       .and_then(|x| x.file_operations?.dynamic_registration)
       .unwrap_or_default();
   if want_file_ops {
-    if let Mode::Root(root) = &ret.mode {
-      // we'd like to activate on millet.toml, but "nested alternate groups are not allowed" at time
-      // of writing, and we'd rather not activate on all toml.
-      let watchers = vec![lsp_types::FileSystemWatcher {
-        glob_pattern: lsp_types::GlobPattern::Relative(lsp_types::RelativePattern {
-          base_uri: lsp_types::OneOf::Right(convert::file_url(root.path.as_path()).unwrap()),
-          pattern: "**/*.{sml,sig,fun,cm,mlb}".to_owned(),
-        }),
-        kind: None,
-      }];
-      let did_changed_registration =
-        convert::registration::<lsp_types::notification::DidChangeWatchedFiles, _>(
-          lsp_types::DidChangeWatchedFilesRegistrationOptions { watchers },
-        );
-      ret.cx.send_request::<lsp_types::request::RegisterCapability>(
-        lsp_types::RegistrationParams { registrations: vec![did_changed_registration] },
-        None,
-      );
-      ret.cx.registered_for_watched_files = true;
-    };
+    register_watched_files(&mut ret);
+  }
+  for id in root_ids {
+    diagnostics::try_publish(&mut ret, id);
   }
-  diagnostics::try_publish(&mut ret);
   if !ret.cx.registered_for_watched_files {
     log::warn!("millet will not necessarily receive notifications when files change on-disk.");
     log::warn!("this means the internal state of millet can get out of sync with what is");
@@ -100,3 +80,82 @@ pub(crate) fn init(init: lsp_types::InitializeParams, sender: Sender<Message>) -
   }
   ret
 }
+
+/// Returns the options for analysis from the options for the server.
+pub(crate) fn analysis_options(options: &config::init::Options) -> analysis::Options {
+  analysis::Options {
+    lines: config::DiagnosticLines::Many,
+    ignore: options.diagnostics.ignore,
+    format: options.format,
+  }
+}
+
+/// Adds a root for each of the folders, showing an error for each folder that is not a valid path.
+/// Does not analyze the new roots, but returns their ids.
+pub(crate) fn add_roots(st: &mut St, urls: Vec<lsp_types::Url>) -> Vec<paths::PathId> {
+  let mut ret = Vec::<paths::PathId>::with_capacity(urls.len());
+  for url in urls {
+    let path = match convert::clean_path_buf(&url) {
+      Ok(x) => x,
+      Err(e) => {
+        st.cx.show_error(format!("cannot initialize workspace root {url}: {e:#}"), Code::n(1018));
+        continue;
+      }
+    };
+    let id = st.cx.paths.get_id(path.as_clean_path());
+    if st.root_ids().any(|x| x == id) {
+      continue;
+    }
+    let (id, root) = st.new_root(path);
+    ret.push(id);
+    match &mut st.mode {
+      Mode::Root(roots) => {
+        roots.insert(id, root);
+      }
+      Mode::NoRoot => {
+        let mut roots = paths::PathMap::<Root>::default();
+        roots.insert(id, root);
+        st.mode = Mode::Root(roots);
+      }
+    }
+  }
+  ret
+}
+
+/// Registers to be notified when the files in any root change on-disk.
+///
+/// Should be called again when the roots change, after unregistering.
+pub(crate) fn register_watched_files(st: &mut St) {
+  let roots = match &st.mode {
+    Mode::Root(roots) => Some(roots.values()),
+    Mode::NoRoot => None,
+  };
+  // we'd like to have one pattern that also has the config file, but "nested alternate groups are
+  // not allowed" at time of writing, and we'd rather not activate on all toml. so we have a separate
+  // watcher for the config file.
+  let watchers: Vec<_> = roots
+    .into_iter()
+    .flatten()
+    .filter_map(|root| convert::file_url(root.path.as_path()).ok())
+    .flat_map(|url| {
+      ["**/*.{sml,sig,fun,cm,mlb}", config::file::PATH].map(|pattern| {
+        lsp_types::FileSystemWatcher {
+          glob_pattern: lsp_types::GlobPattern::Relative(lsp_types::RelativePattern {
+            base_uri: lsp_types::OneOf::Right(url.clone()),
+            pattern: pattern.to_owned(),
+          }),
+          kind: None,
+        }
+      })
+    })
+    .collect();
+  let did_changed_registration =
+    convert::registration::<lsp_types::notification::DidChangeWatchedFiles, _>(
+      lsp_types::DidChangeWatchedFilesRegistrationOptions { watchers },
+    );
+  st.cx.send_request::<lsp_types::request::RegisterCapability>(
+    lsp_types::RegistrationParams { registrations: vec![did_changed_registration] },
+    None,
+  );
+  st.cx.registered_for_watched_files = true;
+}
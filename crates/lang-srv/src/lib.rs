@@ -12,6 +12,9 @@ mod notification;
 mod request;
 mod response;
 mod state;
+mod worker;
+
+use lsp_types::notification::Notification as _;
 
 fn run_inner(
   conn: &lsp_server::Connection,
@@ -19,20 +22,51 @@ fn run_inner(
 ) -> anyhow::Result<()> {
   log::info!("start up main loop: {init:#?}");
   let mut st = init::init(init, conn.sender.clone());
-  for msg in &conn.receiver {
-    match msg {
-      lsp_server::Message::Request(req) => {
-        if conn.handle_shutdown(&req)? {
-          log::info!("shut down main loop");
-          return Ok(());
+  let worker_receiver = st.worker.receiver.clone();
+  loop {
+    crossbeam_channel::select! {
+      recv(conn.receiver) -> msg => {
+        let Ok(msg) = msg else { return Ok(()) };
+        // handle all the messages that are already here together, so that requests in this batch
+        // that the client canceled in this batch need not be handled.
+        let mut msgs = vec![msg];
+        msgs.extend(conn.receiver.try_iter());
+        for msg in &msgs {
+          if let lsp_server::Message::Request(req) = msg {
+            st.cx.req_queue.incoming.register(req.id.clone(), ());
+          }
+        }
+        let (cancels, msgs): (Vec<_>, Vec<_>) = msgs.into_iter().partition(is_cancel);
+        for msg in cancels.into_iter().chain(msgs) {
+          match msg {
+            lsp_server::Message::Request(req) => {
+              if conn.handle_shutdown(&req)? {
+                log::info!("shut down main loop");
+                return Ok(());
+              }
+              request::handle(&mut st, req);
+            }
+            lsp_server::Message::Response(res) => response::handle(&mut st, res),
+            lsp_server::Message::Notification(notif) => notification::handle(&mut st, notif),
+          }
         }
-        request::handle(&mut st, req);
       }
-      lsp_server::Message::Response(res) => response::handle(&mut st, res),
-      lsp_server::Message::Notification(notif) => notification::handle(&mut st, notif),
+      recv(worker_receiver) -> event => match event? {
+        worker::Event::Progress(job, progress) => diagnostics::progress(&st, job, progress),
+        worker::Event::Done(done) => diagnostics::finish(&mut st, *done),
+        worker::Event::Renamed(renamed) => request::renamed(&mut st, *renamed),
+      },
     }
   }
-  Ok(())
+}
+
+fn is_cancel(msg: &lsp_server::Message) -> bool {
+  match msg {
+    lsp_server::Message::Notification(notif) => {
+      notif.method == lsp_types::notification::Cancel::METHOD
+    }
+    lsp_server::Message::Request(_) | lsp_server::Message::Response(_) => false,
+  }
 }
 
 /// Runs the language server over stdio.
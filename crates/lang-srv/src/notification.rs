@@ -2,9 +2,10 @@
 
 use crate::cx::Cx;
 use crate::state::{Mode, St};
-use crate::{convert, diagnostics, helpers};
+use crate::{convert, diagnostics, helpers, init};
 use anyhow::{bail, Result};
-use lsp_server::Notification;
+use lsp_server::{Notification, RequestId};
+use lsp_types::notification::Notification as _;
 use paths::FileSystem as _;
 use std::collections::hash_map::Entry;
 use std::ops::ControlFlow;
@@ -31,7 +32,7 @@ fn try_update_input(
   for change in changes {
     let Ok(path) = convert::clean_path_buf(&change.uri) else { return Err(saw_open_path) };
     let path_id = cx.paths.get_id(path.as_clean_path());
-    if cx.open_paths.contains(&path_id) {
+    if cx.open_paths.contains_key(&path_id) {
       saw_open_path = true;
       continue;
     }
@@ -60,63 +61,136 @@ fn try_update_input(
 #[allow(clippy::too_many_lines)]
 fn go(st: &mut St, mut n: Notification) -> ControlFlow<Result<()>, Notification> {
   n = helpers::try_notif::<lsp_types::notification::DidChangeWatchedFiles, _>(n, |params| {
-    match &mut st.mode {
-      Mode::Root(root) => {
-        match try_update_input(&mut st.cx, &mut root.input, params.changes) {
-          Ok(_) => {
-            // TODO use path ids
-          }
-          Err(saw_open_path) => {
-            if !saw_open_path {
-              root.input = st.cx.get_input(root.path.as_clean_path());
-            }
-          }
+    // only update the roots with changes.
+    let mut changes_by_root = paths::PathMap::<Vec<lsp_types::FileEvent>>::default();
+    for change in params.changes {
+      let path = convert::url_to_path_id(&mut st.cx.paths, &change.uri)?;
+      match st.root_id(path) {
+        Some(root_id) => changes_by_root.entry(root_id).or_default().push(change),
+        None => log::info!("ignoring change to path in no root: {}", change.uri),
+      }
+    }
+    for (root_id, changes) in changes_by_root {
+      let Mode::Root(roots) = &mut st.mode else { break };
+      let Some(root) = roots.get_mut(&root_id) else { continue };
+      // the config file affects the whole input, even when it is open.
+      let config_path = root.path.as_path().join(config::file::PATH);
+      let config_changed = changes.iter().any(|change| {
+        convert::clean_path_buf(&change.uri).is_ok_and(|path| path.as_path() == config_path)
+      });
+      root.evaluations.clear();
+      if config_changed {
+        root.input = st.cx.get_input(root.path.as_clean_path());
+      } else if let Err(saw_open_path) = try_update_input(&mut st.cx, &mut root.input, changes) {
+        if !saw_open_path {
+          root.input = st.cx.get_input(root.path.as_clean_path());
         }
-        diagnostics::try_publish(st);
       }
-      Mode::NoRoot => bail!("unexpected DidChangeWatchedFiles with NoRoot"),
+      diagnostics::try_publish(st, root_id);
     }
     Ok(())
   })?;
   n = helpers::try_notif::<lsp_types::notification::DidChangeTextDocument, _>(n, |params| {
     let url = params.text_document.uri;
     let path = convert::url_to_path_id(&mut st.cx.paths, &url)?;
-    if let Mode::Root(root) = &mut st.mode {
-      let Some(text) = root.input.sources.get_mut(&path) else {
-        bail!("no source in the input for DidChangeTextDocument")
-      };
-      let text = std::panic::AssertUnwindSafe(text);
-      let res = std::panic::catch_unwind(|| {
-        let mut text = text;
-        helpers::apply_changes(*text, params.content_changes);
-      });
-      match res {
-        Ok(()) => {}
-        Err(e) => bail!("apply_changes panicked: {e:?}"),
-      }
-      if st.cx.options.diagnostics.on_change {
-        diagnostics::try_publish(st);
-      } else {
-        st.analysis.update_one(&root.input, path);
+    st.cx.open_paths.insert(path, params.text_document.version);
+    let Some(root_id) = st.root_id(path) else { return Ok(()) };
+    let Mode::Root(roots) = &mut st.mode else { return Ok(()) };
+    let Some(root) = roots.get_mut(&root_id) else { return Ok(()) };
+    let Some(text) = root.input.sources.get_mut(&path) else {
+      bail!("no source in the input for DidChangeTextDocument")
+    };
+    let text = std::panic::AssertUnwindSafe(text);
+    let res = std::panic::catch_unwind(|| {
+      let mut text = text;
+      helpers::apply_changes(*text, params.content_changes);
+    });
+    match res {
+      Ok(()) => {}
+      Err(e) => bail!("apply_changes panicked: {e:?}"),
+    }
+    // keep the current analysis up to date for requests while any new analysis runs.
+    root.analysis.update_one(&root.input, path);
+    root.evaluations.remove(&path);
+    if let Some(analyzing) = &mut root.analyzing {
+      analyzing.edited.insert(path);
+    }
+    if st.cx.options.diagnostics.on_change {
+      diagnostics::try_publish(st, root_id);
+    }
+    Ok(())
+  })?;
+  n = helpers::try_notif::<lsp_types::notification::DidSaveTextDocument, _>(n, |params| {
+    if st.cx.registered_for_watched_files {
+      log::warn!("ignoring DidSaveTextDocument since we registered for watched file events");
+      return Ok(());
+    }
+    let path = convert::url_to_path_id(&mut st.cx.paths, &params.text_document.uri)?;
+    let Some(root_id) = st.root_id(path) else { return Ok(()) };
+    let Mode::Root(roots) = &mut st.mode else { return Ok(()) };
+    let Some(root) = roots.get_mut(&root_id) else { return Ok(()) };
+    root.input = st.cx.get_input(root.path.as_clean_path());
+    diagnostics::try_publish(st, root_id);
+    Ok(())
+  })?;
+  n = helpers::try_notif::<lsp_types::notification::DidChangeConfiguration, _>(n, |params| {
+    if params.settings.is_null() {
+      log::info!("ignoring DidChangeConfiguration with no settings");
+      return Ok(());
+    }
+    let mut options = match serde_json::from_value::<config::init::Options>(params.settings) {
+      Ok(x) => x,
+      Err(e) => bail!("invalid settings: {e}"),
+    };
+    // we only register for watched files when initializing.
+    options.fs_watcher = st.cx.options.fs_watcher;
+    let analysis_options = init::analysis_options(&options);
+    st.cx.options = options;
+    st.empty_analysis.set_options(analysis_options);
+    if let Mode::Root(roots) = &mut st.mode {
+      for root in roots.values_mut() {
+        root.analysis.set_options(analysis_options);
       }
     }
+    // the options affect the diagnostics.
+    let root_ids: Vec<_> = st.root_ids().collect();
+    for root_id in root_ids {
+      diagnostics::try_publish(st, root_id);
+    }
     Ok(())
   })?;
-  n = helpers::try_notif::<lsp_types::notification::DidSaveTextDocument, _>(n, |_| {
-    if let Mode::Root(root) = &mut st.mode {
-      if st.cx.registered_for_watched_files {
-        log::warn!("ignoring DidSaveTextDocument since we registered for watched file events");
-      } else {
-        root.input = st.cx.get_input(root.path.as_clean_path());
-        diagnostics::try_publish(st);
+  n = helpers::try_notif::<lsp_types::notification::DidChangeWorkspaceFolders, _>(n, |params| {
+    for folder in params.event.removed {
+      let root_id = convert::url_to_path_id(&mut st.cx.paths, &folder.uri)?;
+      let Mode::Root(roots) = &mut st.mode else { break };
+      if let Some(root) = roots.remove(&root_id) {
+        diagnostics::clear(&mut st.cx, root);
+      }
+      if roots.is_empty() {
+        st.mode = Mode::NoRoot;
       }
     }
+    let added: Vec<_> = params.event.added.into_iter().map(|x| x.uri).collect();
+    for root_id in init::add_roots(st, added) {
+      diagnostics::try_publish(st, root_id);
+    }
+    // watch the files in the new set of roots.
+    if st.cx.registered_for_watched_files {
+      let method = lsp_types::notification::DidChangeWatchedFiles::METHOD.to_owned();
+      st.cx.send_request::<lsp_types::request::UnregisterCapability>(
+        lsp_types::UnregistrationParams {
+          unregisterations: vec![lsp_types::Unregistration { id: method.clone(), method }],
+        },
+        None,
+      );
+      init::register_watched_files(st);
+    }
     Ok(())
   })?;
   n = helpers::try_notif::<lsp_types::notification::DidOpenTextDocument, _>(n, |params| {
     let url = params.text_document.uri;
     let path = convert::url_to_path_id(&mut st.cx.paths, &url)?;
-    st.cx.open_paths.insert(path);
+    st.cx.open_paths.insert(path, params.text_document.version);
     Ok(())
   })?;
   n = helpers::try_notif::<lsp_types::notification::DidCloseTextDocument, _>(n, |params| {
@@ -125,5 +199,16 @@ fn go(st: &mut St, mut n: Notification) -> ControlFlow<Result<()>, Notification>
     st.cx.open_paths.remove(&path);
     Ok(())
   })?;
+  n = helpers::try_notif::<lsp_types::notification::Cancel, _>(n, |params| {
+    let id = match params.id {
+      lsp_types::NumberOrString::Number(x) => RequestId::from(x),
+      lsp_types::NumberOrString::String(x) => RequestId::from(x),
+    };
+    // only does anything if the request is not yet handled, see the main loop.
+    if let Some(res) = st.cx.req_queue.incoming.cancel(id) {
+      st.cx.send(res.into());
+    }
+    Ok(())
+  })?;
   ControlFlow::Continue(n)
 }
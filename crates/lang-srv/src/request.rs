@@ -1,14 +1,18 @@
 //! Handle requests.
 
 use crate::state::{Mode, St};
-use crate::{convert, helpers};
-use anyhow::Result;
+use crate::{convert, cx, diagnostics, helpers, worker};
+use anyhow::{bail, Result};
 use lsp_server::{Request, Response};
 use std::ops::ControlFlow;
 
+/// The request must already be registered in the queue.
 pub(crate) fn handle(st: &mut St, req: Request) {
   log::info!("got request: {req:?}");
-  st.cx.req_queue.incoming.register(req.id.clone(), ());
+  if st.cx.req_queue.incoming.is_completed(&req.id) {
+    log::info!("not handling canceled request: {:?}", req.id);
+    return;
+  }
   match go(st, req) {
     ControlFlow::Break(Ok(())) => {}
     ControlFlow::Break(Err(e)) => log::error!("couldn't handle request: {e}"),
@@ -19,18 +23,49 @@ pub(crate) fn handle(st: &mut St, req: Request) {
 /// TODO replace with constant from lsp types after it's updated with 3.17
 const REQUEST_FAILED: i32 = -32803;
 
+/// TODO replace with constant from lsp types after it's updated with 3.17
+const CONTENT_MODIFIED: i32 = -32801;
+
+/// Responds to a rename request after the worker checked it for conflicts.
+pub(crate) fn renamed(st: &mut St, renamed: worker::Renamed) {
+  let worker::Renamed { req, root, input, res } = renamed;
+  let root = match &st.mode {
+    Mode::Root(roots) => roots.get(&root),
+    Mode::NoRoot => None,
+  };
+  // the edits are for the input when the rename started, so they're wrong if the root was removed
+  // or the files they edit changed since.
+  let res = match (root, res) {
+    (Some(root), Ok(edits))
+      if edits.keys().all(|path| root.input.sources.get(path) == input.sources.get(path)) =>
+    {
+      match convert::workspace_edit(&st.cx.paths, edits) {
+        Ok(edit) => Response::new_ok(req, edit),
+        Err(e) => Response::new_err(req, REQUEST_FAILED, e.to_string()),
+      }
+    }
+    (_, Ok(_)) => {
+      Response::new_err(req, CONTENT_MODIFIED, "the files changed during the rename".to_owned())
+    }
+    (_, Err(e)) => Response::new_err(req, REQUEST_FAILED, e.to_string()),
+  };
+  st.cx.send_response(res);
+}
+
 #[allow(clippy::too_many_lines)]
 fn go(st: &mut St, mut r: Request) -> ControlFlow<Result<()>, Request> {
   r = helpers::try_req::<lsp_types::request::HoverRequest, _>(r, |id, params| {
     let params = params.text_document_position_params;
     let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params)?;
     let res =
-      st.analysis.get_md(pos, st.cx.options.token_hover.0).map(|(value, range)| lsp_types::Hover {
-        contents: lsp_types::HoverContents::Markup(lsp_types::MarkupContent {
-          kind: lsp_types::MarkupKind::Markdown,
-          value,
-        }),
-        range: Some(convert::lsp_range(range)),
+      st.analysis(pos.path).get_md(pos, st.cx.options.token_hover.0).map(|(value, range)| {
+        lsp_types::Hover {
+          contents: lsp_types::HoverContents::Markup(lsp_types::MarkupContent {
+            kind: lsp_types::MarkupKind::Markdown,
+            value,
+          }),
+          range: Some(convert::lsp_range(range)),
+        }
       });
     st.cx.send_response(Response::new_ok(id, res));
     Ok(())
@@ -39,7 +74,7 @@ fn go(st: &mut St, mut r: Request) -> ControlFlow<Result<()>, Request> {
     let params = params.text_document_position_params;
     let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params)?;
     let res: Vec<_> = st
-      .analysis
+      .analysis(pos.path)
       .get_defs(pos)
       .into_iter()
       .flatten()
@@ -52,7 +87,7 @@ fn go(st: &mut St, mut r: Request) -> ControlFlow<Result<()>, Request> {
     let params = params.text_document_position_params;
     let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params)?;
     let locs: Vec<_> = st
-      .analysis
+      .analysis(pos.path)
       .get_ty_defs(pos)
       .into_iter()
       .flatten()
@@ -62,25 +97,69 @@ fn go(st: &mut St, mut r: Request) -> ControlFlow<Result<()>, Request> {
     st.cx.send_response(Response::new_ok(id, res));
     Ok(())
   })?;
+  r = helpers::try_req::<lsp_types::request::GotoImplementation, _>(r, |id, params| {
+    let params = params.text_document_position_params;
+    let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params)?;
+    let locs: Vec<_> = st
+      .analysis(pos.path)
+      .get_impls(pos)
+      .into_iter()
+      .flatten()
+      .filter_map(|range| convert::lsp_location(&st.cx.paths, range))
+      .collect();
+    let res = (!locs.is_empty()).then_some(lsp_types::GotoDefinitionResponse::Array(locs));
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
   r = helpers::try_req::<lsp_types::request::CodeActionRequest, _>(r, |id, params| {
     let url = params.text_document.uri;
     let path = convert::url_to_path_id(&mut st.cx.paths, &url)?;
     let range = convert::analysis_range(params.range);
     let mut actions = Vec::<lsp_types::CodeActionOrCommand>::new();
-    if let Some((range, new_text)) = st.analysis.fill_case(path.wrap(range.start)) {
+    if let Some(root) = st.root(path) {
+      let more_info_hint = st.cx.options.diagnostics.more_info_hint.0;
+      let want = params.range;
+      let ds = root.analysis.diagnostics(&root.input, path).into_iter().flatten().filter(|d| {
+        let got = convert::lsp_range(d.range);
+        // the ranges overlap.
+        got.start <= want.end && want.start <= got.end
+      });
+      // many diagnostics can have the same fix, like the ones for each missing item of a structure.
+      let mut seen = Vec::<analysis::Fix<text_pos::RangeUtf16>>::new();
+      let ds = ds.map(|mut d| {
+        d.fixes.retain(|fix| {
+          let new = !seen.contains(fix);
+          if new {
+            seen.push(fix.clone());
+          }
+          new
+        });
+        d
+      });
+      actions.extend(ds.flat_map(|d| convert::diagnostic_fixes(&url, d, more_info_hint)));
+    }
+    if let Some((range, new_text)) = st.analysis(path).fill_case(path.wrap(range.start)) {
       actions.push(convert::quick_fix("Fill case".to_owned(), url, range, new_text));
     }
+    let refactors = st.analysis(path).refactors(path.wrap(range));
+    for refactor in refactors {
+      // one refactoring we couldn't convert shouldn't stop the others from being offered.
+      match convert::refactor(&st.cx.paths, refactor) {
+        Ok(action) => actions.push(action),
+        Err(e) => log::error!("couldn't convert a refactoring: {e:#}"),
+      }
+    }
     st.cx.send_response(Response::new_ok(id, actions));
     Ok(())
   })?;
   r = helpers::try_req::<lsp_types::request::Formatting, _>(r, |id, params| {
     let url = params.text_document.uri;
     let path = convert::url_to_path_id(&mut st.cx.paths, &url)?;
-    let res = match &st.mode {
-      Mode::Root(root) => {
+    let res = match st.root_mut(path) {
+      Some(root) => {
         // need to re-compute the internal parse tree etc
-        st.analysis.get_many(&root.input);
-        match st.analysis.format(path, params.options.tab_size) {
+        root.analysis.update_one(&root.input, path);
+        match root.analysis.format(path, params.options.tab_size) {
           Ok((new_text, end)) => {
             let edit = lsp_types::TextEdit {
               range: lsp_types::Range {
@@ -104,35 +183,164 @@ fn go(st: &mut St, mut r: Request) -> ControlFlow<Result<()>, Request> {
           },
         }
       }
-      Mode::NoRoot => Response::new_ok(id, None::<()>),
+      None => Response::new_ok(id, None::<()>),
     };
     st.cx.send_response(res);
     Ok(())
   })?;
+  r = helpers::try_req::<lsp_types::request::CodeLensRequest, _>(r, |id, params| {
+    let url = params.text_document.uri;
+    let path = convert::url_to_path_id(&mut st.cx.paths, &url)?;
+    let evaluations = st.root(path).and_then(|root| root.evaluations.get(&path));
+    let res: Option<Vec<_>> = st.analysis(path).evaluable_vals(path).map(|ranges| {
+      ranges
+        .into_iter()
+        .map(|range| {
+          let evaluation = evaluations
+            .into_iter()
+            .flatten()
+            .find_map(|(start, evaluation)| (*start == range.start).then_some(evaluation));
+          convert::evaluate_code_lens(&url, range, evaluation)
+        })
+        .collect()
+    });
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::ExecuteCommand, _>(r, |id, params| {
+    if params.command != cx::EVALUATE {
+      let msg = format!("unknown command: {}", params.command);
+      st.cx.send_response(Response::new_err(id, REQUEST_FAILED, msg));
+      return Ok(());
+    }
+    let Some(arg) = params.arguments.into_iter().next() else {
+      bail!("no arguments for {}", cx::EVALUATE)
+    };
+    let params: lsp_types::TextDocumentPositionParams = serde_json::from_value(arg)?;
+    let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params)?;
+    let Some(evaluation) = st.analysis(pos.path).evaluate_val(pos) else {
+      let msg = "no declaration to evaluate here".to_owned();
+      st.cx.send_response(Response::new_err(id, REQUEST_FAILED, msg));
+      return Ok(());
+    };
+    st.cx.send_response(Response::new_ok(id, None::<()>));
+    let code_lens_refresh = st.cx.code_lens_refresh;
+    match st.root_mut(pos.path) {
+      // show the evaluation in place of the code lens.
+      Some(root) if code_lens_refresh => {
+        let evaluations = root.evaluations.entry(pos.path).or_default();
+        evaluations.retain(|(start, _)| *start != pos.val);
+        evaluations.push((pos.val, evaluation));
+        st.cx.send_request::<lsp_types::request::CodeLensRefresh>((), None);
+      }
+      _ => st.cx.send_notification::<lsp_types::notification::ShowMessage>(
+        lsp_types::ShowMessageParams {
+          typ: lsp_types::MessageType::INFO,
+          message: evaluation.to_string(),
+        },
+      ),
+    }
+    Ok(())
+  })?;
   r = helpers::try_req::<lsp_types::request::DocumentSymbolRequest, _>(r, |id, params| {
     let url = params.text_document.uri;
     let path = convert::url_to_path_id(&mut st.cx.paths, &url)?;
     let res: Option<Vec<_>> = st
-      .analysis
+      .analysis(path)
       .document_symbols(path)
       .map(|xs| xs.into_iter().map(convert::document_symbol).collect());
     st.cx.send_response(Response::new_ok(id, res));
     Ok(())
   })?;
+  r = helpers::try_req::<lsp_types::request::WorkspaceSymbolRequest, _>(r, |id, params| {
+    let std_basis = st.cx.options.workspace_symbols.std_basis;
+    // the std basis is the same for every root, so only get its symbols once, from the analysis
+    // with no source files.
+    let std_basis_syms = st.empty_analysis.workspace_symbols(&params.query, std_basis);
+    let syms = st.analyses().map(|analysis| analysis.workspace_symbols(&params.query, false));
+    // rank the symbols from all the roots together, so the best ones overall come first.
+    let res: Vec<_> = analysis::WorkspaceSymbol::merge(syms.chain(std::iter::once(std_basis_syms)))
+      .into_iter()
+      .filter_map(|sym| convert::workspace_symbol(&st.cx.paths, sym))
+      .collect();
+    st.cx.send_response(Response::new_ok(id, lsp_types::WorkspaceSymbolResponse::Nested(res)));
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::FoldingRangeRequest, _>(r, |id, params| {
+    let path = convert::url_to_path_id(&mut st.cx.paths, &params.text_document.uri)?;
+    let res: Option<Vec<_>> = st
+      .analysis(path)
+      .folding_ranges(path)
+      .map(|xs| xs.into_iter().map(convert::folding_range).collect());
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::SelectionRangeRequest, _>(r, |id, params| {
+    let path = convert::url_to_path_id(&mut st.cx.paths, &params.text_document.uri)?;
+    let res: Vec<_> = params
+      .positions
+      .into_iter()
+      .map(|pos| {
+        let ranges = st.analysis(path).selection_ranges(path.wrap(convert::analysis_position(pos)));
+        convert::selection_range(pos, ranges.unwrap_or_default())
+      })
+      .collect();
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
   r = helpers::try_req::<lsp_types::request::References, _>(r, |id, params| {
     let params = params.text_document_position;
     let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params)?;
-    let res: Option<Vec<_>> = st.analysis.find_all_references(pos).map(|locs| {
+    let res: Option<Vec<_>> = st.analysis(pos.path).find_all_references(pos).map(|locs| {
       locs.into_iter().filter_map(|loc| convert::lsp_location(&st.cx.paths, loc)).collect()
     });
     st.cx.send_response(Response::new_ok(id, res));
     Ok(())
   })?;
+  r = helpers::try_req::<lsp_types::request::DocumentHighlightRequest, _>(r, |id, params| {
+    let params = params.text_document_position_params;
+    let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params)?;
+    let res: Option<Vec<_>> = st
+      .analysis(pos.path)
+      .document_highlights(pos)
+      .map(|xs| xs.into_iter().map(convert::document_highlight).collect());
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::CallHierarchyPrepare, _>(r, |id, params| {
+    let params = params.text_document_position_params;
+    let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params)?;
+    let res = st
+      .analysis(pos.path)
+      .prepare_call_hierarchy(pos)
+      .and_then(|item| convert::call_hierarchy_item(&st.cx.paths, item))
+      .map(|item| vec![item]);
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::CallHierarchyIncomingCalls, _>(r, |id, params| {
+    let pos = convert::call_hierarchy_item_pos(&mut st.cx.paths, &params.item)?;
+    let res: Option<Vec<_>> = st.analysis(pos.path).incoming_calls(pos).map(|calls| {
+      calls.into_iter().filter_map(|call| convert::incoming_call(&st.cx.paths, call)).collect()
+    });
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::CallHierarchyOutgoingCalls, _>(r, |id, params| {
+    let pos = convert::call_hierarchy_item_pos(&mut st.cx.paths, &params.item)?;
+    let res: Option<Vec<_>> = st.analysis(pos.path).outgoing_calls(pos).map(|calls| {
+      calls.into_iter().filter_map(|call| convert::outgoing_call(&st.cx.paths, call)).collect()
+    });
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
   r = helpers::try_req::<lsp_types::request::Completion, _>(r, |id, params| {
     let params = params.text_document_position;
     let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params)?;
-    let res: Option<Vec<_>> =
-      st.analysis.completions(pos).map(|cs| cs.into_iter().map(convert::completion_item).collect());
+    let res: Option<Vec<_>> = st
+      .analysis(pos.path)
+      .completions(pos)
+      .map(|cs| cs.into_iter().map(convert::completion_item).collect());
     st.cx.send_response(Response::new_ok(id, res));
     Ok(())
   })?;
@@ -141,7 +349,7 @@ fn go(st: &mut St, mut r: Request) -> ControlFlow<Result<()>, Request> {
     let path = convert::url_to_path_id(&mut st.cx.paths, &url)?;
     let range = convert::analysis_range(params.range);
     let res: Vec<_> = st
-      .analysis
+      .analysis(path)
       .inlay_hints(path.wrap(range))
       .into_iter()
       .flat_map(|xs| xs.into_iter().map(convert::inlay_hint))
@@ -149,5 +357,111 @@ fn go(st: &mut St, mut r: Request) -> ControlFlow<Result<()>, Request> {
     st.cx.send_response(Response::new_ok(id, res));
     Ok(())
   })?;
+  r = helpers::try_req::<lsp_types::request::PrepareRenameRequest, _>(r, |id, params| {
+    let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params)?;
+    let res = match st.analysis(pos.path).prepare_rename(pos) {
+      Ok((range, placeholder)) => Response::new_ok(
+        id,
+        lsp_types::PrepareRenameResponse::RangeWithPlaceholder {
+          range: convert::lsp_range(range),
+          placeholder,
+        },
+      ),
+      Err(e) => Response::new_err(id, REQUEST_FAILED, e.to_string()),
+    };
+    st.cx.send_response(res);
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::Rename, _>(r, |id, params| {
+    let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params.text_document_position)?;
+    let (Some(root_id), Some(root)) = (st.root_id(pos.path), st.root(pos.path)) else {
+      st.cx.send_response(Response::new_ok(id, None::<()>));
+      return Ok(());
+    };
+    let rename = match root.analysis.start_rename(&root.input, pos, &params.new_name) {
+      Ok(x) => x,
+      Err(e) => {
+        st.cx.send_response(Response::new_err(id, REQUEST_FAILED, e.to_string()));
+        return Ok(());
+      }
+    };
+    // checking for conflicts analyzes the whole input again, so do it on the worker. responds in
+    // `renamed`.
+    let analysis = root.analysis.fresh();
+    let input = root.input.snapshot();
+    if !st.worker.send_rename(id.clone(), root_id, analysis, input, rename) {
+      st.cx.send_response(Response::new_err(
+        id,
+        REQUEST_FAILED,
+        "couldn't check the rename".to_owned(),
+      ));
+    }
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::SemanticTokensFullRequest, _>(r, |id, params| {
+    let path = convert::url_to_path_id(&mut st.cx.paths, &params.text_document.uri)?;
+    let res = st
+      .analysis(path)
+      .semantic_tokens(path, None)
+      .map(|xs| lsp_types::SemanticTokensResult::Tokens(convert::semantic_tokens(xs)));
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::SemanticTokensRangeRequest, _>(r, |id, params| {
+    let path = convert::url_to_path_id(&mut st.cx.paths, &params.text_document.uri)?;
+    let range = convert::analysis_range(params.range);
+    let res = st
+      .analysis(path)
+      .semantic_tokens(path, Some(range))
+      .map(|xs| lsp_types::SemanticTokensRangeResult::Tokens(convert::semantic_tokens(xs)));
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::SignatureHelpRequest, _>(r, |id, params| {
+    let params = params.text_document_position_params;
+    let pos = convert::text_doc_pos_params(&mut st.cx.paths, &params)?;
+    let res = st.analysis(pos.path).signature_help(pos).map(convert::signature_help);
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::DocumentDiagnosticRequest, _>(r, |id, params| {
+    let url = params.text_document.uri;
+    let path = convert::url_to_path_id(&mut st.cx.paths, &url)?;
+    let file = diagnostics::pull(st, &url, path);
+    let res = lsp_types::DocumentDiagnosticReportResult::Report(
+      lsp_types::DocumentDiagnosticReport::Full(lsp_types::RelatedFullDocumentDiagnosticReport {
+        related_documents: None,
+        full_document_diagnostic_report: lsp_types::FullDocumentDiagnosticReport {
+          result_id: None,
+          items: file.items,
+        },
+      }),
+    );
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
+  r = helpers::try_req::<lsp_types::request::WorkspaceDiagnosticRequest, _>(r, |id, _| {
+    let items: Vec<_> = diagnostics::pull_all(st)
+      .into_iter()
+      .map(|(uri, file)| {
+        lsp_types::WorkspaceDocumentDiagnosticReport::Full(
+          lsp_types::WorkspaceFullDocumentDiagnosticReport {
+            uri,
+            version: file.version.map(i64::from),
+            full_document_diagnostic_report: lsp_types::FullDocumentDiagnosticReport {
+              result_id: None,
+              items: file.items,
+            },
+          },
+        )
+      })
+      .collect();
+    let res =
+      lsp_types::WorkspaceDiagnosticReportResult::Report(lsp_types::WorkspaceDiagnosticReport {
+        items,
+      });
+    st.cx.send_response(Response::new_ok(id, res));
+    Ok(())
+  })?;
   ControlFlow::Continue(r)
 }
@@ -1,24 +1,135 @@
 //! The main mutable state of the language server.
 
 use crate::cx::Cx;
-use fast_hash::FxHashSet;
+use crate::worker::{Cancel, JobId, Worker};
+use fast_hash::FxHashMap;
 use lsp_types::Url;
 
 pub(crate) enum Mode {
-  /// We have a workspace root.
-  Root(Box<Root>),
+  /// We have workspace roots, keyed by the path id of their folder. Never empty.
+  Root(paths::PathMap<Root>),
   /// We have no workspace root.
   NoRoot,
 }
 
+/// A workspace folder.
 pub(crate) struct Root {
   pub(crate) path: paths::CleanPathBuf,
   pub(crate) input: input::Input,
+  /// The most recent finished analysis of the input. Used to answer requests, even while a newer
+  /// analysis is running on the worker.
+  pub(crate) analysis: analysis::Analysis,
+  /// The diagnostics from the most recent finished analysis, for every file with diagnostics or
+  /// that was analyzed.
+  pub(crate) diagnostics: FxHashMap<Url, FileDiagnostics>,
+  pub(crate) analyzing: Option<Analyzing>,
+  /// The evaluations of top-level declarations asked for from code lenses, keyed by the start of
+  /// the declaration. Cleared for a file when it changes.
+  pub(crate) evaluations: paths::PathMap<Vec<(text_pos::PositionUtf16, analysis::Evaluation)>>,
+}
+
+/// The diagnostics for a file.
+#[derive(Clone, Default)]
+pub(crate) struct FileDiagnostics {
+  /// The version of the file the diagnostics are for, if it was open.
+  pub(crate) version: Option<i32>,
+  pub(crate) items: Vec<lsp_types::Diagnostic>,
+}
+
+/// An analysis of all the files of a root running on the worker.
+pub(crate) struct Analyzing {
+  /// The id of the job running on the worker.
+  pub(crate) job: JobId,
+  /// Cancels the job, when a newer one replaces it or the root is removed.
+  pub(crate) cancel: Cancel,
+  /// The token for reporting progress to the client, if the client supports it.
+  pub(crate) progress_token: Option<lsp_types::ProgressToken>,
+  /// The paths edited since this started, which must be updated in the result.
+  pub(crate) edited: paths::PathSet,
+  /// The versions of the open paths when this started.
+  pub(crate) versions: paths::PathMap<i32>,
 }
 
 pub struct St {
   pub(crate) mode: Mode,
   pub(crate) cx: Cx,
-  pub(crate) analysis: analysis::Analysis,
-  pub(crate) has_diagnostics: FxHashSet<Url>,
+  /// An analysis with no files. Used for paths in no root, and to make new analyses for roots.
+  pub(crate) empty_analysis: analysis::Analysis,
+  pub(crate) worker: Worker,
+}
+
+impl St {
+  /// Returns the id of the root for the path, if any.
+  ///
+  /// This is the root whose input has the path, or else the root whose folder most closely
+  /// contains the path.
+  pub(crate) fn root_id(&self, path: paths::PathId) -> Option<paths::PathId> {
+    let Mode::Root(roots) = &self.mode else { return None };
+    let has_path = roots.iter().find_map(|(&id, root)| {
+      (root.input.sources.contains_key(&path) || root.input.groups.contains_key(&path))
+        .then_some(id)
+    });
+    if has_path.is_some() {
+      return has_path;
+    }
+    let path = self.cx.paths.get_path(path).as_path();
+    let containing = roots.iter().filter(|(_, root)| path.starts_with(root.path.as_path()));
+    containing.max_by_key(|(_, root)| root.path.as_path().as_os_str().len()).map(|(&id, _)| id)
+  }
+
+  /// Returns the root for the path, if any.
+  pub(crate) fn root(&self, path: paths::PathId) -> Option<&Root> {
+    let id = self.root_id(path)?;
+    match &self.mode {
+      Mode::Root(roots) => roots.get(&id),
+      Mode::NoRoot => None,
+    }
+  }
+
+  /// Returns the root for the path, if any.
+  pub(crate) fn root_mut(&mut self, path: paths::PathId) -> Option<&mut Root> {
+    let id = self.root_id(path)?;
+    match &mut self.mode {
+      Mode::Root(roots) => roots.get_mut(&id),
+      Mode::NoRoot => None,
+    }
+  }
+
+  /// Returns the analysis to answer requests about the path with.
+  pub(crate) fn analysis(&self, path: paths::PathId) -> &analysis::Analysis {
+    self.root(path).map_or(&self.empty_analysis, |root| &root.analysis)
+  }
+
+  /// Returns the ids of all the roots.
+  pub(crate) fn root_ids(&self) -> impl Iterator<Item = paths::PathId> + '_ {
+    let roots = match &self.mode {
+      Mode::Root(roots) => Some(roots.keys()),
+      Mode::NoRoot => None,
+    };
+    roots.into_iter().flatten().copied()
+  }
+
+  /// Returns the analyses of all the roots.
+  pub(crate) fn analyses(&self) -> impl Iterator<Item = &analysis::Analysis> {
+    let roots = match &self.mode {
+      Mode::Root(roots) => Some(roots.values()),
+      Mode::NoRoot => None,
+    };
+    roots.into_iter().flatten().map(|root| &root.analysis)
+  }
+
+  /// Returns a new root for the folder, with nothing analyzed yet.
+  pub(crate) fn new_root(&mut self, path: paths::CleanPathBuf) -> (paths::PathId, Root) {
+    let id = self.cx.paths.get_id(path.as_clean_path());
+    let input = self.cx.get_input(path.as_clean_path());
+    let root = Root {
+      path,
+      input,
+      analysis: self.empty_analysis.fresh(),
+      diagnostics: FxHashMap::default(),
+      analyzing: None,
+      evaluations: paths::PathMap::default(),
+    };
+    (id, root)
+  }
 }
@@ -0,0 +1,199 @@
+//! See [`Worker`].
+
+use crossbeam_channel::{Receiver, Sender};
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Identifies a [`Job`], so that a result can be matched to the job that was started, and not an
+/// earlier job for the same root, like one for a root that was removed and added again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct JobId(u64);
+
+/// Cancels a running analysis [`Job`], e.g. because its input is out of date. The worker checks it
+/// after each file and reports nothing for a canceled job.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+  pub(crate) fn cancel(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  fn is_canceled(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+}
+
+/// Something for the worker to do.
+enum Job {
+  Analyze(Analyze),
+  Rename(Box<Rename>),
+}
+
+/// A request to analyze all the files of a root.
+struct Analyze {
+  /// The id of the job.
+  id: JobId,
+  /// The id of the root.
+  root: paths::PathId,
+  /// The analysis to analyze with. Should have no source files analyzed yet.
+  analysis: analysis::Analysis,
+  /// The input to analyze.
+  input: input::Input,
+  /// Whether to stop analyzing.
+  cancel: Cancel,
+}
+
+/// A request to check a rename does not change the meaning of the input, by analyzing it before and
+/// after the rename.
+struct Rename {
+  /// The id of the rename request from the client.
+  req: lsp_server::RequestId,
+  /// The id of the root.
+  root: paths::PathId,
+  /// An analysis with the std basis and options to analyze with.
+  analysis: analysis::Analysis,
+  /// The input the rename is for.
+  input: input::Input,
+  /// The rename to check.
+  rename: analysis::Rename,
+}
+
+/// A message from the worker.
+pub(crate) enum Event {
+  /// Some more files of the root were analyzed.
+  Progress(JobId, analysis::Progress),
+  /// The analysis finished.
+  Done(Box<Done>),
+  /// The rename was checked.
+  Renamed(Box<Renamed>),
+}
+
+/// The result of a [`Job`].
+pub(crate) struct Done {
+  /// The id of the job.
+  pub(crate) id: JobId,
+  /// The id of the root.
+  pub(crate) root: paths::PathId,
+  /// The analysis, with all the source files analyzed.
+  pub(crate) analysis: analysis::Analysis,
+  /// The diagnostics for each path.
+  pub(crate) diagnostics: paths::PathMap<Vec<analysis::Diagnostic<text_pos::RangeUtf16>>>,
+}
+
+/// The result of checking a rename.
+pub(crate) struct Renamed {
+  /// The id of the rename request from the client.
+  pub(crate) req: lsp_server::RequestId,
+  /// The id of the root.
+  pub(crate) root: paths::PathId,
+  /// The input the rename was for.
+  pub(crate) input: input::Input,
+  /// The edits, grouped by path, if the rename does not change the meaning of the input.
+  pub(crate) res: Result<paths::PathMap<Vec<analysis::TextEdit>>, analysis::RenameError>,
+}
+
+/// A background thread that analyzes all the files, so that analyzing a big project does not block
+/// the main loop from answering requests.
+pub(crate) struct Worker {
+  sender: Sender<Job>,
+  pub(crate) receiver: Receiver<Event>,
+  next_id: u64,
+}
+
+impl Worker {
+  pub(crate) fn new() -> Self {
+    let (job_sender, job_receiver) = crossbeam_channel::unbounded::<Job>();
+    let (event_sender, event_receiver) = crossbeam_channel::unbounded::<Event>();
+    std::thread::Builder::new()
+      .name("analysis".to_owned())
+      .spawn(move || {
+        // stops when the main loop drops the sender.
+        for job in job_receiver {
+          let event = match job {
+            Job::Analyze(job) => match analyze(job, &event_sender) {
+              Some(x) => x,
+              None => continue,
+            },
+            Job::Rename(job) => {
+              let Rename { req, root, analysis, input, rename } = *job;
+              let res = rename.check(&analysis, &input);
+              Event::Renamed(Box::new(Renamed { req, root, input, res }))
+            }
+          };
+          if event_sender.send(event).is_err() {
+            break;
+          }
+        }
+      })
+      .expect("couldn't spawn analysis thread");
+    Self { sender: job_sender, receiver: event_receiver, next_id: 0 }
+  }
+
+  /// Sends a job to analyze the input of the root to the worker. Returns the id of the job and a
+  /// way to cancel it, or `None` if it couldn't be sent, because the worker thread stopped, e.g.
+  /// because it panicked.
+  ///
+  /// The analysis should have no source files analyzed yet.
+  pub(crate) fn send(
+    &mut self,
+    root: paths::PathId,
+    analysis: analysis::Analysis,
+    input: input::Input,
+  ) -> Option<(JobId, Cancel)> {
+    let id = JobId(self.next_id);
+    self.next_id += 1;
+    let cancel = Cancel::default();
+    let job = Analyze { id, root, analysis, input, cancel: cancel.clone() };
+    self.send_job(Job::Analyze(job)).then_some((id, cancel))
+  }
+
+  /// Sends a job to check the rename of the input to the worker, to respond to the request when
+  /// done. Returns whether it could be sent.
+  pub(crate) fn send_rename(
+    &self,
+    req: lsp_server::RequestId,
+    root: paths::PathId,
+    analysis: analysis::Analysis,
+    input: input::Input,
+    rename: analysis::Rename,
+  ) -> bool {
+    self.send_job(Job::Rename(Box::new(Rename { req, root, analysis, input, rename })))
+  }
+
+  fn send_job(&self, job: Job) -> bool {
+    match self.sender.send(job) {
+      Ok(()) => true,
+      Err(e) => {
+        log::error!("couldn't send a job to the analysis thread: {e}");
+        false
+      }
+    }
+  }
+}
+
+/// Returns `None` if the job was canceled.
+fn analyze(job: Analyze, event_sender: &Sender<Event>) -> Option<Event> {
+  let Analyze { id, root, mut analysis, input, cancel } = job;
+  if cancel.is_canceled() {
+    return None;
+  }
+  let mut percent = 0;
+  let diagnostics = analysis.get_many_with_progress(&input, &mut |progress| {
+    if cancel.is_canceled() {
+      return ControlFlow::Break(());
+    }
+    // only report when the percentage changes, to not flood the client.
+    let new_percent = (progress.done * 100) / progress.total.max(1);
+    if new_percent > percent {
+      percent = new_percent;
+      _ = event_sender.send(Event::Progress(id, progress));
+    }
+    ControlFlow::Continue(())
+  });
+  if cancel.is_canceled() {
+    return None;
+  }
+  Some(Event::Done(Box::new(Done { id, root, analysis, diagnostics })))
+}
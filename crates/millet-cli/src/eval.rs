@@ -0,0 +1,129 @@
+//! Evaluating a single SML file with the dynamics.
+
+use paths::FileSystem as _;
+use std::io::{BufRead as _, Write as _};
+
+/// How to evaluate.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Mode {
+  /// Evaluate to completion, printing the final bindings.
+  Run,
+  /// Print every step of the evaluation.
+  Step,
+}
+
+/// Evaluates the file given in the args, after checking it with the statics. Returns the number of
+/// errors.
+pub(crate) fn get(args: &mut pico_args::Arguments, mode: Mode) -> usize {
+  let max_steps: Option<usize> = match args.opt_value_from_str("--max-steps") {
+    Ok(x) => x,
+    Err(e) => {
+      println!("error[{}]: {}", diagnostic::Code::n(1019), e);
+      return 1;
+    }
+  };
+  let file: std::path::PathBuf = match args.free_from_str() {
+    Ok(x) => x,
+    Err(e) => {
+      println!("error[{}]: {}", diagnostic::Code::n(1019), e);
+      return 1;
+    }
+  };
+  let fs = paths::RealFileSystem::default();
+  let pwd = match fs.current_dir() {
+    Ok(x) => x,
+    Err(e) => {
+      println!("error: couldn't get current dir: {e}");
+      return 1;
+    }
+  };
+  let file = pwd.as_clean_path().join(file.as_path());
+  let contents = match fs.read_to_string(file.as_path()) {
+    Ok(x) => x,
+    Err(e) => {
+      let e = input::Error::from_io(file.as_path().to_owned(), e);
+      crate::show_input_error(pwd.as_path(), &e);
+      return 1;
+    }
+  };
+  let mut store = paths::Store::new();
+  let path = store.get_id(file.as_clean_path());
+  let mut inp = input::Input::default();
+  inp.sources.insert(path, contents);
+  let options = analysis::Options {
+    lines: config::DiagnosticLines::One,
+    ignore: config::init::DiagnosticsIgnore::AfterSyntax,
+    format: config::init::FormatEngine::None,
+  };
+  let mut an = analysis::Analysis::new(analysis::StdBasis::full(), options);
+  let ds = an.get_one_text_range(&inp, path).expect("the input has the source file");
+  let mut stderr = codespan_reporting::term::termcolor::StandardStream::stderr(
+    codespan_reporting::term::termcolor::ColorChoice::Auto,
+  );
+  let config = codespan_reporting::term::Config::default();
+  let files = crate::Files { store: &store, input: &inp, analysis: &an };
+  for d in &ds {
+    let d = crate::mk_diagnostic(path, d);
+    codespan_reporting::term::emit(&mut stderr, &config, &files, &d).unwrap();
+  }
+  let errors = ds.iter().filter(|d| matches!(d.severity, diagnostic::Severity::Error)).count();
+  if errors != 0 {
+    return errors;
+  }
+  let unsupported = an.unsupported(path).unwrap_or_default();
+  for (range, item) in &unsupported {
+    let (line, col) = (range.start.line + 1, range.start.col + 1);
+    println!(
+      "error: {}:{line}:{col}: {item} is not supported when evaluating",
+      file.as_path().display()
+    );
+  }
+  if !unsupported.is_empty() {
+    return unsupported.len();
+  }
+  let Some(mut dynamics) = an.dynamics(path) else { return 0 };
+  let mut stdin = std::io::stdin().lock();
+  let mut buf = String::new();
+  let mut steps = 0usize;
+  loop {
+    if let Mode::Step = mode {
+      println!("==> {steps}");
+      println!("{dynamics}");
+      // without a max, wait for the user before each step, stopping at the end of the input.
+      if max_steps.is_none() {
+        buf.clear();
+        if stdin.read_line(&mut buf).map_or(true, |n| n == 0) {
+          return 0;
+        }
+      }
+    }
+    if max_steps.is_some_and(|max| steps >= max) {
+      println!("==> stopped after {steps} steps");
+      return 0;
+    }
+    steps += 1;
+    match dynamics.step() {
+      sml_dynamics::Progress::Still(d) => {
+        dynamics = d;
+        print!("{}", dynamics.take_output());
+        _ = std::io::stdout().flush();
+      }
+      sml_dynamics::Progress::Done(mut finished) => {
+        print!("{}", finished.take_output());
+        if let Mode::Step = mode {
+          println!("==> done");
+        }
+        print!("{finished}");
+        return 0;
+      }
+      sml_dynamics::Progress::Raise(mut finished) => {
+        print!("{}", finished.take_output());
+        if let Mode::Step = mode {
+          println!("==> raised an exception");
+        }
+        print!("{finished}");
+        return 1;
+      }
+    }
+  }
+}
@@ -1,5 +1,7 @@
 //! A thin CLI front-end for running Millet once over some files.
 
+mod eval;
+
 use paths::FileSystem as _;
 
 fn usage() {
@@ -9,7 +11,16 @@ fn usage() {
     .unwrap_or_else(|| "<unknown>".to_owned());
   println!("usage:");
   println!("  {current_exe_name} [options] <path>");
+  println!("  {current_exe_name} run [--max-steps <n>] <file>");
+  println!("  {current_exe_name} step [--max-steps <n>] <file>");
   let rest_of_usage = r"
+commands:
+  run
+    evaluate the SML file, then print the final bindings or the uncaught exception
+  step
+    print every step of evaluating the SML file, waiting for enter before each one unless
+    --max-steps is given
+
 options:
   -h, --help
     show this help
@@ -17,12 +28,18 @@ options:
     WARNING: THE FORMATTER IS HIGHLY EXPERIMENTAL.
     IT MAY IRREVOCABLY DESTROY SOME OR ALL OF YOUR CODE.
     format all of the SML files
+  --max-steps <n>
+    for run and step, stop evaluating after this many steps
 
 arguments:
   <path>
     path of the project to analyze. the path is a directory containing either:
     - a single .cm or .mlb file
     - a millet.toml config file
+    if the path is `run` or `step` and it exists, it is analyzed, instead of taken as the command
+  <file>
+    path of an SML file to evaluate. it is checked on its own, with only the std basis, before
+    evaluating it
 ";
   print!("{rest_of_usage}");
 }
@@ -40,14 +57,32 @@ fn run() -> usize {
     usage();
     return 0;
   }
-  let format = args.contains("--unsafe-format");
-  let root: std::path::PathBuf = match args.free_from_str() {
+  let cmd = match args.subcommand() {
     Ok(x) => x,
     Err(e) => {
       println!("error[{}]: {}", diagnostic::Code::n(1019), e);
       return 1;
     }
   };
+  // a project at a path named like a command is analyzed, not taken as the command.
+  if !cmd.as_deref().is_some_and(|x| std::path::Path::new(x).exists()) {
+    match cmd.as_deref() {
+      Some("run") => return eval::get(&mut args, eval::Mode::Run),
+      Some("step") => return eval::get(&mut args, eval::Mode::Step),
+      _ => {}
+    }
+  }
+  let format = args.contains("--unsafe-format");
+  let root: std::path::PathBuf = match cmd {
+    Some(x) => x.into(),
+    None => match args.free_from_str() {
+      Ok(x) => x,
+      Err(e) => {
+        println!("error[{}]: {}", diagnostic::Code::n(1019), e);
+        return 1;
+      }
+    },
+  };
   let fs = paths::RealFileSystem::default();
   let pwd = match fs.current_dir() {
     Ok(x) => x,
@@ -0,0 +1,125 @@
+//! Starting a debug session, by checking the file to evaluate.
+
+use crate::msg::{Request, Sender};
+use crate::session;
+use anyhow::Result;
+use paths::FileSystem as _;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeArgs {
+  #[serde(default = "yes")]
+  lines_start_at1: bool,
+  #[serde(default = "yes")]
+  columns_start_at1: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LaunchArgs {
+  program: std::path::PathBuf,
+  #[serde(default)]
+  stop_on_entry: bool,
+}
+
+fn yes() -> bool {
+  true
+}
+
+/// What the client counts lines and columns from.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Base {
+  pub(crate) line: u32,
+  pub(crate) col: u32,
+}
+
+/// Handles requests until the client launches the debuggee, then checks it and runs the session.
+pub(crate) fn run(out: &mut Sender, receiver: &crossbeam_channel::Receiver<Request>) -> Result<()> {
+  let mut base = Base { line: 1, col: 1 };
+  let (req, args) = loop {
+    let Ok(req) = receiver.recv() else { return Ok(()) };
+    match req.command.as_str() {
+      "initialize" => match req.args::<InitializeArgs>() {
+        Ok(args) => {
+          base = Base { line: args.lines_start_at1.into(), col: args.columns_start_at1.into() };
+          let capabilities = serde_json::json!({
+            "supportsConfigurationDoneRequest": true,
+            "supportsTerminateRequest": true,
+          });
+          out.ok(&req, capabilities)?;
+        }
+        Err(e) => out.err(&req, &format!("{e:#}"))?,
+      },
+      "launch" => match req.args::<LaunchArgs>() {
+        Ok(args) => break (req, args),
+        Err(e) => out.err(&req, &format!("{e:#}"))?,
+      },
+      "disconnect" | "terminate" => {
+        out.ok(&req, serde_json::json!({}))?;
+        return Ok(());
+      }
+      _ => out.err(&req, "not launched yet")?,
+    }
+  };
+  let fs = paths::RealFileSystem::default();
+  let file = fs.current_dir()?.as_clean_path().join(args.program.as_path());
+  let contents = match fs.read_to_string(file.as_path()) {
+    Ok(x) => x,
+    Err(e) => {
+      out.err(&req, &format!("couldn't read {}: {e}", file.as_path().display()))?;
+      return Ok(());
+    }
+  };
+  let mut store = paths::Store::new();
+  let path = store.get_id(file.as_clean_path());
+  let mut inp = input::Input::default();
+  inp.sources.insert(path, contents);
+  let options = analysis::Options {
+    lines: config::DiagnosticLines::One,
+    ignore: config::init::DiagnosticsIgnore::AfterSyntax,
+    format: config::init::FormatEngine::None,
+  };
+  let mut an = analysis::Analysis::new(analysis::StdBasis::full(), options);
+  let ds = an.get_one_text_range(&inp, path).expect("the input has the source file");
+  let pos_db = an.source_pos_db(path).expect("the source file was analyzed");
+  let mut errors = 0usize;
+  for d in &ds {
+    let severity = match d.severity {
+      diagnostic::Severity::Warning => "warning",
+      diagnostic::Severity::Error => {
+        errors += 1;
+        "error"
+      }
+    };
+    let line = pos_db.range_utf16(d.range).map_or(0, |range| range.start.line) + base.line;
+    let output =
+      format!("{}:{line}: {severity}[{}]: {}\n", file.as_path().display(), d.code, d.message);
+    out.output("stderr", &output)?;
+  }
+  if errors != 0 {
+    let suffix = if errors == 1 { "" } else { "s" };
+    let message = format!("{errors} error{suffix}. see {} for more information", analysis::URL);
+    out.err(&req, &message)?;
+    return Ok(());
+  }
+  let unsupported = an.unsupported(path).unwrap_or_default();
+  for (range, item) in &unsupported {
+    let line = range.start.line + base.line;
+    let output = format!("{}:{line}: error: {item} is not supported\n", file.as_path().display());
+    out.output("stderr", &output)?;
+  }
+  if let Some((_, item)) = unsupported.first() {
+    let message = match unsupported.len() {
+      1 => format!("cannot evaluate: {item} is not supported"),
+      n => format!("cannot evaluate: {item} and {} more are not supported", n - 1),
+    };
+    out.err(&req, &message)?;
+    return Ok(());
+  }
+  out.ok(&req, serde_json::json!({}))?;
+  // we are now ready to take breakpoints, so the client may configure us.
+  out.event("initialized", serde_json::json!({}))?;
+  let cx = session::Cx { an: &an, path, file: file.as_path(), base };
+  session::run(out, receiver, cx, args.stop_on_entry)
+}
@@ -0,0 +1,39 @@
+//! A debug adapter for stepping through the evaluation of an SML file with the dynamics, over the
+//! Debug Adapter Protocol.
+
+mod launch;
+mod msg;
+mod session;
+
+#[cfg(test)]
+mod tests;
+
+fn main() -> anyhow::Result<()> {
+  panic_hook::install();
+  // NOTE: stdout is for the protocol, but the logger writes to stderr.
+  env_logger::try_init_from_env(env_logger::Env::default().default_filter_or("error"))?;
+  log::info!("start up millet debug adapter");
+  let (sender, receiver) = crossbeam_channel::unbounded();
+  std::thread::spawn(move || {
+    let mut stdin = std::io::stdin().lock();
+    loop {
+      match msg::read(&mut stdin) {
+        Ok(Some(req)) => {
+          log::info!("got request: {req:?}");
+          if sender.send(req).is_err() {
+            break;
+          }
+        }
+        Ok(None) => break,
+        Err(e) => {
+          log::error!("couldn't read request: {e:#}");
+          break;
+        }
+      }
+    }
+  });
+  let mut out = msg::Sender::default();
+  launch::run(&mut out, &receiver)?;
+  log::info!("shut down millet debug adapter");
+  Ok(())
+}
@@ -0,0 +1,113 @@
+//! Reading and writing Debug Adapter Protocol messages.
+
+use anyhow::{bail, Context as _, Result};
+use serde::Deserialize;
+use std::io::{BufRead, Write as _};
+
+/// A request from the client.
+#[derive(Debug, Deserialize)]
+pub(crate) struct Request {
+  seq: i64,
+  #[serde(rename = "type")]
+  kind: String,
+  pub(crate) command: String,
+  #[serde(default)]
+  arguments: serde_json::Value,
+}
+
+impl Request {
+  /// Returns the arguments of this.
+  pub(crate) fn args<T>(&self) -> Result<T>
+  where
+    T: serde::de::DeserializeOwned,
+  {
+    serde_json::from_value(self.arguments.clone())
+      .with_context(|| format!("bad arguments for {}", self.command))
+  }
+}
+
+/// Reads a request from the reader, or returns `None` if the reader is done.
+///
+/// Other kinds of messages are skipped, since we never send requests to the client and so never
+/// expect responses.
+pub(crate) fn read(r: &mut dyn BufRead) -> Result<Option<Request>> {
+  loop {
+    let mut len = None::<usize>;
+    let mut buf = String::new();
+    loop {
+      buf.clear();
+      if r.read_line(&mut buf)? == 0 {
+        return Ok(None);
+      }
+      let line = buf.trim_end();
+      if line.is_empty() {
+        break;
+      }
+      if let Some(n) = line.strip_prefix("Content-Length:") {
+        len = Some(n.trim().parse().context("bad Content-Length")?);
+      }
+    }
+    let Some(len) = len else { bail!("no Content-Length") };
+    let mut content = vec![0u8; len];
+    r.read_exact(&mut content)?;
+    let req: Request = serde_json::from_slice(&content)?;
+    if req.kind == "request" {
+      return Ok(Some(req));
+    }
+    log::warn!("ignoring non-request: {req:?}");
+  }
+}
+
+/// Sends messages to the client over stdout.
+#[derive(Debug, Default)]
+pub(crate) struct Sender {
+  seq: i64,
+}
+
+impl Sender {
+  /// Responds to the request with success.
+  pub(crate) fn ok(&mut self, req: &Request, body: serde_json::Value) -> Result<()> {
+    self.send(serde_json::json!({
+      "type": "response",
+      "request_seq": req.seq,
+      "success": true,
+      "command": req.command,
+      "body": body,
+    }))
+  }
+
+  /// Responds to the request with failure.
+  pub(crate) fn err(&mut self, req: &Request, message: &str) -> Result<()> {
+    self.send(serde_json::json!({
+      "type": "response",
+      "request_seq": req.seq,
+      "success": false,
+      "command": req.command,
+      "message": message,
+    }))
+  }
+
+  /// Sends an event.
+  pub(crate) fn event(&mut self, event: &str, body: serde_json::Value) -> Result<()> {
+    self.send(serde_json::json!({ "type": "event", "event": event, "body": body }))
+  }
+
+  /// Sends an output event with the text, if it is not empty.
+  pub(crate) fn output(&mut self, category: &str, output: &str) -> Result<()> {
+    if output.is_empty() {
+      return Ok(());
+    }
+    self.event("output", serde_json::json!({ "category": category, "output": output }))
+  }
+
+  fn send(&mut self, mut msg: serde_json::Value) -> Result<()> {
+    self.seq += 1;
+    msg["seq"] = self.seq.into();
+    let content = msg.to_string();
+    log::info!("send: {content}");
+    let mut stdout = std::io::stdout().lock();
+    write!(stdout, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    stdout.flush()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,382 @@
+//! A debug session, stepping through the evaluation of a file with the dynamics.
+
+use crate::launch::Base;
+use crate::msg::{Request, Sender};
+use anyhow::{bail, Result};
+use crossbeam_channel::Receiver;
+use fast_hash::FxHashSet;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+
+/// There is only one thread.
+const THREAD_ID: u32 = 1;
+
+#[derive(Debug, Deserialize)]
+struct SetBreakpointsArgs {
+  source: Source,
+  #[serde(default)]
+  breakpoints: Vec<SourceBreakpoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Source {
+  path: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SourceBreakpoint {
+  line: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScopesArgs {
+  frame_id: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VariablesArgs {
+  variables_reference: usize,
+}
+
+/// The context of a session.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Cx<'a> {
+  pub(crate) an: &'a analysis::Analysis,
+  /// The file being evaluated.
+  pub(crate) path: paths::PathId,
+  pub(crate) file: &'a std::path::Path,
+  pub(crate) base: Base,
+}
+
+impl Cx<'_> {
+  fn range(&self, idx: Option<sml_hir::Idx>) -> Option<text_pos::RangeUtf16> {
+    self.an.hir_range(self.path, idx?)
+  }
+
+  fn source(&self) -> Value {
+    let name = self.file.file_name().map(|x| x.to_string_lossy());
+    json!({ "name": name, "path": self.file.display().to_string() })
+  }
+}
+
+struct St<'a> {
+  /// `None` when done evaluating, or while evaluating.
+  dynamics: Option<sml_dynamics::Dynamics<'a>>,
+  /// The stack as of when evaluation last stopped.
+  stack: Vec<sml_dynamics::StackFrame>,
+  /// The lines with breakpoints, counting from 0.
+  breakpoints: FxHashSet<u32>,
+  /// The range of the syntax the dynamics was last about to evaluate.
+  range: Option<text_pos::RangeUtf16>,
+  /// How many calls deep the dynamics was when it was last about to evaluate that syntax.
+  depth: usize,
+  /// Requests that came in while evaluating, to handle after it stops.
+  pending: VecDeque<Request>,
+  stop_on_entry: bool,
+}
+
+/// How to resume evaluating.
+#[derive(Debug, Clone, Copy)]
+enum Resume {
+  /// Until a breakpoint.
+  Continue,
+  /// For one step.
+  StepIn,
+  /// Until at most this many calls deep.
+  Next(usize),
+  /// Until fewer than this many calls deep.
+  Out(usize),
+}
+
+/// Handles requests until the client is done with the session.
+pub(crate) fn run(
+  out: &mut Sender,
+  receiver: &Receiver<Request>,
+  cx: Cx<'_>,
+  stop_on_entry: bool,
+) -> Result<()> {
+  let mut st = St {
+    dynamics: cx.an.dynamics(cx.path),
+    stack: Vec::new(),
+    breakpoints: FxHashSet::default(),
+    range: None,
+    depth: 0,
+    pending: VecDeque::new(),
+    stop_on_entry,
+  };
+  loop {
+    let req = match st.pending.pop_front() {
+      Some(req) => req,
+      None => match receiver.recv() {
+        Ok(req) => req,
+        Err(_) => return Ok(()),
+      },
+    };
+    if handle(out, receiver, cx, &mut st, &req)?.is_break() {
+      return Ok(());
+    }
+  }
+}
+
+fn handle<'a>(
+  out: &mut Sender,
+  receiver: &Receiver<Request>,
+  cx: Cx<'a>,
+  st: &mut St<'a>,
+  req: &Request,
+) -> Result<ControlFlow<()>> {
+  let depth = st.dynamics.as_ref().map_or(0, sml_dynamics::Dynamics::depth);
+  let how = match req.command.as_str() {
+    "setBreakpoints" => {
+      respond(out, req, set_breakpoints(cx, &mut st.breakpoints, req))?;
+      return Ok(ControlFlow::Continue(()));
+    }
+    "setExceptionBreakpoints" | "pause" => {
+      // there are no exception breakpoints, and we are already paused.
+      out.ok(req, json!({}))?;
+      return Ok(ControlFlow::Continue(()));
+    }
+    "configurationDone" => {
+      out.ok(req, json!({}))?;
+      if st.stop_on_entry {
+        stop(out, cx, st, "entry")?;
+        return Ok(ControlFlow::Continue(()));
+      }
+      Resume::Continue
+    }
+    "threads" => {
+      out.ok(req, json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }))?;
+      return Ok(ControlFlow::Continue(()));
+    }
+    "stackTrace" => {
+      out.ok(req, stack_trace(cx, st))?;
+      return Ok(ControlFlow::Continue(()));
+    }
+    "scopes" => {
+      respond(out, req, scopes(st, req))?;
+      return Ok(ControlFlow::Continue(()));
+    }
+    "variables" => {
+      respond(out, req, variables(st, req))?;
+      return Ok(ControlFlow::Continue(()));
+    }
+    "disconnect" | "terminate" => {
+      out.ok(req, json!({}))?;
+      return Ok(ControlFlow::Break(()));
+    }
+    "continue" => {
+      out.ok(req, json!({ "allThreadsContinued": true }))?;
+      Resume::Continue
+    }
+    "next" => {
+      out.ok(req, json!({}))?;
+      Resume::Next(depth)
+    }
+    "stepIn" => {
+      out.ok(req, json!({}))?;
+      Resume::StepIn
+    }
+    "stepOut" => {
+      out.ok(req, json!({}))?;
+      Resume::Out(depth)
+    }
+    _ => {
+      out.err(req, &format!("unsupported request: {}", req.command))?;
+      return Ok(ControlFlow::Continue(()));
+    }
+  };
+  resume(out, receiver, cx, st, how)
+}
+
+fn respond(out: &mut Sender, req: &Request, res: Result<Value>) -> Result<()> {
+  match res {
+    Ok(body) => out.ok(req, body),
+    Err(e) => out.err(req, &format!("{e:#}")),
+  }
+}
+
+/// Evaluates until done, or until it should stop as described by `how`.
+fn resume<'a>(
+  out: &mut Sender,
+  receiver: &Receiver<Request>,
+  cx: Cx<'a>,
+  st: &mut St<'a>,
+  how: Resume,
+) -> Result<ControlFlow<()>> {
+  let Some(mut dynamics) = st.dynamics.take() else {
+    out.event("terminated", json!({}))?;
+    return Ok(ControlFlow::Continue(()));
+  };
+  loop {
+    let mut pause = false;
+    for req in receiver.try_iter() {
+      match req.command.as_str() {
+        "pause" => {
+          out.ok(&req, json!({}))?;
+          pause = true;
+        }
+        "disconnect" | "terminate" => {
+          out.ok(&req, json!({}))?;
+          return Ok(ControlFlow::Break(()));
+        }
+        _ => st.pending.push_back(req),
+      }
+    }
+    if pause {
+      st.dynamics = Some(dynamics);
+      stop(out, cx, st, "pause")?;
+      return Ok(ControlFlow::Continue(()));
+    }
+    dynamics = match dynamics.step() {
+      sml_dynamics::Progress::Still(d) => d,
+      sml_dynamics::Progress::Done(finished) => {
+        finish(out, st, finished, 0)?;
+        return Ok(ControlFlow::Continue(()));
+      }
+      sml_dynamics::Progress::Raise(finished) => {
+        finish(out, st, finished, 1)?;
+        return Ok(ControlFlow::Continue(()));
+      }
+    };
+    out.output("stdout", &dynamics.take_output())?;
+    let range = cx.range(dynamics.idx());
+    // entering or leaving a call re-arms the breakpoints, e.g. for a recursive call on the same line.
+    let new_line = range.is_some_and(|range| {
+      !st.range.is_some_and(|old| old.start.line == range.start.line)
+        || st.depth != dynamics.depth()
+    });
+    if range.is_some() {
+      st.range = range;
+      st.depth = dynamics.depth();
+    }
+    let at_breakpoint =
+      new_line && st.range.is_some_and(|range| st.breakpoints.contains(&range.start.line));
+    let reason = if at_breakpoint {
+      Some("breakpoint")
+    } else {
+      let done = match how {
+        Resume::Continue => false,
+        Resume::StepIn => true,
+        Resume::Next(depth) => dynamics.depth() <= depth,
+        Resume::Out(depth) => dynamics.depth() < depth,
+      };
+      done.then_some("step")
+    };
+    if let Some(reason) = reason {
+      st.dynamics = Some(dynamics);
+      stop(out, cx, st, reason)?;
+      return Ok(ControlFlow::Continue(()));
+    }
+  }
+}
+
+fn stop(out: &mut Sender, cx: Cx<'_>, st: &mut St<'_>, reason: &str) -> Result<()> {
+  if let Some(dynamics) = &st.dynamics {
+    if let Some(range) = cx.range(dynamics.idx()) {
+      st.range = Some(range);
+      st.depth = dynamics.depth();
+    }
+    st.stack = dynamics.stack();
+  }
+  let body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+  out.event("stopped", body)
+}
+
+fn finish(
+  out: &mut Sender,
+  st: &mut St<'_>,
+  mut finished: sml_dynamics::Finished<'_>,
+  exit_code: u32,
+) -> Result<()> {
+  out.output("stdout", &finished.take_output())?;
+  let category = if exit_code == 0 { "console" } else { "stderr" };
+  out.output(category, &finished.to_string())?;
+  st.stack.clear();
+  out.event("exited", json!({ "exitCode": exit_code }))?;
+  out.event("terminated", json!({}))
+}
+
+/// Sets the breakpoints, counting lines from 0, to the ones in the request on lines with syntax the
+/// dynamics may stop at.
+pub(crate) fn set_breakpoints(
+  cx: Cx<'_>,
+  breakpoints: &mut FxHashSet<u32>,
+  req: &Request,
+) -> Result<Value> {
+  let args: SetBreakpointsArgs = req.args()?;
+  let ours = args.source.path.as_deref().is_some_and(|path| path == cx.file);
+  let lines = if ours { cx.an.dynamics_lines(cx.path) } else { None };
+  let lines = lines.unwrap_or_default();
+  if ours {
+    breakpoints.clear();
+  }
+  let breakpoints: Vec<_> = args
+    .breakpoints
+    .iter()
+    .map(|bp| {
+      let line = bp.line.checked_sub(cx.base.line).filter(|line| lines.contains(line));
+      if let Some(line) = line {
+        breakpoints.insert(line);
+      }
+      json!({ "verified": line.is_some(), "line": bp.line })
+    })
+    .collect();
+  Ok(json!({ "breakpoints": breakpoints }))
+}
+
+fn stack_trace(cx: Cx<'_>, st: &St<'_>) -> Value {
+  let frames: Vec<_> = st
+    .stack
+    .iter()
+    .enumerate()
+    .map(|(id, frame)| {
+      let mut range = cx.range(frame.idx);
+      if id == 0 {
+        range = range.or(st.range);
+      }
+      match range {
+        Some(range) => json!({
+          "id": id,
+          "name": frame.name,
+          "source": cx.source(),
+          "line": range.start.line + cx.base.line,
+          "column": range.start.col + cx.base.col,
+          "endLine": range.end.line + cx.base.line,
+          "endColumn": range.end.col + cx.base.col,
+        }),
+        None => json!({ "id": id, "name": frame.name, "line": 0, "column": 0 }),
+      }
+    })
+    .collect();
+  json!({ "stackFrames": frames, "totalFrames": frames.len() })
+}
+
+/// Each frame has one scope, whose variables reference is one more than the frame id, since 0 means
+/// no variables.
+fn scopes(st: &St<'_>, req: &Request) -> Result<Value> {
+  let args: ScopesArgs = req.args()?;
+  if args.frame_id >= st.stack.len() {
+    bail!("no frame with id {}", args.frame_id);
+  }
+  let scope =
+    json!({ "name": "Locals", "variablesReference": args.frame_id + 1, "expensive": false });
+  Ok(json!({ "scopes": [scope] }))
+}
+
+fn variables(st: &St<'_>, req: &Request) -> Result<Value> {
+  let args: VariablesArgs = req.args()?;
+  let frame = args.variables_reference.checked_sub(1).and_then(|id| st.stack.get(id));
+  let Some(frame) = frame else {
+    bail!("no variables with reference {}", args.variables_reference)
+  };
+  let vars: Vec<_> = frame
+    .vals
+    .iter()
+    .map(|(name, val)| json!({ "name": name.as_str(), "value": val, "variablesReference": 0 }))
+    .collect();
+  Ok(json!({ "variables": vars }))
+}
@@ -0,0 +1,72 @@
+//! Tests for reading messages and setting breakpoints.
+
+use crate::launch::Base;
+use crate::msg::{read, Request};
+use crate::session::{set_breakpoints, Cx};
+use fast_hash::FxHashSet;
+use serde_json::json;
+
+fn frame(content: &str) -> String {
+  format!("Content-Length: {}\r\n\r\n{content}", content.len())
+}
+
+fn request(seq: i64, command: &str) -> String {
+  json!({ "seq": seq, "type": "request", "command": command }).to_string()
+}
+
+#[test]
+fn read_requests() {
+  let response = json!({ "seq": 2, "type": "response", "command": "runInTerminal" }).to_string();
+  let input = [
+    frame(&request(1, "initialize")),
+    frame(&response),
+    format!("Content-Type: application/json\r\n{}", frame(&request(3, "launch"))),
+  ]
+  .concat();
+  let mut r = input.as_bytes();
+  assert_eq!(read(&mut r).unwrap().unwrap().command, "initialize");
+  assert_eq!(read(&mut r).unwrap().unwrap().command, "launch");
+  assert!(read(&mut r).unwrap().is_none());
+}
+
+#[test]
+fn read_bad_framing() {
+  let content = request(1, "initialize");
+  let no_len = format!("Content-Type: application/json\r\n\r\n{content}");
+  let bad_len = format!("Content-Length: many\r\n\r\n{content}");
+  let short = format!("Content-Length: {}\r\n\r\n{content}", content.len() + 1);
+  for input in [no_len, bad_len, short] {
+    assert!(read(&mut input.as_bytes()).is_err(), "should not read: {input:?}");
+  }
+}
+
+#[test]
+fn breakpoint_lines() {
+  let contents = ["val x = 1", "", "fun f y =", "  y + 1", ""].join("\n");
+  let file = paths::CleanPathBuf::new(std::path::Path::new("/a.sml")).unwrap();
+  let mut store = paths::Store::new();
+  let path = store.get_id(file.as_clean_path());
+  let mut inp = input::Input::default();
+  inp.sources.insert(path, contents);
+  let mut an = analysis::Analysis::new(analysis::StdBasis::minimal(), analysis::Options::default());
+  an.get_one_text_range(&inp, path).unwrap();
+  let cx = Cx { an: &an, path, file: file.as_path(), base: Base { line: 1, col: 1 } };
+  let mut breakpoints = FxHashSet::default();
+  let want: FxHashSet<u32> = [0, 2, 3].into_iter().collect();
+  let req = |source: &str| -> Request {
+    let lines: Vec<_> = (0..=5).map(|line| json!({ "line": line })).collect();
+    let arguments = json!({ "source": { "path": source }, "breakpoints": lines });
+    let req =
+      json!({ "seq": 1, "type": "request", "command": "setBreakpoints", "arguments": arguments });
+    serde_json::from_value(req).unwrap()
+  };
+  let got = set_breakpoints(cx, &mut breakpoints, &req("/a.sml")).unwrap();
+  let verified: Vec<_> =
+    got["breakpoints"].as_array().unwrap().iter().map(|bp| bp["verified"] == true).collect();
+  assert_eq!(verified, [false, true, false, true, true, false]);
+  assert_eq!(breakpoints, want);
+  // breakpoints in other files are not verified, and leave ours alone.
+  let got = set_breakpoints(cx, &mut breakpoints, &req("/b.sml")).unwrap();
+  assert!(got["breakpoints"].as_array().unwrap().iter().all(|bp| bp["verified"] == false));
+  assert_eq!(breakpoints, want);
+}
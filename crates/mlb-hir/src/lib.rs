@@ -5,7 +5,7 @@ use sml_namespace::Module;
 use text_size_util::WithRange;
 
 /// A basis declaration.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum BasDec {
   /// `basis <name> = <exp>`
   Basis(WithRange<str_util::Name>, Box<BasExp>),
@@ -42,7 +42,7 @@ impl BasDec {
 }
 
 /// A basis expression.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum BasExp {
   /// `bas <dec> end`
   Bas(BasDec),
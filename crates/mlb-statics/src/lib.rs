@@ -8,6 +8,7 @@ use fast_hash::FxHashMap;
 use sml_file_syntax::SourceFileSyntax;
 use sml_syntax::ast::AstNode as _;
 use std::fmt;
+use std::ops::ControlFlow;
 
 pub use std_basis::StdBasis;
 
@@ -22,6 +23,15 @@ pub struct MlbStatics {
   pub source_files: paths::PathMap<SourceFile>,
 }
 
+/// Progress through analyzing the source files.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+  /// How many source files have been analyzed so far.
+  pub done: usize,
+  /// How many source files there are.
+  pub total: usize,
+}
+
 /// A source file.
 #[derive(Debug)]
 pub struct SourceFile {
@@ -31,6 +41,10 @@ pub struct SourceFile {
   pub statics_errors: Vec<sml_statics::Error>,
   /// Statics information from the file.
   pub info: sml_statics::info::Info,
+  /// Id statuses for path expressions in the file.
+  pub exp_id_statuses: sml_statics_types::info::IdStatusMap<sml_hir::Exp>,
+  /// Id statuses for path patterns in the file.
+  pub pat_id_statuses: sml_statics_types::info::IdStatusMap<sml_hir::Pat>,
   /// The fixity env this file was (or should be, when updating it) parsed under.
   ///
   /// Empty when `fixity_across_files` is false.
@@ -105,6 +119,10 @@ struct St<'a> {
   source_files: paths::PathMap<SourceFile>,
   mlb_errors: Vec<Error>,
   report_diagnostics: bool,
+  total: usize,
+  on_progress: &'a mut dyn FnMut(Progress) -> ControlFlow<()>,
+  /// Whether `on_progress` asked to stop.
+  stopped: bool,
 }
 
 impl<'a> St<'a> {
@@ -144,7 +162,8 @@ impl MBasis {
   }
 }
 
-/// Runs analysis.
+/// Runs analysis, calling `on_progress` after analyzing each source file. If it breaks, stops early,
+/// with only some of the source files analyzed.
 #[must_use]
 pub fn get(
   syms_tys: &mut sml_statics_types::St,
@@ -153,6 +172,7 @@ pub fn get(
   source_file_contents: &paths::PathMap<String>,
   bas_decs: &paths::PathMap<&mlb_hir::BasDec>,
   root_group_paths: &[paths::PathId],
+  on_progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
 ) -> MlbStatics {
   let mut st = St {
     syms_tys,
@@ -160,6 +180,9 @@ pub fn get(
     source_files: paths::PathMap::default(),
     mlb_errors: Vec::new(),
     report_diagnostics: true,
+    total: source_file_contents.len(),
+    on_progress,
+    stopped: false,
   };
   for &path in root_group_paths {
     let std_basis = MBasis {
@@ -205,6 +228,9 @@ fn get_bas_dec(
   ac: &mut MBasis,
   dec: &mlb_hir::BasDec,
 ) {
+  if st.stopped {
+    return;
+  }
   match dec {
     mlb_hir::BasDec::Basis(name, exp) => {
       let mut exp_m_basis = MBasis::default();
@@ -305,6 +331,9 @@ fn get_source_file(
   fix_env: sml_fixity::Env,
   syntax: SourceFileSyntax,
 ) {
+  if st.stopped {
+    return;
+  }
   let mode = sml_statics_types::mode::Mode::Regular(Some(path));
   let checked =
     sml_statics::get(st.syms_tys, &scope.bs, mode, &syntax.lower.arenas, &syntax.lower.root);
@@ -314,11 +343,13 @@ fn get_source_file(
     bs: checked.info.basis().clone(),
   });
   let mut info = checked.info;
-  add_all_doc_comments(syntax.parse.root.syntax(), &syntax.lower, &mut info);
+  add_all_doc_comments(syntax.parse.root().syntax(), &syntax.lower, &mut info);
   let mut file = SourceFile {
     syntax,
     statics_errors: checked.errors,
     info,
+    exp_id_statuses: checked.exp_id_statuses,
+    pat_id_statuses: checked.pat_id_statuses,
     fix_env: if lang.fixity_across_files {
       scope.fix_env.clone()
     } else {
@@ -341,6 +372,8 @@ fn get_source_file(
   //
   // this drops the errors from any previous analyses of this file on the floor.
   st.source_files.insert(path, file);
+  let progress = Progress { done: st.source_files.len(), total: st.total };
+  st.stopped = (st.on_progress)(progress).is_break();
 }
 
 /// Processes a single group file.
@@ -393,5 +426,37 @@ pub fn update_one(
   let checked =
     sml_statics::get(syms_tys, &sf.scope, mode, &sf.syntax.lower.arenas, &sf.syntax.lower.root);
   sf.info = checked.info;
-  add_all_doc_comments(sf.syntax.parse.root.syntax(), &sf.syntax.lower, &mut sf.info);
+  sf.exp_id_statuses = checked.exp_id_statuses;
+  sf.pat_id_statuses = checked.pat_id_statuses;
+  add_all_doc_comments(sf.syntax.parse.root().syntax(), &sf.syntax.lower, &mut sf.info);
+}
+
+/// Analyzes a single source file on its own, with only the basis `bs` in scope.
+#[must_use]
+pub fn get_one(
+  syms_tys: &mut sml_statics_types::St,
+  lang: &Language,
+  bs: &sml_statics::basis::Bs,
+  path: paths::PathId,
+  contents: &str,
+) -> SourceFile {
+  let mut fix_env = sml_fixity::STD_BASIS.clone();
+  let syntax = SourceFileSyntax::new(&mut fix_env, lang, sml_file::Kind::Sml, contents);
+  let mode = sml_statics_types::mode::Mode::Regular(Some(path));
+  let checked = sml_statics::get(syms_tys, bs, mode, &syntax.lower.arenas, &syntax.lower.root);
+  let mut info = checked.info;
+  add_all_doc_comments(syntax.parse.root().syntax(), &syntax.lower, &mut info);
+  SourceFile {
+    syntax,
+    statics_errors: checked.errors,
+    info,
+    exp_id_statuses: checked.exp_id_statuses,
+    pat_id_statuses: checked.pat_id_statuses,
+    fix_env: if lang.fixity_across_files {
+      sml_fixity::STD_BASIS.clone()
+    } else {
+      sml_fixity::Env::default()
+    },
+    scope: bs.clone(),
+  }
 }
@@ -54,6 +54,11 @@ impl StdBasis {
   pub fn get_info(&self, s: &str) -> Option<&Info> {
     self.info.get(s)
   }
+
+  /// Returns an iterator over the names of the files in this and their info.
+  pub fn infos(&self) -> impl Iterator<Item = (&'static str, &Info)> + '_ {
+    self.info.iter().map(|(&name, info)| (name, info))
+  }
 }
 
 const STREAM_IO_REGULAR: &str = "  structure StreamIO : STREAM_IO";
@@ -122,7 +127,7 @@ where
       panic!("{name}: statics error: {e}");
     }
     let mut info = checked.info;
-    add_all_doc_comments(started.parse.root.syntax(), &low, &mut info);
+    add_all_doc_comments(started.parse.root().syntax(), &low, &mut info);
     (name, info)
   });
   let info: FxHashMap<_, _> = iter.collect();
@@ -3,19 +3,28 @@
 #![cfg(test)]
 #![allow(clippy::too_many_lines)]
 
+use once_cell::sync::Lazy;
 use std::io::BufRead as _;
 
+static STD_BASIS: Lazy<mlb_statics::StdBasis> = Lazy::new(mlb_statics::StdBasis::full);
+
 fn env_var_enabled(s: &str) -> bool {
   std::env::var_os(s).map_or(false, |x| x == "1")
 }
 
-#[allow(dead_code)]
-fn check(s: &str, steps: &[&str]) {
-  let show_debug = env_var_enabled("MILLET_SHOW_DEBUG");
-  let show_steps = env_var_enabled("MILLET_SHOW_STEPS");
-  let show = show_debug || show_steps;
-  let manually_advance = env_var_enabled("MILLET_MANUALLY_ADVANCE");
-  let check_steps = !env_var_enabled("MILLET_NO_CHECK_STEPS");
+/// Calls `f` with the dynamics for `s`.
+fn with_dynamics<F>(s: &str, f: F)
+where
+  F: FnOnce(sml_dynamics::Dynamics<'_>),
+{
+  with_cx(s, |cx, root| f(sml_dynamics::Dynamics::new(cx, root.to_vec()).expect("no str decs")));
+}
+
+/// Calls `f` with the context for running the dynamics on `s`, and the top-level decs of `s`.
+fn with_cx<F>(s: &str, f: F)
+where
+  F: FnOnce(sml_dynamics::Cx<'_>, &[sml_hir::StrDecIdx]),
+{
   let mut fix_env = sml_fixity::STD_BASIS.clone();
   let lang = config::lang::Language::default();
   let sf = sml_file_syntax::SourceFileSyntax::new(&mut fix_env, &lang, sml_file::Kind::Sml, s);
@@ -28,66 +37,124 @@ fn check(s: &str, steps: &[&str]) {
   if let Some(e) = sf.lower.errors.first() {
     panic!("lower error: {e}");
   }
-  let (mut syms_tys, bs) = sml_statics::basis::minimal();
+  let mut syms_tys = STD_BASIS.syms_tys().clone();
+  let bs = STD_BASIS.basis();
   let mode = sml_statics_types::mode::Mode::Dynamics;
-  let statics = sml_statics::get(&mut syms_tys, &bs, mode, &sf.lower.arenas, &sf.lower.root);
+  let statics = sml_statics::get(&mut syms_tys, bs, mode, &sf.lower.arenas, &sf.lower.root);
   if let Some(e) = statics.errors.first() {
     panic!("statics error: {}", e.display(&syms_tys, config::DiagnosticLines::One));
   }
-  // TODO have these be the same as the ones from the std basis
-  let match_ = syms_tys.syms.insert_exn(sml_path::Path::one(str_util::Name::new("Match")), None);
-  let bind = syms_tys.syms.insert_exn(sml_path::Path::one(str_util::Name::new("Bind")), None);
   let cx = sml_dynamics::Cx {
     ars: &sf.lower.arenas,
     exp: &statics.exp_id_statuses,
     pat: &statics.pat_id_statuses,
-    bind,
-    match_,
+    exns: sml_dynamics::Exns::new(&bs.env).expect("no exns in std basis"),
   };
-  let mut dynamics = sml_dynamics::Dynamics::new(cx, sf.lower.root.clone()).expect("no str decs");
-  let mut stdin = std::io::stdin().lock();
-  let mut buf = String::new();
-  let mut steps = steps.iter();
-  loop {
-    if show {
-      println!("==>");
-    }
-    if show_debug {
-      dynamics.show_debug();
-    }
-    if show_steps {
-      println!("{dynamics:#}");
-    }
-    if manually_advance {
-      stdin.read_line(&mut buf).expect("couldn't read");
-      buf.clear();
+  f(cx, &sf.lower.root);
+}
+
+#[allow(dead_code)]
+fn check(s: &str, steps: &[&str]) {
+  let show_debug = env_var_enabled("MILLET_SHOW_DEBUG");
+  let show_steps = env_var_enabled("MILLET_SHOW_STEPS");
+  let show = show_debug || show_steps;
+  let manually_advance = env_var_enabled("MILLET_MANUALLY_ADVANCE");
+  let check_steps = !env_var_enabled("MILLET_NO_CHECK_STEPS");
+  with_dynamics(s, |mut dynamics| {
+    let mut stdin = std::io::stdin().lock();
+    let mut buf = String::new();
+    let mut steps = steps.iter();
+    loop {
+      if show {
+        println!("==>");
+      }
+      if show_debug {
+        dynamics.show_debug();
+      }
+      if show_steps {
+        println!("{dynamics:#}");
+      }
+      if manually_advance {
+        stdin.read_line(&mut buf).expect("couldn't read");
+        buf.clear();
+      }
+      if check_steps {
+        let want = rm_whitespace(steps.next().expect("missing step").trim());
+        let got = rm_whitespace(&dynamics.to_string());
+        pretty_assertions::assert_str_eq!(want, got);
+      }
+      match dynamics.step() {
+        sml_dynamics::Progress::Still(d) => dynamics = d,
+        sml_dynamics::Progress::Done(_) => {
+          if show {
+            println!("==> done");
+          }
+          break;
+        }
+        sml_dynamics::Progress::Raise(_) => {
+          if show {
+            println!("==> raised an exception");
+          }
+          break;
+        }
+      }
     }
     if check_steps {
-      let want = rm_whitespace(steps.next().expect("missing step").trim());
-      let got = rm_whitespace(&dynamics.to_string());
-      pretty_assertions::assert_str_eq!(want, got);
+      if let Some(step) = steps.next() {
+        panic!("extra step: {step}");
+      }
     }
+  });
+}
+
+/// Runs `s` to completion and checks what it printed.
+fn check_output(s: &str, want: &str) {
+  with_dynamics(s, |mut dynamics| {
+    let mut got = String::new();
+    loop {
+      got.push_str(&dynamics.take_output());
+      match dynamics.step() {
+        sml_dynamics::Progress::Still(d) => dynamics = d,
+        sml_dynamics::Progress::Done(mut finished) => {
+          got.push_str(&finished.take_output());
+          break;
+        }
+        sml_dynamics::Progress::Raise(finished) => panic!("raised an exception: {finished}"),
+      }
+    }
+    pretty_assertions::assert_str_eq!(want, got);
+  });
+}
+
+/// Runs `s` to completion and checks how it finished.
+fn check_finished(s: &str, want: &str) {
+  with_dynamics(s, |mut dynamics| loop {
     match dynamics.step() {
       sml_dynamics::Progress::Still(d) => dynamics = d,
-      sml_dynamics::Progress::Done => {
-        if show {
-          println!("==> done");
-        }
+      sml_dynamics::Progress::Done(finished) | sml_dynamics::Progress::Raise(finished) => {
+        pretty_assertions::assert_str_eq!(want, finished.to_string());
         break;
       }
-      sml_dynamics::Progress::Raise => {
-        if show {
-          println!("==> raised an exception");
+    }
+  });
+}
+
+/// Evaluates the exp of the first `val` in `s` on its own, and checks how it finished.
+fn check_exp_finished(s: &str, want: &str) {
+  with_cx(s, |cx, root| {
+    let sml_hir::StrDec::Dec(decs) = &cx.ars.str_dec[root[0]] else { panic!("not a dec") };
+    let sml_hir::Dec::Val(_, val_binds, _) = &cx.ars.dec[decs[0]] else { panic!("not a val") };
+    let mut dynamics = sml_dynamics::Dynamics::new_exp(cx, val_binds[0].exp);
+    loop {
+      match dynamics.step() {
+        sml_dynamics::Progress::Still(d) => dynamics = d,
+        sml_dynamics::Progress::Done(finished) | sml_dynamics::Progress::Raise(finished) => {
+          pretty_assertions::assert_str_eq!(want, finished.to_string());
+          break;
         }
-        break;
       }
     }
-  }
-  if check_steps {
-    if let Some(step) = steps.next() {
-      panic!("extra step: {step}");
-    }
-  }
+  });
 }
 
 fn rm_whitespace(s: &str) -> String {
@@ -369,3 +436,236 @@ val four =
     ],
   );
 }
+
+#[test]
+fn structure_path() {
+  check(
+    r"
+structure S = struct val x = 1 end
+val y = S.x
+",
+    &[
+      r"
+structure S = struct val x = 1 end
+val y = S.x
+",
+      r"
+structure S = struct val x = 1 end
+val y = S.x
+",
+      r"
+val y = S.x
+",
+      r"
+val y = 1
+",
+    ],
+  );
+}
+
+#[test]
+fn functor_app() {
+  check(
+    r"
+signature SIG = sig val x : int end
+functor F (A : SIG) = struct val y = A.x end
+local
+  structure B = struct val x = 1 val w = 2 end
+in
+  structure C = F (B :> SIG)
+end
+val z = C.y
+",
+    &[
+      r"
+signature ...
+functor ...
+local
+  structure B = struct val x = 1 val w = 2 end
+in
+  structure C = F(B)
+end
+val z = C.y
+",
+      r"
+local
+  structure B = struct val w = 2 end
+in
+  structure C = F(B)
+end
+val z = C.y
+",
+      r"
+local
+  structure B = struct val w = 2 val x = 1 end
+in
+  structure C = F(B)
+end
+val z = C.y
+",
+      r"
+local in
+  structure C = F(B)
+end
+val z = C.y
+",
+      r"
+local in
+  structure C = F(struct val w = 2 val x = 1 end)
+end
+val z = C.y
+",
+      r"
+local in
+  structure C = struct val y = A.x end
+end
+val z = C.y
+",
+      r"
+local in
+  structure C = struct val y = 1 end
+end
+val z = C.y
+",
+      r"
+local in
+  structure C = struct val y = 1 end
+end
+val z = C.y
+",
+      r"
+val z = C.y
+",
+      r"
+val z = 1
+",
+    ],
+  );
+}
+
+#[test]
+fn std_basis_paths() {
+  for path in sml_dynamics::std_basis_paths() {
+    let mut env = &STD_BASIS.basis().env;
+    for name in path.prefix() {
+      env = env.str_env.get(name).unwrap_or_else(|| panic!("{path}: no structure {name}"));
+    }
+    let val_info = env.val_env.get(path.last()).unwrap_or_else(|| panic!("{path}: not defined"));
+    let is_val = matches!(val_info.id_status, sml_statics_types::info::IdStatus::Val);
+    assert!(is_val, "{path}: not a value");
+  }
+}
+
+#[test]
+fn refs() {
+  check_output(
+    r#"
+val r = ref 0
+fun incr () = r := !r + 1
+val () = List.app (fn _ => incr ()) [1, 2, 3]
+val () = print (Int.toString (!r) ^ "\n")
+val () = case r of ref n => print (Int.toString (n * 2) ^ "\n")
+"#,
+    "3\n6\n",
+  );
+}
+
+#[test]
+fn lists() {
+  check_output(
+    r#"
+val xs = List.map (fn x => x * x) [1, 2, 3]
+val () = print (String.concatWith "," (map Int.toString xs) ^ "\n")
+val () = print (Int.toString (foldl op+ 0 xs) ^ "\n")
+val () = print (implode (rev (explode "abc")) ^ "\n")
+val () = print (Int.toString (length (List.filter (fn x => x > 1) xs)) ^ "\n")
+"#,
+    "1,4,9\n14\ncba\n2\n",
+  );
+}
+
+#[test]
+fn builtin_exceptions() {
+  check_output(
+    r#"
+val () = print ((Int.toString (1 div 0)) handle Div => "div\n")
+val () = print (hd [] handle Empty => "empty\n")
+val () = print (valOf NONE handle Option => "option\n")
+val () = print (str (String.sub ("abc", 3)) handle Subscript => "subscript\n")
+"#,
+    "div\nempty\noption\nsubscript\n",
+  );
+}
+
+#[test]
+fn int_overflow() {
+  check_output(
+    r#"
+val big = 4611686018427387903
+val () = print (Int.toString big ^ "\n")
+val () = print ((Int.toString (big + 1)) handle Overflow => "add\n")
+val () = print ((Int.toString (~big - 2)) handle Overflow => "sub\n")
+val () = print ((Int.toString (valOf (Int.fromString "4611686018427387904"))) handle Overflow => "from string\n")
+val () = print ((Int.toString (valOf (Int.fromString "99999999999999999999"))) handle Overflow => "from long string\n")
+"#,
+    "4611686018427387903\nadd\nsub\nfrom string\nfrom long string\n",
+  );
+}
+
+#[test]
+fn finished_bindings() {
+  check_finished(
+    r"
+val x = 1 + 2
+val y = x * 2
+structure S = struct val z = y end
+",
+    "S:\n  z: 6\nx: 3\ny: 6\n",
+  );
+}
+
+#[test]
+fn finished_uncaught() {
+  check_finished(
+    r"
+val x = 1
+val y = x div 0
+",
+    "uncaught exception Div\n",
+  );
+}
+
+#[test]
+fn stack() {
+  let s = r"
+fun add a b = if a = 0 then b else 1 + add (a - 1) b
+val x = add 2 3
+";
+  with_dynamics(s, |mut dynamics| {
+    while dynamics.depth() < 3 {
+      match dynamics.step() {
+        sml_dynamics::Progress::Still(d) => dynamics = d,
+        sml_dynamics::Progress::Done(_) | sml_dynamics::Progress::Raise(_) => {
+          panic!("finished before the deepest call")
+        }
+      }
+    }
+    let stack = dynamics.stack();
+    let names: Vec<_> = stack.iter().map(|frame| frame.name.as_str()).collect();
+    assert_eq!(names, ["add", "add", "add", "top level"]);
+    let got: Vec<_> = stack
+      .iter()
+      .map(|frame| {
+        frame.vals.iter().find(|(name, _)| name.as_str() == "a").map(|(_, a)| a.as_str())
+      })
+      .collect();
+    assert_eq!(got, [Some("0"), Some("1"), Some("2"), None]);
+  });
+}
+
+#[test]
+fn exp_finished() {
+  check_exp_finished("val _ = let val x = 2 in x * (x + 1) end", "6\n");
+  check_exp_finished("val _ = case [1, 2] of [] => 0 | x :: _ => x", "1\n");
+  check_exp_finished("val _ = 1 div 0", "uncaught exception Div\n");
+}
@@ -0,0 +1,871 @@
+//! The parts of the std basis implemented natively, and what happens when they are applied.
+
+use crate::step::apply_val;
+use crate::types::{ConKind, Cx, Exception, FrameKind, St, Step, Val};
+use sml_hir::SCon;
+use str_util::Name;
+
+/// `Int.precision`, the number of bits in an `int`. This is what SML/NJ uses on 64-bit machines.
+const INT_PRECISION: u32 = 63;
+/// `Int.minInt`.
+const MIN_INT: i64 = -(1 << (INT_PRECISION - 1));
+/// `Int.maxInt`.
+const MAX_INT: i64 = (1 << (INT_PRECISION - 1)) - 1;
+
+/// A value from the std basis implemented natively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Builtin {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Mod,
+  RealDiv,
+  Neg,
+  Abs,
+  Lt,
+  LtEq,
+  Gt,
+  GtEq,
+  Eq,
+  Neq,
+  Not,
+  Concat,
+  Append,
+  Compose,
+  Before,
+  Ignore,
+  Deref,
+  Assign,
+  Print,
+  ExnName,
+  ExnMessage,
+  IntToString,
+  IntFromString,
+  IntMin,
+  IntMax,
+  IntCompare,
+  RealFromInt,
+  RealToString,
+  RealFloor,
+  RealCeil,
+  RealRound,
+  RealTrunc,
+  CharOrd,
+  CharChr,
+  CharIsDigit,
+  CharIsAlpha,
+  CharIsSpace,
+  CharToUpper,
+  CharToLower,
+  StringSize,
+  StringSub,
+  StringSubstring,
+  StringConcat,
+  StringConcatWith,
+  StringExplode,
+  StringImplode,
+  StringStr,
+  StringIsPrefix,
+  ListNull,
+  ListLength,
+  ListHd,
+  ListTl,
+  ListLast,
+  ListRev,
+  ListNth,
+  ListTake,
+  ListDrop,
+  ListConcat,
+  ListMap,
+  ListApp,
+  ListFoldl,
+  ListFoldr,
+  ListFilter,
+  ListExists,
+  ListAll,
+  ListFind,
+  ListTabulate,
+  OptionIsSome,
+  OptionValOf,
+  OptionGetOpt,
+  OptionMap,
+  TextIoStdOut,
+  TextIoOutput,
+}
+
+/// Where the builtins are bound in the std basis: the structure, if any, and the name.
+pub(crate) const BINDINGS: [(Option<&str>, &str, Builtin); 112] = [
+  (None, "+", Builtin::Add),
+  (None, "-", Builtin::Sub),
+  (None, "*", Builtin::Mul),
+  (None, "div", Builtin::Div),
+  (None, "mod", Builtin::Mod),
+  (None, "/", Builtin::RealDiv),
+  (None, "~", Builtin::Neg),
+  (None, "abs", Builtin::Abs),
+  (None, "<", Builtin::Lt),
+  (None, "<=", Builtin::LtEq),
+  (None, ">", Builtin::Gt),
+  (None, ">=", Builtin::GtEq),
+  (None, "=", Builtin::Eq),
+  (None, "<>", Builtin::Neq),
+  (None, "not", Builtin::Not),
+  (None, "^", Builtin::Concat),
+  (None, "@", Builtin::Append),
+  (None, "o", Builtin::Compose),
+  (None, "before", Builtin::Before),
+  (None, "ignore", Builtin::Ignore),
+  (None, "!", Builtin::Deref),
+  (None, ":=", Builtin::Assign),
+  (None, "print", Builtin::Print),
+  (None, "exnName", Builtin::ExnName),
+  (None, "exnMessage", Builtin::ExnMessage),
+  (None, "real", Builtin::RealFromInt),
+  (None, "floor", Builtin::RealFloor),
+  (None, "ceil", Builtin::RealCeil),
+  (None, "round", Builtin::RealRound),
+  (None, "trunc", Builtin::RealTrunc),
+  (None, "ord", Builtin::CharOrd),
+  (None, "chr", Builtin::CharChr),
+  (None, "size", Builtin::StringSize),
+  (None, "substring", Builtin::StringSubstring),
+  (None, "concat", Builtin::StringConcat),
+  (None, "explode", Builtin::StringExplode),
+  (None, "implode", Builtin::StringImplode),
+  (None, "str", Builtin::StringStr),
+  (None, "null", Builtin::ListNull),
+  (None, "length", Builtin::ListLength),
+  (None, "hd", Builtin::ListHd),
+  (None, "tl", Builtin::ListTl),
+  (None, "rev", Builtin::ListRev),
+  (None, "map", Builtin::ListMap),
+  (None, "app", Builtin::ListApp),
+  (None, "foldl", Builtin::ListFoldl),
+  (None, "foldr", Builtin::ListFoldr),
+  (None, "isSome", Builtin::OptionIsSome),
+  (None, "valOf", Builtin::OptionValOf),
+  (None, "getOpt", Builtin::OptionGetOpt),
+  (Some("Int"), "+", Builtin::Add),
+  (Some("Int"), "-", Builtin::Sub),
+  (Some("Int"), "*", Builtin::Mul),
+  (Some("Int"), "div", Builtin::Div),
+  (Some("Int"), "mod", Builtin::Mod),
+  (Some("Int"), "~", Builtin::Neg),
+  (Some("Int"), "abs", Builtin::Abs),
+  (Some("Int"), "toString", Builtin::IntToString),
+  (Some("Int"), "fromString", Builtin::IntFromString),
+  (Some("Int"), "min", Builtin::IntMin),
+  (Some("Int"), "max", Builtin::IntMax),
+  (Some("Int"), "compare", Builtin::IntCompare),
+  (Some("Real"), "fromInt", Builtin::RealFromInt),
+  (Some("Real"), "toString", Builtin::RealToString),
+  (Some("Real"), "floor", Builtin::RealFloor),
+  (Some("Real"), "ceil", Builtin::RealCeil),
+  (Some("Real"), "round", Builtin::RealRound),
+  (Some("Real"), "trunc", Builtin::RealTrunc),
+  (Some("Char"), "ord", Builtin::CharOrd),
+  (Some("Char"), "chr", Builtin::CharChr),
+  (Some("Char"), "isDigit", Builtin::CharIsDigit),
+  (Some("Char"), "isAlpha", Builtin::CharIsAlpha),
+  (Some("Char"), "isSpace", Builtin::CharIsSpace),
+  (Some("Char"), "toUpper", Builtin::CharToUpper),
+  (Some("Char"), "toLower", Builtin::CharToLower),
+  (Some("String"), "size", Builtin::StringSize),
+  (Some("String"), "sub", Builtin::StringSub),
+  (Some("String"), "substring", Builtin::StringSubstring),
+  (Some("String"), "^", Builtin::Concat),
+  (Some("String"), "concat", Builtin::StringConcat),
+  (Some("String"), "concatWith", Builtin::StringConcatWith),
+  (Some("String"), "explode", Builtin::StringExplode),
+  (Some("String"), "implode", Builtin::StringImplode),
+  (Some("String"), "str", Builtin::StringStr),
+  (Some("String"), "isPrefix", Builtin::StringIsPrefix),
+  (Some("List"), "null", Builtin::ListNull),
+  (Some("List"), "length", Builtin::ListLength),
+  (Some("List"), "hd", Builtin::ListHd),
+  (Some("List"), "tl", Builtin::ListTl),
+  (Some("List"), "last", Builtin::ListLast),
+  (Some("List"), "rev", Builtin::ListRev),
+  (Some("List"), "nth", Builtin::ListNth),
+  (Some("List"), "take", Builtin::ListTake),
+  (Some("List"), "drop", Builtin::ListDrop),
+  (Some("List"), "@", Builtin::Append),
+  (Some("List"), "concat", Builtin::ListConcat),
+  (Some("List"), "map", Builtin::ListMap),
+  (Some("List"), "app", Builtin::ListApp),
+  (Some("List"), "foldl", Builtin::ListFoldl),
+  (Some("List"), "foldr", Builtin::ListFoldr),
+  (Some("List"), "filter", Builtin::ListFilter),
+  (Some("List"), "exists", Builtin::ListExists),
+  (Some("List"), "all", Builtin::ListAll),
+  (Some("List"), "find", Builtin::ListFind),
+  (Some("List"), "tabulate", Builtin::ListTabulate),
+  (Some("Option"), "isSome", Builtin::OptionIsSome),
+  (Some("Option"), "valOf", Builtin::OptionValOf),
+  (Some("Option"), "getOpt", Builtin::OptionGetOpt),
+  (Some("Option"), "map", Builtin::OptionMap),
+  (Some("TextIO"), "stdOut", Builtin::TextIoStdOut),
+  (Some("TextIO"), "output", Builtin::TextIoOutput),
+  (Some("TextIO"), "print", Builtin::Print),
+];
+
+/// Returns the paths of the values in the std basis that the dynamics implements natively.
+pub fn std_basis_paths() -> impl Iterator<Item = sml_path::Path> {
+  BINDINGS.iter().map(|&(str, name, _)| sml_path::Path::new(str.map(Name::new), Name::new(name)))
+}
+
+impl Builtin {
+  pub(crate) fn as_str(self) -> &'static str {
+    match self {
+      Builtin::Add => "+",
+      Builtin::Sub => "-",
+      Builtin::Mul => "*",
+      Builtin::Div => "div",
+      Builtin::Mod => "mod",
+      Builtin::RealDiv => "/",
+      Builtin::Neg => "~",
+      Builtin::Abs => "abs",
+      Builtin::Lt => "<",
+      Builtin::LtEq => "<=",
+      Builtin::Gt => ">",
+      Builtin::GtEq => ">=",
+      Builtin::Eq => "=",
+      Builtin::Neq => "<>",
+      Builtin::Not => "not",
+      Builtin::Concat => "^",
+      Builtin::Append => "@",
+      Builtin::Compose => "o",
+      Builtin::Before => "before",
+      Builtin::Ignore => "ignore",
+      Builtin::Deref => "!",
+      Builtin::Assign => ":=",
+      Builtin::Print => "print",
+      Builtin::ExnName => "exnName",
+      Builtin::ExnMessage => "exnMessage",
+      Builtin::IntToString => "Int.toString",
+      Builtin::IntFromString => "Int.fromString",
+      Builtin::IntMin => "Int.min",
+      Builtin::IntMax => "Int.max",
+      Builtin::IntCompare => "Int.compare",
+      Builtin::RealFromInt => "real",
+      Builtin::RealToString => "Real.toString",
+      Builtin::RealFloor => "floor",
+      Builtin::RealCeil => "ceil",
+      Builtin::RealRound => "round",
+      Builtin::RealTrunc => "trunc",
+      Builtin::CharOrd => "ord",
+      Builtin::CharChr => "chr",
+      Builtin::CharIsDigit => "Char.isDigit",
+      Builtin::CharIsAlpha => "Char.isAlpha",
+      Builtin::CharIsSpace => "Char.isSpace",
+      Builtin::CharToUpper => "Char.toUpper",
+      Builtin::CharToLower => "Char.toLower",
+      Builtin::StringSize => "size",
+      Builtin::StringSub => "String.sub",
+      Builtin::StringSubstring => "substring",
+      Builtin::StringConcat => "concat",
+      Builtin::StringConcatWith => "String.concatWith",
+      Builtin::StringExplode => "explode",
+      Builtin::StringImplode => "implode",
+      Builtin::StringStr => "str",
+      Builtin::StringIsPrefix => "String.isPrefix",
+      Builtin::ListNull => "null",
+      Builtin::ListLength => "length",
+      Builtin::ListHd => "hd",
+      Builtin::ListTl => "tl",
+      Builtin::ListLast => "List.last",
+      Builtin::ListRev => "rev",
+      Builtin::ListNth => "List.nth",
+      Builtin::ListTake => "List.take",
+      Builtin::ListDrop => "List.drop",
+      Builtin::ListConcat => "List.concat",
+      Builtin::ListMap => "map",
+      Builtin::ListApp => "app",
+      Builtin::ListFoldl => "foldl",
+      Builtin::ListFoldr => "foldr",
+      Builtin::ListFilter => "List.filter",
+      Builtin::ListExists => "List.exists",
+      Builtin::ListAll => "List.all",
+      Builtin::ListFind => "List.find",
+      Builtin::ListTabulate => "List.tabulate",
+      Builtin::OptionIsSome => "isSome",
+      Builtin::OptionValOf => "valOf",
+      Builtin::OptionGetOpt => "getOpt",
+      Builtin::OptionMap => "Option.map",
+      Builtin::TextIoStdOut => "TextIO.stdOut",
+      Builtin::TextIoOutput => "TextIO.output",
+    }
+  }
+
+  /// Returns how many curried arguments this takes. This is 0 for the ones that are not functions.
+  fn arity(self) -> usize {
+    match self {
+      Builtin::TextIoStdOut => 0,
+      Builtin::Compose
+      | Builtin::StringConcatWith
+      | Builtin::StringIsPrefix
+      | Builtin::ListMap
+      | Builtin::ListApp
+      | Builtin::ListFilter
+      | Builtin::ListExists
+      | Builtin::ListAll
+      | Builtin::ListFind
+      | Builtin::OptionMap => 2,
+      Builtin::ListFoldl | Builtin::ListFoldr => 3,
+      _ => 1,
+    }
+  }
+}
+
+/// A higher-order builtin in the middle of calling a function it was given.
+#[derive(Debug)]
+pub(crate) struct Callback {
+  pub(crate) builtin: Builtin,
+  /// The function being called.
+  func: Val,
+  /// What the function was last called with.
+  cur: Val,
+  /// What to call the function with next, in reverse order.
+  rest: Vec<Val>,
+  /// For `List.tabulate`, how many times to call the function. The indices to call it with are made
+  /// one at a time, instead of all up front in `rest`, so a huge count doesn't use huge memory.
+  count: usize,
+  /// The results so far. For folds, this is just the accumulator.
+  ac: Vec<Val>,
+}
+
+/// Applies the builtin to the arguments given to it so far.
+pub(crate) fn apply(st: &mut St, cx: Cx<'_>, builtin: Builtin, args: Vec<Val>) -> (Step, bool) {
+  if args.len() < builtin.arity() {
+    return (Step::Val(Val::Builtin(builtin, args)), false);
+  }
+  let mut args = args.into_iter();
+  let mut arg = || args.next().expect("missing arg");
+  let res = match builtin {
+    Builtin::Compose => {
+      let [f, g] = arg().unwrap_tuple();
+      let cb = Callback { builtin, func: g, cur: arg(), rest: Vec::new(), count: 0, ac: vec![f] };
+      return call(st, cx, cb);
+    }
+    Builtin::ListMap
+    | Builtin::ListApp
+    | Builtin::ListFilter
+    | Builtin::ListExists
+    | Builtin::ListAll
+    | Builtin::ListFind => {
+      let func = arg();
+      let mut rest = arg().unwrap_list();
+      rest.reverse();
+      let cb = Callback { builtin, func, cur: Val::unit(), rest, count: 0, ac: Vec::new() };
+      return next(st, cx, cb);
+    }
+    Builtin::ListFoldl | Builtin::ListFoldr => {
+      let func = arg();
+      let init = arg();
+      let mut rest = arg().unwrap_list();
+      if builtin == Builtin::ListFoldl {
+        rest.reverse();
+      }
+      let cb = Callback { builtin, func, cur: Val::unit(), rest, count: 0, ac: vec![init] };
+      return next(st, cx, cb);
+    }
+    Builtin::ListTabulate => {
+      let [n, func] = arg().unwrap_tuple();
+      match n.unwrap_int(cx) {
+        Ok(n) => match usize::try_from(n) {
+          Ok(count) => {
+            let rest = Vec::new();
+            let cb = Callback { builtin, func, cur: Val::unit(), rest, count, ac: Vec::new() };
+            return next(st, cx, cb);
+          }
+          Err(_) => Err(Exception::empty("Size", cx.exns.size)),
+        },
+        Err(e) => Err(e),
+      }
+    }
+    Builtin::OptionMap => {
+      let func = arg();
+      match arg().unwrap_option() {
+        None => Ok(Val::option(None)),
+        Some(x) => {
+          let cb = Callback { builtin, func, cur: x, rest: Vec::new(), count: 0, ac: Vec::new() };
+          return call(st, cx, cb);
+        }
+      }
+    }
+    Builtin::StringConcatWith => {
+      let sep = arg().unwrap_string();
+      let ss: Vec<_> = arg().unwrap_list().into_iter().map(Val::unwrap_string).collect();
+      Ok(Val::string(ss.join(sep.as_str())))
+    }
+    Builtin::StringIsPrefix => {
+      let prefix = arg().unwrap_string();
+      Ok(Val::bool(arg().unwrap_string().starts_with(prefix.as_str())))
+    }
+    _ => get(st, cx, builtin, arg()),
+  };
+  match res {
+    Ok(val) => (Step::Val(val), true),
+    Err(e) => (Step::Raise(e), true),
+  }
+}
+
+/// Continues a higher-order builtin, after the function it called returned `val`.
+pub(crate) fn resume(st: &mut St, cx: Cx<'_>, mut cb: Callback, val: Val) -> (Step, bool) {
+  match cb.builtin {
+    Builtin::Compose => match cb.ac.pop() {
+      Some(f) => {
+        cb.func = f;
+        cb.cur = val;
+        return call(st, cx, cb);
+      }
+      None => return (Step::Val(val), true),
+    },
+    Builtin::ListMap | Builtin::ListTabulate => cb.ac.push(val),
+    Builtin::ListFilter => {
+      if val.unwrap_bool() {
+        cb.ac.push(cb.cur.clone());
+      }
+    }
+    Builtin::ListExists => {
+      if val.unwrap_bool() {
+        return (Step::Val(Val::bool(true)), true);
+      }
+    }
+    Builtin::ListAll => {
+      if !val.unwrap_bool() {
+        return (Step::Val(Val::bool(false)), true);
+      }
+    }
+    Builtin::ListFind => {
+      if val.unwrap_bool() {
+        return (Step::Val(Val::option(Some(cb.cur))), true);
+      }
+    }
+    Builtin::ListFoldl | Builtin::ListFoldr => cb.ac = vec![val],
+    Builtin::OptionMap => return (Step::Val(Val::option(Some(val))), true),
+    _ => {}
+  }
+  next(st, cx, cb)
+}
+
+/// Calls the function with the next value, or finishes if there are no more.
+fn next(st: &mut St, cx: Cx<'_>, mut cb: Callback) -> (Step, bool) {
+  let x = match cb.builtin {
+    Builtin::ListTabulate => (cb.ac.len() < cb.count).then(|| Val::int(len(cb.ac.len()))),
+    _ => cb.rest.pop(),
+  };
+  match x {
+    Some(x) => {
+      cb.cur = x;
+      call(st, cx, cb)
+    }
+    None => {
+      let val = match cb.builtin {
+        Builtin::ListMap | Builtin::ListFilter | Builtin::ListTabulate => Val::list(cb.ac),
+        Builtin::ListExists => Val::bool(false),
+        Builtin::ListAll => Val::bool(true),
+        Builtin::ListFind => Val::option(None),
+        Builtin::ListFoldl | Builtin::ListFoldr => cb.ac.pop().expect("no accumulator"),
+        _ => Val::unit(),
+      };
+      (Step::Val(val), true)
+    }
+  }
+}
+
+/// Calls the function with the current value, and comes back to the builtin afterwards.
+///
+/// Each call is a visible step, even if applying the function is not, like for a constructor, so a
+/// builtin that calls a function many times can't run for many steps in one visible step.
+fn call(st: &mut St, cx: Cx<'_>, cb: Callback) -> (Step, bool) {
+  let func = cb.func.clone();
+  let arg = match cb.builtin {
+    Builtin::ListFoldl | Builtin::ListFoldr => {
+      let acc = cb.ac.last().expect("no accumulator").clone();
+      Val::tuple([cb.cur.clone(), acc])
+    }
+    _ => cb.cur.clone(),
+  };
+  st.push_with_cur_env(FrameKind::Callback(cb));
+  let (step, _) = apply_val(st, cx, func, arg);
+  (step, true)
+}
+
+/// Applies a first-order builtin to its last argument.
+#[allow(clippy::too_many_lines)]
+fn get(st: &mut St, cx: Cx<'_>, builtin: Builtin, arg: Val) -> Result<Val, Exception> {
+  let ret = match builtin {
+    Builtin::Add | Builtin::Sub | Builtin::Mul | Builtin::Div | Builtin::Mod => {
+      let [x, y] = arg.unwrap_pair().map(Val::unwrap_scon);
+      arith(cx, builtin, x, y)?
+    }
+    Builtin::RealDiv => {
+      let [x, y] = arg.unwrap_pair().map(Val::unwrap_real);
+      Val::SCon(SCon::Real(x / y))
+    }
+    Builtin::Neg | Builtin::Abs => {
+      let neg = builtin == Builtin::Neg;
+      match arg.unwrap_scon() {
+        SCon::Int(x) => {
+          let x = x.to_i64().ok_or_else(|| overflow(cx))?;
+          checked_int(cx, if neg { x.checked_neg() } else { x.checked_abs() })?
+        }
+        SCon::Real(x) => Val::SCon(SCon::Real(if neg { -x } else { x.abs() })),
+        scon => unreachable!("bad scon type: {scon:?}"),
+      }
+    }
+    Builtin::Lt | Builtin::LtEq | Builtin::Gt | Builtin::GtEq => {
+      let [x, y] = arg.unwrap_pair().map(Val::unwrap_scon);
+      let ord = compare(cx, &x, &y)?;
+      let res = match builtin {
+        Builtin::Lt => ord.is_lt(),
+        Builtin::LtEq => ord.is_le(),
+        Builtin::Gt => ord.is_gt(),
+        _ => ord.is_ge(),
+      };
+      Val::bool(res)
+    }
+    Builtin::Eq | Builtin::Neq => {
+      let [x, y] = arg.unwrap_pair();
+      Val::bool(equal(&x, &y) == (builtin == Builtin::Eq))
+    }
+    Builtin::Not => Val::bool(!arg.unwrap_bool()),
+    Builtin::Concat => {
+      let [x, y] = arg.unwrap_pair().map(Val::unwrap_string);
+      Val::string(format!("{x}{y}"))
+    }
+    Builtin::Append => {
+      let [xs, ys] = arg.unwrap_pair().map(Val::unwrap_list);
+      Val::list(xs.into_iter().chain(ys).collect())
+    }
+    Builtin::Before => {
+      let [x, _] = arg.unwrap_pair();
+      x
+    }
+    Builtin::Ignore => Val::unit(),
+    Builtin::Deref => match arg {
+      Val::Ref(loc) => st.store[loc].clone(),
+      _ => unreachable!("not Ref: {arg:?}"),
+    },
+    Builtin::Assign => match arg.unwrap_pair() {
+      [Val::Ref(loc), val] => {
+        st.store[loc] = val;
+        Val::unit()
+      }
+      [r, _] => unreachable!("not Ref: {r:?}"),
+    },
+    Builtin::Print => {
+      st.output.push_str(&arg.unwrap_string());
+      Val::unit()
+    }
+    Builtin::ExnName | Builtin::ExnMessage => {
+      let Val::Con(con) = arg else { unreachable!("not Con") };
+      let mut ret = con.name.as_str().to_owned();
+      if builtin == Builtin::ExnMessage {
+        if let Some(Val::SCon(SCon::String(s))) = con.arg.as_deref() {
+          ret.push_str(": ");
+          ret.push_str(s);
+        }
+      }
+      Val::string(ret)
+    }
+    Builtin::IntToString => Val::string(arg.unwrap_int(cx)?.to_string().replace('-', "~")),
+    Builtin::IntFromString => Val::option(int_from_string(cx, &arg.unwrap_string())?),
+    Builtin::IntMin | Builtin::IntMax => {
+      let [x, y] = arg.unwrap_pair();
+      let (x, y) = (x.unwrap_int(cx)?, y.unwrap_int(cx)?);
+      Val::int(if builtin == Builtin::IntMin { x.min(y) } else { x.max(y) })
+    }
+    Builtin::IntCompare => {
+      let [x, y] = arg.unwrap_pair();
+      let name = match x.unwrap_int(cx)?.cmp(&y.unwrap_int(cx)?) {
+        std::cmp::Ordering::Less => "LESS",
+        std::cmp::Ordering::Equal => "EQUAL",
+        std::cmp::Ordering::Greater => "GREATER",
+      };
+      Val::dat(name)
+    }
+    Builtin::RealFromInt => {
+      #[allow(clippy::cast_precision_loss)]
+      let x = arg.unwrap_int(cx)? as f64;
+      Val::SCon(SCon::Real(x))
+    }
+    Builtin::RealToString => Val::string(real_to_string(arg.unwrap_real())),
+    Builtin::RealFloor | Builtin::RealCeil | Builtin::RealRound | Builtin::RealTrunc => {
+      let x = arg.unwrap_real();
+      let x = match builtin {
+        Builtin::RealFloor => x.floor(),
+        Builtin::RealCeil => x.ceil(),
+        Builtin::RealRound => x.round_ties_even(),
+        _ => x.trunc(),
+      };
+      if x.is_nan() {
+        return Err(Exception::empty("Domain", cx.exns.domain));
+      }
+      #[allow(clippy::cast_precision_loss)]
+      let (min, max) = (MIN_INT as f64, MAX_INT as f64);
+      if x < min || x > max {
+        return Err(overflow(cx));
+      }
+      #[allow(clippy::cast_possible_truncation)]
+      let x = x as i64;
+      checked_int(cx, Some(x))?
+    }
+    Builtin::CharOrd => Val::int(i64::from(u32::from(arg.unwrap_char()))),
+    Builtin::CharChr => {
+      let c =
+        u8::try_from(arg.unwrap_int(cx)?).map_err(|_| Exception::empty("Chr", cx.exns.chr))?;
+      Val::SCon(SCon::Char(char::from(c)))
+    }
+    Builtin::CharIsDigit => Val::bool(arg.unwrap_char().is_ascii_digit()),
+    Builtin::CharIsAlpha => Val::bool(arg.unwrap_char().is_ascii_alphabetic()),
+    Builtin::CharIsSpace => Val::bool(arg.unwrap_char().is_ascii_whitespace()),
+    Builtin::CharToUpper => Val::SCon(SCon::Char(arg.unwrap_char().to_ascii_uppercase())),
+    Builtin::CharToLower => Val::SCon(SCon::Char(arg.unwrap_char().to_ascii_lowercase())),
+    Builtin::StringSize => Val::int(len(arg.unwrap_string().chars().count())),
+    Builtin::StringSub => {
+      let [s, i] = arg.unwrap_pair();
+      let (s, i) = (s.unwrap_string(), i.unwrap_int(cx)?);
+      let c = usize::try_from(i).ok().and_then(|i| s.chars().nth(i));
+      Val::SCon(SCon::Char(c.ok_or_else(|| subscript(cx))?))
+    }
+    Builtin::StringSubstring => {
+      let [s, i, n] = arg.unwrap_tuple();
+      let (s, i, n) = (s.unwrap_string(), i.unwrap_int(cx)?, n.unwrap_int(cx)?);
+      let chars: Vec<_> = s.chars().collect();
+      let range = usize::try_from(i).ok().zip(usize::try_from(n).ok());
+      let range = range.and_then(|(i, n)| Some(i..i.checked_add(n)?));
+      let sub = range.and_then(|range| chars.get(range)).ok_or_else(|| subscript(cx))?;
+      Val::string(sub.iter().collect())
+    }
+    Builtin::StringConcat => {
+      let ss: Vec<_> = arg.unwrap_list().into_iter().map(Val::unwrap_string).collect();
+      Val::string(ss.concat())
+    }
+    Builtin::StringExplode => {
+      let chars = arg.unwrap_string().chars().map(|c| Val::SCon(SCon::Char(c))).collect();
+      Val::list(chars)
+    }
+    Builtin::StringImplode => {
+      Val::string(arg.unwrap_list().into_iter().map(Val::unwrap_char).collect())
+    }
+    Builtin::StringStr => Val::string(arg.unwrap_char().to_string()),
+    Builtin::ListNull => Val::bool(arg.unwrap_list().is_empty()),
+    Builtin::ListLength => Val::int(len(arg.unwrap_list().len())),
+    Builtin::ListHd | Builtin::ListTl | Builtin::ListLast => {
+      let mut xs = arg.unwrap_list();
+      if xs.is_empty() {
+        return Err(Exception::empty("Empty", cx.exns.empty));
+      }
+      match builtin {
+        Builtin::ListHd => xs.swap_remove(0),
+        Builtin::ListTl => Val::list(xs.split_off(1)),
+        _ => xs.pop().expect("empty list"),
+      }
+    }
+    Builtin::ListRev => {
+      let mut xs = arg.unwrap_list();
+      xs.reverse();
+      Val::list(xs)
+    }
+    Builtin::ListNth | Builtin::ListTake | Builtin::ListDrop => {
+      let [xs, i] = arg.unwrap_pair();
+      let (mut xs, i) = (xs.unwrap_list(), i.unwrap_int(cx)?);
+      let i = usize::try_from(i).ok().filter(|&i| match builtin {
+        Builtin::ListNth => i < xs.len(),
+        _ => i <= xs.len(),
+      });
+      let i = i.ok_or_else(|| subscript(cx))?;
+      match builtin {
+        Builtin::ListNth => xs.swap_remove(i),
+        Builtin::ListTake => {
+          xs.truncate(i);
+          Val::list(xs)
+        }
+        _ => Val::list(xs.split_off(i)),
+      }
+    }
+    Builtin::ListConcat => {
+      Val::list(arg.unwrap_list().into_iter().flat_map(Val::unwrap_list).collect())
+    }
+    Builtin::OptionIsSome => Val::bool(arg.unwrap_option().is_some()),
+    Builtin::OptionValOf => {
+      arg.unwrap_option().ok_or_else(|| Exception::empty("Option", cx.exns.option))?
+    }
+    Builtin::OptionGetOpt => {
+      let [opt, default] = arg.unwrap_pair();
+      opt.unwrap_option().unwrap_or(default)
+    }
+    Builtin::TextIoOutput => {
+      let [_, s] = arg.unwrap_pair();
+      st.output.push_str(&s.unwrap_string());
+      Val::unit()
+    }
+    Builtin::Compose
+    | Builtin::StringConcatWith
+    | Builtin::StringIsPrefix
+    | Builtin::ListMap
+    | Builtin::ListApp
+    | Builtin::ListFoldl
+    | Builtin::ListFoldr
+    | Builtin::ListFilter
+    | Builtin::ListExists
+    | Builtin::ListAll
+    | Builtin::ListFind
+    | Builtin::ListTabulate
+    | Builtin::OptionMap => unreachable!("handled by apply"),
+    Builtin::TextIoStdOut => unreachable!("not a function"),
+  };
+  Ok(ret)
+}
+
+fn arith(cx: Cx<'_>, builtin: Builtin, x: SCon, y: SCon) -> Result<Val, Exception> {
+  let ret = match (x, y) {
+    (SCon::Int(x), SCon::Int(y)) => {
+      let x = x.to_i64().ok_or_else(|| overflow(cx))?;
+      let y = y.to_i64().ok_or_else(|| overflow(cx))?;
+      if matches!(builtin, Builtin::Div | Builtin::Mod) && y == 0 {
+        return Err(Exception::empty("Div", cx.exns.div));
+      }
+      let res = match builtin {
+        Builtin::Add => x.checked_add(y),
+        Builtin::Sub => x.checked_sub(y),
+        Builtin::Mul => x.checked_mul(y),
+        // rounds towards negative infinity.
+        Builtin::Div => {
+          x.checked_div(y).map(|q| if x % y != 0 && (x < 0) != (y < 0) { q - 1 } else { q })
+        }
+        // has the same sign as the divisor.
+        Builtin::Mod => {
+          x.checked_rem(y).map(|r| if r != 0 && (r < 0) != (y < 0) { r + y } else { r })
+        }
+        _ => unreachable!("not arithmetic: {builtin:?}"),
+      };
+      checked_int(cx, res)?
+    }
+    (SCon::Word(x), SCon::Word(y)) => {
+      if matches!(builtin, Builtin::Div | Builtin::Mod) && y == 0 {
+        return Err(Exception::empty("Div", cx.exns.div));
+      }
+      let res = match builtin {
+        Builtin::Add => x.wrapping_add(y),
+        Builtin::Sub => x.wrapping_sub(y),
+        Builtin::Mul => x.wrapping_mul(y),
+        Builtin::Div => x / y,
+        Builtin::Mod => x % y,
+        _ => unreachable!("not arithmetic: {builtin:?}"),
+      };
+      Val::SCon(SCon::Word(res))
+    }
+    (SCon::Real(x), SCon::Real(y)) => {
+      let res = match builtin {
+        Builtin::Add => x + y,
+        Builtin::Sub => x - y,
+        Builtin::Mul => x * y,
+        _ => unreachable!("not real arithmetic: {builtin:?}"),
+      };
+      Val::SCon(SCon::Real(res))
+    }
+    (x, y) => unreachable!("bad scon types: {x:?}, {y:?}"),
+  };
+  Ok(ret)
+}
+
+fn compare(cx: Cx<'_>, x: &SCon, y: &SCon) -> Result<std::cmp::Ordering, Exception> {
+  let ret = match (x, y) {
+    (SCon::Int(x), SCon::Int(y)) => {
+      let x = x.to_i64().ok_or_else(|| overflow(cx))?;
+      let y = y.to_i64().ok_or_else(|| overflow(cx))?;
+      x.cmp(&y)
+    }
+    (SCon::Word(x), SCon::Word(y)) => x.cmp(y),
+    (SCon::Real(x), SCon::Real(y)) => x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal),
+    (SCon::Char(x), SCon::Char(y)) => x.cmp(y),
+    (SCon::String(x), SCon::String(y)) => x.cmp(y),
+    (x, y) => unreachable!("bad scon types: {x:?}, {y:?}"),
+  };
+  Ok(ret)
+}
+
+/// Returns whether the values are equal. Refs are equal only if they are the same ref.
+fn equal(x: &Val, y: &Val) -> bool {
+  match (x, y) {
+    (Val::SCon(x), Val::SCon(y)) => match (x, y) {
+      (SCon::Int(x), SCon::Int(y)) => x == y,
+      (SCon::Word(x), SCon::Word(y)) => x == y,
+      (SCon::Char(x), SCon::Char(y)) => x == y,
+      (SCon::String(x), SCon::String(y)) => x == y,
+      (x, y) => unreachable!("bad scon types for equality: {x:?}, {y:?}"),
+    },
+    (Val::Con(x), Val::Con(y)) => {
+      let same_con = match (&x.kind, &y.kind) {
+        (ConKind::Dat, ConKind::Dat) => x.name == y.name,
+        (ConKind::Exn(x), ConKind::Exn(y)) => x == y,
+        (ConKind::Dat, ConKind::Exn(_)) | (ConKind::Exn(_), ConKind::Dat) => false,
+      };
+      same_con
+        && match (&x.arg, &y.arg) {
+          (None, None) => true,
+          (Some(x), Some(y)) => equal(x, y),
+          (None, Some(_)) | (Some(_), None) => false,
+        }
+    }
+    (Val::Record(xs), Val::Record(ys)) => {
+      xs.len() == ys.len() && xs.iter().all(|(lab, x)| ys.get(lab).is_some_and(|y| equal(x, y)))
+    }
+    (Val::Vector(xs), Val::Vector(ys)) => {
+      xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| equal(x, y))
+    }
+    (Val::Ref(x), Val::Ref(y)) => x == y,
+    (x, y) => unreachable!("bad types for equality: {x:?}, {y:?}"),
+  }
+}
+
+fn overflow(cx: Cx<'_>) -> Exception {
+  Exception::empty("Overflow", cx.exns.overflow)
+}
+
+/// Returns the int, or raises `Overflow` if there is none or it is out of the range of `int`.
+fn checked_int(cx: Cx<'_>, x: Option<i64>) -> Result<Val, Exception> {
+  match x {
+    Some(x) if (MIN_INT..=MAX_INT).contains(&x) => Ok(Val::int(x)),
+    _ => Err(overflow(cx)),
+  }
+}
+
+fn subscript(cx: Cx<'_>) -> Exception {
+  Exception::empty("Subscript", cx.exns.subscript)
+}
+
+fn len(n: usize) -> i64 {
+  i64::try_from(n).expect("too long")
+}
+
+/// Like `Real.toString`, e.g. `1.0` or `~2.5`.
+fn real_to_string(x: f64) -> String {
+  let mut ret = x.to_string().replace('-', "~");
+  if x.is_finite() && !ret.contains('.') {
+    ret.push_str(".0");
+  }
+  ret
+}
+
+/// Like `Int.fromString`: skips leading whitespace, reads an optional sign and then as many decimal
+/// digits as possible, and ignores the rest. Raises `Overflow` if the number is out of the range of
+/// `int`.
+fn int_from_string(cx: Cx<'_>, s: &str) -> Result<Option<Val>, Exception> {
+  let s = s.trim_start();
+  let (neg, s) = match s.strip_prefix(['~', '-']) {
+    Some(s) => (true, s),
+    None => (false, s.strip_prefix('+').unwrap_or(s)),
+  };
+  let digits = s.find(|c: char| !c.is_ascii_digit()).map_or(s, |idx| &s[..idx]);
+  if digits.is_empty() {
+    return Ok(None);
+  }
+  // too many digits to parse is also out of range.
+  let x = digits.parse::<i64>().ok().and_then(|x| if neg { x.checked_neg() } else { Some(x) });
+  checked_int(cx, x).map(Some)
+}
@@ -2,14 +2,16 @@
 
 #![allow(clippy::too_many_lines)]
 
-use crate::dynamics::Dynamics;
+use crate::dynamics::{Dynamics, Finished};
 use crate::types::{Con, Env, Exception, FrameKind, Step, Val};
+use fast_hash::FxHashSet;
 use sml_hir::Lab;
 use std::fmt;
+use str_util::Name;
 
 impl fmt::Display for Dynamics<'_> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    let mut cx = DisplayCx { ars: self.cx.ars, indent: 0 };
+    let mut cx = DisplayCx { ars: self.cx.ars, store: &self.st.store, indent: 0 };
     if f.alternate() {
       f.write_str("(* env:\n")?;
       if let Some(frame) = self.st.frames.last() {
@@ -22,7 +24,13 @@ impl fmt::Display for Dynamics<'_> {
     for frame in &self.st.frames {
       frame_prec.push(prec);
       match &frame.kind {
-        FrameKind::Handle(_) | FrameKind::DecSeq(_) | FrameKind::StrDecSeq(_) => {}
+        FrameKind::Handle(_)
+        | FrameKind::DecSeq(_)
+        | FrameKind::StrDecSeq(_)
+        | FrameKind::Ascription(_)
+        | FrameKind::AppFunctorBody
+        | FrameKind::Body(_)
+        | FrameKind::Top => {}
         FrameKind::AppFunc(_) => {
           if matches!(prec, Prec::Atomic) {
             f.write_str("(")?;
@@ -47,7 +55,15 @@ impl fmt::Display for Dynamics<'_> {
           }
           prec = Prec::Min;
         }
-        FrameKind::AppClosureArg(matcher) => {
+        FrameKind::Vector(vs, _) => {
+          f.write_str("#[")?;
+          for val in vs {
+            ValDisplay { val, prec: Prec::Min, cx }.fmt(f)?;
+            f.write_str(", ")?;
+          }
+          prec = Prec::Min;
+        }
+        FrameKind::AppClosureArg(matcher, _) => {
           if matches!(prec, Prec::Atomic) {
             f.write_str("(")?;
           }
@@ -56,14 +72,26 @@ impl fmt::Display for Dynamics<'_> {
           f.write_str(") ")?;
           prec = Prec::Atomic;
         }
-        FrameKind::AppBuiltinArg(builtin) => {
+        FrameKind::AppBuiltinArg(builtin, args) => {
           if matches!(prec, Prec::Atomic) {
             f.write_str("(")?;
           }
           f.write_str(builtin.as_str())?;
+          for val in args {
+            f.write_str(" ")?;
+            ValDisplay { val, prec: Prec::Atomic, cx }.fmt(f)?;
+          }
           f.write_str(" ")?;
           prec = Prec::Atomic;
         }
+        FrameKind::Callback(cb) => {
+          if matches!(prec, Prec::Atomic) {
+            f.write_str("(")?;
+          }
+          f.write_str(cb.builtin.as_str())?;
+          f.write_str(" (")?;
+          prec = Prec::Min;
+        }
         FrameKind::AppConArg(name, _) => {
           if matches!(prec, Prec::Atomic) {
             f.write_str("(")?;
@@ -97,11 +125,35 @@ impl fmt::Display for Dynamics<'_> {
           cx.indent += 1;
           write_nl_indent(cx.indent, f)?;
         }
-        FrameKind::In(_) => {
+        FrameKind::In(_) | FrameKind::StrIn(_) => {
           f.write_str("local in")?;
           cx.indent += 1;
           write_nl_indent(cx.indent, f)?;
         }
+        FrameKind::StrBind(_, name, _) => {
+          f.write_str("structure ")?;
+          name.fmt(f)?;
+          f.write_str(" = ")?;
+        }
+        FrameKind::Struct(_) => {
+          f.write_str("struct")?;
+          cx.indent += 1;
+          write_nl_indent(cx.indent, f)?;
+        }
+        FrameKind::StrLet(_, _) => {
+          f.write_str("let")?;
+          cx.indent += 1;
+          write_nl_indent(cx.indent, f)?;
+        }
+        FrameKind::StrLocal(_, _) => {
+          f.write_str("local")?;
+          cx.indent += 1;
+          write_nl_indent(cx.indent, f)?;
+        }
+        FrameKind::AppFunctorArg(name, _) => {
+          name.fmt(f)?;
+          f.write_str("(")?;
+        }
       }
     }
     match self.step.as_ref().ok_or(fmt::Error)? {
@@ -114,18 +166,26 @@ impl fmt::Display for Dynamics<'_> {
       Step::Dec(dec) => DecDisplay { dec: *dec, cx }.fmt(f)?,
       Step::DecDone => {}
       Step::StrDec(str_dec) => StrDecDisplay { str_dec: *str_dec, cx }.fmt(f)?,
+      Step::StrExp(str_exp) => StrExpDisplay { str_exp: Some(*str_exp), cx }.fmt(f)?,
+      Step::Str(env) => StrDisplay { env, cx }.fmt(f)?,
     }
     for frame in self.st.frames.iter().rev() {
       let prec = frame_prec.pop().unwrap();
       match &frame.kind {
         FrameKind::Raise
-        | FrameKind::AppClosureArg(_)
-        | FrameKind::AppBuiltinArg(_)
+        | FrameKind::AppClosureArg(_, _)
+        | FrameKind::AppBuiltinArg(_, _)
         | FrameKind::AppConArg(_, _) => {
           if matches!(prec, Prec::Atomic) {
             f.write_str(")")?;
           }
         }
+        FrameKind::Callback(_) => {
+          f.write_str(")")?;
+          if matches!(prec, Prec::Atomic) {
+            f.write_str(")")?;
+          }
+        }
         FrameKind::Record(is_tuple, _, _, es) => {
           if !es.is_empty() {
             f.write_str(", ")?;
@@ -140,6 +200,14 @@ impl fmt::Display for Dynamics<'_> {
             f.write_str(" }")?;
           }
         }
+        FrameKind::Vector(_, es) => {
+          if !es.is_empty() {
+            f.write_str(", ")?;
+          }
+          let rows = es.iter().rev().map(|&exp| ExpDisplay { exp, prec: Prec::Min, cx });
+          fmt_util::comma_seq(f, rows)?;
+          f.write_str("]")?;
+        }
         FrameKind::AppFunc(exp) => {
           f.write_str(" ")?;
           ExpDisplay { exp: *exp, prec: Prec::Atomic, cx }.fmt(f)?;
@@ -207,6 +275,66 @@ impl fmt::Display for Dynamics<'_> {
             StrDecDisplay { str_dec, cx }.fmt(f)?;
           }
         }
+        FrameKind::StrBind(_, _, str_binds) => {
+          for (name, str_exp) in str_binds.iter().rev() {
+            write_nl_indent(cx.indent, f)?;
+            f.write_str("and ")?;
+            name.fmt(f)?;
+            f.write_str(" = ")?;
+            StrExpDisplay { str_exp: *str_exp, cx }.fmt(f)?;
+          }
+        }
+        FrameKind::Struct(str_decs) => {
+          for &str_dec in str_decs.iter().rev() {
+            write_nl_indent(cx.indent, f)?;
+            StrDecDisplay { str_dec, cx }.fmt(f)?;
+          }
+          cx.indent -= 1;
+          write_nl_indent(cx.indent, f)?;
+          f.write_str("end")?;
+        }
+        FrameKind::StrLet(str_decs, str_exp) => {
+          for &str_dec in str_decs.iter().rev() {
+            write_nl_indent(cx.indent, f)?;
+            StrDecDisplay { str_dec, cx }.fmt(f)?;
+          }
+          write_nl_indent(cx.indent - 1, f)?;
+          f.write_str("in")?;
+          write_nl_indent(cx.indent, f)?;
+          StrExpDisplay { str_exp: *str_exp, cx }.fmt(f)?;
+          cx.indent -= 1;
+          write_nl_indent(cx.indent, f)?;
+          f.write_str("end")?;
+        }
+        FrameKind::StrLocal(local_str_decs, in_str_decs) => {
+          for &str_dec in local_str_decs.iter().rev() {
+            write_nl_indent(cx.indent, f)?;
+            StrDecDisplay { str_dec, cx }.fmt(f)?;
+          }
+          write_nl_indent(cx.indent - 1, f)?;
+          f.write_str("in")?;
+          for &str_dec in in_str_decs.iter().rev() {
+            write_nl_indent(cx.indent, f)?;
+            StrDecDisplay { str_dec, cx }.fmt(f)?;
+          }
+          cx.indent -= 1;
+          write_nl_indent(cx.indent, f)?;
+          f.write_str("end")?;
+        }
+        FrameKind::StrIn(in_str_decs) => {
+          for &str_dec in in_str_decs.iter().rev() {
+            write_nl_indent(cx.indent, f)?;
+            StrDecDisplay { str_dec, cx }.fmt(f)?;
+          }
+          cx.indent -= 1;
+          write_nl_indent(cx.indent, f)?;
+          f.write_str("end")?;
+        }
+        FrameKind::AppFunctorArg(_, _) => f.write_str(")")?,
+        FrameKind::Ascription(_)
+        | FrameKind::AppFunctorBody
+        | FrameKind::Body(_)
+        | FrameKind::Top => {}
       }
     }
     assert!(frame_prec.is_empty());
@@ -214,15 +342,53 @@ impl fmt::Display for Dynamics<'_> {
   }
 }
 
+impl fmt::Display for Finished<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let cx = DisplayCx { ars: self.cx.ars, store: &self.st.store, indent: 0 };
+    match &self.exception {
+      None => match &self.val {
+        None => EnvDisplay { env: &self.st.env, cx }.fmt(f),
+        Some(val) => writeln!(f, "{}", ValDisplay { val, prec: Prec::Min, cx }),
+      },
+      Some(exception) => {
+        f.write_str("uncaught exception ")?;
+        ExceptionDisplay { exception, cx }.fmt(f)?;
+        f.write_str("\n")
+      }
+    }
+  }
+}
+
+/// Returns the values bound in the env, but not in the std basis, sorted by name and displayed.
+pub(crate) fn vals(ars: &sml_hir::Arenas, store: &[Val], env: &Env) -> Vec<(Name, String)> {
+  let cx = DisplayCx { ars, store, indent: 0 };
+  let mut ret = Vec::<(Name, String)>::new();
+  let mut seen = FxHashSet::<&Name>::default();
+  let mut env = env;
+  // the outermost env is the std basis.
+  while let Some(outer) = env.outer.as_deref() {
+    for (name, val) in &env.val {
+      // inner bindings shadow outer ones.
+      if seen.insert(name) {
+        ret.push((name.clone(), ValDisplay { val, prec: Prec::Min, cx }.to_string()));
+      }
+    }
+    env = outer;
+  }
+  ret.sort_unstable_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+  ret
+}
+
 #[derive(Debug, Clone, Copy)]
 struct DisplayCx<'a> {
   ars: &'a sml_hir::Arenas,
+  store: &'a [Val],
   indent: usize,
 }
 
 impl<'a> DisplayCx<'a> {
   fn indented(self) -> DisplayCx<'a> {
-    DisplayCx { ars: self.ars, indent: self.indent + 1 }
+    DisplayCx { ars: self.ars, store: self.store, indent: self.indent + 1 }
   }
 }
 
@@ -291,6 +457,12 @@ impl fmt::Display for ValDisplay<'_> {
         }
         Ok(())
       }
+      Val::Vector(vals) => {
+        f.write_str("#[")?;
+        let vals = vals.iter().map(|val| ValDisplay { val, prec: Prec::Min, cx: self.cx });
+        fmt_util::comma_seq(f, vals)?;
+        f.write_str("]")
+      }
       Val::Closure(clos) => {
         let needs_paren = matches!(self.prec, Prec::App | Prec::Matcher | Prec::Atomic);
         if needs_paren {
@@ -302,7 +474,33 @@ impl fmt::Display for ValDisplay<'_> {
         }
         Ok(())
       }
-      Val::Builtin(builtin) => f.write_str(builtin.as_str()),
+      Val::Builtin(builtin, args) => {
+        let needs_paren = !args.is_empty() && matches!(self.prec, Prec::Atomic);
+        if needs_paren {
+          f.write_str("(")?;
+        }
+        f.write_str(builtin.as_str())?;
+        for val in args {
+          f.write_str(" ")?;
+          ValDisplay { val, prec: Prec::Atomic, cx: self.cx }.fmt(f)?;
+        }
+        if needs_paren {
+          f.write_str(")")?;
+        }
+        Ok(())
+      }
+      Val::Ref(loc) => {
+        let needs_paren = matches!(self.prec, Prec::Atomic);
+        if needs_paren {
+          f.write_str("(")?;
+        }
+        f.write_str("ref ")?;
+        ValDisplay { val: &self.cx.store[*loc], prec: Prec::Atomic, cx: self.cx }.fmt(f)?;
+        if needs_paren {
+          f.write_str(")")?;
+        }
+        Ok(())
+      }
     }
   }
 }
@@ -475,7 +673,12 @@ impl fmt::Display for ExpDisplay<'_> {
       sml_hir::Exp::Typed(exp, _, _) => {
         ExpDisplay { exp: *exp, prec: self.prec, cx: self.cx }.fmt(f)
       }
-      sml_hir::Exp::Vector(_) => todo!(),
+      sml_hir::Exp::Vector(exps) => {
+        f.write_str("#[")?;
+        let exps = exps.iter().map(|&exp| ExpDisplay { exp, prec: Prec::Min, cx: self.cx });
+        fmt_util::comma_seq(f, exps)?;
+        f.write_str("]")
+      }
     }
   }
 }
@@ -568,7 +771,12 @@ impl fmt::Display for PatDisplay<'_> {
         }
         Ok(())
       }
-      sml_hir::Pat::Vector(_) => todo!(),
+      sml_hir::Pat::Vector(pats) => {
+        f.write_str("#[")?;
+        let pats = pats.iter().map(|&pat| PatDisplay { pat, ars: self.ars, atomic: false });
+        fmt_util::comma_seq(f, pats)?;
+        f.write_str("]")
+      }
     }
   }
 }
@@ -775,6 +983,39 @@ impl fmt::Display for StrExpDisplay<'_> {
   }
 }
 
+/// An evaluated structure.
+struct StrDisplay<'a> {
+  env: &'a Env,
+  cx: DisplayCx<'a>,
+}
+
+impl fmt::Display for StrDisplay<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("struct")?;
+    let cx_indented = self.cx.indented();
+    let mut strs: Vec<_> = self.env.str.iter().collect();
+    strs.sort_unstable_by_key(|&(name, _)| name.as_str());
+    for (name, env) in strs {
+      write_nl_indent(cx_indented.indent, f)?;
+      f.write_str("structure ")?;
+      name.fmt(f)?;
+      f.write_str(" = ")?;
+      StrDisplay { env, cx: cx_indented }.fmt(f)?;
+    }
+    let mut vals: Vec<_> = self.env.val.iter().collect();
+    vals.sort_unstable_by_key(|&(name, _)| name.as_str());
+    for (name, val) in vals {
+      write_nl_indent(cx_indented.indent, f)?;
+      f.write_str("val ")?;
+      name.fmt(f)?;
+      f.write_str(" = ")?;
+      ValDisplay { val, prec: Prec::Min, cx: cx_indented }.fmt(f)?;
+    }
+    write_nl_indent(self.cx.indent, f)?;
+    f.write_str("end")
+  }
+}
+
 struct EnvDisplay<'a> {
   env: &'a Env,
   cx: DisplayCx<'a>,
@@ -782,7 +1023,9 @@ struct EnvDisplay<'a> {
 
 impl fmt::Display for EnvDisplay<'_> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    for (name, env) in &self.env.str {
+    let mut strs: Vec<_> = self.env.str.iter().collect();
+    strs.sort_unstable_by_key(|&(name, _)| name.as_str());
+    for (name, env) in strs {
       for _ in 0..self.cx.indent {
         f.write_str("  ")?;
       }
@@ -790,7 +1033,9 @@ impl fmt::Display for EnvDisplay<'_> {
       f.write_str(":\n")?;
       EnvDisplay { env, cx: self.cx.indented() }.fmt(f)?;
     }
-    for (name, val) in &self.env.val {
+    let mut vals: Vec<_> = self.env.val.iter().collect();
+    vals.sort_unstable_by_key(|&(name, _)| name.as_str());
+    for (name, val) in vals {
       for _ in 0..self.cx.indent {
         f.write_str("  ")?;
       }
@@ -799,6 +1044,10 @@ impl fmt::Display for EnvDisplay<'_> {
       ValDisplay { val, prec: Prec::Min, cx: self.cx }.fmt(f)?;
       f.write_str("\n")?;
     }
+    // the outermost env is the std basis, which would just be noise.
+    if let Some(env) = self.env.outer.as_deref().filter(|env| env.outer.is_some()) {
+      EnvDisplay { env, cx: self.cx }.fmt(f)?;
+    }
     Ok(())
   }
 }
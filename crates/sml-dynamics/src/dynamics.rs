@@ -1,7 +1,9 @@
 //! The overall dynamics type.
 
+use crate::display::vals;
 use crate::step::step;
-use crate::types::{Cx, FrameKind, St, Step};
+use crate::types::{Cx, Exception, FrameKind, St, Step, Val};
+use str_util::Name;
 
 /// A runner of the dynamics.
 #[derive(Debug)]
@@ -22,6 +24,14 @@ impl<'a> Dynamics<'a> {
     Some(Self { cx, st, step: Some(Step::StrDec(str_dec)) })
   }
 
+  /// Returns a new dynamics for evaluating the exp on its own. When done, it displays as the value.
+  #[must_use]
+  pub fn new_exp(cx: Cx<'a>, exp: sml_hir::ExpIdx) -> Self {
+    let mut st = St::new_with_std_basis();
+    st.push_with_cur_env(FrameKind::Top);
+    Self { cx, st, step: Some(Step::exp(exp)) }
+  }
+
   /// Takes a step.
   ///
   /// # Panics
@@ -35,11 +45,20 @@ impl<'a> Dynamics<'a> {
       s = new_s;
       if self.st.frames.is_empty() {
         return match s {
-          Step::Val(_) | Step::Exp(_) | Step::Dec(_) | Step::StrDec(_) => {
+          Step::Val(val) => {
+            let val = Some(val);
+            Progress::Done(Finished { cx: self.cx, st: self.st, exception: None, val })
+          }
+          Step::Exp(_) | Step::Dec(_) | Step::StrDec(_) | Step::StrExp(_) | Step::Str(_) => {
             unreachable!("not done, but no frames")
           }
-          Step::Raise(_) => Progress::Raise,
-          Step::DecDone => Progress::Done,
+          Step::Raise(exception) => {
+            let exception = Some(exception);
+            Progress::Raise(Finished { cx: self.cx, st: self.st, exception, val: None })
+          }
+          Step::DecDone => {
+            Progress::Done(Finished { cx: self.cx, st: self.st, exception: None, val: None })
+          }
         };
       }
       if change {
@@ -50,6 +69,43 @@ impl<'a> Dynamics<'a> {
     Progress::Still(self)
   }
 
+  /// Returns what the program has printed since the last time this was called.
+  pub fn take_output(&mut self) -> String {
+    std::mem::take(&mut self.st.output)
+  }
+
+  /// Returns the syntax about to be evaluated, if any.
+  #[must_use]
+  pub fn idx(&self) -> Option<sml_hir::Idx> {
+    self.step.as_ref().and_then(Step::idx)
+  }
+
+  /// Returns how many function calls are being evaluated.
+  #[must_use]
+  pub fn depth(&self) -> usize {
+    self.st.frames.iter().filter(|frame| matches!(frame.kind, FrameKind::Body(_))).count()
+  }
+
+  /// Returns the function calls being evaluated, innermost first, followed by the top level.
+  #[must_use]
+  pub fn stack(&self) -> Vec<StackFrame> {
+    let mut ret = Vec::<StackFrame>::new();
+    let mut idx = self.idx();
+    let mut env = &self.st.env;
+    for (i, frame) in self.st.frames.iter().enumerate().rev() {
+      let FrameKind::Body(call) = &frame.kind else { continue };
+      let name = call.name.as_ref().map_or("fn", Name::as_str).to_owned();
+      ret.push(StackFrame { name, idx, vals: vals(self.cx.ars, &self.st.store, env) });
+      idx = call.arg.map(sml_hir::Idx::Exp);
+      // the caller's env is saved in the frame it will return to. `raise` does not save it.
+      let caller = self.st.frames[..i].iter().rev().find(|f| !matches!(f.kind, FrameKind::Raise));
+      env = caller.map_or(&self.st.env, |f| &f.env);
+    }
+    let vals = vals(self.cx.ars, &self.st.store, env);
+    ret.push(StackFrame { name: "top level".to_owned(), idx, vals });
+    ret
+  }
+
   /// Prints debug output. TODO remove
   pub fn show_debug(&self) {
     for frame in &self.st.frames {
@@ -59,13 +115,46 @@ impl<'a> Dynamics<'a> {
   }
 }
 
+/// A function call being evaluated, or the top level.
+#[derive(Debug)]
+pub struct StackFrame {
+  /// The name of the function, or `fn` if it has none.
+  pub name: String,
+  /// The syntax being evaluated in this frame, if known.
+  ///
+  /// For a frame that is not the innermost, this is the argument of the call it is waiting on.
+  pub idx: Option<sml_hir::Idx>,
+  /// The values bound in this frame, sorted by name and displayed. Does not include the std basis.
+  pub vals: Vec<(Name, String)>,
+}
+
 /// A way for the dynamics to progress.
 #[derive(Debug)]
 pub enum Progress<'a> {
   /// Still evaluating.
   Still(Dynamics<'a>),
   /// Done evaluating.
-  Done,
-  /// Raised an exception.
-  Raise,
+  Done(Finished<'a>),
+  /// Raised an exception that was not handled.
+  Raise(Finished<'a>),
+}
+
+/// The dynamics after it finished evaluating.
+///
+/// Displays as the top-level bindings, or the value for [`Dynamics::new_exp`], if it finished
+/// normally, else as the unhandled exception.
+#[derive(Debug)]
+pub struct Finished<'a> {
+  pub(crate) cx: Cx<'a>,
+  pub(crate) st: St,
+  pub(crate) exception: Option<Exception>,
+  pub(crate) val: Option<Val>,
+}
+
+impl Finished<'_> {
+  /// Returns what the program has printed since the last time this or
+  /// [`Dynamics::take_output`] was called.
+  pub fn take_output(&mut self) -> String {
+    std::mem::take(&mut self.st.output)
+  }
 }
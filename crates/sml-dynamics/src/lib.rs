@@ -1,10 +1,12 @@
 //! The dynamic semantics, aka, running a program.
 
+mod builtin;
 mod display;
 mod dynamics;
 mod pat_match;
 mod step;
 mod types;
 
-pub use dynamics::{Dynamics, Progress};
-pub use types::Cx;
+pub use builtin::std_basis_paths;
+pub use dynamics::{Dynamics, Finished, Progress, StackFrame};
+pub use types::{Cx, Exns};
@@ -4,13 +4,20 @@ use crate::types::{ConKind, Cx, Val, ValEnv};
 use sml_hir::SCon;
 use sml_statics_types::info::IdStatus;
 
-pub(crate) fn get(ac: &mut ValEnv, cx: Cx<'_>, pat: sml_hir::PatIdx, val: &Val) -> bool {
+/// The store has the contents of the refs.
+pub(crate) fn get(
+  ac: &mut ValEnv,
+  cx: Cx<'_>,
+  store: &[Val],
+  pat: sml_hir::PatIdx,
+  val: &Val,
+) -> bool {
   let pat = pat.expect("no pat");
   match (&cx.ars.pat[pat], val) {
     (sml_hir::Pat::Wild, _) => true,
     (sml_hir::Pat::Con(path, pat_arg), _) => match &cx.pat[pat] {
-      IdStatus::Con => get_con(ac, cx, path.last(), ConKind::Dat, *pat_arg, val),
-      IdStatus::Exn(exn) => get_con(ac, cx, path.last(), ConKind::Exn(*exn), *pat_arg, val),
+      IdStatus::Con => get_con(ac, cx, store, path.last(), ConKind::Dat, *pat_arg, val),
+      IdStatus::Exn(exn) => get_con(ac, cx, store, path.last(), ConKind::Exn(*exn), *pat_arg, val),
       IdStatus::Val => {
         assert!(path.prefix().is_empty());
         assert!(pat_arg.is_none());
@@ -18,7 +25,7 @@ pub(crate) fn get(ac: &mut ValEnv, cx: Cx<'_>, pat: sml_hir::PatIdx, val: &Val)
         true
       }
     },
-    (_, Val::Closure { .. } | Val::Builtin(_)) => {
+    (_, Val::Closure { .. } | Val::Builtin(_, _)) => {
       unreachable!("match non-(Wild or Con) with Closure or Builtin")
     }
     (sml_hir::Pat::SCon(pat_sc), Val::SCon(val_sc)) => match (pat_sc, val_sc) {
@@ -32,24 +39,24 @@ pub(crate) fn get(ac: &mut ValEnv, cx: Cx<'_>, pat: sml_hir::PatIdx, val: &Val)
         unreachable!("SCon types do not match")
       }
     },
-    (sml_hir::Pat::SCon(_), Val::Con(_) | Val::Record(_)) => {
-      unreachable!("match SCon with (Con or Record")
+    (sml_hir::Pat::SCon(_), Val::Con(_) | Val::Record(_) | Val::Vector(_) | Val::Ref(_)) => {
+      unreachable!("match SCon with (Con or Record or Vector or Ref)")
     }
     (sml_hir::Pat::Record { rows: pat_rows, allows_other: _ }, Val::Record(val_rows)) => {
-      pat_rows.iter().all(|(lab, pat)| get(ac, cx, *pat, &val_rows[lab]))
+      pat_rows.iter().all(|(lab, pat)| get(ac, cx, store, *pat, &val_rows[lab]))
     }
-    (sml_hir::Pat::Record { .. }, Val::SCon(_) | Val::Con(_)) => {
-      unreachable!("match Record with (SCon or Con")
+    (sml_hir::Pat::Record { .. }, Val::SCon(_) | Val::Con(_) | Val::Vector(_) | Val::Ref(_)) => {
+      unreachable!("match Record with (SCon or Con or Vector or Ref)")
     }
-    (sml_hir::Pat::Typed(pat, _), _) => get(ac, cx, *pat, val),
+    (sml_hir::Pat::Typed(pat, _), _) => get(ac, cx, store, *pat, val),
     (sml_hir::Pat::As(name, pat), val) => {
       ac.insert(name.clone(), val.clone());
-      get(ac, cx, *pat, val)
+      get(ac, cx, store, *pat, val)
     }
     (sml_hir::Pat::Or(or_pat), val) => {
       let mut or_ac = ValEnv::default();
       for pat in or_pat.all_pats() {
-        if !get(&mut or_ac, cx, pat, val) {
+        if !get(&mut or_ac, cx, store, pat, val) {
           or_ac.clear();
           continue;
         }
@@ -60,18 +67,29 @@ pub(crate) fn get(ac: &mut ValEnv, cx: Cx<'_>, pat: sml_hir::PatIdx, val: &Val)
       }
       false
     }
-    (sml_hir::Pat::Vector(_), _) => todo!(),
+    (sml_hir::Pat::Vector(pats), Val::Vector(vals)) => {
+      pats.len() == vals.len()
+        && pats.iter().zip(vals).all(|(&pat, val)| get(ac, cx, store, pat, val))
+    }
+    (sml_hir::Pat::Vector(_), Val::SCon(_) | Val::Con(_) | Val::Record(_) | Val::Ref(_)) => {
+      unreachable!("match Vector with (SCon or Con or Record or Ref)")
+    }
   }
 }
 
 fn get_con(
   ac: &mut ValEnv,
   cx: Cx<'_>,
+  store: &[Val],
   name: &str_util::Name,
   kind: ConKind,
   pat_arg: Option<sml_hir::PatIdx>,
   val: &Val,
 ) -> bool {
+  if let Val::Ref(loc) = val {
+    let pat_arg = pat_arg.expect("pat ref has no arg");
+    return get(ac, cx, store, pat_arg, &store[*loc]);
+  }
   let Val::Con(con) = val else { unreachable!("match Con with non-Con") };
   let same_con = match (kind, &con.kind) {
     (ConKind::Dat, ConKind::Dat) => *name == con.name,
@@ -83,7 +101,7 @@ fn get_con(
   }
   match (pat_arg, &con.arg) {
     (None, None) => true,
-    (Some(pat_arg), Some(val_arg)) => get(ac, cx, pat_arg, val_arg.as_ref()),
+    (Some(pat_arg), Some(val_arg)) => get(ac, cx, store, pat_arg, val_arg.as_ref()),
     (Some(_), None) => unreachable!("pat Con has arg but val does not"),
     (None, Some(_)) => unreachable!("pat Con has no arg but val does"),
   }
@@ -1,9 +1,10 @@
 //! Stepping a stack machine.
 
-use crate::pat_match;
 use crate::types::{
-  Builtin, Closure, Con, ConKind, Cx, Env, Frame, FrameKind, St, Step, Val, ValEnv,
+  Call, Closure, Con, ConKind, Cx, Env, Frame, FrameKind, Functor, Sig, St, Step, StrEnv, Val,
+  ValEnv,
 };
+use crate::{builtin, pat_match};
 use fast_hash::FxHashSet;
 use sml_hir::Lab;
 use sml_statics_types::info::IdStatus;
@@ -26,10 +27,10 @@ pub(crate) fn step(st: &mut St, cx: Cx<'_>, s: Step) -> (Step, bool) {
         }
         IdStatus::Val => {
           let env = st.env.get(path.prefix()).expect("no env");
-          let val = env.val[path.last()].clone();
+          let val = env.get_val(path.last()).expect("no val").clone();
           let visible = match &val {
-            Val::SCon(_) | Val::Record(_) | Val::Closure(_) => true,
-            Val::Builtin(_) => false,
+            Val::SCon(_) | Val::Record(_) | Val::Vector(_) | Val::Closure(_) | Val::Ref(_) => true,
+            Val::Builtin(_, _) => false,
             Val::Con(con) => !path.prefix().is_empty() || path.last() != &con.name,
           };
           (Step::Val(val), visible)
@@ -67,13 +68,38 @@ pub(crate) fn step(st: &mut St, cx: Cx<'_>, s: Step) -> (Step, bool) {
         st.frames.push(Frame::new(Env::empty(), FrameKind::Raise));
         (Step::exp(*exp), false)
       }
-      sml_hir::Exp::Fn(matcher, _) => {
-        let clos =
-          Closure { env: st.env.clone(), this: FxHashSet::default(), matcher: matcher.clone() };
+      sml_hir::Exp::Fn(matcher, flavor) => {
+        let is_call = matches!(flavor, sml_hir::FnFlavor::Fn | sml_hir::FnFlavor::FunArg);
+        // the `fn`s for the arguments of a curried `fun` after the first are part of that `fun`.
+        let name = if matches!(flavor, sml_hir::FnFlavor::FunArg) {
+          st.frames.iter().rev().find_map(|frame| match &frame.kind {
+            FrameKind::Body(call) => Some(call.name.clone()),
+            _ => None,
+          })
+        } else {
+          None
+        };
+        let clos = Closure {
+          env: st.env.clone(),
+          this: FxHashSet::default(),
+          matcher: matcher.clone(),
+          is_call,
+          name: name.flatten(),
+        };
         (Step::Val(Val::Closure(clos)), false)
       }
       sml_hir::Exp::Typed(exp, _, _) => (Step::exp(*exp), false),
-      sml_hir::Exp::Vector(_) => todo!(),
+      sml_hir::Exp::Vector(exps) => {
+        let mut exps = exps.clone();
+        exps.reverse();
+        match exps.pop() {
+          None => (Step::Val(Val::Vector(Vec::new())), false),
+          Some(exp) => {
+            st.push_with_cur_env(FrameKind::Vector(Vec::new(), exps));
+            (Step::exp(exp), false)
+          }
+        }
+      }
     },
     Step::Val(val) => match st.frames.pop() {
       // done evaluating
@@ -90,57 +116,60 @@ pub(crate) fn step(st: &mut St, cx: Cx<'_>, s: Step) -> (Step, bool) {
             }
           }
         }
-        FrameKind::AppFunc(arg) => match val {
-          Val::Closure(clos) => {
-            st.env = frame.env;
-            let mut env = clos.env.clone();
-            // recursion!
-            for name in &clos.this {
-              env.val.insert(name.clone(), Val::Closure(clos.clone()));
+        FrameKind::Vector(mut vals, mut exps) => {
+          vals.push(val);
+          st.env = frame.env;
+          match exps.pop() {
+            None => (Step::Val(Val::Vector(vals)), false),
+            Some(exp) => {
+              st.push_with_cur_env(FrameKind::Vector(vals, exps));
+              (Step::exp(exp), false)
             }
-            st.frames.push(Frame::new(env, FrameKind::AppClosureArg(clos.matcher)));
-            (Step::exp(arg), false)
           }
-          Val::Con(con) => {
-            assert!(con.arg.is_none(), "Con already has arg");
-            st.env = frame.env;
-            st.push_with_cur_env(FrameKind::AppConArg(con.name, con.kind));
-            (Step::exp(arg), false)
-          }
-          Val::Builtin(b) => {
-            st.push_with_cur_env(FrameKind::AppBuiltinArg(b));
-            (Step::exp(arg), false)
+        }
+        FrameKind::AppFunc(arg) => {
+          st.env = frame.env;
+          match val {
+            Val::Closure(clos) => {
+              let env = closure_env(&clos);
+              let call = Call::new(&clos, arg);
+              st.frames.push(Frame::new(env, FrameKind::AppClosureArg(clos.matcher, call)));
+            }
+            Val::Con(con) => {
+              assert!(con.arg.is_none(), "Con already has arg");
+              st.push_with_cur_env(FrameKind::AppConArg(con.name, con.kind));
+            }
+            Val::Builtin(b, args) => st.push_with_cur_env(FrameKind::AppBuiltinArg(b, args)),
+            Val::SCon(_) | Val::Record(_) | Val::Vector(_) | Val::Ref(_) => {
+              unreachable!("AppFunc not Closure or Con or Builtin")
+            }
           }
-          _ => unreachable!("AppFunc not Closure or Con"),
-        },
-        FrameKind::AppClosureArg(matcher) => {
+          (Step::exp(arg), false)
+        }
+        FrameKind::AppClosureArg(matcher, call) => {
           let mut ac = ValEnv::default();
           for arm in matcher {
-            if pat_match::get(&mut ac, cx, arm.pat, &val) {
+            if pat_match::get(&mut ac, cx, &st.store, arm.pat, &val) {
               st.env = frame.env;
               st.env.val.extend(ac);
+              if let Some(call) = call {
+                st.push_with_cur_env(FrameKind::Body(call));
+              }
               return (Step::exp(arm.exp), true);
             }
           }
           (Step::Raise(cx.match_exn()), true)
         }
-        FrameKind::AppBuiltinArg(builtin) => match builtin {
-          Builtin::Add => {
-            let [x, y] = val.unwrap_pair().map(Val::unwrap_scon);
-            let res = match (x, y) {
-              // TODO raise for overflow
-              (sml_hir::SCon::Int(x), sml_hir::SCon::Int(y)) => sml_hir::SCon::Int(x + y),
-              (sml_hir::SCon::Real(x), sml_hir::SCon::Real(y)) => sml_hir::SCon::Real(x + y),
-              // TODO raise for overflow
-              (sml_hir::SCon::Word(x), sml_hir::SCon::Word(y)) => sml_hir::SCon::Word(x + y),
-              _ => unreachable!("bad scon types"),
-            };
-            (Step::Val(Val::SCon(res)), true)
-          }
-        },
-        FrameKind::AppConArg(name, kind) => {
-          (Step::Val(Val::Con(Con { name, kind, arg: Some(Box::new(val)) })), false)
+        FrameKind::AppBuiltinArg(b, mut args) => {
+          st.env = frame.env;
+          args.push(val);
+          builtin::apply(st, cx, b, args)
         }
+        FrameKind::Callback(cb) => {
+          st.env = frame.env;
+          builtin::resume(st, cx, cb, val)
+        }
+        FrameKind::AppConArg(name, kind) => (Step::Val(con_app(st, name, kind, val)), false),
         FrameKind::Raise => match val {
           Val::Con(con) => {
             (Step::Raise(con.try_into().expect("Raise Con but not Exception")), false)
@@ -149,6 +178,8 @@ pub(crate) fn step(st: &mut St, cx: Cx<'_>, s: Step) -> (Step, bool) {
         },
         // handle wasn't needed, as head didn't raise
         FrameKind::Handle(_) => (Step::Val(val), true),
+        // the function returned
+        FrameKind::Body(_) | FrameKind::Top => (Step::Val(val), false),
         FrameKind::ValBind(recursive, pat, mut val_binds) => {
           let mut ac = ValEnv::default();
           if recursive {
@@ -157,10 +188,11 @@ pub(crate) fn step(st: &mut St, cx: Cx<'_>, s: Step) -> (Step, bool) {
             let Val::Closure(mut clos) = val else { unreachable!("val rec value must be Closure") };
             assert!(clos.this.is_empty());
             clos.this.clone_from(&this);
+            clos.name = this.iter().next().cloned();
             for name in this {
               ac.insert(name, Val::Closure(clos.clone()));
             }
-          } else if !pat_match::get(&mut ac, cx, pat, &val) {
+          } else if !pat_match::get(&mut ac, cx, &st.store, pat, &val) {
             return (Step::Raise(cx.bind_exn()), true);
           }
           st.env = frame.env;
@@ -177,7 +209,15 @@ pub(crate) fn step(st: &mut St, cx: Cx<'_>, s: Step) -> (Step, bool) {
         | FrameKind::Local(_, _)
         | FrameKind::In(_)
         | FrameKind::DecSeq(_)
-        | FrameKind::StrDecSeq(_) => {
+        | FrameKind::StrDecSeq(_)
+        | FrameKind::StrBind(_, _, _)
+        | FrameKind::Struct(_)
+        | FrameKind::StrLet(_, _)
+        | FrameKind::StrLocal(_, _)
+        | FrameKind::StrIn(_)
+        | FrameKind::Ascription(_)
+        | FrameKind::AppFunctorArg(_, _)
+        | FrameKind::AppFunctorBody => {
           unreachable!("bad surrounding frame for Val")
         }
       },
@@ -190,7 +230,7 @@ pub(crate) fn step(st: &mut St, cx: Cx<'_>, s: Step) -> (Step, bool) {
           let mut ac = ValEnv::default();
           let val = Val::Con(exception.clone().into());
           for arm in matcher {
-            if pat_match::get(&mut ac, cx, arm.pat, &val) {
+            if pat_match::get(&mut ac, cx, &st.store, arm.pat, &val) {
               st.env = frame.env;
               st.env.val.extend(ac);
               return (Step::exp(arm.exp), true);
@@ -215,8 +255,14 @@ pub(crate) fn step(st: &mut St, cx: Cx<'_>, s: Step) -> (Step, bool) {
       | sml_hir::Dec::Datatype(_, _)
       | sml_hir::Dec::DatatypeCopy(_, _)
       | sml_hir::Dec::Abstype(_, _, _)
-      | sml_hir::Dec::Exception(_)
-      | sml_hir::Dec::Open(_) => step_dec(st),
+      | sml_hir::Dec::Exception(_) => step_dec(st),
+      sml_hir::Dec::Open(paths) => {
+        for path in paths {
+          let env = get_str(&st.env, path).clone();
+          st.env.extend(env);
+        }
+        step_dec(st)
+      }
       sml_hir::Dec::Local(local_decs, in_decs) => {
         let mut local_decs = local_decs.clone();
         let mut in_decs = in_decs.clone();
@@ -233,10 +279,109 @@ pub(crate) fn step(st: &mut St, cx: Cx<'_>, s: Step) -> (Step, bool) {
         st.push_with_cur_env(FrameKind::DecSeq(decs));
         step_dec(st)
       }
-      sml_hir::StrDec::Structure(_) => todo!(),
-      sml_hir::StrDec::Signature(_) => todo!(),
-      sml_hir::StrDec::Functor(_) => todo!(),
-      sml_hir::StrDec::Local(_, _) => todo!(),
+      sml_hir::StrDec::Structure(str_binds) => {
+        let mut str_binds: Vec<_> =
+          str_binds.iter().map(|str_bind| (str_bind.name.clone(), str_bind.str_exp)).collect();
+        str_binds.reverse();
+        let (name, str_exp) = str_binds.pop().expect("no str binds");
+        st.push_with_cur_env(FrameKind::StrBind(StrEnv::default(), name, str_binds));
+        (Step::str_exp(str_exp), false)
+      }
+      sml_hir::StrDec::Signature(sig_binds) => {
+        let sigs: Vec<_> = sig_binds
+          .iter()
+          .map(|sig_bind| (sig_bind.name.clone(), sig(cx, &st.env, sig_bind.sig_exp)))
+          .collect();
+        st.env.sig.extend(sigs);
+        step_dec(st)
+      }
+      sml_hir::StrDec::Functor(functor_binds) => {
+        let functors: Vec<_> = functor_binds
+          .iter()
+          .map(|functor_bind| {
+            let functor = Functor {
+              env: st.env.clone(),
+              param_name: functor_bind.param_name.clone(),
+              param_sig: sig(cx, &st.env, functor_bind.param_sig),
+              body: functor_bind.body,
+            };
+            (functor_bind.functor_name.clone(), functor)
+          })
+          .collect();
+        st.env.fun.extend(functors);
+        step_dec(st)
+      }
+      sml_hir::StrDec::Local(local_str_decs, in_str_decs) => {
+        let mut local_str_decs = local_str_decs.clone();
+        let mut in_str_decs = in_str_decs.clone();
+        local_str_decs.reverse();
+        in_str_decs.reverse();
+        st.push_with_cur_env(FrameKind::StrLocal(local_str_decs, in_str_decs));
+        st.nest_env();
+        step_dec(st)
+      }
+    },
+    Step::StrExp(str_exp) => match &cx.ars.str_exp[str_exp] {
+      sml_hir::StrExp::Struct(str_decs) => {
+        let mut str_decs = str_decs.clone();
+        str_decs.reverse();
+        st.push_with_cur_env(FrameKind::Struct(str_decs));
+        st.nest_env();
+        step_dec(st)
+      }
+      sml_hir::StrExp::Path(path) => (Step::Str(get_str(&st.env, path).clone()), true),
+      sml_hir::StrExp::Ascription(str_exp, _, sig_exp) => {
+        let sig = sig(cx, &st.env, *sig_exp);
+        st.push_with_cur_env(FrameKind::Ascription(sig));
+        (Step::str_exp(*str_exp), false)
+      }
+      sml_hir::StrExp::App(name, arg, _) => {
+        let functor = st.env.get_fun(name).expect("no functor").clone();
+        st.push_with_cur_env(FrameKind::AppFunctorArg(name.clone(), functor));
+        (Step::str_exp(*arg), false)
+      }
+      sml_hir::StrExp::Let(str_decs, str_exp) => {
+        let mut str_decs = str_decs.clone();
+        str_decs.reverse();
+        st.push_with_cur_env(FrameKind::StrLet(str_decs, *str_exp));
+        st.nest_env();
+        step_dec(st)
+      }
+    },
+    Step::Str(env) => match st.frames.pop() {
+      None => unreachable!("no frame for Str"),
+      Some(frame) => match frame.kind {
+        FrameKind::StrBind(mut str_env, name, mut str_binds) => {
+          str_env.insert(name, env);
+          st.env = frame.env;
+          match str_binds.pop() {
+            Some((name, str_exp)) => {
+              st.push_with_cur_env(FrameKind::StrBind(str_env, name, str_binds));
+              (Step::str_exp(str_exp), false)
+            }
+            None => {
+              st.env.str.extend(str_env);
+              (step_dec(st).0, true)
+            }
+          }
+        }
+        FrameKind::Ascription(sig) => {
+          st.env = frame.env;
+          (Step::Str(env.restrict(&sig)), false)
+        }
+        FrameKind::AppFunctorArg(_, functor) => {
+          st.frames.push(Frame::new(frame.env, FrameKind::AppFunctorBody));
+          let mut body_env = functor.env.nested();
+          body_env.str.insert(functor.param_name, env.restrict(&functor.param_sig));
+          st.env = body_env;
+          (Step::str_exp(functor.body), true)
+        }
+        FrameKind::AppFunctorBody => {
+          st.env = frame.env;
+          (Step::Str(env), false)
+        }
+        _ => unreachable!("bad surrounding frame for Str"),
+      },
     },
     // done with a dec
     Step::DecDone => {
@@ -252,12 +397,20 @@ fn step_dec(st: &mut St) -> (Step, bool) {
     match frame.kind {
       FrameKind::Record(_, _, _, _)
       | FrameKind::AppFunc(_)
-      | FrameKind::AppClosureArg(_)
-      | FrameKind::AppBuiltinArg(_)
+      | FrameKind::AppClosureArg(_, _)
+      | FrameKind::AppBuiltinArg(_, _)
+      | FrameKind::Callback(_)
       | FrameKind::AppConArg(_, _)
       | FrameKind::Raise
       | FrameKind::Handle(_)
-      | FrameKind::ValBind(_, _, _) => unreachable!("bad surrounding frame for Dec"),
+      | FrameKind::ValBind(_, _, _)
+      | FrameKind::Vector(_, _)
+      | FrameKind::StrBind(_, _, _)
+      | FrameKind::Ascription(_)
+      | FrameKind::AppFunctorArg(_, _)
+      | FrameKind::AppFunctorBody
+      | FrameKind::Body(_)
+      | FrameKind::Top => unreachable!("bad surrounding frame for Dec"),
       FrameKind::Let(mut decs, exp) => match decs.pop() {
         None => return (Step::exp(exp), change),
         Some(dec) => {
@@ -293,11 +446,136 @@ fn step_dec(st: &mut St) -> (Step, bool) {
           return (Step::StrDec(str_dec), change);
         }
       },
+      FrameKind::Struct(mut str_decs) => match str_decs.pop() {
+        None => {
+          let env = std::mem::replace(&mut st.env, frame.env).without_outer();
+          return (Step::Str(env), change);
+        }
+        Some(str_dec) => {
+          st.frames.push(Frame::new(frame.env, FrameKind::Struct(str_decs)));
+          return (Step::StrDec(str_dec), change);
+        }
+      },
+      FrameKind::StrLet(mut str_decs, str_exp) => match str_decs.pop() {
+        None => return (Step::str_exp(str_exp), change),
+        Some(str_dec) => {
+          st.push_with_cur_env(FrameKind::StrLet(str_decs, str_exp));
+          return (Step::StrDec(str_dec), change);
+        }
+      },
+      FrameKind::StrLocal(mut local_str_decs, in_str_decs) => match local_str_decs.pop() {
+        None => {
+          st.frames.push(Frame::new(frame.env, FrameKind::StrIn(in_str_decs)));
+          st.nest_env();
+        }
+        Some(str_dec) => {
+          st.frames.push(Frame::new(frame.env, FrameKind::StrLocal(local_str_decs, in_str_decs)));
+          return (Step::StrDec(str_dec), change);
+        }
+      },
+      FrameKind::StrIn(mut in_str_decs) => match in_str_decs.pop() {
+        None => {
+          // only the bindings from after the `in` are visible after the `local`.
+          let env = std::mem::replace(&mut st.env, frame.env).without_outer();
+          st.env.extend(env);
+          change = true;
+        }
+        Some(str_dec) => {
+          st.frames.push(Frame::new(frame.env, FrameKind::StrIn(in_str_decs)));
+          return (Step::StrDec(str_dec), change);
+        }
+      },
     }
   }
   (Step::DecDone, change)
 }
 
+/// Applies a function value to an argument value.
+pub(crate) fn apply_val(st: &mut St, cx: Cx<'_>, func: Val, arg: Val) -> (Step, bool) {
+  match func {
+    Val::Closure(clos) => {
+      let env = closure_env(&clos);
+      let call = Call::new(&clos, None);
+      st.frames.push(Frame::new(env, FrameKind::AppClosureArg(clos.matcher, call)));
+      (Step::Val(arg), false)
+    }
+    Val::Con(con) => (Step::Val(con_app(st, con.name, con.kind, arg)), false),
+    Val::Builtin(b, mut args) => {
+      args.push(arg);
+      builtin::apply(st, cx, b, args)
+    }
+    Val::SCon(_) | Val::Record(_) | Val::Vector(_) | Val::Ref(_) => {
+      unreachable!("apply not Closure or Con or Builtin")
+    }
+  }
+}
+
+/// Returns the env to evaluate the body of the closure in, before binding the argument.
+fn closure_env(clos: &Closure) -> Env {
+  let mut env = clos.env.clone();
+  // recursion!
+  for name in &clos.this {
+    env.val.insert(name.clone(), Val::Closure(clos.clone()));
+  }
+  env
+}
+
+/// Applies the constructor to the argument. Applying `ref` allocates a new location in the store.
+fn con_app(st: &mut St, name: Name, kind: ConKind, arg: Val) -> Val {
+  if matches!(kind, ConKind::Dat) && name.as_str() == "ref" {
+    st.store.push(arg);
+    return Val::Ref(st.store.len() - 1);
+  }
+  Val::Con(Con { name, kind, arg: Some(Box::new(arg)) })
+}
+
+/// Returns the structure at the path.
+fn get_str<'e>(env: &'e Env, path: &sml_path::Path) -> &'e Env {
+  env.get(path.prefix()).ok().and_then(|env| env.get_str(path.last())).expect("no str")
+}
+
+/// Returns what the signature specifies.
+fn sig(cx: Cx<'_>, env: &Env, sig_exp: sml_hir::SigExpIdx) -> Sig {
+  match &cx.ars.sig_exp[sig_exp.expect("no sig exp")] {
+    sml_hir::SigExp::Spec(specs) => {
+      let mut ret = Sig::default();
+      for &spec in specs {
+        add_spec(cx, env, &mut ret, spec);
+      }
+      ret
+    }
+    sml_hir::SigExp::Name(name) => env.get_sig(name).expect("no sig").clone(),
+    sml_hir::SigExp::Where(sig_exp, _) => sig(cx, env, *sig_exp),
+  }
+}
+
+fn add_spec(cx: Cx<'_>, env: &Env, ac: &mut Sig, spec: sml_hir::SpecIdx) {
+  match &cx.ars.spec[spec] {
+    sml_hir::Spec::Val(_, val_descs) => {
+      ac.vals.extend(val_descs.iter().map(|val_desc| val_desc.name.clone()));
+    }
+    sml_hir::Spec::Str(str_desc) => {
+      ac.strs.insert(str_desc.name.clone(), sig(cx, env, str_desc.sig_exp));
+    }
+    sml_hir::Spec::Include(sig_exp) => {
+      let other = sig(cx, env, *sig_exp);
+      ac.vals.extend(other.vals);
+      ac.strs.extend(other.strs);
+    }
+    sml_hir::Spec::Sharing(specs, _, _) => {
+      for &spec in specs {
+        add_spec(cx, env, ac, spec);
+      }
+    }
+    // constructors and exceptions are not looked up in the env.
+    sml_hir::Spec::Ty(_)
+    | sml_hir::Spec::EqTy(_)
+    | sml_hir::Spec::Datatype(_)
+    | sml_hir::Spec::DatatypeCopy(_, _)
+    | sml_hir::Spec::Exception(_) => {}
+  }
+}
+
 fn rec_fn_names(ars: &sml_hir::Arenas, ac: &mut FxHashSet<Name>, pat: sml_hir::PatIdx) {
   match &ars.pat[pat.expect("no pat")] {
     sml_hir::Pat::Wild => {}
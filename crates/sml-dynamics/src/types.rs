@@ -1,8 +1,9 @@
 //! Dynamics types.
 
-use fast_hash::{map, FxHashMap, FxHashSet};
+use crate::builtin::{Builtin, Callback, BINDINGS};
+use fast_hash::{FxHashMap, FxHashSet};
 use sml_hir::{la_arena, Lab, SCon};
-use sml_statics_types::info::IdStatusMap;
+use sml_statics_types::info::{IdStatus, IdStatusMap};
 use sml_statics_types::sym::Exn;
 use std::collections::BTreeMap;
 use str_util::Name;
@@ -12,18 +13,66 @@ pub(crate) enum Val {
   SCon(SCon),
   Con(Con),
   Record(BTreeMap<Lab, Val>),
+  Vector(Vec<Val>),
   Closure(Closure),
-  Builtin(Builtin),
+  /// A builtin, and the curried arguments it has been applied to so far.
+  Builtin(Builtin, Vec<Val>),
+  /// A location in the store.
+  Ref(usize),
 }
 
 impl Val {
+  pub(crate) fn unit() -> Val {
+    Val::Record(BTreeMap::new())
+  }
+
+  pub(crate) fn tuple<const N: usize>(vals: [Val; N]) -> Val {
+    Val::Record(vals.into_iter().enumerate().map(|(idx, val)| (Lab::tuple(idx), val)).collect())
+  }
+
+  pub(crate) fn int(x: i64) -> Val {
+    Val::SCon(SCon::Int(x.into()))
+  }
+
+  pub(crate) fn string(s: String) -> Val {
+    Val::SCon(SCon::String(s.into()))
+  }
+
+  /// Returns a constructor from the std basis with no argument, like `true` or `NONE`.
+  pub(crate) fn dat(name: &str) -> Val {
+    Val::Con(Con::empty(Name::new(name), ConKind::Dat))
+  }
+
+  pub(crate) fn bool(b: bool) -> Val {
+    Val::dat(if b { "true" } else { "false" })
+  }
+
+  pub(crate) fn list(vals: Vec<Val>) -> Val {
+    vals.into_iter().rev().fold(Val::dat("nil"), |ac, val| {
+      let arg = Val::tuple([val, ac]);
+      Val::Con(Con { name: Name::new("::"), kind: ConKind::Dat, arg: Some(Box::new(arg)) })
+    })
+  }
+
+  pub(crate) fn option(val: Option<Val>) -> Val {
+    match val {
+      None => Val::dat("NONE"),
+      Some(val) => {
+        Val::Con(Con { name: Name::new("SOME"), kind: ConKind::Dat, arg: Some(Box::new(val)) })
+      }
+    }
+  }
+
   pub(crate) fn unwrap_pair(self) -> [Val; 2] {
+    self.unwrap_tuple()
+  }
+
+  pub(crate) fn unwrap_tuple<const N: usize>(self) -> [Val; N] {
     match self {
-      Val::Record(mut rows) => {
-        assert_eq!(rows.len(), 2);
-        let fst = rows.remove(&Lab::tuple(0)).expect("no fst");
-        let snd = rows.remove(&Lab::tuple(1)).expect("no snd");
-        [fst, snd]
+      Val::Record(rows) => {
+        assert_eq!(rows.len(), N);
+        let vals: Vec<_> = rows.into_values().collect();
+        vals.try_into().expect("wrong number of rows")
       }
       _ => unreachable!("not Record: {self:?}"),
     }
@@ -35,17 +84,73 @@ impl Val {
       _ => unreachable!("not SCon: {self:?}"),
     }
   }
-}
 
-#[derive(Debug, Clone, Copy)]
-pub(crate) enum Builtin {
-  Add,
-}
+  /// Returns the int, or raises `Overflow` if it is too big.
+  pub(crate) fn unwrap_int(self, cx: Cx<'_>) -> Result<i64, Exception> {
+    match self {
+      Val::SCon(SCon::Int(x)) => {
+        x.to_i64().ok_or_else(|| Exception::empty("Overflow", cx.exns.overflow))
+      }
+      _ => unreachable!("not Int: {self:?}"),
+    }
+  }
+
+  pub(crate) fn unwrap_real(self) -> f64 {
+    match self {
+      Val::SCon(SCon::Real(x)) => x,
+      _ => unreachable!("not Real: {self:?}"),
+    }
+  }
+
+  pub(crate) fn unwrap_char(self) -> char {
+    match self {
+      Val::SCon(SCon::Char(c)) => c,
+      _ => unreachable!("not Char: {self:?}"),
+    }
+  }
+
+  pub(crate) fn unwrap_string(self) -> str_util::SmolStr {
+    match self {
+      Val::SCon(SCon::String(s)) => s,
+      _ => unreachable!("not String: {self:?}"),
+    }
+  }
 
-impl Builtin {
-  pub(crate) fn as_str(self) -> &'static str {
+  pub(crate) fn unwrap_bool(self) -> bool {
     match self {
-      Builtin::Add => "+",
+      Val::Con(con) if con.arg.is_none() => match con.name.as_str() {
+        "true" => true,
+        "false" => false,
+        _ => unreachable!("not bool: {}", con.name),
+      },
+      _ => unreachable!("not bool: {self:?}"),
+    }
+  }
+
+  pub(crate) fn unwrap_list(self) -> Vec<Val> {
+    let mut ret = Vec::<Val>::new();
+    let mut val = self;
+    loop {
+      match val {
+        Val::Con(con) if con.name.as_str() == "nil" => return ret,
+        Val::Con(con) if con.name.as_str() == "::" => {
+          let [hd, tl] = con.arg.expect("no arg for ::").unwrap_pair();
+          ret.push(hd);
+          val = tl;
+        }
+        _ => unreachable!("not list: {val:?}"),
+      }
+    }
+  }
+
+  pub(crate) fn unwrap_option(self) -> Option<Val> {
+    match self {
+      Val::Con(con) => match con.name.as_str() {
+        "NONE" => None,
+        "SOME" => Some(*con.arg.expect("no arg for SOME")),
+        _ => unreachable!("not option: {}", con.name),
+      },
+      _ => unreachable!("not option: {self:?}"),
     }
   }
 }
@@ -55,6 +160,10 @@ pub(crate) struct Closure {
   pub(crate) env: Env,
   pub(crate) this: FxHashSet<Name>,
   pub(crate) matcher: Vec<sml_hir::Arm>,
+  /// Whether applying this is a call of a function from the source, as opposed to e.g. a `case`.
+  pub(crate) is_call: bool,
+  /// The name of the function this is a part of, if known.
+  pub(crate) name: Option<Name>,
 }
 
 #[derive(Debug, Clone)]
@@ -80,31 +189,133 @@ impl Con {
 pub(crate) struct Env {
   pub(crate) str: StrEnv,
   pub(crate) val: ValEnv,
+  pub(crate) sig: SigEnv,
+  pub(crate) fun: FunEnv,
+  /// The env this one is nested in. Its bindings are also in this env, unless shadowed.
+  pub(crate) outer: Option<Box<Env>>,
 }
 
 impl Env {
   pub(crate) fn empty() -> Env {
-    Env { str: StrEnv::default(), val: ValEnv::default() }
+    Env {
+      str: StrEnv::default(),
+      val: ValEnv::default(),
+      sig: SigEnv::default(),
+      fun: FunEnv::default(),
+      outer: None,
+    }
   }
 
   pub(crate) fn std_basis() -> Env {
-    Env { str: StrEnv::default(), val: map([(Name::new("+"), Val::Builtin(Builtin::Add))]) }
+    let mut ret = Env::empty();
+    for (str, name, builtin) in BINDINGS {
+      let env = match str {
+        None => &mut ret,
+        Some(str) => ret.str.entry(Name::new(str)).or_insert_with(Env::empty),
+      };
+      env.val.insert(Name::new(name), Val::Builtin(builtin, Vec::new()));
+    }
+    ret
+  }
+
+  /// Returns a new empty env nested in this one.
+  pub(crate) fn nested(self) -> Env {
+    Env { outer: Some(Box::new(self)), ..Env::empty() }
+  }
+
+  /// Returns the bindings in this env, but not the ones in the env it is nested in.
+  pub(crate) fn without_outer(self) -> Env {
+    Env { outer: None, ..self }
+  }
+
+  /// Adds all the bindings in the other env, but not the ones in the env it is nested in.
+  pub(crate) fn extend(&mut self, other: Env) {
+    self.str.extend(other.str);
+    self.val.extend(other.val);
+    self.sig.extend(other.sig);
+    self.fun.extend(other.fun);
   }
 
   pub(crate) fn get<'e, 'n>(&'e self, names: &'n [Name]) -> Result<&'e Env, &'n Name> {
     let mut ret = self;
     for name in names {
-      ret = match ret.str.get(name) {
+      ret = match ret.get_str(name) {
         Some(x) => x,
         None => return Err(name),
       };
     }
     Ok(ret)
   }
+
+  pub(crate) fn get_str(&self, name: &Name) -> Option<&Env> {
+    self.lookup(|env| env.str.get(name))
+  }
+
+  pub(crate) fn get_val(&self, name: &Name) -> Option<&Val> {
+    self.lookup(|env| env.val.get(name))
+  }
+
+  pub(crate) fn get_sig(&self, name: &Name) -> Option<&Sig> {
+    self.lookup(|env| env.sig.get(name))
+  }
+
+  pub(crate) fn get_fun(&self, name: &Name) -> Option<&Functor> {
+    self.lookup(|env| env.fun.get(name))
+  }
+
+  fn lookup<'e, T, F>(&'e self, f: F) -> Option<&'e T>
+  where
+    F: Fn(&'e Env) -> Option<&'e T>,
+  {
+    let mut env = self;
+    loop {
+      if let Some(x) = f(env) {
+        return Some(x);
+      }
+      env = env.outer.as_deref()?;
+    }
+  }
+
+  /// Returns the part of this env that the signature lets through.
+  ///
+  /// This is the same for opaque and transparent ascription, since they differ only in what they
+  /// reveal about types.
+  pub(crate) fn restrict(&self, sig: &Sig) -> Env {
+    let val = sig
+      .vals
+      .iter()
+      .filter_map(|name| Some((name.clone(), self.get_val(name)?.clone())))
+      .collect();
+    let str = sig
+      .strs
+      .iter()
+      .filter_map(|(name, sig)| Some((name.clone(), self.get_str(name)?.restrict(sig))))
+      .collect();
+    Env { str, val, ..Env::empty() }
+  }
 }
 
 pub(crate) type StrEnv = FxHashMap<Name, Env>;
 pub(crate) type ValEnv = FxHashMap<Name, Val>;
+pub(crate) type SigEnv = FxHashMap<Name, Sig>;
+pub(crate) type FunEnv = FxHashMap<Name, Functor>;
+
+/// The values and structures a signature specifies. Only these are visible after ascribing a
+/// structure to the signature.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Sig {
+  pub(crate) vals: FxHashSet<Name>,
+  pub(crate) strs: FxHashMap<Name, Sig>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Functor {
+  /// The env where the functor was declared.
+  pub(crate) env: Env,
+  pub(crate) param_name: Name,
+  pub(crate) param_sig: Sig,
+  pub(crate) body: sml_hir::StrExpIdx,
+}
 
 #[derive(Debug)]
 pub(crate) enum Step {
@@ -114,12 +325,30 @@ pub(crate) enum Step {
   Dec(sml_hir::DecIdx),
   DecDone,
   StrDec(sml_hir::StrDecIdx),
+  StrExp(la_arena::Idx<sml_hir::StrExp>),
+  /// A structure, evaluated to the env of its bindings.
+  Str(Env),
 }
 
 impl Step {
   pub(crate) fn exp(idx: sml_hir::ExpIdx) -> Self {
     Self::Exp(idx.expect("no exp"))
   }
+
+  pub(crate) fn str_exp(idx: sml_hir::StrExpIdx) -> Self {
+    Self::StrExp(idx.expect("no str exp"))
+  }
+
+  /// Returns the syntax this is evaluating, if any.
+  pub(crate) fn idx(&self) -> Option<sml_hir::Idx> {
+    match self {
+      Step::Exp(exp) => Some((*exp).into()),
+      Step::Dec(dec) => Some((*dec).into()),
+      Step::StrDec(str_dec) => Some((*str_dec).into()),
+      Step::StrExp(str_exp) => Some((*str_exp).into()),
+      Step::Val(_) | Step::Raise(_) | Step::DecDone | Step::Str(_) => None,
+    }
+  }
 }
 
 #[derive(Debug, Clone)]
@@ -129,6 +358,12 @@ pub(crate) struct Exception {
   pub(crate) arg: Option<Box<Val>>,
 }
 
+impl Exception {
+  pub(crate) fn empty(name: &str, exn: Exn) -> Self {
+    Self { name: Name::new(name), exn, arg: None }
+  }
+}
+
 impl TryFrom<Con> for Exception {
   type Error = (Name, Option<Box<Val>>);
 
@@ -163,9 +398,11 @@ impl Frame {
 pub(crate) enum FrameKind {
   /// The bool is whether this is actually a tuple.
   Record(bool, BTreeMap<Lab, Val>, Lab, Vec<(Lab, sml_hir::ExpIdx)>),
+  Vector(Vec<Val>, Vec<sml_hir::ExpIdx>),
   AppFunc(sml_hir::ExpIdx),
-  AppClosureArg(Vec<sml_hir::Arm>),
-  AppBuiltinArg(Builtin),
+  AppClosureArg(Vec<sml_hir::Arm>, Option<Call>),
+  AppBuiltinArg(Builtin, Vec<Val>),
+  Callback(Callback),
   AppConArg(Name, ConKind),
   Raise,
   Handle(Vec<sml_hir::Arm>),
@@ -176,6 +413,37 @@ pub(crate) enum FrameKind {
   In(Vec<sml_hir::DecIdx>),
   DecSeq(Vec<sml_hir::DecIdx>),
   StrDecSeq(Vec<sml_hir::StrDecIdx>),
+  /// The structures already evaluated, the name of the one being evaluated, and the rest.
+  StrBind(StrEnv, Name, Vec<(Name, sml_hir::StrExpIdx)>),
+  /// The env of the frame is the one the `struct` is in.
+  Struct(Vec<sml_hir::StrDecIdx>),
+  StrLet(Vec<sml_hir::StrDecIdx>, sml_hir::StrExpIdx),
+  /// The env of the frame is the one the `local` is in. Ditto for `StrIn`.
+  StrLocal(Vec<sml_hir::StrDecIdx>, Vec<sml_hir::StrDecIdx>),
+  StrIn(Vec<sml_hir::StrDecIdx>),
+  Ascription(Sig),
+  AppFunctorArg(Name, Functor),
+  AppFunctorBody,
+  /// The body of a function being evaluated. The env of the frame is the one the body started in.
+  Body(Call),
+  /// The bottom of the stack when evaluating an expression on its own.
+  Top,
+}
+
+/// A call of a function.
+#[derive(Debug, Clone)]
+pub(crate) struct Call {
+  /// The name of the function, if known.
+  pub(crate) name: Option<Name>,
+  /// The argument, if the function was applied in the source and not by a builtin.
+  pub(crate) arg: sml_hir::ExpIdx,
+}
+
+impl Call {
+  /// Returns the call for applying the closure, if it is a call.
+  pub(crate) fn new(clos: &Closure, arg: sml_hir::ExpIdx) -> Option<Self> {
+    clos.is_call.then(|| Self { name: clos.name.clone(), arg })
+  }
 }
 
 /// A context under which we run dynamics.
@@ -187,19 +455,57 @@ pub struct Cx<'a> {
   pub exp: &'a IdStatusMap<sml_hir::Exp>,
   /// A mapping from path patterns to what kind of identifier status those paths are.
   pub pat: &'a IdStatusMap<sml_hir::Pat>,
-  /// The built-in `Bind` exception.
-  pub bind: Exn,
-  /// The built-in `Match` exception.
-  pub match_: Exn,
+  /// The exceptions raised by the dynamics itself.
+  pub exns: Exns,
 }
 
 impl Cx<'_> {
   pub(crate) fn match_exn(&self) -> Exception {
-    Exception { name: Name::new("Match"), exn: self.match_, arg: None }
+    Exception::empty("Match", self.exns.match_)
   }
 
   pub(crate) fn bind_exn(&self) -> Exception {
-    Exception { name: Name::new("Bind"), exn: self.bind, arg: None }
+    Exception::empty("Bind", self.exns.bind)
+  }
+}
+
+/// The exceptions raised by the dynamics itself, as opposed to by a `raise` in the program.
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct Exns {
+  pub bind: Exn,
+  pub match_: Exn,
+  pub overflow: Exn,
+  pub div: Exn,
+  pub domain: Exn,
+  pub subscript: Exn,
+  pub size: Exn,
+  pub chr: Exn,
+  pub empty: Exn,
+  pub option: Exn,
+}
+
+impl Exns {
+  /// Returns the exceptions from the env of a basis, like the std basis, or `None` if some are
+  /// missing.
+  #[must_use]
+  pub fn new(env: &sml_statics_types::env::Env) -> Option<Self> {
+    let get = |name: &str| match env.val_env.get(&Name::new(name))?.id_status {
+      IdStatus::Exn(exn) => Some(exn),
+      IdStatus::Con | IdStatus::Val => None,
+    };
+    Some(Self {
+      bind: get("Bind")?,
+      match_: get("Match")?,
+      overflow: get("Overflow")?,
+      div: get("Div")?,
+      domain: get("Domain")?,
+      subscript: get("Subscript")?,
+      size: get("Size")?,
+      chr: get("Chr")?,
+      empty: get("Empty")?,
+      option: get("Option")?,
+    })
   }
 }
 
@@ -207,15 +513,31 @@ impl Cx<'_> {
 pub(crate) struct St {
   pub(crate) env: Env,
   pub(crate) frames: Vec<Frame>,
+  /// The contents of the refs.
+  pub(crate) store: Vec<Val>,
+  /// What the program has printed.
+  pub(crate) output: String,
 }
 
 impl St {
+  /// The std basis is the outermost env, so the top-level declarations are nested in it.
   pub(crate) fn new_with_std_basis() -> St {
-    St { env: Env::std_basis(), frames: Vec::new() }
+    St {
+      env: Env::std_basis().nested(),
+      frames: Vec::new(),
+      store: Vec::new(),
+      output: String::new(),
+    }
   }
 
   pub(crate) fn push_with_cur_env(&mut self, kind: FrameKind) {
     let env = self.env.clone();
     self.frames.push(Frame::new(env, kind));
   }
+
+  /// Nests the current env in a new empty env.
+  pub(crate) fn nest_env(&mut self) {
+    let env = std::mem::replace(&mut self.env, Env::empty());
+    self.env = env.nested();
+  }
 }
@@ -25,7 +25,7 @@ impl SourceFileSyntax {
   ) -> Self {
     elapsed::log("SourceFileSyntax::new", || {
       let (lex_errors, parse) = Self::lex_and_parse(fix_env, contents);
-      let mut lower = sml_hir_lower::get(lang, kind, &parse.root);
+      let mut lower = sml_hir_lower::get(lang, kind, &parse.root());
       sml_ty_var_scope::get(&mut lower.arenas, &lower.root);
       Self { pos_db: text_pos::PositionDb::new(contents), lex_errors, parse, lower, kind }
     })
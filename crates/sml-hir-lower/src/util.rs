@@ -229,6 +229,18 @@ impl Error {
     self.range
   }
 
+  /// Returns whether this is an error about unnecessary parentheses.
+  #[must_use]
+  pub fn is_unnecessary_parens(&self) -> bool {
+    matches!(self.kind, ErrorKind::UnnecessaryParens)
+  }
+
+  /// Returns whether this is an error about a trailing separator.
+  #[must_use]
+  pub fn is_trailing(&self) -> bool {
+    matches!(self.kind, ErrorKind::Trailing(_))
+  }
+
   /// Returns the code for this.
   ///
   /// No longer in use:
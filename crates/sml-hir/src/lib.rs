@@ -419,6 +419,15 @@ impl UnutterableTyVar {
   pub fn new(equality: bool, idx: usize) -> Self {
     Self { equality, idx }
   }
+
+  /// Returns a type variable named like this, but with a name that can be written in source. So,
+  /// unlike this, it may clash with type variables from the source.
+  #[must_use]
+  pub fn utterable(&self) -> TyVar {
+    let ticks = if self.equality { 2 } else { 1 };
+    let name: String = std::iter::repeat('\'').take(ticks).chain(idx_to_name(self.idx)).collect();
+    TyVar::name(Name::new(name))
+  }
 }
 
 impl fmt::Display for UnutterableTyVar {
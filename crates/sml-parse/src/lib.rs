@@ -12,28 +12,37 @@ mod util;
 pub mod parser;
 
 use sml_syntax::ast::{AstNode as _, Root};
-use sml_syntax::kind::SyntaxKind as SK;
+use sml_syntax::kind::{SyntaxKind as SK, SyntaxNode};
 use token::Token;
 
 /// The result of a parse.
 #[derive(Debug)]
 pub struct Parse {
-  /// The root.
-  pub root: Root,
+  /// The root, as a green node. Unlike syntax nodes, this may be sent to other threads.
+  green: rowan::GreenNode,
   /// The errors encountered when parsing.
   pub errors: Vec<parser::Error>,
 }
 
+impl Parse {
+  /// Returns the root.
+  ///
+  /// # Panics
+  ///
+  /// If casting the root node to a Root failed (an internal error).
+  #[must_use]
+  pub fn root(&self) -> Root {
+    Root::cast(SyntaxNode::new_root(self.green.clone())).unwrap()
+  }
+}
+
 /// Returns a parse of the tokens.
-///
-/// # Panics
-///
-/// If casting the root node to a Root failed (an internal error).
 pub fn get(tokens: &[Token<'_, SK>], fe: &mut sml_fixity::Env) -> Parse {
   let mut p = parser::Parser::new(tokens);
   root::root(&mut p, fe);
   let mut sink = event_parse::rowan_sink::RowanSink::default();
   p.finish(&mut sink);
   let (node, errors) = sink.finish();
-  Parse { root: Root::cast(node).unwrap(), errors: errors.into_iter().map(parser::Error).collect() }
+  let green = node.green().into_owned();
+  Parse { green, errors: errors.into_iter().map(parser::Error).collect() }
 }
@@ -117,6 +117,12 @@ impl Error {
     self.0.range
   }
 
+  /// Returns whether this is an error about an unnecessary `op`.
+  #[must_use]
+  pub fn is_unnecessary_op(&self) -> bool {
+    matches!(self.0.kind, ErrorKind::UnnecessaryOp)
+  }
+
   /// Returns the code for this.
   #[must_use]
   pub fn code(&self) -> Code {
@@ -1,7 +1,7 @@
 //! [`SCon`], a special value constructor.
 
 use num_bigint::BigInt;
-use num_traits::Num as _;
+use num_traits::{Num as _, ToPrimitive as _};
 use std::fmt;
 use str_util::SmolStr;
 
@@ -62,6 +62,15 @@ impl From<i32> for Int {
   }
 }
 
+impl From<i64> for Int {
+  fn from(value: i64) -> Self {
+    match i32::try_from(value) {
+      Ok(x) => Self(IntRepr::Finite(x)),
+      Err(_) => Self(IntRepr::Big(BigInt::from(value))),
+    }
+  }
+}
+
 impl std::ops::Add for Int {
   type Output = Int;
 
@@ -92,6 +101,15 @@ impl std::ops::Mul<i32> for Int {
 }
 
 impl Int {
+  /// Returns this as an `i64`, if it fits.
+  #[must_use]
+  pub fn to_i64(&self) -> Option<i64> {
+    match &self.0 {
+      IntRepr::Finite(x) => Some(i64::from(*x)),
+      IntRepr::Big(x) => x.to_i64(),
+    }
+  }
+
   /// Parses an int with the given radix from a string.
   ///
   /// # Errors
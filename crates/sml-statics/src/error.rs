@@ -17,7 +17,8 @@ pub(crate) enum ErrorKind {
   Unsupported(&'static str),
   Undefined(Item, str_util::Name),
   Duplicate(Item, str_util::Name),
-  Missing(Item, str_util::Name),
+  /// The last is a stub declaration for the missing item, if one could be made.
+  Missing(Item, str_util::Name, Option<String>),
   Extra(Item, str_util::Name),
   Circularity(Circularity),
   IncompatibleTys(Incompatible, Ty, Ty),
@@ -78,7 +79,9 @@ impl fmt::Display for ErrorKindDisplay<'_> {
         Ok(())
       }
       ErrorKind::Duplicate(item, name) => write!(f, "duplicate {item}: `{name}`"),
-      ErrorKind::Missing(item, name) => write!(f, "missing {item} required by signature: `{name}`"),
+      ErrorKind::Missing(item, name, _) => {
+        write!(f, "missing {item} required by signature: `{name}`")
+      }
       ErrorKind::Extra(item, name) => write!(f, "extra {item} not present in signature: `{name}`"),
       ErrorKind::Circularity(circ) => {
         let mv = circ.meta_var.display(self.st, config::DiagnosticLines::One);
@@ -248,6 +251,40 @@ impl Error {
     ErrorKindDisplay { kind: &self.kind, st, lines }
   }
 
+  /// Returns the undefined name and what it might have been meant to be, if this is an error about
+  /// an undefined name that may be similar to some other bit of syntax.
+  #[must_use]
+  pub fn suggestion(&self) -> Option<(&str, &'static str)> {
+    match &self.kind {
+      ErrorKind::Undefined(_, name) => {
+        suggestion::get(name.as_str()).map(|sug| (name.as_str(), sug))
+      }
+      _ => None,
+    }
+  }
+
+  /// Returns a stub declaration for the missing item, if this is an error about an item required by
+  /// a signature but missing from a structure.
+  #[must_use]
+  pub fn stub(&self) -> Option<&str> {
+    match &self.kind {
+      ErrorKind::Missing(_, _, stub) => stub.as_deref(),
+      _ => None,
+    }
+  }
+
+  /// Returns whether this is an error about an unused item.
+  #[must_use]
+  pub fn is_unused(&self) -> bool {
+    matches!(self.kind, ErrorKind::Unused(_, _))
+  }
+
+  /// Returns whether this is an error about a `fn` that can be eta-reduced.
+  #[must_use]
+  pub fn can_eta_reduce(&self) -> bool {
+    matches!(self.kind, ErrorKind::CanEtaReduce(_))
+  }
+
   /// Return the code for this.
   ///
   /// No longer used:
@@ -259,7 +296,7 @@ impl Error {
       ErrorKind::Unsupported(_) => Code::n(5999),
       ErrorKind::Undefined(_, _) => Code::n(5001),
       ErrorKind::Duplicate(_, _) => Code::n(5002),
-      ErrorKind::Missing(_, _) => Code::n(5003),
+      ErrorKind::Missing(_, _, _) => Code::n(5003),
       ErrorKind::Extra(_, _) => Code::n(5004),
       ErrorKind::Circularity(_) => Code::n(5005),
       ErrorKind::IncompatibleTys(_, _, _) => Code::n(5006),
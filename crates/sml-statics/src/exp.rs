@@ -55,8 +55,9 @@ fn get(st: &mut St<'_>, cfg: Cfg, cx: &Cx, ars: &sml_hir::Arenas, exp: sml_hir::
           if let Some(d) = &val_info.disallow {
             st.err(exp, ErrorKind::Disallowed(Item::Val, d.clone(), path.last().clone()));
           }
+          let old = st.exp_id_statuses.insert(exp, val_info.id_status);
           if let Mode::Dynamics = st.info.mode {
-            assert!(st.exp_id_statuses.insert(exp, val_info.id_status).is_none());
+            assert!(old.is_none());
           }
           ty_scheme = Some(val_info.ty_scheme.clone());
           for &def in &val_info.defs {
@@ -1,6 +1,7 @@
 //! See [`Info`].
 
 use crate::basis::Bs;
+use fast_hash::FxHashMap;
 use sml_hir::la_arena;
 use sml_statics_types::ty::{Ty, TyData, TyScheme};
 use sml_statics_types::util::ty_syms;
@@ -109,6 +110,9 @@ pub(crate) struct Entries {
   pub(crate) defs: Defs,
   pub(crate) docs: Docs,
   pub(crate) tys: TyEntries,
+  /// A map from the def of a signature item to the defs of the structure items that implement it,
+  /// from the ascriptions in this file.
+  pub(crate) impls: FxHashMap<def::Def, def::Set>,
 }
 
 /// Information about HIR indices.
@@ -148,6 +152,23 @@ impl Info {
     Some(ty_entry.to_string())
   }
 
+  /// Returns the type of this index.
+  #[must_use]
+  pub fn get_ty(&self, idx: sml_hir::Idx) -> Option<Ty> {
+    Some(self.entries.tys.get(idx)?.ty)
+  }
+
+  /// Returns the most general type scheme of this index.
+  #[must_use]
+  pub fn get_ty_scheme(&self, idx: sml_hir::Idx) -> Option<TyScheme> {
+    let ty_entry = self.entries.tys.get(idx)?;
+    let ret = match &ty_entry.ty_scheme {
+      Some(ty_scheme) => ty_scheme.clone(),
+      None => TyScheme::zero(ty_entry.ty),
+    };
+    Some(ret)
+  }
+
   /// Returns documentation for this index.
   #[must_use]
   pub fn get_doc(&self, idx: sml_hir::Idx) -> Option<&str> {
@@ -202,12 +223,13 @@ impl Info {
   /// Returns the symbols for this file.
   ///
   /// You also have to pass down the `path` that this `Info` is for. It's slightly odd, but we
-  /// need it to know which `Def`s we should actually include in the return value.
+  /// need it to know which `Def`s we should actually include in the return value. This is a
+  /// `def::Path` so that it can be a built-in library file as well as a regular source file.
   #[must_use]
   pub fn document_symbols(
     &self,
     st: &sml_statics_types::St,
-    path: paths::PathId,
+    path: def::Path,
   ) -> Vec<DocumentSymbol> {
     let mut ret = Vec::<DocumentSymbol>::new();
     ret.extend(self.bs.fun_env.iter().flat_map(|(name, fun_sig)| {
@@ -243,11 +265,25 @@ impl Info {
     ret
   }
 
+  /// Returns the defs of the structure items that implement the signature item with the given
+  /// definition, for structures in this file that ascribe to the signature.
+  pub fn get_impls(&self, def: def::Def) -> impl Iterator<Item = def::Def> + '_ {
+    self.entries.impls.get(&def).into_iter().flatten().copied()
+  }
+
   /// Returns indices that have the given definition.
   pub fn get_with_def(&self, def: def::Def) -> impl Iterator<Item = sml_hir::Idx> + '_ {
     self.entries.defs.with_def(def)
   }
 
+  /// Returns a `sig ... end` with specifications for everything in the structure with this name
+  /// declared in this file.
+  #[must_use]
+  pub fn show_sig(&self, st: &sml_statics_types::St, name: &str_util::Name) -> Option<String> {
+    let env = self.bs.env.str_env.get(name)?;
+    Some(crate::show::sig(st, env))
+  }
+
   /// Returns a string representation of a type annotation for the pattern.
   #[must_use]
   pub fn show_pat_ty_annot(
@@ -309,12 +345,7 @@ impl fmt::Display for TyEntryDisplay<'_> {
 }
 
 /// need to do extend instead of a big chain of chains because of the borrow checker.
-fn env_syms(
-  ac: &mut Vec<DocumentSymbol>,
-  st: &sml_statics_types::St,
-  path: paths::PathId,
-  env: &Env,
-) {
+fn env_syms(ac: &mut Vec<DocumentSymbol>, st: &sml_statics_types::St, path: def::Path, env: &Env) {
   ac.extend(env.str_env.iter().flat_map(|(name, env)| {
     env.defs.iter().filter_map(|&def| {
       let idx = def_idx(path, def)?;
@@ -357,12 +388,9 @@ fn env_syms(
   }));
 }
 
-fn def_idx(path: paths::PathId, def: def::Def) -> Option<sml_hir::Idx> {
+fn def_idx(path: def::Path, def: def::Def) -> Option<sml_hir::Idx> {
   match def {
-    def::Def::Path(p, idx) => match p {
-      def::Path::Regular(p) => (p == path).then_some(idx),
-      def::Path::BuiltinLib(_) => None,
-    },
+    def::Def::Path(p, idx) => (p == path).then_some(idx),
     def::Def::Primitive(_) => None,
   }
 }
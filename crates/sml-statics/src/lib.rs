@@ -14,6 +14,7 @@ mod exp;
 mod get_env;
 mod pat;
 mod pat_match;
+mod show;
 mod st;
 mod top_dec;
 mod ty;
@@ -33,9 +34,9 @@ pub struct Statics {
   pub info: info::Info,
   /// The errors from the top decs.
   pub errors: Vec<Error>,
-  /// Id statuses for path expressions. Only populated when the mode is Dynamics.
+  /// Id statuses for path expressions.
   pub exp_id_statuses: sml_statics_types::info::IdStatusMap<sml_hir::Exp>,
-  /// Id statuses for path patterns. Only populated when the mode is Dynamics.
+  /// Id statuses for path patterns.
   pub pat_id_statuses: sml_statics_types::info::IdStatusMap<sml_hir::Pat>,
 }
 
@@ -96,8 +96,9 @@ fn get_(
         let ty = st.syms_tys.tys.meta_var(cfg.gen);
         insert_name(st, pat_idx.into(), cfg.inner, ve, path.last().clone(), ty);
         // a little WET with val_info_for_var
+        let old = st.pat_id_statuses.insert(pat_idx, IdStatus::Val);
         if let Mode::Dynamics = st.info.mode {
-          assert!(st.pat_id_statuses.insert(pat_idx, IdStatus::Val).is_none());
+          assert!(old.is_none());
         }
         return Some(PatRet { pm_pat: Pat::zero(Con::Any, pat), ty, ty_scheme, defs });
       }
@@ -111,8 +112,9 @@ fn get_(
       if let Some(d) = &val_info.disallow {
         st.err(pat_idx, ErrorKind::Disallowed(Item::Val, d.clone(), path.last().clone()));
       }
+      let old = st.pat_id_statuses.insert(pat_idx, val_info.id_status);
       if let Mode::Dynamics = st.info.mode {
-        assert!(st.pat_id_statuses.insert(pat_idx, val_info.id_status).is_none());
+        assert!(old.is_none());
       }
       let variant_name = match &val_info.id_status {
         IdStatus::Val => {
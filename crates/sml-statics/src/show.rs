@@ -0,0 +1,230 @@
+//! Showing items from environments as SML source.
+
+use fast_hash::FxHashSet;
+use sml_statics_types::env::Env;
+use sml_statics_types::info::{IdStatus, TyInfo, ValInfo};
+use sml_statics_types::sym::Sym;
+use sml_statics_types::ty::{BoundTyVarData, TyData, TyScheme, TyVarKind};
+use sml_statics_types::{util::ty_syms, St};
+use std::fmt::Write as _;
+
+/// Returns a `sig ... end` with specifications for everything in the env.
+pub(crate) fn sig(st: &St, env: &Env) -> String {
+  let mut ret = "sig\n".to_owned();
+  for spec in specs(st, env) {
+    for line in spec.lines() {
+      ret.push_str("  ");
+      ret.push_str(line);
+      ret.push('\n');
+    }
+  }
+  ret.push_str("end");
+  ret
+}
+
+fn specs(st: &St, env: &Env) -> Vec<String> {
+  let iter = entries(st, env).into_iter().filter_map(|entry| match entry {
+    Entry::Str(name, env) => Some(format!("structure {name} : {}", sig(st, env))),
+    Entry::Ty(name, ty_info) => Some(ty_spec(st, name, ty_info)),
+    Entry::Val(name, val_info) => val_spec(st, name, val_info),
+  });
+  iter.collect()
+}
+
+/// An item in an env.
+pub(crate) enum Entry<'a> {
+  Str(&'a str_util::Name, &'a Env),
+  Ty(&'a str_util::Name, &'a TyInfo),
+  Val(&'a str_util::Name, &'a ValInfo),
+}
+
+/// Returns the items in the env, each after the items that define the types it mentions, so they
+/// may be written out in this order. Otherwise, structures come before types before values, and
+/// each of those is alphabetical.
+pub(crate) fn entries<'a>(st: &St, env: &'a Env) -> Vec<Entry<'a>> {
+  let mut strs: Vec<_> = env.str_env.iter().collect();
+  strs.sort_unstable_by_key(|&(name, _)| name.as_str());
+  let mut tys: Vec<_> = env.ty_env.iter().collect();
+  tys.sort_unstable_by_key(|&(name, _)| name.as_str());
+  let mut vals: Vec<_> = env.val_env.iter().collect();
+  vals.sort_unstable_by_key(|&(name, _)| name.as_str());
+  let mut rest: Vec<_> = std::iter::empty()
+    .chain(strs.into_iter().map(|(name, env)| Entry::Str(name, env)))
+    .chain(tys.into_iter().map(|(name, ty_info)| Entry::Ty(name, ty_info)))
+    .chain(vals.into_iter().map(|(name, val_info)| Entry::Val(name, val_info)))
+    .map(|entry| {
+      let mut defined = FxHashSet::default();
+      let mut used = FxHashSet::default();
+      entry_syms(st, &entry, &mut defined, &mut used);
+      (entry, defined, used)
+    })
+    .collect();
+  let mut ret = Vec::with_capacity(rest.len());
+  while !rest.is_empty() {
+    let ready = (0..rest.len()).find(|&idx| {
+      let used = &rest[idx].2;
+      rest
+        .iter()
+        .enumerate()
+        .all(|(other, (_, defined, _))| other == idx || defined.is_disjoint(used))
+    });
+    // there's a cycle, as with mutually recursive datatypes, so just take the first one.
+    let (entry, _, _) = rest.remove(ready.unwrap_or(0));
+    ret.push(entry);
+  }
+  ret
+}
+
+/// Adds the syms the entry defines to `defined`, and the syms it mentions to `used`.
+fn entry_syms(st: &St, entry: &Entry<'_>, defined: &mut FxHashSet<Sym>, used: &mut FxHashSet<Sym>) {
+  match *entry {
+    Entry::Str(_, env) => {
+      for (name, env) in env.str_env.iter() {
+        entry_syms(st, &Entry::Str(name, env), defined, used);
+      }
+      for (name, ty_info) in env.ty_env.iter() {
+        entry_syms(st, &Entry::Ty(name, ty_info), defined, used);
+      }
+      for (name, val_info) in env.val_env.iter() {
+        entry_syms(st, &Entry::Val(name, val_info), defined, used);
+      }
+    }
+    Entry::Ty(name, ty_info) => {
+      if let TyData::Con(data) = st.tys.data(ty_info.ty_scheme.ty) {
+        if !ty_info.val_env.is_empty() || is_named(st, data.sym, name) {
+          defined.insert(data.sym);
+        }
+      }
+      ty_syms(&st.tys, ty_info.ty_scheme.ty, &mut |sym| {
+        used.insert(sym);
+      });
+      for (_, val_info) in ty_info.val_env.iter() {
+        ty_syms(&st.tys, val_info.ty_scheme.ty, &mut |sym| {
+          used.insert(sym);
+        });
+      }
+    }
+    Entry::Val(_, val_info) => ty_syms(&st.tys, val_info.ty_scheme.ty, &mut |sym| {
+      used.insert(sym);
+    }),
+  }
+}
+
+/// Returns whether the sym is for a type with this name, like a datatype or abstract type declared
+/// with the name.
+fn is_named(st: &St, sym: Sym, name: &str_util::Name) -> bool {
+  st.syms.get(sym).is_some_and(|x| x.path.last() == name)
+}
+
+fn ty_spec(st: &St, name: &str_util::Name, ty_info: &TyInfo) -> String {
+  if !ty_info.val_env.is_empty() {
+    return datatype(st, name, ty_info);
+  }
+  let ty_scheme = utterable(&ty_info.ty_scheme);
+  let ty_vars = ty_vars(&ty_scheme);
+  // a type named by this very name, like one from an opaque ascription, is abstract.
+  let is_abstract = match st.tys.data(ty_scheme.ty) {
+    TyData::Con(data) => is_named(st, data.sym, name),
+    _ => false,
+  };
+  if is_abstract {
+    format!("type {ty_vars}{name}")
+  } else {
+    let ty = ty_scheme.display(st, config::DiagnosticLines::One);
+    format!("type {ty_vars}{name} = {ty}")
+  }
+}
+
+fn val_spec(st: &St, name: &str_util::Name, val_info: &ValInfo) -> Option<String> {
+  let ret = match val_info.id_status {
+    IdStatus::Con => return None,
+    IdStatus::Exn(_) => exception(st, name, val_info),
+    IdStatus::Val => {
+      let ty_scheme = utterable(&val_info.ty_scheme);
+      let ty = ty_scheme.display(st, config::DiagnosticLines::One);
+      format!("val {name} : {ty}")
+    }
+  };
+  Some(ret)
+}
+
+/// Returns a `datatype` for the type, which should have constructors.
+pub(crate) fn datatype(st: &St, name: &str_util::Name, ty_info: &TyInfo) -> String {
+  let ty_vars = ty_vars(&utterable(&ty_info.ty_scheme));
+  let mut ret = format!("datatype {ty_vars}{name} =");
+  let mut cons: Vec<_> = ty_info.val_env.iter().collect();
+  // in the order they were declared, if we know it.
+  let sym_info = match st.tys.data(ty_info.ty_scheme.ty) {
+    TyData::Con(data) => st.syms.get(data.sym),
+    _ => None,
+  };
+  match sym_info {
+    Some(sym_info) => {
+      let order: Vec<_> = sym_info.ty_info.val_env.iter().map(|(name, _)| name).collect();
+      cons.sort_by_key(|&(name, _)| order.iter().position(|&x| x == name));
+    }
+    None => cons.sort_unstable_by_key(|&(name, _)| name.as_str()),
+  }
+  for (idx, (con, val_info)) in cons.into_iter().enumerate() {
+    let sep = if idx == 0 { "" } else { " |" };
+    write!(ret, "{sep} {con}").unwrap();
+    if let Some(param) = param(st, &val_info.ty_scheme) {
+      write!(ret, " of {param}").unwrap();
+    }
+  }
+  ret
+}
+
+/// Returns an `exception` for the value, which should be an exception.
+pub(crate) fn exception(st: &St, name: &str_util::Name, val_info: &ValInfo) -> String {
+  match param(st, &val_info.ty_scheme) {
+    Some(param) => format!("exception {name} of {param}"),
+    None => format!("exception {name}"),
+  }
+}
+
+/// Returns the type variables bound by the type scheme, formatted to come before a type name.
+pub(crate) fn ty_vars(ty_scheme: &TyScheme) -> String {
+  let names: Vec<_> = ty_scheme
+    .bound_vars
+    .iter()
+    .filter_map(|data| match data {
+      BoundTyVarData::Named(ty_var) => Some(ty_var.to_string()),
+      BoundTyVarData::Kind(_) => None,
+    })
+    .collect();
+  match names.as_slice() {
+    [] => String::new(),
+    [name] => format!("{name} "),
+    _ => format!("({}) ", names.join(", ")),
+  }
+}
+
+/// Returns the displayed type of the argument to the constructor or exception, if it takes one.
+fn param(st: &St, ty_scheme: &TyScheme) -> Option<String> {
+  let ty_scheme = utterable(ty_scheme);
+  let TyData::Fn(data) = st.tys.data(ty_scheme.ty) else { return None };
+  let param = TyScheme { bound_vars: ty_scheme.bound_vars, ty: data.param };
+  Some(param.display(st, config::DiagnosticLines::One).to_string())
+}
+
+/// Returns the type scheme, but with the bound type variables with generated names given names that
+/// can be written in source.
+///
+/// The generated names are usually unutterable, so they cannot clash with names from the source,
+/// but here we are writing source.
+pub(crate) fn utterable(ty_scheme: &TyScheme) -> TyScheme {
+  let bound_vars = ty_scheme
+    .bound_vars
+    .iter()
+    .enumerate()
+    .map(|(idx, data)| match data {
+      BoundTyVarData::Named(_) => data.clone(),
+      BoundTyVarData::Kind(kind) => {
+        let equality = matches!(kind, TyVarKind::Equality);
+        BoundTyVarData::Named(sml_hir::UnutterableTyVar::new(equality, idx).utterable())
+      }
+    })
+    .collect();
+  TyScheme { bound_vars, ty: ty_scheme.ty }
+}
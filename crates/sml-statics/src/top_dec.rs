@@ -5,6 +5,7 @@ mod env_syms;
 mod instance;
 mod realize;
 mod sharing_ty;
+mod stub;
 mod ty_con_paths;
 mod where_ty;
 
@@ -230,7 +231,13 @@ fn get_str_exp(
         Mode::Regular(_) | Mode::Dynamics => {
           instance::env_of_sig(st, idx, &mut subst, &str_exp_env, &sig);
           realize::get_env(&mut st.syms_tys.tys, &subst, &mut to_add);
-          enrich::get_env(st, idx, &str_exp_env, &to_add);
+          enrich::get_env(st, idx, &sig.ty_names, &str_exp_env, &to_add);
+          // the ascribing structure expression as a whole implements the signature.
+          if let Some(sig_exp) = *sig_exp {
+            let sig_defs = st.info.entries.defs.sig_exp.get(sig_exp).cloned().unwrap_or_default();
+            let asc_def = st.def(idx);
+            enrich::add_impls(st, &asc_def, &sig_defs);
+          }
         }
         Mode::BuiltinLib(_) => {
           // @test(hover::doc::std_basis_structure)
@@ -300,7 +307,7 @@ fn get_str_exp(
       realize::get_env(&mut st.syms_tys.tys, &subst, &mut to_add);
       let mut param_env = fun_sig.param.env.clone();
       realize::get_env(&mut st.syms_tys.tys, &subst, &mut param_env);
-      enrich::get_env(st, arg_idx, &arg_env, &param_env);
+      enrich::get_env(st, arg_idx, &fun_sig.param.ty_names, &arg_env, &param_env);
       let def = st.def_opt(idx);
       for (_, env) in to_add.str_env.iter_mut() {
         env.defs.extend(def.iter().copied());
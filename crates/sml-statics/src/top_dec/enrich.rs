@@ -5,32 +5,62 @@
 //! env only for use here that has the indices?
 
 use crate::compatible::{eq_ty_fn, generalizes};
-use crate::{error::ErrorKind, st::St};
+use crate::show::{self, Entry};
+use crate::{error::ErrorKind, st::St, top_dec::stub};
+use sml_statics_types::env::{Env, TyNameSet};
 use sml_statics_types::info::{IdStatus, TyInfo, ValInfo};
-use sml_statics_types::{env::Env, item::Item};
+use sml_statics_types::{def, item::Item};
 
-pub(crate) fn get_env(st: &mut St<'_>, idx: sml_hir::Idx, general: &Env, specific: &Env) {
-  for (name, specific) in specific.str_env.iter() {
-    match general.str_env.get(name) {
-      Some(general) => get_env(st, idx, general, specific),
-      None => st.err(idx, ErrorKind::Missing(Item::Struct, name.clone())),
-    }
-  }
-  for (name, specific) in specific.ty_env.iter() {
-    match general.ty_env.get(name) {
-      Some(general) => get_ty_info(st, idx, general.clone(), specific.clone()),
-      None => st.err(idx, ErrorKind::Missing(Item::Ty, name.clone())),
-    }
-  }
-  for (name, specific) in specific.val_env.iter() {
-    match general.val_env.get(name) {
-      Some(general) => get_val_info(st, idx, general, specific, name),
-      None => st.err(idx, ErrorKind::Missing(Item::Val, name.clone())),
+pub(crate) fn get_env(
+  st: &mut St<'_>,
+  idx: sml_hir::Idx,
+  ty_names: &TyNameSet,
+  general: &Env,
+  specific: &Env,
+) {
+  get_env_impl(st, idx, Some(ty_names), general, specific);
+}
+
+/// Only emits stubs for missing items if `ty_names` is `Some`, since the stubs are inserted into the
+/// top-level structure, so they would be in the wrong place for missing items of sub-structures.
+fn get_env_impl(
+  st: &mut St<'_>,
+  idx: sml_hir::Idx,
+  ty_names: Option<&TyNameSet>,
+  general: &Env,
+  specific: &Env,
+) {
+  add_impls(st, &general.defs, &specific.defs);
+  // in dependency order, so the stubs for the missing items are too.
+  for entry in show::entries(st.syms_tys, specific) {
+    match entry {
+      Entry::Str(name, specific) => match general.str_env.get(name) {
+        Some(general) => get_env_impl(st, idx, None, general, specific),
+        None => {
+          let stub = ty_names.map(|ty_names| stub::str(st.syms_tys, ty_names, name, specific));
+          st.err(idx, ErrorKind::Missing(Item::Struct, name.clone(), stub));
+        }
+      },
+      Entry::Ty(name, specific) => match general.ty_env.get(name) {
+        Some(general) => get_ty_info(st, idx, general.clone(), specific.clone()),
+        None => {
+          let stub = ty_names.map(|ty_names| stub::ty(st.syms_tys, ty_names, name, specific));
+          st.err(idx, ErrorKind::Missing(Item::Ty, name.clone(), stub));
+        }
+      },
+      Entry::Val(name, specific) => match general.val_env.get(name) {
+        Some(general) => get_val_info(st, idx, general, specific, name),
+        None => {
+          let stub = ty_names.and_then(|_| stub::val(st.syms_tys, name, specific));
+          st.err(idx, ErrorKind::Missing(Item::Val, name.clone(), stub));
+        }
+      },
     }
   }
 }
 
 fn get_ty_info(st: &mut St<'_>, idx: sml_hir::Idx, mut general: TyInfo, specific: TyInfo) {
+  add_impls(st, &general.defs, &specific.defs);
   eq_ty_fn(st, idx, specific.ty_scheme, general.ty_scheme.clone());
   if specific.val_env.is_empty() {
     return;
@@ -38,12 +68,13 @@ fn get_ty_info(st: &mut St<'_>, idx: sml_hir::Idx, mut general: TyInfo, specific
   for (name, specific) in specific.val_env {
     match general.val_env.remove(&name) {
       Some(general) => {
+        add_impls(st, &general.defs, &specific.defs);
         if !general.id_status.same_kind_as(specific.id_status) {
           st.err(idx, ErrorKind::WrongIdStatus(name.clone()));
         }
         eq_ty_fn(st, idx, specific.ty_scheme, general.ty_scheme.clone());
       }
-      None => st.err(idx, ErrorKind::Missing(Item::Val, name.clone())),
+      None => st.err(idx, ErrorKind::Missing(Item::Val, name.clone(), None)),
     }
   }
   for (name, _) in general.val_env.iter() {
@@ -58,6 +89,7 @@ fn get_val_info(
   specific: &ValInfo,
   name: &str_util::Name,
 ) {
+  add_impls(st, &general.defs, &specific.defs);
   generalizes(st, idx, &general.ty_scheme, &specific.ty_scheme);
   if !general.id_status.same_kind_as(specific.id_status)
     && !matches!(specific.id_status, IdStatus::Val)
@@ -65,3 +97,14 @@ fn get_val_info(
     st.err(idx, ErrorKind::WrongIdStatus(name.clone()));
   }
 }
+
+/// Records that the general defs implement the specific defs.
+///
+/// The general defs may already contain the specific defs, like when a structure that already
+/// ascribed to a signature ascribes to it again. We skip those.
+pub(crate) fn add_impls(st: &mut St<'_>, general: &def::Set, specific: &def::Set) {
+  for &spec in specific {
+    let impls = general.iter().copied().filter(|def| !specific.contains(def));
+    st.info.entries.impls.entry(spec).or_default().extend(impls);
+  }
+}
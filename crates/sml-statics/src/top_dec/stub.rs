@@ -0,0 +1,70 @@
+//! Stub declarations for items required by a signature but missing from a structure.
+//!
+//! Values are stubbed out with `raise Fail "todo"`. Types and datatypes are copied from the
+//! signature, except abstract types, which are stubbed out with `unit`.
+
+use crate::show::{self, Entry};
+use sml_statics_types::env::{Env, TyNameSet};
+use sml_statics_types::info::{IdStatus, TyInfo, ValInfo};
+use sml_statics_types::{ty::TyData, St};
+
+const TODO: &str = "raise Fail \"todo\"";
+
+/// Returns a stub for the structure.
+pub(crate) fn str(st: &St, ty_names: &TyNameSet, name: &str_util::Name, env: &Env) -> String {
+  let mut ret = format!("structure {name} = struct\n");
+  for stub in env_stubs(st, ty_names, env) {
+    for line in stub.lines() {
+      ret.push_str("  ");
+      ret.push_str(line);
+      ret.push('\n');
+    }
+  }
+  ret.push_str("end");
+  ret
+}
+
+/// Returns a stub for the type or datatype.
+pub(crate) fn ty(st: &St, ty_names: &TyNameSet, name: &str_util::Name, ty_info: &TyInfo) -> String {
+  if !ty_info.val_env.is_empty() {
+    return show::datatype(st, name, ty_info);
+  }
+  let ty_scheme = show::utterable(&ty_info.ty_scheme);
+  let ty_vars = show::ty_vars(&ty_scheme);
+  let is_abstract = match st.tys.data(ty_scheme.ty) {
+    TyData::Con(data) => ty_names.contains(&data.sym),
+    _ => false,
+  };
+  if is_abstract {
+    format!("type {ty_vars}{name} = unit")
+  } else {
+    let ty = ty_scheme.display(st, config::DiagnosticLines::One);
+    format!("type {ty_vars}{name} = {ty}")
+  }
+}
+
+/// Returns a stub for the value, unless it is a constructor, which is stubbed with its datatype.
+pub(crate) fn val(st: &St, name: &str_util::Name, val_info: &ValInfo) -> Option<String> {
+  let ret = match val_info.id_status {
+    IdStatus::Con => return None,
+    IdStatus::Exn(_) => show::exception(st, name, val_info),
+    IdStatus::Val => {
+      // a `fun` instead of a `val`, so it can be polymorphic.
+      if matches!(st.tys.data(val_info.ty_scheme.ty), TyData::Fn(_)) {
+        format!("fun {name} _ = {TODO}")
+      } else {
+        format!("val {name} = {TODO}")
+      }
+    }
+  };
+  Some(ret)
+}
+
+fn env_stubs(st: &St, ty_names: &TyNameSet, env: &Env) -> Vec<String> {
+  let iter = show::entries(st, env).into_iter().filter_map(|entry| match entry {
+    Entry::Str(name, env) => Some(str(st, ty_names, name, env)),
+    Entry::Ty(name, ty_info) => Some(ty(st, ty_names, name, ty_info)),
+    Entry::Val(name, val_info) => val(st, name, val_info),
+  });
+  iter.collect()
+}
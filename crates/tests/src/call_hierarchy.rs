@@ -0,0 +1,62 @@
+//! Call hierarchy for functions.
+
+use crate::check::check;
+
+#[test]
+fn incoming() {
+  check(
+    r"
+fun f x = x + 1
+(** ^ calls-in: g, g, h *)
+fun g y = f (f y)
+val h = fn z => f z
+",
+  );
+}
+
+#[test]
+fn outgoing() {
+  check(
+    r"
+fun f x = x + 1
+(** ^ calls-out: <none> *)
+fun g y = f (f y)
+(** ^ calls-out: f, f *)
+val h = fn z => f z
+(** ^ calls-out: f *)
+",
+  );
+}
+
+#[test]
+fn from_use() {
+  check(
+    r"
+fun f x = x + 1
+fun g y = f y
+(**       ^ calls-in: g *)
+",
+  );
+}
+
+#[test]
+fn recursive() {
+  check(
+    r"
+fun loop n = if n = 0 then 0 else loop (n - 1)
+(** ^^^^ calls-in: loop *)
+(**     v calls-out: loop *)
+val _ = loop 3
+",
+  );
+}
+
+#[test]
+fn not_fun() {
+  check(
+    r"
+val x = 1
+(** ^ calls-in: <none> *)
+",
+  );
+}
@@ -132,3 +132,234 @@ where
   };
   raw::get(files, opts);
 }
+
+/// Asserts that applying the fix with the given title, from some diagnostic for the program, results
+/// in the other program.
+#[track_caller]
+pub(crate) fn check_fix(before: &str, title: &str, after: &str) {
+  let (input, _) = input::get(raw::one_file_fs(before));
+  let mut an = analysis::Analysis::new(analysis::StdBasis::minimal(), analysis::Options::default());
+  let fix = an
+    .get_many_text_range(&input)
+    .into_values()
+    .flatten()
+    .flat_map(|d| d.fixes)
+    .find(|fix| fix.title == title);
+  let Some(mut fix) = fix else { panic!("no fix with title: {title}") };
+  // apply from the end, so the earlier ranges stay valid.
+  fix.edits.sort_by_key(|&(range, _)| std::cmp::Reverse(range.start()));
+  let mut got = before.to_owned();
+  for (range, text) in fix.edits {
+    got.replace_range(std::ops::Range::<usize>::from(range), text.as_str());
+  }
+  pretty_assertions::assert_str_eq!(after, got);
+}
+
+/// Asserts that applying the refactoring with the given title, for the range of the first occurrence
+/// of `at` in the program, results in the other program.
+#[track_caller]
+pub(crate) fn check_refactor(before: &str, at: &str, title: &str, after: &str) {
+  check_refactor_impl(&raw::one_file_fs(before), at, title, &[after], false);
+}
+
+/// Like [`check_refactor`], but the refactoring should leave some changes to be made by hand.
+#[track_caller]
+pub(crate) fn check_incomplete_refactor(before: &str, at: &str, title: &str, after: &str) {
+  check_refactor_impl(&raw::one_file_fs(before), at, title, &[after], true);
+}
+
+/// Like [`check_refactor`], but allows multiple files, provided as pairs of (filename, contents).
+/// The range is of the first occurrence of `at` in the first SML file, and `after` has the expected
+/// contents of each SML file, in order.
+#[track_caller]
+pub(crate) fn check_refactor_multi<const N: usize, const M: usize>(
+  files: [(&str, &str); N],
+  at: &str,
+  title: &str,
+  after: [&str; M],
+) {
+  check_refactor_impl(&files, at, title, &after, false);
+}
+
+/// Asserts that there is no refactoring with the given title for the range of the first occurrence
+/// of `at` in the program.
+#[track_caller]
+pub(crate) fn check_no_refactor(before: &str, at: &str, title: &str) {
+  let (_, refactor) = get_refactor(&raw::one_file_fs(before), at, title);
+  assert!(refactor.is_none(), "unexpected refactoring with title: {title}");
+}
+
+#[track_caller]
+fn check_refactor_impl(
+  files: &[(&str, &str)],
+  at: &str,
+  title: &str,
+  after: &[&str],
+  incomplete: bool,
+) {
+  let (sources, refactor) = get_refactor(files, at, title);
+  let Some(mut refactor) = refactor else { panic!("no refactoring with title: {title}") };
+  assert_eq!(incomplete, refactor.incomplete, "wrong incompleteness");
+  assert_eq!(sources.len(), after.len(), "wrong number of expected files");
+  for ((path, before), after) in sources.into_iter().zip(after) {
+    let edits = refactor.edits.remove(&path).unwrap_or_default();
+    pretty_assertions::assert_str_eq!(*after, apply(before, edits));
+  }
+  assert!(refactor.edits.is_empty(), "edits to files that are not SML files");
+}
+
+/// Returns the SML files, with their paths and contents, and the refactoring with the title for
+/// the range of the first occurrence of `at` in the first of them, if there is one.
+fn get_refactor<'a>(
+  files: &[(&str, &'a str)],
+  at: &str,
+  title: &str,
+) -> (Vec<(paths::PathId, &'a str)>, Option<analysis::Refactor>) {
+  let (input, store) = input::get(files.iter().copied());
+  let mut an = analysis::Analysis::new(analysis::StdBasis::minimal(), analysis::Options::default());
+  an.get_many(&input);
+  let sources: Vec<_> = files
+    .iter()
+    .filter(|(name, _)| name.ends_with(".sml"))
+    .map(|&(name, contents)| {
+      let path = input
+        .sources
+        .keys()
+        .find(|&&path| store.get_path(path).as_path().ends_with(name))
+        .expect("no source file");
+      (*path, contents)
+    })
+    .collect();
+  let &(path, before) = sources.first().expect("no source file");
+  let start = before.find(at).expect("no occurrence of the range text");
+  let range = text_pos::RangeUtf16 {
+    start: position(before, start),
+    end: position(before, start + at.len()),
+  };
+  let refactor = an.refactors(path.wrap(range)).into_iter().find(|r| r.title == title);
+  (sources, refactor)
+}
+
+/// Asserts that renaming the name at the first occurrence of `at` in the program to the new name
+/// results in the other program, or fails with the message if `after` is `Err`.
+#[track_caller]
+pub(crate) fn check_rename(before: &str, at: &str, new_name: &str, after: Result<&str, &str>) {
+  let (input, _) = input::get(raw::one_file_fs(before));
+  let mut an = analysis::Analysis::new(analysis::StdBasis::minimal(), analysis::Options::default());
+  an.get_many(&input);
+  let path = *input.sources.keys().next().expect("no source file");
+  let start = before.find(at).expect("no occurrence of the position text");
+  let got = an.rename(&input, path.wrap(position(before, start)), new_name);
+  match (after, got) {
+    (Ok(after), Ok(mut edits)) => {
+      let file_edits = edits.remove(&path).unwrap_or_default();
+      assert!(edits.is_empty(), "edits to other files");
+      pretty_assertions::assert_str_eq!(after, apply(before, file_edits));
+    }
+    (Err(want), Err(got)) => pretty_assertions::assert_str_eq!(want, got.to_string()),
+    (Ok(_), Err(e)) => panic!("expected rename to succeed, but it failed: {e}"),
+    (Err(_), Ok(_)) => panic!("expected rename to fail, but it succeeded"),
+  }
+}
+
+/// Asserts that searching the workspace symbols of the program for the query returns symbols with
+/// these qualified names, in this order.
+#[track_caller]
+pub(crate) fn check_workspace_symbols(s: &str, query: &str, want: &[&str]) {
+  let (input, _) = input::get(raw::one_file_fs(s));
+  let mut an = analysis::Analysis::new(analysis::StdBasis::minimal(), analysis::Options::default());
+  an.get_many(&input);
+  let got: Vec<_> = an
+    .workspace_symbols(query, false)
+    .into_iter()
+    .map(|sym| match sym.container_name {
+      Some(container) => format!("{container}.{}", sym.name),
+      None => sym.name,
+    })
+    .collect();
+  assert_eq!(want, got);
+}
+
+/// Asserts that searching the workspace symbols of the program and the full std basis for the query
+/// finds the std basis item with this qualified name.
+#[track_caller]
+pub(crate) fn check_workspace_symbols_std_basis(s: &str, query: &str, want: &str) {
+  let (input, _) = input::get(raw::one_file_fs(s));
+  let mut an = analysis::Analysis::new(analysis::StdBasis::full(), analysis::Options::default());
+  an.get_many(&input);
+  let found = an.workspace_symbols(query, true).into_iter().any(|sym| {
+    let name = match sym.container_name {
+      Some(container) => format!("{container}.{}", sym.name),
+      None => sym.name,
+    };
+    name == want && matches!(sym.location, analysis::SymbolLocation::BuiltinLib(_))
+  });
+  assert!(found, "no std basis symbol {want}");
+}
+
+/// Asserts that the program, with the full std basis, uses exactly these parts of the std basis the
+/// dynamics does not implement, in any order.
+#[track_caller]
+pub(crate) fn check_unsupported(s: &str, want: &[&str]) {
+  let (input, _) = input::get(raw::one_file_fs(s));
+  let mut an = analysis::Analysis::new(analysis::StdBasis::full(), analysis::Options::default());
+  an.get_many(&input);
+  let path = *input.sources.keys().next().expect("no source file");
+  let mut got: Vec<_> = an.unsupported(path).unwrap().into_iter().map(|(_, s)| s).collect();
+  got.sort_unstable();
+  let mut want = want.to_vec();
+  want.sort_unstable();
+  assert_eq!(want, got);
+  assert_eq!(want.is_empty(), an.dynamics(path).is_some());
+}
+
+/// Asserts that the top-level `val` declarations in the program that can be evaluated on their own,
+/// with the full std basis, evaluate to these, in order.
+#[track_caller]
+pub(crate) fn check_evaluate(s: &str, want: &[&str]) {
+  let (input, _) = input::get(raw::one_file_fs(s));
+  let mut an = analysis::Analysis::new(analysis::StdBasis::full(), analysis::Options::default());
+  an.get_many(&input);
+  let path = *input.sources.keys().next().expect("no source file");
+  let got: Vec<_> = an
+    .evaluable_vals(path)
+    .unwrap()
+    .into_iter()
+    .map(|range| an.evaluate_val(path.wrap(range.start)).expect("no evaluation").to_string())
+    .collect();
+  assert_eq!(want, got);
+}
+
+fn apply(s: &str, mut edits: Vec<analysis::TextEdit>) -> String {
+  // apply from the end, so the earlier ranges stay valid.
+  edits.sort_by_key(|edit| std::cmp::Reverse((edit.range.start.line, edit.range.start.col)));
+  let mut ret = s.to_owned();
+  for edit in edits {
+    let range = offset(&ret, edit.range.start)..offset(&ret, edit.range.end);
+    ret.replace_range(range, edit.new_text.as_str());
+  }
+  ret
+}
+
+fn position(s: &str, offset: usize) -> text_pos::PositionUtf16 {
+  let before = &s[..offset];
+  let line = before.matches('\n').count();
+  let col = before.rsplit('\n').next().unwrap_or_default().encode_utf16().count();
+  text_pos::PositionUtf16 { line: line.try_into().unwrap(), col: col.try_into().unwrap() }
+}
+
+fn offset(s: &str, pos: text_pos::PositionUtf16) -> usize {
+  let line_start: usize = s.split_inclusive('\n').take(pos.line as usize).map(str::len).sum();
+  let line = &s[line_start..];
+  let mut col = 0;
+  line
+    .char_indices()
+    .find_map(|(idx, c)| {
+      if col >= pos.col as usize {
+        return Some(line_start + idx);
+      }
+      col += c.len_utf16();
+      None
+    })
+    .unwrap_or(s.len())
+}
@@ -118,12 +118,36 @@ impl Expect {
     if let Some(msg) = msg.strip_prefix("use: ") {
       return Self { kind: Kind::Use, msg: msg.to_owned() };
     }
+    if let Some(msg) = msg.strip_prefix("impl: ") {
+      return Self { kind: Kind::Impl, msg: msg.to_owned() };
+    }
     if let Some(msg) = msg.strip_prefix("completions: ") {
       return Self { kind: Kind::Completions { with_std: false }, msg: msg.to_owned() };
     }
     if let Some(msg) = msg.strip_prefix("completions(with-std): ") {
       return Self { kind: Kind::Completions { with_std: true }, msg: msg.to_owned() };
     }
+    if let Some(msg) = msg.strip_prefix("calls-in: ") {
+      return Self { kind: Kind::CallsIn, msg: msg.to_owned() };
+    }
+    if let Some(msg) = msg.strip_prefix("calls-out: ") {
+      return Self { kind: Kind::CallsOut, msg: msg.to_owned() };
+    }
+    if let Some(msg) = msg.strip_prefix("fold: ") {
+      return Self { kind: Kind::Fold, msg: msg.to_owned() };
+    }
+    if let Some(msg) = msg.strip_prefix("selection: ") {
+      return Self { kind: Kind::Selection, msg: msg.to_owned() };
+    }
+    if let Some(msg) = msg.strip_prefix("highlight: ") {
+      return Self { kind: Kind::Highlight, msg: msg.to_owned() };
+    }
+    if let Some(msg) = msg.strip_prefix("signature: ") {
+      return Self { kind: Kind::Signature, msg: msg.to_owned() };
+    }
+    if let Some(msg) = msg.strip_prefix("token: ") {
+      return Self { kind: Kind::Token, msg: msg.to_owned() };
+    }
     if let Some(msg) = msg.strip_prefix("exact: ") {
       return Self { kind: Kind::Exact, msg: msg.to_owned() };
     }
@@ -146,8 +170,27 @@ pub(crate) enum Kind {
   Def,
   /// This points at a usage site for something.
   Use,
+  /// This points at a signature item implemented by something.
+  Impl,
   /// The listed completions should be available at this region.
   Completions { with_std: bool },
+  /// The functions that call the function here, once for each call.
+  CallsIn,
+  /// The functions that the function here calls, once for each call.
+  CallsOut,
+  /// The folding ranges starting on this line should have these kinds and numbers of lines.
+  Fold,
+  /// The first few ranges to expand a selection through from here should have this text, separated
+  /// by ` | `.
+  Selection,
+  /// This is an occurrence of the named item, as a `read` or `write`. Highlighting at any of the
+  /// occurrences of the name should highlight exactly all of them.
+  Highlight,
+  /// The signature help here should be the label, with the parameters in `[]` and the active
+  /// parameter in `[[]]`.
+  Signature,
+  /// This should be a semantic token with the given kind and modifiers.
+  Token,
   /// There should be an error that exactly matches the given message.
   Exact,
   /// There should be an error that contains the message.
@@ -160,6 +203,7 @@ impl fmt::Display for Kind {
       Kind::Hover => f.write_str("hover"),
       Kind::Def => f.write_str("def"),
       Kind::Use => f.write_str("use"),
+      Kind::Impl => f.write_str("impl"),
       Kind::Completions { with_std } => {
         f.write_str("completions")?;
         if *with_std {
@@ -167,6 +211,13 @@ impl fmt::Display for Kind {
         }
         Ok(())
       }
+      Kind::CallsIn => f.write_str("calls-in"),
+      Kind::CallsOut => f.write_str("calls-out"),
+      Kind::Fold => f.write_str("fold"),
+      Kind::Selection => f.write_str("selection"),
+      Kind::Highlight => f.write_str("highlight"),
+      Kind::Signature => f.write_str("signature"),
+      Kind::Token => f.write_str("token"),
       Kind::Exact => f.write_str("exact"),
       Kind::Contains => f.write_str("contains"),
     }
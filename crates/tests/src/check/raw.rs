@@ -157,7 +157,7 @@ where
             ck.reasons.push(r);
           }
         }
-        expect::Kind::Use => {
+        expect::Kind::Use | expect::Kind::Impl => {
           let pos = match region {
             expect::Region::Exact { line, col_start, .. } => {
               text_pos::PositionUtf16 { line, col: col_start }
@@ -167,7 +167,10 @@ where
               continue;
             }
           };
-          let got_defs = an.get_defs(path.wrap(pos));
+          let got_defs = match expect.kind {
+            expect::Kind::Impl => an.get_impls(path.wrap(pos)),
+            _ => an.get_defs(path.wrap(pos)),
+          };
           match defs.get(expect.msg.as_str()) {
             Some(&def) => {
               let any_def_matches = got_defs.iter().flatten().any(|&gd| {
@@ -221,6 +224,160 @@ where
             ck.reasons.push(reason::Reason::MismatchedCompletions(path.wrap(region), want, got));
           }
         }
+        expect::Kind::CallsIn | expect::Kind::CallsOut => {
+          let pos = match region {
+            expect::Region::Exact { line, col_start, .. } => {
+              text_pos::PositionUtf16 { line, col: col_start }
+            }
+            expect::Region::Line(n) => {
+              ck.reasons.push(reason::Reason::InvalidInexact(path.wrap(n), expect.kind));
+              continue;
+            }
+          };
+          let calls = match expect.kind {
+            expect::Kind::CallsIn => an.incoming_calls(path.wrap(pos)),
+            _ => an.outgoing_calls(path.wrap(pos)),
+          };
+          let mut got: Vec<_> = calls
+            .into_iter()
+            .flatten()
+            .flat_map(|call| std::iter::repeat(call.item.name).take(call.from_ranges.len()))
+            .collect();
+          got.sort_unstable();
+          let got = if got.is_empty() { "<none>".to_owned() } else { got.join(", ") };
+          if got != expect.msg {
+            ck.reasons.push(reason::Reason::Mismatched(path.wrap(region), expect.msg.clone(), got));
+          }
+        }
+        expect::Kind::Fold => {
+          let line = match region {
+            expect::Region::Exact { line, .. } | expect::Region::Line(line) => line,
+          };
+          let got: Vec<_> = an
+            .folding_ranges(path)
+            .into_iter()
+            .flatten()
+            .filter(|fold| fold.range.start.line == line)
+            .map(|fold| {
+              let kind = match fold.kind {
+                analysis::FoldingRangeKind::Region => "region",
+                analysis::FoldingRangeKind::Comment => "comment",
+              };
+              format!("{kind}, {} lines", fold.range.end.line - fold.range.start.line + 1)
+            })
+            .collect();
+          let got = if got.is_empty() { "<none>".to_owned() } else { got.join("; ") };
+          if got != expect.msg {
+            ck.reasons.push(reason::Reason::Mismatched(path.wrap(region), expect.msg.clone(), got));
+          }
+        }
+        expect::Kind::Selection => {
+          let pos = match region {
+            expect::Region::Exact { line, col_start, .. } => {
+              text_pos::PositionUtf16 { line, col: col_start }
+            }
+            expect::Region::Line(n) => {
+              ck.reasons.push(reason::Reason::InvalidInexact(path.wrap(n), expect.kind));
+              continue;
+            }
+          };
+          let contents = &input.sources[&path];
+          let pos_db = text_pos::PositionDb::new(contents);
+          let want: Vec<_> = expect.msg.split(" | ").collect();
+          let got: Vec<_> = an
+            .selection_ranges(path.wrap(pos))
+            .into_iter()
+            .flatten()
+            .take(want.len())
+            .filter_map(|range| {
+              let range = pos_db.text_range_utf16(range)?;
+              let text = &contents[std::ops::Range::<usize>::from(range)];
+              Some(text.split_whitespace().collect::<Vec<_>>().join(" "))
+            })
+            .collect();
+          if want != got {
+            let r =
+              reason::Reason::Mismatched(path.wrap(region), expect.msg.clone(), got.join(" | "));
+            ck.reasons.push(r);
+          }
+        }
+        expect::Kind::Highlight => {
+          let pos = match region {
+            expect::Region::Exact { line, col_start, .. } => {
+              text_pos::PositionUtf16 { line, col: col_start }
+            }
+            expect::Region::Line(n) => {
+              ck.reasons.push(reason::Reason::InvalidInexact(path.wrap(n), expect.kind));
+              continue;
+            }
+          };
+          let name = expect.msg.split(' ').next();
+          let want: BTreeSet<_> = file
+            .iter()
+            .filter(|(_, e)| {
+              matches!(e.kind, expect::Kind::Highlight) && e.msg.split(' ').next() == name
+            })
+            .map(|(r, e)| format!("{r} {}", e.msg.split_once(' ').map_or("", |(_, kind)| kind)))
+            .collect();
+          let got: BTreeSet<_> = an
+            .document_highlights(path.wrap(pos))
+            .into_iter()
+            .flatten()
+            .map(|h| {
+              let r = expect::Region::Exact {
+                line: h.range.start.line,
+                col_start: h.range.start.col,
+                col_end: h.range.end.col,
+              };
+              let kind = match h.kind {
+                analysis::DocumentHighlightKind::Read => "read",
+                analysis::DocumentHighlightKind::Write => "write",
+              };
+              format!("{r} {kind}")
+            })
+            .collect();
+          if want != got {
+            let want: Vec<_> = want.into_iter().collect();
+            let got: Vec<_> = got.into_iter().collect();
+            let r = reason::Reason::Mismatched(path.wrap(region), want.join(", "), got.join(", "));
+            ck.reasons.push(r);
+          }
+        }
+        expect::Kind::Signature => {
+          let pos = match region {
+            expect::Region::Exact { line, col_start, .. } => {
+              text_pos::PositionUtf16 { line, col: col_start }
+            }
+            expect::Region::Line(n) => {
+              ck.reasons.push(reason::Reason::InvalidInexact(path.wrap(n), expect.kind));
+              continue;
+            }
+          };
+          let got =
+            an.signature_help(path.wrap(pos)).map_or_else(|| "<none>".to_owned(), signature);
+          if got != expect.msg {
+            ck.reasons.push(reason::Reason::Mismatched(path.wrap(region), expect.msg.clone(), got));
+          }
+        }
+        expect::Kind::Token => {
+          let (line, col_start, col_end) = match region {
+            expect::Region::Exact { line, col_start, col_end } => (line, col_start, col_end),
+            expect::Region::Line(n) => {
+              ck.reasons.push(reason::Reason::InvalidInexact(path.wrap(n), expect.kind));
+              continue;
+            }
+          };
+          let tokens = an.semantic_tokens(path, None).unwrap_or_default();
+          let token = tokens.iter().find(|tok| {
+            tok.range.start.line == line
+              && tok.range.start.col == col_start
+              && tok.range.end.col == col_end
+          });
+          let got = token.map_or_else(|| "<none>".to_owned(), semantic_token);
+          if got != expect.msg {
+            ck.reasons.push(reason::Reason::Mismatched(path.wrap(region), expect.msg.clone(), got));
+          }
+        }
         expect::Kind::Exact | expect::Kind::Contains => {}
       }
     }
@@ -241,6 +398,40 @@ where
   }
 }
 
+/// Returns the label of the signature help, with the parameters in `[]` and the active parameter in
+/// `[[]]`.
+fn signature(help: analysis::SignatureHelp) -> String {
+  let label: Vec<_> = help.label.encode_utf16().collect();
+  let mut ret = Vec::<u16>::new();
+  let mut prev = 0usize;
+  for (idx, &(start, end)) in help.params.iter().enumerate() {
+    let (start, end) = (start as usize, end as usize);
+    let brackets = if help.active_param == u32::try_from(idx).ok() { 2 } else { 1 };
+    ret.extend_from_slice(&label[prev..start]);
+    ret.extend(std::iter::repeat(u16::from(b'[')).take(brackets));
+    ret.extend_from_slice(&label[start..end]);
+    ret.extend(std::iter::repeat(u16::from(b']')).take(brackets));
+    prev = end;
+  }
+  ret.extend_from_slice(&label[prev..]);
+  String::from_utf16_lossy(&ret)
+}
+
+/// Returns the kind and modifiers of the token, like `Function declaration`.
+fn semantic_token(token: &analysis::SemanticToken) -> String {
+  let mut ret = format!("{:?}", token.kind);
+  let mods = [
+    (token.modifiers.declaration, "declaration"),
+    (token.modifiers.default_library, "default-library"),
+    (token.modifiers.mutable, "mutable"),
+  ];
+  for (_, name) in mods.into_iter().filter(|&(has, _)| has) {
+    ret.push(' ');
+    ret.push_str(name);
+  }
+  ret
+}
+
 const STD_NAMES: [&str; 20] = [
   "*", "+", "-", "/", "::", "<", "<=", "<>", "=", ">", ">=", "abs", "div", "false", "mod", "nil",
   "ref", "true", "use", "~",
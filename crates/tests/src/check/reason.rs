@@ -55,7 +55,15 @@ fn try_region(
       expect::Kind::Hover
       | expect::Kind::Def
       | expect::Kind::Use
-      | expect::Kind::Completions { .. } => Ok(false),
+      | expect::Kind::Impl
+      | expect::Kind::Completions { .. }
+      | expect::Kind::CallsIn
+      | expect::Kind::CallsOut
+      | expect::Kind::Fold
+      | expect::Kind::Selection
+      | expect::Kind::Highlight
+      | expect::Kind::Signature
+      | expect::Kind::Token => Ok(false),
       expect::Kind::Exact => {
         if exp.msg == got {
           Ok(true)
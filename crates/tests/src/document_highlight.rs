@@ -0,0 +1,66 @@
+//! Highlighting the occurrences of an item in a file.
+
+use crate::check::check;
+
+#[test]
+fn val() {
+  check(
+    r"
+val foo = 1
+(** ^^^ highlight: foo write *)
+(**             vvv highlight: foo read *)
+val bar = foo + foo
+(**       ^^^ highlight: foo read *)
+",
+  );
+}
+
+#[test]
+fn shadow() {
+  check(
+    r"
+val x = 1
+(** ^ highlight: outer write *)
+(**       v highlight: inner read *)
+fun f x = x
+(**   ^ highlight: inner write *)
+val y = x
+(**     ^ highlight: outer read *)
+",
+  );
+}
+
+#[test]
+fn fun() {
+  check(
+    r"
+(**                 v highlight: f read *)
+fun f x = if x then f false else x
+(** ^ highlight: f write *)
+",
+  );
+}
+
+#[test]
+fn constructor() {
+  check(
+    r"
+datatype d = A | B
+(**          ^ highlight: a write *)
+val _ = A
+(**     ^ highlight: a read *)
+",
+  );
+}
+
+#[test]
+fn ty() {
+  check(
+    r"
+type t = int
+(**  ^ highlight: t write *)
+val x : t = 1
+(**     ^ highlight: t read *)
+",
+  );
+}
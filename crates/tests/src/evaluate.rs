@@ -0,0 +1,79 @@
+//! Tests for which programs can be evaluated with the dynamics.
+
+use crate::check::check_unsupported;
+
+#[test]
+fn supported() {
+  check_unsupported(
+    r"
+structure S = struct
+  val xs = List.map (fn x => x + 1) [1, 2, 3]
+end
+val n = length S.xs
+val () = print (Int.toString n)
+",
+    &[],
+  );
+}
+
+#[test]
+fn vals() {
+  check_unsupported(
+    r"
+val a = Math.sqrt 2.0
+val b = List.partition (fn x => x > 1) [1, 2, 3]
+val c = Int.maxInt
+",
+    &["value `Int.maxInt`", "value `List.partition`", "value `Math.sqrt`"],
+  );
+}
+
+#[test]
+fn open() {
+  check_unsupported(
+    r"
+open List
+val xs = map (fn x => x) [1]
+",
+    &["structure `List`"],
+  );
+}
+
+#[test]
+fn str_path() {
+  check_unsupported(
+    r"
+structure L = List
+val xs = L.rev [1]
+",
+    &["structure `List`"],
+  );
+}
+
+#[test]
+fn sig_name() {
+  check_unsupported(
+    r"
+structure K : ORD_KEY = struct
+  type ord_key = int
+  val compare = Int.compare
+end
+",
+    &["signature `ORD_KEY`"],
+  );
+}
+
+#[test]
+fn own_structure() {
+  check_unsupported(
+    r"
+signature SIG = sig val x : int end
+structure S : SIG = struct val x = 1 end
+functor F (A : SIG) = struct val y = A.x end
+structure T = F (S)
+open T
+val z = y + S.x
+",
+    &[],
+  );
+}
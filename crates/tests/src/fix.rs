@@ -0,0 +1,184 @@
+//! Automatic fixes for diagnostics.
+
+use crate::check::check_fix;
+
+#[test]
+fn unnecessary_op() {
+  check_fix(
+    r"
+val op x = 3
+",
+    "Remove unnecessary `op`",
+    r"
+val x = 3
+",
+  );
+}
+
+#[test]
+fn unnecessary_parens() {
+  check_fix(
+    r"
+fun inc (x) = x + 1
+",
+    "Remove unnecessary parentheses",
+    r"
+fun inc x = x + 1
+",
+  );
+}
+
+#[test]
+fn trailing() {
+  check_fix(
+    r"
+val x = (1, 2,)
+",
+    "Remove trailing `,`",
+    r"
+val x = (1, 2)
+",
+  );
+}
+
+#[test]
+fn suggestion() {
+  check_fix(
+    r"
+type t = integer
+",
+    "Replace with `int`",
+    r"
+type t = int
+",
+  );
+}
+
+#[test]
+fn unused() {
+  check_fix(
+    r"
+fun ignoreVar x = 3
+",
+    "Replace unused `x` with `_`",
+    r"
+fun ignoreVar _ = 3
+",
+  );
+}
+
+#[test]
+fn unused_as() {
+  check_fix(
+    r"
+fun f (x as (a, b)) = a + b
+",
+    "Remove unused `x`",
+    r"
+fun f ((a, b)) = a + b
+",
+  );
+}
+
+#[test]
+fn eta_reduce() {
+  check_fix(
+    r"
+fun inc x = x + 1
+fun app f x = f x
+val _ = app (fn n => inc n) 1
+",
+    "Simplify to `inc`",
+    r"
+fun inc x = x + 1
+fun app f x = f x
+val _ = app inc 1
+",
+  );
+}
+
+#[test]
+fn missing() {
+  check_fix(
+    r#"
+signature SIG = sig
+  type t
+  val f : t -> t
+end
+
+structure S : SIG = struct
+end
+"#,
+    "Add stubs for missing items",
+    r#"
+signature SIG = sig
+  type t
+  val f : t -> t
+end
+
+structure S : SIG = struct
+  type t = unit
+  fun f _ = raise Fail "todo"
+end
+"#,
+  );
+}
+
+#[test]
+fn missing_datatype() {
+  check_fix(
+    r#"
+signature SIG = sig
+  datatype d = A | B of int
+end
+
+structure S : SIG = struct end
+"#,
+    "Add stubs for missing items",
+    r#"
+signature SIG = sig
+  datatype d = A | B of int
+end
+
+structure S : SIG = struct
+  datatype d = A | B of int
+end
+"#,
+  );
+}
+
+#[test]
+fn missing_dependency_order() {
+  check_fix(
+    r#"
+signature SIG = sig
+  type t
+  structure A : sig
+    val x : t
+  end
+  type a = t list
+end
+
+structure S : SIG = struct
+end
+"#,
+    "Add stubs for missing items",
+    r#"
+signature SIG = sig
+  type t
+  structure A : sig
+    val x : t
+  end
+  type a = t list
+end
+
+structure S : SIG = struct
+  type t = unit
+  structure A = struct
+    val x = raise Fail "todo"
+  end
+  type a = t list
+end
+"#,
+  );
+}
@@ -0,0 +1,74 @@
+//! Folding ranges.
+
+use crate::check::check;
+
+#[test]
+fn structure() {
+  check(
+    r"
+(** - fold: region, 3 lines *)
+structure S = struct
+  val x = 1
+end
+",
+  );
+}
+
+#[test]
+fn let_exp() {
+  check(
+    r"
+(** - fold: region, 4 lines *)
+val z = let
+  val a = 1
+in a
+end
+",
+  );
+}
+
+#[test]
+fn case_many_arms() {
+  check(
+    r"
+(** - fold: region, 3 lines *)
+fun f x = case x of
+  0 => 1
+| _ => 2
+",
+  );
+}
+
+#[test]
+fn case_one_arm() {
+  check(
+    r"
+(** - fold: <none> *)
+val y = case 1 of
+  _ => 2
+",
+  );
+}
+
+#[test]
+fn comment() {
+  check(
+    r"
+(** - fold: comment, 3 lines *)
+(* a
+ * comment
+ *)
+val x = 1
+",
+  );
+}
+
+#[test]
+fn one_line() {
+  check(
+    r"
+(** - fold: <none> *)
+structure S = struct val x = 1 end
+",
+  );
+}
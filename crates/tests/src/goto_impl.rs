@@ -0,0 +1,66 @@
+//! Tests for go to implementation.
+
+use crate::check::check;
+
+#[test]
+fn val() {
+  check(
+    r"
+signature SIG = sig
+  val x : int
+(**   ^ impl: x *)
+end
+structure Str : SIG = struct
+  val x = 3
+(**   ^ def: x *)
+end
+",
+  );
+}
+
+#[test]
+fn datatype_con() {
+  check(
+    r"
+signature SIG = sig
+  datatype d = A
+(**            ^ impl: A *)
+end
+structure Str :> SIG = struct
+  datatype d = A
+(** + def: A *)
+end
+",
+  );
+}
+
+#[test]
+fn many_structures() {
+  check(
+    r"
+signature SIG = sig
+  type t
+(**    ^ impl: t *)
+end
+structure A : SIG = struct
+  type t = int
+(** + def: t *)
+end
+structure B : SIG = struct
+  type t = unit
+end
+",
+  );
+}
+
+#[test]
+fn signature() {
+  check(
+    r"
+signature SIG = sig end
+(**       ^ impl: SIG *)
+structure Str : SIG = struct end
+(** + def: SIG *)
+",
+  );
+}
@@ -1,4 +1,5 @@
 //! Hover tests.
 
 mod doc;
+mod evaluate;
 mod ty;
@@ -0,0 +1,92 @@
+//! Tests for showing the value of an expression on hover.
+
+use crate::check::{check_evaluate, check_with_std_basis};
+
+#[test]
+fn value() {
+  check_with_std_basis(
+    r"
+val _ = let val x = 21 in x * 2 end
+(**     ^ hover: 42 *)
+",
+  );
+}
+
+#[test]
+fn raise() {
+  check_with_std_basis(
+    r"
+val _ = let val x = 21 in x div 0 end
+(**     ^ hover: raises `Div` *)
+",
+  );
+}
+
+#[test]
+fn stopped() {
+  check_with_std_basis(
+    r"
+val _ = let fun f x : int = f x in f () end
+(**     ^ hover: did not finish evaluating *)
+",
+  );
+}
+
+#[test]
+fn code_lens() {
+  check_evaluate(
+    r#"
+val a = 1 + 2
+val b = [a, a]
+fun f x = x
+val (c, d) = (1, "hi")
+"#,
+    &["a: 3", r#"c: 1, d: "hi""#],
+  );
+}
+
+#[test]
+fn code_lens_raise() {
+  check_evaluate("val x = 1 div 0", &["uncaught exception Div"]);
+}
+
+#[test]
+fn code_lens_stopped() {
+  check_evaluate("val x = let fun f n : int = f n in f 0 end", &["stopped after 10000 steps"]);
+}
+
+#[test]
+fn code_lens_big_tabulate() {
+  check_evaluate("val xs = List.tabulate (1000000000, fn i => i)", &["stopped after 10000 steps"]);
+}
+
+#[test]
+fn code_lens_big_tabulate_con() {
+  check_evaluate("val xs = List.tabulate (1000000000, SOME)", &["stopped after 10000 steps"]);
+}
+
+#[test]
+fn code_lens_big_map_con() {
+  check_evaluate(
+    r#"
+val xs =
+  let fun go (0, s) = s | go (n, s) = go (n - 1, s ^ s)
+  in List.map SOME (String.explode (go (16, "a")))
+  end
+"#,
+    &["stopped after 10000 steps"],
+  );
+}
+
+#[test]
+fn code_lens_unsupported() {
+  check_evaluate(
+    r"
+val x = Math.sqrt 2.0
+val y = List.partition (fn x => x > 1) [1, 2]
+val z = Int.maxInt
+val w = List.length [1, 2]
+",
+    &["w: 2"],
+  );
+}
@@ -13,6 +13,7 @@ mod diagnostics {
 
 mod basis_path_var;
 mod big;
+mod call_hierarchy;
 mod cannot_rebind;
 mod check;
 mod circularity;
@@ -22,15 +23,20 @@ mod datatype_copy;
 mod deviations;
 mod disallow;
 mod docs;
+mod document_highlight;
 mod dupe;
 mod empty;
 mod equality;
+mod evaluate;
 mod exn;
+mod fix;
 mod fixity;
+mod folding_range;
 mod forbid_opaque_asc;
 mod functor;
 mod generalize;
 mod goto_def;
+mod goto_impl;
 mod hover;
 mod incomplete;
 mod infix_without_op;
@@ -44,13 +50,18 @@ mod num_record;
 mod open;
 mod overload;
 mod pat;
+mod refactor;
+mod rename;
 mod repo;
 mod rest_pat;
 mod rust;
+mod semantic_tokens;
+mod selection_range;
 mod sep;
 mod shadow;
 mod sig;
 mod sig_fun_file;
+mod signature_help;
 mod smoke;
 mod std_basis;
 mod symbolic;
@@ -60,3 +71,4 @@ mod unused;
 mod use_builtin;
 mod val_rec;
 mod well_known;
+mod workspace_symbols;
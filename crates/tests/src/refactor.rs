@@ -0,0 +1,428 @@
+//! Refactorings, which are code actions not tied to any diagnostic.
+
+use crate::check::{
+  check_incomplete_refactor, check_no_refactor, check_refactor, check_refactor_multi,
+};
+
+#[test]
+fn generate_sig() {
+  check_refactor(
+    r"
+structure ListPair = struct
+  datatype 'a t = Nil | Cons of 'a * 'a t
+  type u = int list
+  exception Empty
+  val x = 3
+  fun id x = x
+end
+",
+    "ListPair",
+    "Generate signature `LIST_PAIR`",
+    r"
+signature LIST_PAIR = sig
+  datatype 'a t = Nil | Cons of 'a * 'a t
+  type u = int list
+  exception Empty
+  val id : 'a -> 'a
+  val x : int
+end
+
+structure ListPair = struct
+  datatype 'a t = Nil | Cons of 'a * 'a t
+  type u = int list
+  exception Empty
+  val x = 3
+  fun id x = x
+end
+",
+  );
+}
+
+#[test]
+fn generate_sig_dependency_order() {
+  check_refactor(
+    r"
+structure Foo = struct
+  datatype d = D
+  structure A = struct
+    val x = D
+  end
+end
+",
+    "Foo",
+    "Generate signature `FOO`",
+    r"
+signature FOO = sig
+  datatype d = D
+  structure A : sig
+    val x : d
+  end
+end
+
+structure Foo = struct
+  datatype d = D
+  structure A = struct
+    val x = D
+  end
+end
+",
+  );
+}
+
+#[test]
+fn generate_sig_ascribe() {
+  check_refactor(
+    r"
+structure Foo = struct
+  val x = 3
+end
+",
+    "Foo",
+    "Generate signature `FOO` and ascribe to it",
+    r"
+signature FOO = sig
+  val x : int
+end
+
+structure Foo : FOO = struct
+  val x = 3
+end
+",
+  );
+}
+
+#[test]
+fn annotate_val() {
+  check_refactor(
+    r"
+val x = 3
+",
+    "x",
+    "Add type annotation",
+    r"
+val x : int = 3
+",
+  );
+}
+
+#[test]
+fn annotate_tuple() {
+  check_refactor(
+    r"
+val (a, b) = (1, 2.0)
+",
+    "b",
+    "Add type annotation",
+    r"
+val (a, b : real) = (1, 2.0)
+",
+  );
+}
+
+#[test]
+fn annotate_fun() {
+  check_refactor(
+    r"
+fun add x y = x + y + 1
+",
+    "add",
+    "Add type annotations to `add`",
+    r"
+fun add (x : int) (y : int) : int = x + y + 1
+",
+  );
+}
+
+#[test]
+fn annotate_fun_tuple_unqualify() {
+  check_refactor(
+    r"
+structure S = struct
+  datatype t = A | B
+  fun both (x, y) = x = A andalso y = B
+end
+",
+    "both",
+    "Add type annotations to `both`",
+    r"
+structure S = struct
+  datatype t = A | B
+  fun both (x : t, y : t) : bool = x = A andalso y = B
+end
+",
+  );
+}
+
+#[test]
+fn annotate_unqualify_shadowed() {
+  check_no_refactor(
+    r"
+structure S = struct
+  datatype t = A
+  structure T = struct
+    datatype t = B
+    val x = A
+  end
+end
+",
+    "x",
+    "Add type annotation",
+  );
+}
+
+#[test]
+fn annotate_unqualify_opened() {
+  check_no_refactor(
+    r"
+structure U = struct datatype t = C end
+structure S = struct
+  datatype t = A
+  open U
+  val x = A
+end
+",
+    "x",
+    "Add type annotation",
+  );
+}
+
+#[test]
+fn extract_val() {
+  check_refactor(
+    r"
+val y =
+  let
+    val a = 1
+  in
+    a + 2 * 3
+  end
+",
+    "2 * 3",
+    "Extract into `val`",
+    r"
+val y =
+  let
+    val a = 1
+    val extracted = 2 * 3
+  in
+    a + extracted
+  end
+",
+  );
+}
+
+#[test]
+fn extract_fun() {
+  check_refactor(
+    r"
+fun f x =
+  let
+    val y = x + 1
+  in
+    y * y + x
+  end
+",
+    "y * y + x",
+    "Extract into `fun`",
+    r"
+fun extracted y x = y * y + x
+
+fun f x =
+  let
+    val y = x + 1
+  in
+    extracted y x
+  end
+",
+  );
+}
+
+#[test]
+fn extract_fun_ty_var() {
+  check_refactor(
+    r"
+fun id (x : 'a) = (fn (y : 'a) => y) x
+",
+    "fn (y : 'a) => y",
+    "Extract into `fun`",
+    r"
+fun 'a extracted () = fn (y : 'a) => y
+
+fun id (x : 'a) = (extracted ()) x
+",
+  );
+}
+
+#[test]
+fn extract_val_in_fn() {
+  check_no_refactor(
+    r"
+val f =
+  let
+    val a = 1
+  in
+    fn x => x + a * 2
+  end
+",
+    "a * 2",
+    "Extract into `val`",
+  );
+}
+
+#[test]
+fn extract_val_in_branch() {
+  check_no_refactor(
+    r"
+val y =
+  let
+    val a = 1
+  in
+    if a = 1 then a div 0 else a
+  end
+",
+    "a div 0",
+    "Extract into `val`",
+  );
+}
+
+#[test]
+fn extract_fun_poly_param() {
+  check_no_refactor(
+    r#"
+fun f x =
+  let
+    fun id y = y
+  in
+    (id 1, id "a")
+  end
+"#,
+    r#"(id 1, id "a")"#,
+    "Extract into `fun`",
+  );
+}
+
+#[test]
+fn inline_parens() {
+  check_refactor(
+    r"
+val y =
+  let
+    val a = 2
+    val x = a :: nil
+  in
+    x :: x :: nil
+  end
+",
+    "x = a",
+    "Inline `x`",
+    r"
+val y =
+  let
+    val a = 2
+  in
+    (a :: nil) :: (a :: nil) :: nil
+  end
+",
+  );
+}
+
+#[test]
+fn inline_no_parens() {
+  check_refactor(
+    r"
+val x = 1 :: nil
+val y = 0 :: x
+val z = [x, x]
+",
+    "x",
+    "Inline `x`",
+    r"
+val y = 0 :: 1 :: nil
+val z = [1 :: nil, 1 :: nil]
+",
+  );
+}
+
+#[test]
+fn inline_expansive() {
+  check_no_refactor(
+    r"
+val x = 1 + 2
+val y = x
+",
+    "x",
+    "Inline `x`",
+  );
+}
+
+#[test]
+fn curry() {
+  check_refactor(
+    r"
+fun add (a, b) = a + b
+val x = add (1, 2)
+val y = add (3 * 4, x)
+val f = add
+",
+    "add",
+    "Convert `add` to curried form",
+    r"
+fun add a b = a + b
+val x = add 1 2
+val y = add (3 * 4) x
+val f = (fn (x1, x2) => add x1 x2)
+",
+  );
+}
+
+#[test]
+fn uncurry() {
+  check_incomplete_refactor(
+    r"
+fun add 0 b = b
+  | add a b = a + add 0 b
+val x = add 1 2
+val inc = add 1
+val g = add (x + 1)
+",
+    "add",
+    "Convert `add` to tupled form (1 use needs manual changes)",
+    r"
+fun add (0, b) = b
+  | add (a, b) = a + add (0, b)
+val x = add (1, 2)
+val inc = (fn x1 => add (1, x1))
+val g = add (x + 1)
+",
+  );
+}
+
+#[test]
+fn curry_multi_file() {
+  check_refactor_multi(
+    [
+      ("s.mlb", "a.sml b.sml"),
+      ("a.sml", "fun add (a, b) = a + b\n"),
+      ("b.sml", "val x = add (1, 2)\nval f = add\n"),
+    ],
+    "add",
+    "Convert `add` to curried form",
+    ["fun add a b = a + b\n", "val x = add 1 2\nval f = (fn (x1, x2) => add x1 x2)\n"],
+  );
+}
+
+#[test]
+fn expand_record_pat() {
+  check_refactor(
+    r"
+type t = {a : int, b : int, c : int}
+fun f ({a, ...} : t) = a
+",
+    "...",
+    "Expand to all fields",
+    r"
+type t = {a : int, b : int, c : int}
+fun f ({a, b = _, c = _} : t) = a
+",
+  );
+}
@@ -0,0 +1,125 @@
+//! Renaming items.
+
+use crate::check::check_rename;
+
+#[test]
+fn val() {
+  check_rename(
+    r"
+val foo = 1
+val bar = foo + foo
+",
+    "foo",
+    "quz",
+    Ok(
+      r"
+val quz = 1
+val bar = quz + quz
+",
+    ),
+  );
+}
+
+#[test]
+fn local_shadows_top_level() {
+  check_rename(
+    r"
+val bar = 1
+fun inc foo = foo + 1
+",
+    "foo",
+    "bar",
+    Ok(
+      r"
+val bar = 1
+fun inc bar = bar + 1
+",
+    ),
+  );
+}
+
+#[test]
+fn captured_by_let() {
+  check_rename(
+    r"
+val foo = 1
+fun f () = let val bar = 2 in foo + bar end
+",
+    "foo",
+    "bar",
+    Err("renaming to `bar` would change what some names refer to"),
+  );
+}
+
+#[test]
+fn captured_by_fn() {
+  check_rename(
+    r"
+val foo = 1
+val f = fn bar => foo + bar
+",
+    "foo",
+    "bar",
+    Err("renaming to `bar` would change what some names refer to"),
+  );
+}
+
+#[test]
+fn captured_by_case() {
+  check_rename(
+    r"
+val foo = 1
+val x = case 2 of bar => foo + bar
+",
+    "foo",
+    "bar",
+    Err("renaming to `bar` would change what some names refer to"),
+  );
+}
+
+#[test]
+fn captures_outer() {
+  check_rename(
+    r"
+val bar = 1
+fun f foo = bar + foo
+",
+    "foo",
+    "bar",
+    Err("renaming to `bar` would change what some names refer to"),
+  );
+}
+
+#[test]
+fn shadowed_by_later() {
+  check_rename(
+    r"
+val foo = 1
+val bar = 2
+val x = foo + bar
+",
+    "foo",
+    "bar",
+    Err("renaming to `bar` would change what some names refer to"),
+  );
+}
+
+#[test]
+fn not_captured_after_scope() {
+  check_rename(
+    r"
+val foo = 1
+val x = let val bar = 2 in bar end
+val y = foo
+",
+    "foo",
+    "bar",
+    Ok(
+      r"
+val bar = 1
+val x = let val bar = 2 in bar end
+val y = bar
+",
+    ),
+  );
+}
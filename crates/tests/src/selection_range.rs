@@ -0,0 +1,26 @@
+//! Selection ranges.
+
+use crate::check::check;
+
+#[test]
+fn infix() {
+  check(
+    r"
+val y = 1 + 2 * 3
+(**         ^ selection: 2 | 2 * 3 | 1 + 2 * 3 | y = 1 + 2 * 3 *)
+",
+  );
+}
+
+#[test]
+fn multi_line() {
+  check(
+    r"
+(** v selection: z | z = let val a = 1 in a end *)
+val z = let
+  val a = 1
+in a
+end
+",
+  );
+}
@@ -0,0 +1,120 @@
+//! Semantic tokens.
+
+use crate::check::check;
+
+#[test]
+fn fun_decl() {
+  check(
+    r"
+(** vvv token: Function declaration *)
+fun inc x = x + 1
+(**     ^ token: Value declaration *)
+",
+  );
+}
+
+#[test]
+fn fun_body() {
+  check(
+    r"
+(**         v token: Value *)
+fun inc x = x + 1
+(**           ^ token: Operator default-library *)
+val two = inc 1
+(**       ^^^ token: Function *)
+",
+  );
+}
+
+#[test]
+fn datatype() {
+  check(
+    r"
+(**      vv token: TyVar *)
+datatype 'a tree = Leaf | Node of 'a tree * 'a tree
+(**         ^^^^ token: Type declaration *)
+",
+  );
+}
+
+#[test]
+fn constructor() {
+  check(
+    r"
+datatype t = Leaf | Node of t * t
+(**          ^^^^ token: Constructor declaration *)
+val t = Node (Leaf, Leaf)
+(**     ^^^^ token: Constructor *)
+",
+  );
+}
+
+#[test]
+fn ty() {
+  check(
+    r"
+(**  vvv token: Type declaration *)
+type num = int
+(**        ^^^ token: Type default-library *)
+val n : num = 3
+(**     ^^^ token: Type *)
+",
+  );
+}
+
+#[test]
+fn mutable() {
+  check(
+    r"
+val r = ref 0
+(** ^ token: Value declaration mutable *)
+val _ = r
+(**     ^ token: Value mutable *)
+",
+  );
+}
+
+#[test]
+fn structure() {
+  check(
+    r"
+structure S = struct val x = 1 end
+(**       ^ token: Structure declaration *)
+(**     v token: Structure *)
+val y = S.x
+(**       ^ token: Value *)
+",
+  );
+}
+
+#[test]
+fn exception() {
+  check(
+    r"
+exception E
+(**       ^ token: Exception declaration *)
+val _ = E
+(**     ^ token: Exception *)
+",
+  );
+}
+
+#[test]
+fn label() {
+  check(
+    r"
+val p = {a = 1}
+(**      ^ token: Label *)
+",
+  );
+}
+
+#[test]
+fn not_token() {
+  check(
+    r"
+val p = 1
+(**     ^ token: <none> *)
+",
+  );
+}
@@ -0,0 +1,57 @@
+//! Signature help for function applications.
+
+use crate::check::check;
+
+#[test]
+fn curried() {
+  check(
+    r"
+fun add x y = x + y
+val _ = add 1 2
+(**           ^ signature: add : [int] -> [[int]] -> int *)
+",
+  );
+}
+
+#[test]
+fn tupled_first() {
+  check(
+    r"
+fun f (a, b) c = a + b + c
+val _ = f (1, 2) 3
+(**           ^ signature: f : [int] * [[int]] -> [int] -> int *)
+",
+  );
+}
+
+#[test]
+fn tupled_then_curried() {
+  check(
+    r"
+fun f (a, b) c = a + b + c
+val _ = f (1, 2) 3
+(**              ^ signature: f : [int] * [int] -> [[int]] -> int *)
+",
+  );
+}
+
+#[test]
+fn paren_not_tuple() {
+  check(
+    r"
+val h = fn x => x + 1
+val _ = h (1)
+(**        ^ signature: h : [[int]] -> int *)
+",
+  );
+}
+
+#[test]
+fn not_app() {
+  check(
+    r"
+val _ = 1
+(**     ^ signature: <none> *)
+",
+  );
+}
@@ -0,0 +1,85 @@
+//! Searching for symbols across the workspace.
+
+use crate::check::{check_workspace_symbols, check_workspace_symbols_std_basis};
+
+#[test]
+fn qualified() {
+  check_workspace_symbols(
+    r"
+structure FooImpl = struct
+  val bar = 1
+end
+",
+    "FIbar",
+    &["FooImpl.bar"],
+  );
+}
+
+#[test]
+fn consecutive_first() {
+  check_workspace_symbols(
+    r"
+val xaxbxc = 1
+val abc = 2
+",
+    "abc",
+    &["abc", "xaxbxc"],
+  );
+}
+
+#[test]
+fn word_start_first() {
+  check_workspace_symbols(
+    r"
+val knob = 1
+val getName = 2
+",
+    "n",
+    &["getName", "knob"],
+  );
+}
+
+#[test]
+fn shorter_first() {
+  check_workspace_symbols(
+    r"
+val foo_bar = 1
+val foo = 2
+",
+    "foo",
+    &["foo", "foo_bar"],
+  );
+}
+
+#[test]
+fn ignore_case_and_space() {
+  check_workspace_symbols(
+    r"
+val fooBar = 1
+",
+    "FOO bar",
+    &["fooBar"],
+  );
+}
+
+#[test]
+fn no_match() {
+  check_workspace_symbols(
+    r"
+val foo = 1
+",
+    "oof",
+    &[],
+  );
+}
+
+#[test]
+fn std_basis() {
+  check_workspace_symbols_std_basis(
+    r"
+val foo = 1
+",
+    "List.partition",
+    "List.partition",
+  );
+}
@@ -1,6 +1,7 @@
 import * as path from "path";
 import * as vscode from "vscode";
 import {
+  DidChangeConfigurationNotification,
   LanguageClient,
   type LanguageClientOptions,
   type ServerOptions,
@@ -26,20 +27,37 @@ export async function activate(cx: vscode.ExtensionContext) {
   };
   const clientOpts: LanguageClientOptions = {
     documentSelector: [{ scheme: "file", language: "sml" }],
-    // @sync(init-options)
-    initializationOptions: {
-      token_hover: config.get("server.hover.token.enable"),
-      fs_watcher: config.get("server.fileSystemWatcher.enable"),
-      format: config.get("format.engine"),
-      diagnostics: {
-        on_change: config.get("server.diagnostics.onChange.enable"),
-        more_info_hint: config.get("server.diagnostics.moreInfoHint.enable"),
-        ignore: config.get("server.diagnostics.ignore"),
-      },
-    },
+    initializationOptions: getInitOptions(config),
   };
   client = new LanguageClient("millet", serverOpts, clientOpts);
   await client.start();
+  cx.subscriptions.push(
+    vscode.workspace.onDidChangeConfiguration(async (e) => {
+      if (client === null || !e.affectsConfiguration("millet")) {
+        return;
+      }
+      await client.sendNotification(DidChangeConfigurationNotification.type, {
+        settings: getInitOptions(vscode.workspace.getConfiguration("millet")),
+      });
+    }),
+  );
+}
+
+function getInitOptions(config: vscode.WorkspaceConfiguration) {
+  // @sync(init-options)
+  return {
+    token_hover: config.get("server.hover.token.enable"),
+    fs_watcher: config.get("server.fileSystemWatcher.enable"),
+    format: config.get("format.engine"),
+    diagnostics: {
+      on_change: config.get("server.diagnostics.onChange.enable"),
+      more_info_hint: config.get("server.diagnostics.moreInfoHint.enable"),
+      ignore: config.get("server.diagnostics.ignore"),
+    },
+    workspace_symbols: {
+      std_basis: config.get("server.workspaceSymbols.stdBasis.enable"),
+    },
+  };
 }
 
 export async function deactivate() {